@@ -1,4 +1,4 @@
-use pcf::lex::*;
+use pcf::{Source, lex};
 
 macro_rules! fatal {
     ($($arg:tt)+) => {{
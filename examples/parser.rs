@@ -0,0 +1,30 @@
+use pcf::{Source, parse};
+
+macro_rules! fatal {
+    ($($arg:tt)+) => {{
+        eprintln!($($arg)+);
+        std::process::exit(0);
+    }}
+}
+
+fn main() {
+    let mut args = std::env::args();
+    args.next()
+        .unwrap_or_else(|| fatal!("expected program name as first argument"));
+
+    let file = args
+        .next()
+        .unwrap_or_else(|| fatal!("expected input filename as second argument"));
+
+    let src = Source::new(
+        &file,
+        std::fs::read_to_string(&file)
+            .unwrap_or_else(|e| fatal!("could not open input filename: {}", e)),
+    );
+    let res = parse(&src);
+
+    match res {
+        Ok(document) => println!("{:#?}", document),
+        Err(error) => println!("{}", error),
+    }
+}
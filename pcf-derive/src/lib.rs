@@ -0,0 +1,446 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, quote_spanned};
+use syn::{
+    Attribute, Data, DeriveInput, Expr, Fields, Generics, Ident, LitStr, Result, Type,
+    parse_macro_input, parse_quote, spanned::Spanned,
+};
+
+#[derive(Default)]
+struct Options {
+    rename: Option<String>,
+    default: Option<Option<Expr>>,
+    range: Option<Expr>,
+    comment: Option<String>,
+    tag: Option<String>,
+    content: Option<String>,
+}
+
+impl Options {
+    fn parse(attrs: &[Attribute], allowed: &[&str]) -> Result<Self> {
+        let mut options = Self::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("pcf")) {
+            attr.parse_nested_meta(|meta| {
+                if !allowed.iter().any(|name| meta.path.is_ident(name)) {
+                    return Err(meta.error(format!("expected one of `{}`", allowed.join("`, `"))));
+                }
+
+                if meta.path.is_ident("rename") {
+                    options.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("default") {
+                    options.default = Some(match meta.input.peek(syn::Token![=]) {
+                        true => Some(meta.value()?.parse()?),
+                        false => None,
+                    });
+                } else if meta.path.is_ident("range") {
+                    options.range = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("comment") {
+                    options.comment = Some(match meta.input.peek(syn::Token![=]) {
+                        true => meta.value()?.parse::<LitStr>()?.value(),
+                        false => docs(attrs),
+                    });
+                } else if meta.path.is_ident("tag") {
+                    options.tag = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("content") {
+                    options.content = Some(meta.value()?.parse::<LitStr>()?.value());
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(options)
+    }
+
+    fn field(attrs: &[Attribute]) -> Result<Self> {
+        Self::parse(attrs, &["rename", "default", "range", "comment"])
+    }
+
+    fn variant(attrs: &[Attribute]) -> Result<Self> {
+        Self::parse(attrs, &["rename"])
+    }
+
+    // enums are externally tagged unless both `tag` and `content` are given
+    fn tagging(input: &DeriveInput) -> Result<Option<(String, String)>> {
+        let options = Self::parse(&input.attrs, &["tag", "content"])?;
+
+        match (options.tag, options.content) {
+            (Some(tag), Some(content)) => Ok(Some((tag, content))),
+            (None, None) => Ok(None),
+            _ => Err(syn::Error::new(
+                input.ident.span(),
+                "`tag` and `content` must be given together",
+            )),
+        }
+    }
+
+    fn key(&self, ident: &Ident) -> String {
+        self.rename.clone().unwrap_or_else(|| {
+            let ident = ident.to_string();
+            ident.strip_prefix("r#").map(String::from).unwrap_or(ident)
+        })
+    }
+}
+
+// a bare `comment` reuses the doc comment, minus the space rustdoc leaves after `///`
+fn docs(attrs: &[Attribute]) -> String {
+    let lines = attrs.iter().filter_map(|attr| match &attr.meta {
+        syn::Meta::NameValue(meta) if meta.path.is_ident("doc") => match &meta.value {
+            Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(doc),
+                ..
+            }) => Some(doc.value()),
+            _ => None,
+        },
+        _ => None,
+    });
+
+    lines
+        .map(|line| line.strip_prefix(' ').map(String::from).unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn bound(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+
+    generics
+}
+
+fn from_field(cursor: &Ident, key: &str, ty: &Type, options: &Options) -> TokenStream2 {
+    let missing = match &options.default {
+        Some(Some(default)) => quote!(#default),
+        Some(None) => quote!(::core::default::Default::default()),
+        None => quote!(#cursor.field::<#ty>(#key)?),
+    };
+
+    let check = options.range.as_ref().map(|range| {
+        quote_spanned! {range.span()=>
+            if !(#range).contains(&value) {
+                return ::core::result::Result::Err(
+                    child.error(::pcf::extract::ExtractErrorKind::OutOfRange),
+                );
+            }
+        }
+    });
+
+    quote! {
+        match #cursor.child(#key)? {
+            ::core::option::Option::Some(child) => {
+                let value = <#ty as ::pcf::extract::FromValue>::from_value(&child)?;
+                #check
+                value
+            }
+            ::core::option::Option::None => #missing,
+        }
+    }
+}
+
+// builds `constructor` from the value under `cursor`, evaluating to a `Result<Self, _>`
+fn from_fields(fields: &Fields, cursor: &Ident, constructor: TokenStream2) -> Result<TokenStream2> {
+    Ok(match fields {
+        Fields::Named(fields) => {
+            let fields = fields
+                .named
+                .iter()
+                .map(|field| {
+                    let ident = field.ident.as_ref().expect("named field");
+                    let options = Options::field(&field.attrs)?;
+                    let value = from_field(cursor, &options.key(ident), &field.ty, &options);
+                    Ok(quote!(#ident: #value))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            quote! {{
+                #cursor.table()?;
+                ::core::result::Result::Ok(#constructor { #(#fields),* })
+            }}
+        }
+        // newtypes are transparent, wider tuples read their fields from an array
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => quote! {
+            ::pcf::extract::FromValue::from_value(&#cursor).map(#constructor)
+        },
+        Fields::Unnamed(fields) => {
+            let len = fields.unnamed.len();
+            let elements = (0..len).map(|i| {
+                quote! {
+                    ::pcf::extract::FromValue::from_value(&#cursor.element(#i)?.ok_or_else(
+                        || #cursor.error(::pcf::extract::ExtractErrorKind::Invalid),
+                    )?)?
+                }
+            });
+
+            quote! {{
+                if #cursor.array()?.len() != #len {
+                    return ::core::result::Result::Err(
+                        #cursor.error(::pcf::extract::ExtractErrorKind::Invalid),
+                    );
+                }
+
+                ::core::result::Result::Ok(#constructor(#(#elements),*))
+            }}
+        }
+        Fields::Unit => quote!(::core::result::Result::Ok(#constructor)),
+    })
+}
+
+fn from_variants(input: &DeriveInput, data: &syn::DataEnum) -> Result<TokenStream2> {
+    let content = format_ident!("content");
+
+    let split = match Options::tagging(input)? {
+        Some((tag, content)) => quote!(cursor.tagged(#tag, #content)?),
+        None => quote!(cursor.variant()?),
+    };
+
+    let arms = data
+        .variants
+        .iter()
+        .map(|variant| {
+            let ident = &variant.ident;
+            let name = Options::variant(&variant.attrs)?.key(ident);
+            let invalid = quote! {
+                ::core::result::Result::Err(
+                    cursor.error(::pcf::extract::ExtractErrorKind::Invalid),
+                )
+            };
+
+            let (binding, present) = match &variant.fields {
+                Fields::Unit => (quote!(_), invalid.clone()),
+                fields => (
+                    quote!(#content),
+                    from_fields(fields, &content, quote!(Self::#ident))?,
+                ),
+            };
+
+            // a newtype without content is treated like a missing field, so `Option`s
+            // and other types with a `from_missing` fallback still come through
+            let absent = match &variant.fields {
+                Fields::Unit => quote!(::core::result::Result::Ok(Self::#ident)),
+                Fields::Unnamed(fields) if fields.unnamed.len() == 1 => quote! {
+                    ::pcf::extract::FromValue::from_missing(cursor.path().clone(), cursor.span())
+                        .map(Self::#ident)
+                },
+                _ => invalid,
+            };
+
+            Ok(quote! {
+                (#name, ::core::option::Option::Some(#binding)) => #present,
+                (#name, ::core::option::Option::None) => #absent,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        match #split {
+            #(#arms)*
+            _ => ::core::result::Result::Err(
+                cursor.error(::pcf::extract::ExtractErrorKind::UnknownVariant),
+            ),
+        }
+    })
+}
+
+fn derive_from_pcf(input: DeriveInput) -> Result<TokenStream2> {
+    let name = &input.ident;
+    let generics = bound(&input.generics, quote!(::pcf::extract::FromValue));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            Options::parse(&input.attrs, &[])?;
+            from_fields(&data.fields, &format_ident!("cursor"), quote!(Self))?
+        }
+        Data::Enum(data) => from_variants(&input, data)?,
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                input.span(),
+                "FromPcf cannot be derived for unions",
+            ));
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::pcf::extract::FromValue for #name #ty_generics #where_clause {
+            fn from_value(
+                cursor: &::pcf::extract::Cursor<'_>,
+            ) -> ::core::result::Result<Self, ::pcf::extract::ExtractError> {
+                #body
+            }
+        }
+    })
+}
+
+// names the fields so they can be matched out of `self` or out of a variant
+fn bindings(fields: &Fields) -> (TokenStream2, Vec<Ident>) {
+    let idents = (0..fields.len())
+        .map(|i| format_ident!("__field{}", i))
+        .collect::<Vec<_>>();
+
+    let pattern = match fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote!({ #(#names: #idents),* })
+        }
+        Fields::Unnamed(_) => quote!((#(#idents),*)),
+        Fields::Unit => quote!(),
+    };
+
+    (pattern, idents)
+}
+
+// evaluates to the `Option<Value>` for the fields bound by `bindings`
+fn to_fields(fields: &Fields, idents: &[Ident]) -> Result<TokenStream2> {
+    Ok(match fields {
+        Fields::Named(fields) => {
+            let fields = fields
+                .named
+                .iter()
+                .zip(idents)
+                .map(|(field, binding)| {
+                    let ident = field.ident.as_ref().expect("named field");
+                    let options = Options::field(&field.attrs)?;
+                    let key = options.key(ident);
+
+                    let set = match &options.comment {
+                        Some(comment) => quote! {
+                            let mut entry = ::pcf::value::Entry::new(
+                                value,
+                                ::core::default::Default::default(),
+                                ::core::default::Default::default(),
+                            );
+                            entry
+                                .comments
+                                .set(::pcf::value::CommentPosition::Leading, #comment);
+                            table.insert(#key.into(), entry);
+                        },
+                        None => quote!(table.set(#key, value);),
+                    };
+
+                    Ok(quote! {
+                        if let ::core::option::Option::Some(value) =
+                            ::pcf::emit::ToPcf::to_pcf(#binding)
+                        {
+                            #set
+                        }
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            quote! {{
+                let mut table = ::pcf::value::Table::new();
+                #(#fields)*
+                ::core::option::Option::Some(::pcf::value::Value::Table(table))
+            }}
+        }
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+            quote!(::pcf::emit::ToPcf::to_pcf(#(#idents)*))
+        }
+        Fields::Unnamed(_) => quote! {
+            ::core::option::Option::Some(::pcf::value::Value::Array(
+                [#(::pcf::emit::ToPcf::to_pcf(#idents)),*]
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+            ))
+        },
+        Fields::Unit => quote!(::core::option::Option::None),
+    })
+}
+
+fn to_variants(input: &DeriveInput, data: &syn::DataEnum) -> Result<TokenStream2> {
+    let tagging = Options::tagging(input)?;
+
+    let arms = data
+        .variants
+        .iter()
+        .map(|variant| {
+            let ident = &variant.ident;
+            let name = Options::variant(&variant.attrs)?.key(ident);
+            let (pattern, idents) = bindings(&variant.fields);
+            let value = to_fields(&variant.fields, &idents)?;
+
+            // variants without content are written the same way unit variants are
+            let body = match &tagging {
+                Some((tag, content)) => quote! {
+                    let mut table = ::pcf::value::Table::new();
+                    table.set(#tag, ::pcf::value::Value::String(#name.into()));
+
+                    if let ::core::option::Option::Some(value) = #value {
+                        table.set(#content, value);
+                    }
+
+                    ::core::option::Option::Some(::pcf::value::Value::Table(table))
+                },
+                None => quote! {
+                    match #value {
+                        ::core::option::Option::Some(value) => {
+                            let mut table = ::pcf::value::Table::new();
+                            table.set(#name, value);
+                            ::core::option::Option::Some(::pcf::value::Value::Table(table))
+                        }
+                        ::core::option::Option::None => ::core::option::Option::Some(
+                            ::pcf::value::Value::String(#name.into()),
+                        ),
+                    }
+                },
+            };
+
+            Ok(quote!(Self::#ident #pattern => { #body }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        match self {
+            #(#arms)*
+        }
+    })
+}
+
+fn derive_to_pcf(input: DeriveInput) -> Result<TokenStream2> {
+    let name = &input.ident;
+    let generics = bound(&input.generics, quote!(::pcf::emit::ToPcf));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            Options::parse(&input.attrs, &[])?;
+            let (pattern, idents) = bindings(&data.fields);
+            let value = to_fields(&data.fields, &idents)?;
+            quote!(let Self #pattern = self; #value)
+        }
+        Data::Enum(data) => to_variants(&input, data)?,
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                input.span(),
+                "ToPcf cannot be derived for unions",
+            ));
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::pcf::emit::ToPcf for #name #ty_generics #where_clause {
+            fn to_pcf(&self) -> ::core::option::Option<::pcf::value::Value> {
+                #body
+            }
+        }
+    })
+}
+
+#[proc_macro_derive(FromPcf, attributes(pcf))]
+pub fn from_pcf(input: TokenStream) -> TokenStream {
+    derive_from_pcf(parse_macro_input!(input as DeriveInput))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(ToPcf, attributes(pcf))]
+pub fn to_pcf(input: TokenStream) -> TokenStream {
+    derive_to_pcf(parse_macro_input!(input as DeriveInput))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,144 @@
+use crate::{emit, lex::*, utils::*, value::*};
+use alloc::string::String;
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+// nesting is capped so that long inputs grow documents wider rather than deeper
+const MAX_DEPTH: usize = 4;
+
+fn value(u: &mut Unstructured<'_>, depth: usize) -> Result<Value> {
+    let kinds = match depth < MAX_DEPTH {
+        true => 6,
+        false => 4,
+    };
+
+    Ok(match u.int_in_range(0..=kinds)? {
+        0 => Value::String(u.arbitrary()?),
+        1 => Value::Bytes(u.arbitrary()?),
+        2 => Value::Integer(u.arbitrary()?),
+        3 => Value::Float(emit::round_trip_float(u.arbitrary()?)),
+        4 => Value::Bool(u.arbitrary()?),
+        5 => Value::Array(
+            (0..u.arbitrary_len::<Value>()?)
+                .map(|_| value(u, depth + 1))
+                .collect::<Result<_>>()?,
+        ),
+        _ => Value::Table(table(u, depth + 1)?),
+    })
+}
+
+fn table(u: &mut Unstructured<'_>, depth: usize) -> Result<Table> {
+    let mut table = Table::new();
+
+    // pcf has no empty keys, so those entries are dropped to keep every table representable,
+    // and keys are normalized the way bare keys are when they're read back
+    for _ in 0..u.arbitrary_len::<(String, Value)>()? {
+        let key = normalize_identifier(u.arbitrary()?);
+        let value = value(u, depth)?;
+
+        if !key.is_empty() {
+            table.set(&key, value);
+        }
+    }
+
+    Ok(table)
+}
+
+// comments end at the line break, so they are generated without any
+fn comment(u: &mut Unstructured<'_>) -> Result<String> {
+    let comment: String = u.arbitrary()?;
+    Ok(comment.replace(['\n', '\r'], " "))
+}
+
+fn span(u: &mut Unstructured<'_>) -> Result<Span> {
+    let (line, col, offset, len): (u16, u16, u32, u16) = u.arbitrary()?;
+    let (line, col, offset, len) = (
+        usize::from(line) + 1,
+        usize::from(col) + 1,
+        offset as usize,
+        usize::from(len),
+    );
+
+    Ok(Span {
+        begin: Location::new(line, col, offset),
+        end: Location::new(line, col + len, offset + len),
+    })
+}
+
+impl<'a> Arbitrary<'a> for Value {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        value(u, 0)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Table {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        table(u, 0)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Document {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut document = Document::new(u.arbitrary()?);
+
+        for _ in 0..u.arbitrary_len::<String>()? {
+            document.comments.push(comment(u)?);
+        }
+
+        Ok(document)
+    }
+}
+
+impl<'a> Arbitrary<'a> for LexemeKind {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=20)? {
+            0 => Self::String(u.arbitrary()?),
+            1 => Self::Bytes(u.arbitrary()?),
+            2 => Self::Ident(u.arbitrary()?),
+            3 => Self::Integer(u.arbitrary()?),
+            4 => Self::Float(u.arbitrary()?),
+            5 => Self::Bool(u.arbitrary()?),
+            6 => Self::LBrack,
+            7 => Self::RBrack,
+            8 => Self::LBrace,
+            9 => Self::RBrace,
+            10 => Self::Equal,
+            11 => Self::Comma,
+            12 => Self::Dot,
+            13 => Self::Plus,
+            14 => Self::Minus,
+            15 => Self::Star,
+            16 => Self::Slash,
+            17 => Self::Ampersand,
+            18 => Self::Separator,
+            19 => Self::Comment(comment(u)?),
+            _ => Self::Directive(u.arbitrary()?),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Lexeme {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self {
+            kind: u.arbitrary()?,
+            span: span(u)?,
+        })
+    }
+}
+
+// the text is an emitted arbitrary document, so it always parses back to `document`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidSource {
+    pub text: String,
+    pub document: Document,
+}
+
+impl<'a> Arbitrary<'a> for ValidSource {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let document: Document = u.arbitrary()?;
+
+        Ok(Self {
+            text: emit::to_string(&document),
+            document,
+        })
+    }
+}
@@ -0,0 +1,433 @@
+use crate::{
+    utils::Span,
+    value::{Entry as TableEntry, Table, Value, ValueKind},
+};
+use alloc::{collections::btree_map::BTreeMap, string::String, vec::Vec};
+use core::{fmt, ops::Range};
+
+pub(crate) const ROOT: usize = 0;
+
+// children are linked through their siblings rather than kept in a vec of their own, since a
+// section can reopen a table long after its first entries were read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Children {
+    first: Option<usize>,
+    last: Option<usize>,
+    len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    String(Range<usize>),
+    Bytes(Range<usize>),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Children),
+    Table(Children),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Slot {
+    node: Node,
+    span: Span,
+    // the next element when this is in an array
+    next: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    key: Range<usize>,
+    key_span: Span,
+    value: usize,
+    next: Option<usize>,
+    // the next entry of the same table whose key hashes the same
+    collision: Option<usize>,
+}
+
+// a whole document in a handful of buffers: every string and key shares one text buffer, every
+// value and entry one vec each, so the lot is freed at once; it's read-only, `to_table` makes the
+// usual tree when one is needed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arena {
+    slots: Vec<Slot>,
+    entries: Vec<Entry>,
+    text: String,
+    bytes: Vec<u8>,
+    index: BTreeMap<(usize, u64), usize>,
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn hash(key: &str) -> u64 {
+    key.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Self {
+            slots: alloc::vec![Slot {
+                node: Node::Table(Children::default()),
+                span: Span::default(),
+                next: None,
+            }],
+            entries: Vec::new(),
+            text: String::new(),
+            bytes: Vec::new(),
+            index: BTreeMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> ArenaTable<'_> {
+        ArenaTable {
+            arena: self,
+            node: ROOT,
+        }
+    }
+
+    fn slot(&mut self, node: Node, span: Span) -> usize {
+        self.slots.push(Slot {
+            node,
+            span,
+            next: None,
+        });
+        self.slots.len() - 1
+    }
+
+    fn children(&self, node: usize) -> Option<Children> {
+        match self.slots.get(node)?.node {
+            Node::Array(children) | Node::Table(children) => Some(children),
+            _ => None,
+        }
+    }
+
+    fn children_mut(&mut self, node: usize) -> Option<&mut Children> {
+        match &mut self.slots.get_mut(node)?.node {
+            Node::Array(children) | Node::Table(children) => Some(children),
+            _ => None,
+        }
+    }
+
+    fn view(&self, node: usize) -> ArenaValue<'_> {
+        match &self.slots[node].node {
+            Node::String(range) => ArenaValue::String(&self.text[range.clone()]),
+            Node::Bytes(range) => ArenaValue::Bytes(&self.bytes[range.clone()]),
+            Node::Integer(int) => ArenaValue::Integer(*int),
+            Node::Float(float) => ArenaValue::Float(*float),
+            Node::Bool(bool) => ArenaValue::Bool(*bool),
+            Node::Array(_) => ArenaValue::Array(ArenaArray { arena: self, node }),
+            Node::Table(_) => ArenaValue::Table(ArenaTable { arena: self, node }),
+        }
+    }
+
+    pub(crate) fn key(&mut self, key: &str) -> Range<usize> {
+        let begin = self.text.len();
+        self.text.push_str(key);
+        begin..self.text.len()
+    }
+
+    pub(crate) fn value(&mut self, value: &Value, span: Span) -> usize {
+        let node = match value {
+            Value::String(string) => Node::String(self.key(string)),
+            Value::Bytes(bytes) => {
+                let begin = self.bytes.len();
+                self.bytes.extend_from_slice(bytes);
+                Node::Bytes(begin..self.bytes.len())
+            }
+            Value::Integer(int) => Node::Integer(*int),
+            Value::Float(float) => Node::Float(*float),
+            Value::Bool(bool) => Node::Bool(*bool),
+            Value::Array(array) => {
+                let node = self.container(false, span);
+
+                for element in array {
+                    let element = self.value(element, span);
+                    self.push(node, element);
+                }
+
+                return node;
+            }
+            Value::Table(table) => {
+                let node = self.container(true, span);
+
+                for (key, entry) in table.iter() {
+                    let value = self.value(&entry.value, entry.span);
+                    let key = self.key(key);
+                    self.add(node, key, entry.key_span, value);
+                }
+
+                return node;
+            }
+        };
+
+        self.slot(node, span)
+    }
+
+    pub(crate) fn container(&mut self, table: bool, span: Span) -> usize {
+        let children = Children::default();
+        self.slot(
+            match table {
+                true => Node::Table(children),
+                false => Node::Array(children),
+            },
+            span,
+        )
+    }
+
+    pub(crate) fn close(&mut self, node: usize, open: Span, close: Span) {
+        // a merged table keeps the span it was first opened with
+        if let Some(slot) = self.slots.get_mut(node).filter(|s| s.span == open) {
+            slot.span.end = close.end;
+        }
+    }
+
+    pub(crate) fn is_table(&self, node: usize) -> bool {
+        matches!(self.slots[node].node, Node::Table(_))
+    }
+
+    pub(crate) fn is_array(&self, node: usize) -> bool {
+        matches!(self.slots[node].node, Node::Array(_))
+    }
+
+    pub(crate) fn last(&self, array: usize) -> Option<usize> {
+        self.children(array)?.last
+    }
+
+    pub(crate) fn element(&self, array: usize, index: usize) -> Option<usize> {
+        let first = self.children(array)?.first;
+        core::iter::successors(first, |node| self.slots[*node].next).nth(index)
+    }
+
+    pub(crate) fn push(&mut self, array: usize, value: usize) {
+        let Some(children) = self.children_mut(array) else {
+            return;
+        };
+        let last = children.last.replace(value);
+        children.first.get_or_insert(value);
+        children.len += 1;
+
+        if let Some(last) = last {
+            self.slots[last].next = Some(value);
+        }
+    }
+
+    pub(crate) fn find(&self, table: usize, key: &str) -> Option<usize> {
+        let mut found = self.index.get(&(table, hash(key))).copied();
+
+        while let Some(entry) = found {
+            if self.text[self.entries[entry].key.clone()] == *key {
+                return Some(entry);
+            }
+
+            found = self.entries[entry].collision;
+        }
+
+        None
+    }
+
+    pub(crate) fn text(&self, range: Range<usize>) -> &str {
+        &self.text[range]
+    }
+
+    pub(crate) fn entry(&self, entry: usize) -> (Range<usize>, Span, usize) {
+        let entry = &self.entries[entry];
+        (entry.key.clone(), entry.key_span, entry.value)
+    }
+
+    pub(crate) fn first_entry(&self, table: usize) -> Option<usize> {
+        self.children(table).filter(|_| self.is_table(table))?.first
+    }
+
+    pub(crate) fn next_entry(&self, entry: usize) -> Option<usize> {
+        self.entries[entry].next
+    }
+
+    pub(crate) fn add(
+        &mut self,
+        table: usize,
+        key: Range<usize>,
+        key_span: Span,
+        value: usize,
+    ) -> usize {
+        let id = self.entries.len();
+        let collision = self
+            .index
+            .insert((table, hash(&self.text[key.clone()])), id);
+
+        self.entries.push(Entry {
+            key,
+            key_span,
+            value,
+            next: None,
+            collision,
+        });
+
+        if let Some(children) = self.children_mut(table) {
+            let last = children.last.replace(id);
+            children.first.get_or_insert(id);
+            children.len += 1;
+
+            if let Some(last) = last {
+                self.entries[last].next = Some(id);
+            }
+        }
+
+        id
+    }
+
+    pub(crate) fn replace(&mut self, entry: usize, key_span: Span, value: usize) {
+        let entry = &mut self.entries[entry];
+        entry.key_span = key_span;
+        entry.value = value;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ArenaValue<'a> {
+    String(&'a str),
+    Bytes(&'a [u8]),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Array(ArenaArray<'a>),
+    Table(ArenaTable<'a>),
+}
+
+impl ArenaValue<'_> {
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            Self::String(_) => ValueKind::String,
+            Self::Bytes(_) => ValueKind::Bytes,
+            Self::Integer(_) => ValueKind::Integer,
+            Self::Float(_) => ValueKind::Float,
+            Self::Bool(_) => ValueKind::Bool,
+            Self::Array(_) => ValueKind::Array,
+            Self::Table(_) => ValueKind::Table,
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match *self {
+            Self::String(string) => Value::String(string.into()),
+            Self::Bytes(bytes) => Value::Bytes(bytes.into()),
+            Self::Integer(int) => Value::Integer(int),
+            Self::Float(float) => Value::Float(float),
+            Self::Bool(bool) => Value::Bool(bool),
+            Self::Array(array) => Value::Array(array.iter().map(|v| v.to_value()).collect()),
+            Self::Table(table) => Value::Table(table.to_table()),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ArenaArray<'a> {
+    arena: &'a Arena,
+    node: usize,
+}
+
+impl<'a> ArenaArray<'a> {
+    pub fn len(&self) -> usize {
+        self.arena.children(self.node).map_or(0, |c| c.len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn span(&self) -> Span {
+        self.arena.slots[self.node].span
+    }
+
+    pub fn get(&self, index: usize) -> Option<ArenaValue<'a>> {
+        self.iter().nth(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ArenaValue<'a>> + use<'a> {
+        let arena = self.arena;
+        let first = arena.children(self.node).and_then(|c| c.first);
+
+        core::iter::successors(first, move |node| arena.slots[*node].next)
+            .map(move |node| arena.view(node))
+    }
+}
+
+impl fmt::Debug for ArenaArray<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ArenaTable<'a> {
+    arena: &'a Arena,
+    node: usize,
+}
+
+impl<'a> ArenaTable<'a> {
+    pub fn len(&self) -> usize {
+        self.arena.children(self.node).map_or(0, |c| c.len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn span(&self) -> Span {
+        self.arena.slots[self.node].span
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.arena.find(self.node, key).is_some()
+    }
+
+    pub fn get(&self, key: &str) -> Option<ArenaValue<'a>> {
+        let (_, _, value) = self.arena.entry(self.arena.find(self.node, key)?);
+        Some(self.arena.view(value))
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (&'a str, Span, usize)> + use<'a> {
+        let arena = self.arena;
+
+        core::iter::successors(arena.first_entry(self.node), move |entry| {
+            arena.next_entry(*entry)
+        })
+        .map(move |entry| {
+            let (key, key_span, value) = arena.entry(entry);
+            (arena.text(key), key_span, value)
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, ArenaValue<'a>)> + use<'a> {
+        let arena = self.arena;
+        self.entries()
+            .map(move |(key, _, value)| (key, arena.view(value)))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &'a str> + use<'a> {
+        self.entries().map(|(key, _, _)| key)
+    }
+
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+
+        for (key, key_span, value) in self.entries() {
+            let span = self.arena.slots[value].span;
+            let value = self.arena.view(value).to_value();
+            table.insert(key.into(), TableEntry::new(value, key_span, span));
+        }
+
+        table
+    }
+}
+
+impl fmt::Debug for ArenaTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{
+    messages::{self, English, Messages},
+    utils::{Error, Label as Labelled, Span},
+};
+use ::ariadne::{Label, Report, ReportBuilder, ReportKind, Source};
+use core::ops::Range;
+use std::io;
+
+pub type ReportSpan<'a> = (&'a str, Range<usize>);
+
+impl<'a> Error<'a> {
+    // ariadne counts in chars by default, so spans are converted rather than switching its index
+    // type, which a caller's own config would silently undo
+    fn range(&self, span: Span) -> ReportSpan<'a> {
+        let chars = |offset: usize| {
+            self.src
+                .content
+                .get(..offset)
+                .map_or(offset, |s| s.chars().count())
+        };
+
+        (
+            self.src.file,
+            chars(span.begin.offset)..chars(span.end.offset),
+        )
+    }
+
+    pub fn report(&self) -> ReportBuilder<'a, ReportSpan<'a>> {
+        self.report_with(&English)
+    }
+
+    pub fn report_with(&self, messages: &dyn Messages) -> ReportBuilder<'a, ReportSpan<'a>> {
+        let mut labels = alloc::vec![(self.span, Labelled::Here)];
+        labels.extend(self.kind.related());
+        // labels out of source order are drawn as separate groups
+        labels.sort_by_key(|(span, _)| *span);
+
+        let mut report = Report::build(ReportKind::Error, self.range(self.span))
+            .with_code(self.kind.code())
+            .with_message(messages::text(|f| messages.error(f, &self.kind)))
+            .with_labels(labels.into_iter().map(|(span, label)| {
+                Label::new(self.range(span)).with_message(messages.label(label))
+            }));
+
+        if let Some(note) = self.note() {
+            report.add_note(messages::text(|f| messages.hint(f, note)));
+        }
+
+        // the cache only holds this error's own file, so include sites are listed as notes
+        for included in self.included() {
+            report.add_note(messages::text(|f| {
+                messages.included(f, &included.file, included.span)
+            }));
+        }
+
+        if let Some(help) = self.help() {
+            report.set_help(messages::text(|f| messages.hint(f, help)));
+        }
+
+        report
+    }
+
+    pub fn cache(&self) -> (&'a str, Source<&'a str>) {
+        (self.src.file, Source::from(self.src.content.as_str()))
+    }
+
+    pub fn write_report(&self, w: impl io::Write) -> io::Result<()> {
+        self.report().finish().write(self.cache(), w)
+    }
+}
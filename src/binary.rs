@@ -0,0 +1,241 @@
+use crate::{utils::Span, value::*};
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+const MAGIC: &[u8; 4] = b"PCF\x01";
+const MAX_DEPTH: usize = 128;
+
+const STRING: u8 = 0;
+const BYTES: u8 = 1;
+const INTEGER: u8 = 2;
+const FLOAT: u8 = 3;
+const FALSE: u8 = 4;
+const TRUE: u8 = 5;
+const ARRAY: u8 = 6;
+const TABLE: u8 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    DuplicateKey,
+    InvalidMagic,
+    InvalidTag(u8),
+    InvalidUtf8,
+    NestingTooDeep,
+    Overflow,
+    TrailingBytes,
+    UnexpectedEnd,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateKey => write!(f, "encountered duplicate key during decoding"),
+            Self::InvalidMagic => write!(f, "encountered invalid header during decoding"),
+            Self::InvalidTag(tag) => write!(f, "encountered invalid tag {} during decoding", tag),
+            Self::InvalidUtf8 => write!(f, "encountered invalid utf-8 during decoding"),
+            Self::NestingTooDeep => write!(f, "encountered excessive nesting during decoding"),
+            Self::Overflow => write!(f, "encountered overflowing length during decoding"),
+            Self::TrailingBytes => write!(f, "encountered trailing bytes during decoding"),
+            Self::UnexpectedEnd => write!(f, "encountered unexpected end during decoding"),
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+
+    out.push(n as u8);
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn write_table(out: &mut Vec<u8>, table: &Table) {
+    write_varint(out, table.len() as u64);
+
+    for (key, entry) in table.iter() {
+        write_bytes(out, key.as_bytes());
+        write_value(out, &entry.value);
+    }
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::String(s) => {
+            out.push(STRING);
+            write_bytes(out, s.as_bytes());
+        }
+        Value::Bytes(b) => {
+            out.push(BYTES);
+            write_bytes(out, b);
+        }
+        Value::Integer(i) => {
+            out.push(INTEGER);
+            // zigzag keeps small negative numbers short
+            write_varint(out, ((i << 1) ^ (i >> 63)) as u64);
+        }
+        Value::Float(float) => {
+            out.push(FLOAT);
+            out.extend_from_slice(&float.to_le_bytes());
+        }
+        Value::Bool(b) => out.push(if *b { TRUE } else { FALSE }),
+        Value::Array(array) => {
+            out.push(ARRAY);
+            write_varint(out, array.len() as u64);
+
+            for value in array {
+                write_value(out, value);
+            }
+        }
+        Value::Table(table) => {
+            out.push(TABLE);
+            write_table(out, table);
+        }
+    }
+}
+
+pub fn encode_into(out: &mut Vec<u8>, document: &Document) {
+    out.extend_from_slice(MAGIC);
+    write_table(out, &document.root);
+}
+
+pub fn encode(document: &Document) -> Vec<u8> {
+    let mut out = Vec::new();
+    encode_into(&mut out, document);
+    out
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+}
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, len: usize) -> Result<&'b [u8], DecodeError> {
+        if len > self.bytes.len() {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut n = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            n |= u64::from(byte & 0x7f)
+                .checked_shl(shift)
+                .filter(|v| v >> shift == u64::from(byte & 0x7f))
+                .ok_or(DecodeError::Overflow)?;
+
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+
+        Err(DecodeError::Overflow)
+    }
+
+    fn len(&mut self) -> Result<usize, DecodeError> {
+        let len = usize::try_from(self.varint()?).map_err(|_| DecodeError::Overflow)?;
+
+        // every element takes at least one byte, which bounds allocations on corrupt input
+        match len > self.bytes.len() {
+            true => Err(DecodeError::UnexpectedEnd),
+            false => Ok(len),
+        }
+    }
+
+    fn bytes(&mut self) -> Result<&'b [u8], DecodeError> {
+        let len = self.len()?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        let bytes = self.bytes()?;
+        core::str::from_utf8(bytes)
+            .map(String::from)
+            .map_err(|_| DecodeError::InvalidUtf8)
+    }
+
+    fn table(&mut self, depth: usize) -> Result<Table, DecodeError> {
+        let len = self.len()?;
+        let mut table = Table::new();
+
+        for _ in 0..len {
+            let key = self.string()?;
+            let value = self.value(depth + 1)?;
+
+            if table
+                .insert(key, Entry::new(value, Span::default(), Span::default()))
+                .is_some()
+            {
+                return Err(DecodeError::DuplicateKey);
+            }
+        }
+
+        Ok(table)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, DecodeError> {
+        if depth > MAX_DEPTH {
+            return Err(DecodeError::NestingTooDeep);
+        }
+
+        Ok(match self.byte()? {
+            STRING => Value::String(self.string()?),
+            BYTES => Value::Bytes(self.bytes()?.into()),
+            INTEGER => {
+                let n = self.varint()?;
+                Value::Integer((n >> 1) as i64 ^ -((n & 1) as i64))
+            }
+            FLOAT => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(self.take(8)?);
+                Value::Float(f64::from_le_bytes(bytes))
+            }
+            FALSE => Value::Bool(false),
+            TRUE => Value::Bool(true),
+            ARRAY => {
+                let len = self.len()?;
+                let mut array = Vec::with_capacity(len);
+
+                for _ in 0..len {
+                    array.push(self.value(depth + 1)?);
+                }
+
+                Value::Array(array)
+            }
+            TABLE => Value::Table(self.table(depth)?),
+            tag => return Err(DecodeError::InvalidTag(tag)),
+        })
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Document, DecodeError> {
+    let mut reader = Reader { bytes };
+
+    if reader.take(MAGIC.len()).ok() != Some(MAGIC) {
+        return Err(DecodeError::InvalidMagic);
+    }
+
+    let root = reader.table(0)?;
+
+    match reader.bytes.is_empty() {
+        true => Ok(Document::new(root)),
+        false => Err(DecodeError::TrailingBytes),
+    }
+}
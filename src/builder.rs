@@ -0,0 +1,112 @@
+use crate::{path::*, utils::*, value::*};
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildErrorKind {
+    InvalidKey,
+    DuplicateKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildError {
+    pub path: KeyPath,
+    pub kind: BuildErrorKind,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {}",
+            self.path,
+            match self.kind {
+                BuildErrorKind::InvalidKey => "encountered invalid key during building",
+                BuildErrorKind::DuplicateKey => "encountered duplicate key during building",
+            }
+        )
+    }
+}
+
+impl core::error::Error for BuildError {}
+
+pub fn is_valid_key(key: &str) -> bool {
+    !key.chars().any(|c| c == '"' || c.is_control())
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct Builder {
+    table: Table,
+    error: Option<BuildError>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.insert(key, value.into());
+        self
+    }
+
+    pub fn array<V: Into<Value>>(self, key: &str, values: impl IntoIterator<Item = V>) -> Self {
+        let values: Vec<Value> = values.into_iter().map(Into::into).collect();
+        self.set(key, values)
+    }
+
+    pub fn table(mut self, key: &str, f: impl FnOnce(Builder) -> Builder) -> Self {
+        match f(Builder::new()).build_table() {
+            Ok(table) => self.insert(key, Value::Table(table)),
+            Err(mut error) if self.error.is_none() => {
+                let mut path = KeyPath::new();
+                path.push(PathSegment::Key(key.into()));
+                path.extend(error.path.segments().iter().cloned());
+                error.path = path;
+                self.error = Some(error);
+            }
+            Err(_) => {}
+        }
+
+        self
+    }
+
+    pub fn build_table(self) -> core::result::Result<Table, BuildError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.table),
+        }
+    }
+
+    pub fn build(self) -> core::result::Result<Document, BuildError> {
+        self.build_table().map(Document::new)
+    }
+
+    fn insert(&mut self, key: &str, value: Value) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let kind = if !is_valid_key(key) {
+            BuildErrorKind::InvalidKey
+        } else if self.table.contains_key(key) {
+            BuildErrorKind::DuplicateKey
+        } else {
+            self.table.insert(
+                String::from(key),
+                Entry::new(value, Span::default(), Span::default()),
+            );
+            return;
+        };
+
+        let mut path = KeyPath::new();
+        path.push(PathSegment::Key(key.into()));
+        self.error = Some(BuildError { path, kind });
+    }
+}
+
+impl Document {
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{
+    diagnostic::Diagnostic,
+    emit,
+    lex::LexemeStream,
+    parse::parse_tokens,
+    utils::{Error, ErrorKind, RenderOptions, Source, Span},
+};
+use alloc::string::String;
+use core::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Lex,
+    Parse,
+    Render,
+    Emit,
+    Reparse,
+}
+
+// what one input did; rejecting an input is a fine outcome, the rest point at a bug in pcf
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Accepted,
+    Rejected { kind: ErrorKind, span: Span },
+    // the document was emitted as text that doesn't parse back to it
+    Mismatch { emitted: String },
+    // only caught with `std`, without it the panic unwinds out of the check as usual
+    Panicked { stage: Stage, message: String },
+}
+
+impl Outcome {
+    pub fn is_bug(&self) -> bool {
+        matches!(self, Self::Mismatch { .. } | Self::Panicked { .. })
+    }
+}
+
+#[cfg(feature = "std")]
+fn guard<T>(stage: Stage, f: impl FnOnce() -> T) -> Result<T, Outcome> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(|payload| {
+        let message = match payload.downcast::<&str>() {
+            Ok(message) => String::from(*message),
+            Err(payload) => payload.downcast::<String>().map_or_else(
+                |_| String::from("panicked with a non-string payload"),
+                |message| *message,
+            ),
+        };
+
+        Outcome::Panicked { stage, message }
+    })
+}
+
+#[cfg(not(feature = "std"))]
+fn guard<T>(_: Stage, f: impl FnOnce() -> T) -> Result<T, Outcome> {
+    Ok(f())
+}
+
+// an error's every rendering is drawn too, since those slice the source by the error's spans
+fn rejected(error: Error<'_>) -> Outcome {
+    let (kind, span) = (error.kind, error.span);
+    let rendered = guard(Stage::Render, || {
+        let mut out = String::new();
+        let _ = error.render(&mut out, &RenderOptions::new().context(true));
+        let _ = write!(out, "{}", error.clone().into_owned());
+        let _ = write!(out, "{}", Diagnostic::from(error));
+    });
+
+    match rendered {
+        Ok(()) => Outcome::Rejected { kind, span },
+        Err(outcome) => outcome,
+    }
+}
+
+fn outcome(result: Result<Outcome, Outcome>) -> Outcome {
+    result.unwrap_or_else(|outcome| outcome)
+}
+
+pub fn lex(input: &str) -> Outcome {
+    let src = Source::new("check", input.into());
+
+    outcome(
+        guard(Stage::Lex, || crate::lex::lex(&src)).map(|result| match result {
+            Ok(_) => Outcome::Accepted,
+            Err(error) => rejected(error),
+        }),
+    )
+}
+
+pub fn parse(input: &str) -> Outcome {
+    let src = Source::new("check", input.into());
+
+    outcome(
+        guard(Stage::Parse, || crate::parse(&src)).map(|result| match result {
+            Ok(_) => Outcome::Accepted,
+            Err(error) => rejected(error),
+        }),
+    )
+}
+
+// tokens from anywhere, with spans that needn't point into `input` or come in order
+pub fn tokens(input: &str, lexemes: LexemeStream) -> Outcome {
+    let src = Source::new("check", input.into());
+
+    outcome(
+        guard(Stage::Parse, || parse_tokens(lexemes, &src)).map(|result| match result {
+            Ok(_) => Outcome::Accepted,
+            Err(error) => rejected(error),
+        }),
+    )
+}
+
+// whatever parses has to come back the same from the text it's emitted as
+pub fn round_trip(input: &str) -> Outcome {
+    let src = Source::new("check", input.into());
+
+    let document = match guard(Stage::Parse, || crate::parse(&src)) {
+        Ok(Ok(document)) => document,
+        Ok(Err(error)) => return rejected(error),
+        Err(outcome) => return outcome,
+    };
+
+    outcome(
+        guard(Stage::Emit, || emit::to_string(&document)).and_then(|emitted| {
+            let reparsed = Source::new("check", emitted.clone());
+
+            guard(Stage::Reparse, || match crate::parse(&reparsed) {
+                // a comment after the last entry can come back as the document's rather than the
+                // entry's, so only the values are compared
+                Ok(again) if again.root == document.root => Outcome::Accepted,
+                _ => Outcome::Mismatch {
+                    emitted: emitted.clone(),
+                },
+            })
+        }),
+    )
+}
+
+// runs every check on the input, reporting the first bug or else how parsing went
+pub fn never_panics(input: &str) -> Outcome {
+    match lex(input) {
+        outcome if outcome.is_bug() => outcome,
+        _ => round_trip(input),
+    }
+}
@@ -0,0 +1,51 @@
+use crate::value::*;
+use alloc::{format, string::String};
+
+impl Value {
+    pub fn coerce_i64(&self) -> Option<i64> {
+        match self {
+            Self::Integer(i) => Some(*i),
+            Self::Float(f) if *f >= i64::MIN as f64 && *f < i64::MAX as f64 => {
+                let i = *f as i64;
+                (i as f64 == *f).then_some(i)
+            }
+            Self::Bool(b) => Some(*b as i64),
+            Self::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn coerce_f64(&self) -> Option<f64> {
+        match self {
+            Self::Float(f) => Some(*f),
+            Self::Integer(i) => Some(*i as f64),
+            Self::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn coerce_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(b) => Some(*b),
+            Self::Integer(0) => Some(false),
+            Self::Integer(1) => Some(true),
+            Self::String(s) => match s.trim().to_ascii_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => Some(true),
+                "false" | "no" | "off" | "0" => Some(false),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn coerce_string(&self) -> Option<String> {
+        match self {
+            Self::String(s) => Some(s.clone()),
+            Self::Bytes(b) => String::from_utf8(b.clone()).ok(),
+            Self::Integer(i) => Some(format!("{}", i)),
+            Self::Float(f) => Some(format!("{}", f)),
+            Self::Bool(b) => Some(format!("{}", b)),
+            _ => None,
+        }
+    }
+}
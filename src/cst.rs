@@ -0,0 +1,951 @@
+use crate::{
+    diff::*, emit::write_key, lex::*, parse::parse_recovering, path::*, utils::*, value::*,
+};
+use alloc::{collections::vec_deque::VecDeque, format, string::String, vec::Vec};
+use core::fmt::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxKind {
+    Whitespace,
+    Newline,
+    Comment,
+    String,
+    Bytes,
+    Ident,
+    Integer,
+    Float,
+    Bool,
+    LBrack,
+    RBrack,
+    LBrace,
+    RBrace,
+    Equal,
+    Comma,
+    Dot,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Ampersand,
+    Separator,
+    Directive,
+    Alias,
+    Anchor,
+    Document,
+    Header,
+    Include,
+    ArrayHeader,
+    Entry,
+    Expression,
+    Key,
+    Array,
+    Table,
+}
+
+impl SyntaxKind {
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Self::Whitespace | Self::Newline | Self::Comment)
+    }
+}
+
+impl From<TokenKind> for SyntaxKind {
+    fn from(token: TokenKind) -> Self {
+        match token {
+            TokenKind::String => Self::String,
+            TokenKind::Bytes => Self::Bytes,
+            TokenKind::Ident => Self::Ident,
+            TokenKind::Integer => Self::Integer,
+            TokenKind::Float => Self::Float,
+            TokenKind::Bool => Self::Bool,
+            TokenKind::LBrack => Self::LBrack,
+            TokenKind::RBrack => Self::RBrack,
+            TokenKind::LBrace => Self::LBrace,
+            TokenKind::RBrace => Self::RBrace,
+            TokenKind::Equal => Self::Equal,
+            TokenKind::Comma => Self::Comma,
+            TokenKind::Dot => Self::Dot,
+            TokenKind::Plus => Self::Plus,
+            TokenKind::Minus => Self::Minus,
+            TokenKind::Star => Self::Star,
+            TokenKind::Slash => Self::Slash,
+            TokenKind::Ampersand => Self::Ampersand,
+            TokenKind::Separator => Self::Separator,
+            TokenKind::Comment => Self::Comment,
+            TokenKind::Directive => Self::Directive,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxToken {
+    pub kind: SyntaxKind,
+    pub text: String,
+}
+
+impl SyntaxToken {
+    pub fn new(kind: SyntaxKind, text: String) -> Self {
+        Self { kind, text }
+    }
+}
+
+impl fmt::Display for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxElement {
+    pub fn kind(&self) -> SyntaxKind {
+        match self {
+            Self::Node(node) => node.kind,
+            Self::Token(token) => token.kind,
+        }
+    }
+}
+
+impl fmt::Display for SyntaxElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Node(node) => write!(f, "{}", node),
+            Self::Token(token) => write!(f, "{}", token),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    pub fn new(kind: SyntaxKind) -> Self {
+        Self {
+            kind,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|c| match c {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = &SyntaxToken> {
+        self.children.iter().filter_map(|c| match c {
+            SyntaxElement::Token(token) => Some(token),
+            SyntaxElement::Node(_) => None,
+        })
+    }
+
+    pub fn key(&self) -> Option<Vec<String>> {
+        let key = self.nodes().find(|n| n.kind == SyntaxKind::Key)?;
+
+        Some(
+            key.tokens()
+                .filter_map(|t| match t.kind {
+                    SyntaxKind::Ident => Some(t.text.clone()),
+                    SyntaxKind::String => Some(unquote(&t.text).into()),
+                    _ => None,
+                })
+                .collect(),
+        )
+    }
+
+    fn value_mut(&mut self) -> Option<&mut SyntaxElement> {
+        let equal = self
+            .children
+            .iter()
+            .position(|c| c.kind() == SyntaxKind::Equal)?;
+
+        self.children[equal + 1..]
+            .iter_mut()
+            .find(|c| !c.kind().is_trivia())
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            write!(f, "{}", child)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cst {
+    root: SyntaxNode,
+}
+
+impl Cst {
+    pub fn parse<'a>(src: &'a Source<'a>) -> Result<'a, Self> {
+        let (_, errors) = parse_recovering(src);
+
+        if let Some(error) = errors
+            .into_iter()
+            .find(|e| e.kind != ErrorKind::Parsing(ParsingError::IncludeNotFound))
+        {
+            return Err(error);
+        }
+
+        Ok(Self {
+            root: Builder::new(tokenize(src)?).document(),
+        })
+    }
+
+    pub fn root(&self) -> &SyntaxNode {
+        &self.root
+    }
+
+    pub fn set(&mut self, path: &str, value: &Value) -> bool {
+        let src = Source::new("", format!("{}", value));
+        let Ok(tokens) = tokenize(&src) else {
+            return false;
+        };
+        let replacement = Builder::new(tokens).value();
+
+        match find_entry(&mut self.root, path).and_then(SyntaxNode::value_mut) {
+            // the anchor stays, so the aliases of it are left to `update` to sort out
+            Some(SyntaxElement::Node(anchor)) if anchor.kind == SyntaxKind::Anchor => {
+                match anchor.children.last_mut() {
+                    Some(target) => *target = replacement,
+                    None => return false,
+                }
+
+                true
+            }
+            Some(target) => {
+                *target = replacement;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, path: &str) -> bool {
+        let Some(address) = locate(&self.root, path) else {
+            return false;
+        };
+        let Some((&index, parents)) = address.split_last() else {
+            return false;
+        };
+        let Some(parent) = node_at(&mut self.root, parents) else {
+            return false;
+        };
+
+        if parent.children.get(index).map(SyntaxElement::kind) != Some(SyntaxKind::Entry) {
+            return false;
+        }
+
+        parent.children.remove(index);
+        let kind = |parent: &SyntaxNode, i: usize| parent.children.get(i).map(SyntaxElement::kind);
+
+        if parent.kind == SyntaxKind::Document {
+            while matches!(
+                kind(parent, index),
+                Some(SyntaxKind::Whitespace | SyntaxKind::Comment)
+            ) {
+                parent.children.remove(index);
+            }
+
+            if kind(parent, index) == Some(SyntaxKind::Newline) {
+                parent.children.remove(index);
+            }
+
+            let mut index = index;
+
+            while index > 0 && kind(parent, index - 1) == Some(SyntaxKind::Whitespace) {
+                index -= 1;
+                parent.children.remove(index);
+            }
+
+            return true;
+        }
+
+        let next = (index..parent.children.len()).find(|&i| !parent.children[i].kind().is_trivia());
+        let previous = (0..index)
+            .rev()
+            .find(|&i| !parent.children[i].kind().is_trivia());
+
+        if let Some(next) = next.filter(|&i| kind(parent, i) == Some(SyntaxKind::Comma)) {
+            parent.children.drain(index..=next);
+
+            while kind(parent, index) == Some(SyntaxKind::Whitespace) {
+                parent.children.remove(index);
+            }
+        } else if let Some(previous) =
+            previous.filter(|&i| kind(parent, i) == Some(SyntaxKind::Comma))
+        {
+            parent.children.drain(previous..index);
+        }
+
+        true
+    }
+
+    pub fn insert(&mut self, path: &str, value: &Value) -> bool {
+        let Ok(mut path) = KeyPath::parse(path) else {
+            return false;
+        };
+        let Some(PathSegment::Key(key)) = path.pop() else {
+            return false;
+        };
+
+        let mut text = String::new();
+        let _ = write_key(&mut text, &key);
+        let _ = write!(text, " = {}", value);
+        let src = Source::new("", text);
+        let Ok(tokens) = tokenize(&src) else {
+            return false;
+        };
+        let entry = SyntaxElement::Node(Builder::new(tokens).entry());
+
+        if !path.is_empty()
+            && let Some(SyntaxElement::Node(table)) =
+                find_entry(&mut self.root, &format!("{}", path)).and_then(SyntaxNode::value_mut)
+        {
+            return table.kind == SyntaxKind::Table && insert_inline(table, entry);
+        }
+
+        let Some(keys) = path
+            .iter()
+            .map(PathSegment::key)
+            .collect::<Option<Vec<_>>>()
+        else {
+            return false;
+        };
+        let children = &mut self.root.children;
+        let kind = |children: &[SyntaxElement], i: usize| children.get(i).map(SyntaxElement::kind);
+
+        let start = match keys.is_empty() {
+            true => 0,
+            false => match children.iter().position(|c| match c {
+                SyntaxElement::Node(node) => {
+                    node.kind == SyntaxKind::Header
+                        && node.key().is_some_and(|k| k.iter().eq(keys.iter()))
+                }
+                SyntaxElement::Token(_) => false,
+            }) {
+                Some(header) => header + 1,
+                None => return false,
+            },
+        };
+        let end = (start..children.len())
+            .find(|&i| {
+                matches!(
+                    kind(children, i),
+                    Some(SyntaxKind::Header | SyntaxKind::ArrayHeader)
+                )
+            })
+            .unwrap_or(children.len());
+
+        let mut at = (start..end)
+            .rev()
+            .find(|&i| kind(children, i) == Some(SyntaxKind::Entry))
+            .map_or(start, |i| i + 1);
+
+        while at < end
+            && matches!(
+                kind(children, at),
+                Some(SyntaxKind::Whitespace | SyntaxKind::Comment)
+            )
+        {
+            at += 1;
+        }
+
+        let newline = || SyntaxElement::Token(SyntaxToken::new(SyntaxKind::Newline, "\n".into()));
+
+        if kind(children, at) == Some(SyntaxKind::Newline) {
+            at += 1;
+        } else if at > 0 {
+            children.insert(at, newline());
+            at += 1;
+        }
+
+        children.insert(at, entry);
+        children.insert(at + 1, newline());
+        true
+    }
+
+    // edits can leave the text meaning something else than the document: an alias repeats
+    // whatever its anchor now holds, and a table emptied of its dotted keys has no text left, so
+    // a second pass writes out whatever still differs and anything further falls back
+    pub fn update(&mut self, document: &Document) -> bool {
+        for _ in 0..2 {
+            if !self.apply(document) {
+                return false;
+            }
+        }
+
+        let src = Source::new("", format!("{}", self));
+        let (updated, errors) = parse_recovering(&src);
+
+        errors
+            .iter()
+            .all(|e| e.kind == ErrorKind::Parsing(ParsingError::IncludeNotFound))
+            && diff(&updated, document).is_empty()
+    }
+
+    fn apply(&mut self, document: &Document) -> bool {
+        let src = Source::new("", format!("{}", self));
+        let (original, _) = parse_recovering(&src);
+
+        for change in diff(&original, document) {
+            let path = format!("{}", change.path());
+            let applied = match &change {
+                Change::Added { value, .. } => self.insert(&path, value),
+                Change::Removed { .. } => self.remove(&path),
+                Change::Modified { new, .. } => self.set(&path, new),
+            };
+
+            if !applied && !self.replace_ancestor(change.path(), document) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    // lines up the `=` of the entries under each header the way `EmitOptions::align` does,
+    // so entries added or renamed by `update` sit in line with the ones kept from the source
+    pub fn align(&mut self) {
+        let children = &mut self.root.children;
+        let mut start = 0;
+
+        while start < children.len() {
+            let end = (start..children.len())
+                .find(|&i| {
+                    matches!(
+                        children[i].kind(),
+                        SyntaxKind::Header | SyntaxKind::ArrayHeader
+                    )
+                })
+                .unwrap_or(children.len());
+
+            align_entries(&mut children[start..end]);
+            start = end + 1;
+        }
+    }
+
+    fn replace_ancestor(&mut self, path: &KeyPath, document: &Document) -> bool {
+        let mut path = path.clone();
+
+        while path.pop().is_some() && !path.is_empty() {
+            if let Some(value) = document.get(&path)
+                && self.set(&format!("{}", path), value)
+            {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+fn align_entries(block: &mut [SyntaxElement]) {
+    let entries = || {
+        block.iter().filter_map(|c| match c {
+            SyntaxElement::Node(node) if node.kind == SyntaxKind::Entry => Some(node),
+            _ => None,
+        })
+    };
+    let width = |entry: &SyntaxNode| match entry.children.first() {
+        Some(key) => format!("{}", key).chars().count(),
+        None => 0,
+    };
+    let Some(widest) = entries().map(width).max() else {
+        return;
+    };
+
+    for child in block.iter_mut() {
+        let SyntaxElement::Node(entry) = child else {
+            continue;
+        };
+        let Some(equal) = entry
+            .children
+            .iter()
+            .position(|c| c.kind() == SyntaxKind::Equal)
+            .filter(|_| entry.kind == SyntaxKind::Entry)
+        else {
+            continue;
+        };
+
+        let padding = " ".repeat(widest - width(entry) + 1);
+        let space = SyntaxElement::Token(SyntaxToken::new(SyntaxKind::Whitespace, padding));
+        entry.children.splice(1..equal, [space]);
+    }
+}
+
+fn insert_inline(table: &mut SyntaxNode, entry: SyntaxElement) -> bool {
+    let Some(close) = table
+        .children
+        .iter()
+        .rposition(|c| c.kind() == SyntaxKind::RBrace)
+    else {
+        return false;
+    };
+    let Some(last) = table.children[..close]
+        .iter()
+        .rposition(|c| !c.kind().is_trivia())
+    else {
+        return false;
+    };
+
+    let space = || SyntaxElement::Token(SyntaxToken::new(SyntaxKind::Whitespace, " ".into()));
+    let mut items = Vec::new();
+
+    if table.children[last].kind() == SyntaxKind::Entry {
+        items.push(SyntaxElement::Token(SyntaxToken::new(
+            SyntaxKind::Comma,
+            ",".into(),
+        )));
+    }
+
+    items.push(space());
+    items.push(entry);
+
+    if last + 1 == close {
+        items.push(space());
+    }
+
+    table.children.splice(last + 1..last + 1, items);
+    true
+}
+
+impl fmt::Display for Cst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.root)
+    }
+}
+
+fn unquote(text: &str) -> &str {
+    text.strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(text)
+}
+
+fn trivia(text: &str, tokens: &mut Vec<SyntaxToken>) {
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let newline = rest.find('\n').unwrap_or(rest.len());
+
+        if newline > 0 {
+            tokens.push(SyntaxToken::new(
+                SyntaxKind::Whitespace,
+                rest[..newline].into(),
+            ));
+        }
+
+        if newline < rest.len() {
+            tokens.push(SyntaxToken::new(SyntaxKind::Newline, "\n".into()));
+            rest = &rest[newline + 1..];
+        } else {
+            rest = "";
+        }
+    }
+}
+
+pub fn tokenize<'a>(src: &'a Source<'a>) -> Result<'a, Vec<SyntaxToken>> {
+    let lexemes = lex_with(
+        src,
+        LexerOptions {
+            comments: true,
+            ..Default::default()
+        },
+    )?;
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+
+    for lexeme in lexemes {
+        trivia(
+            src.content
+                .get(offset..lexeme.span.begin.offset)
+                .unwrap_or_default(),
+            &mut tokens,
+        );
+        tokens.push(SyntaxToken::new(
+            lexeme.kind.token().into(),
+            lexeme.text(src).into(),
+        ));
+        offset = lexeme.span.end.offset;
+    }
+
+    trivia(src.content.get(offset..).unwrap_or_default(), &mut tokens);
+    Ok(tokens)
+}
+
+struct Builder {
+    tokens: VecDeque<SyntaxToken>,
+}
+
+impl Builder {
+    fn new(tokens: Vec<SyntaxToken>) -> Self {
+        Self {
+            tokens: tokens.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<SyntaxKind> {
+        self.tokens.iter().map(|t| t.kind).find(|k| !k.is_trivia())
+    }
+
+    fn bump(&mut self, node: &mut SyntaxNode) {
+        if let Some(token) = self.tokens.pop_front() {
+            node.children.push(SyntaxElement::Token(token));
+        }
+    }
+
+    fn trivia(&mut self, node: &mut SyntaxNode) {
+        while self.tokens.front().is_some_and(|t| t.kind.is_trivia()) {
+            self.bump(node);
+        }
+    }
+
+    fn significant(&mut self, node: &mut SyntaxNode) {
+        self.trivia(node);
+        self.bump(node);
+    }
+
+    fn document(&mut self) -> SyntaxNode {
+        let mut node = SyntaxNode::new(SyntaxKind::Document);
+
+        loop {
+            self.trivia(&mut node);
+
+            let child = match self.peek() {
+                None => break,
+                Some(SyntaxKind::LBrack) => self.header(),
+                Some(SyntaxKind::Directive) => self.include(),
+                Some(SyntaxKind::Separator) => {
+                    self.bump(&mut node);
+                    continue;
+                }
+                Some(_) => self.entry(),
+            };
+
+            node.children.push(SyntaxElement::Node(child));
+        }
+
+        node
+    }
+
+    fn header(&mut self) -> SyntaxNode {
+        let mut node = SyntaxNode::new(SyntaxKind::Header);
+        self.bump(&mut node);
+
+        if self.peek() == Some(SyntaxKind::LBrack) {
+            node.kind = SyntaxKind::ArrayHeader;
+            self.significant(&mut node);
+        }
+
+        self.trivia(&mut node);
+        node.children.push(SyntaxElement::Node(self.key()));
+
+        while self.peek() == Some(SyntaxKind::RBrack) {
+            self.significant(&mut node);
+        }
+
+        node
+    }
+
+    fn include(&mut self) -> SyntaxNode {
+        let mut node = SyntaxNode::new(SyntaxKind::Include);
+        self.bump(&mut node);
+        self.significant(&mut node);
+        node
+    }
+
+    fn key(&mut self) -> SyntaxNode {
+        let mut node = SyntaxNode::new(SyntaxKind::Key);
+        self.bump(&mut node);
+
+        while self.peek() == Some(SyntaxKind::Dot) {
+            self.significant(&mut node);
+            self.significant(&mut node);
+        }
+
+        node
+    }
+
+    fn entry(&mut self) -> SyntaxNode {
+        let mut node = SyntaxNode::new(SyntaxKind::Entry);
+        node.children.push(SyntaxElement::Node(self.key()));
+
+        if self.peek() == Some(SyntaxKind::Equal) {
+            self.significant(&mut node);
+            self.trivia(&mut node);
+            node.children.push(self.expression());
+        }
+
+        node
+    }
+
+    fn continues(&self) -> bool {
+        self.tokens
+            .iter()
+            .find(|t| t.kind != SyntaxKind::Whitespace)
+            .is_some_and(|t| {
+                matches!(
+                    t.kind,
+                    SyntaxKind::Plus | SyntaxKind::Minus | SyntaxKind::Star | SyntaxKind::Slash
+                )
+            })
+    }
+
+    fn expression(&mut self) -> SyntaxElement {
+        let first = self.value();
+
+        if !self.continues() {
+            return first;
+        }
+
+        let mut node = SyntaxNode::new(SyntaxKind::Expression);
+        node.children.push(first);
+
+        while self.continues() {
+            self.trivia(&mut node);
+
+            if matches!(
+                self.peek(),
+                Some(SyntaxKind::Plus | SyntaxKind::Minus | SyntaxKind::Star | SyntaxKind::Slash)
+            ) {
+                self.significant(&mut node);
+                self.trivia(&mut node);
+            }
+
+            node.children.push(self.value());
+        }
+
+        SyntaxElement::Node(node)
+    }
+
+    fn value(&mut self) -> SyntaxElement {
+        let (kind, close) = match self.tokens.front().map(|t| t.kind) {
+            Some(SyntaxKind::LBrack) => (SyntaxKind::Array, SyntaxKind::RBrack),
+            Some(SyntaxKind::LBrace) => (SyntaxKind::Table, SyntaxKind::RBrace),
+            Some(SyntaxKind::Ident) => return SyntaxElement::Node(self.key()),
+            Some(SyntaxKind::Star) => {
+                let mut node = SyntaxNode::new(SyntaxKind::Alias);
+                self.bump(&mut node);
+                self.significant(&mut node);
+                return SyntaxElement::Node(node);
+            }
+            Some(SyntaxKind::Ampersand) => {
+                let mut node = SyntaxNode::new(SyntaxKind::Anchor);
+                self.bump(&mut node);
+                self.significant(&mut node);
+                self.trivia(&mut node);
+                node.children.push(self.value());
+                return SyntaxElement::Node(node);
+            }
+            _ => {
+                return SyntaxElement::Token(
+                    self.tokens
+                        .pop_front()
+                        .unwrap_or_else(|| SyntaxToken::new(SyntaxKind::Whitespace, "".into())),
+                );
+            }
+        };
+        let mut node = SyntaxNode::new(kind);
+        self.bump(&mut node);
+
+        loop {
+            self.trivia(&mut node);
+
+            match self.peek() {
+                None => break,
+                Some(k) if k == close => {
+                    self.bump(&mut node);
+                    break;
+                }
+                Some(SyntaxKind::Comma) => self.bump(&mut node),
+                Some(_) if kind == SyntaxKind::Table => {
+                    let entry = self.entry();
+                    node.children.push(SyntaxElement::Node(entry));
+                }
+                Some(_) => {
+                    let value = self.expression();
+                    node.children.push(value);
+                }
+            }
+        }
+
+        SyntaxElement::Node(node)
+    }
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.into()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+fn within(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.starts_with('.') || rest.starts_with('['))
+}
+
+fn locate(root: &SyntaxNode, path: &str) -> Option<Vec<usize>> {
+    let mut section = String::new();
+    let mut arrays: Vec<(String, usize)> = Vec::new();
+
+    for (i, node) in root.children.iter().enumerate() {
+        let SyntaxElement::Node(node) = node else {
+            continue;
+        };
+        let Some(key) = node.key() else {
+            continue;
+        };
+
+        if node.kind == SyntaxKind::Entry {
+            let full = key.iter().fold(section.clone(), |p, k| join(&p, k));
+            let mut address = alloc::vec![i];
+
+            if full == path || within(path, &full) && locate_entry(node, &full, path, &mut address)
+            {
+                return Some(address);
+            }
+
+            continue;
+        }
+
+        section.clear();
+
+        for (j, segment) in key.iter().enumerate() {
+            section = join(&section, segment);
+
+            if node.kind == SyntaxKind::ArrayHeader && j + 1 == key.len() {
+                break;
+            }
+
+            if let Some((_, index)) = arrays.iter().find(|(p, _)| *p == section) {
+                section = format!("{}[{}]", section, index);
+            }
+        }
+
+        if node.kind == SyntaxKind::ArrayHeader {
+            let index = match arrays.iter_mut().find(|(p, _)| *p == section) {
+                Some((_, index)) => {
+                    *index += 1;
+                    *index
+                }
+                None => {
+                    arrays.push((section.clone(), 0));
+                    0
+                }
+            };
+
+            section = format!("{}[{}]", section, index);
+        }
+    }
+
+    None
+}
+
+fn locate_entry(entry: &SyntaxNode, prefix: &str, path: &str, address: &mut Vec<usize>) -> bool {
+    let value = entry
+        .children
+        .iter()
+        .enumerate()
+        .skip_while(|(_, c)| c.kind() != SyntaxKind::Equal)
+        .find(|(_, c)| !c.kind().is_trivia() && c.kind() != SyntaxKind::Equal);
+
+    match value {
+        Some((i, SyntaxElement::Node(node))) if node.kind == SyntaxKind::Anchor => {
+            let Some((j, SyntaxElement::Node(value))) =
+                node.children.iter().enumerate().next_back()
+            else {
+                return false;
+            };
+
+            address.extend([i, j]);
+            locate_in(value, prefix, path, address)
+        }
+        Some((i, SyntaxElement::Node(node))) => {
+            address.push(i);
+            locate_in(node, prefix, path, address)
+        }
+        _ => false,
+    }
+}
+
+fn locate_in(node: &SyntaxNode, prefix: &str, path: &str, address: &mut Vec<usize>) -> bool {
+    let mut elements = 0;
+
+    for (i, child) in node.children.iter().enumerate() {
+        if child.kind().is_trivia()
+            || matches!(
+                child.kind(),
+                SyntaxKind::LBrack
+                    | SyntaxKind::RBrack
+                    | SyntaxKind::LBrace
+                    | SyntaxKind::RBrace
+                    | SyntaxKind::Comma
+            )
+        {
+            continue;
+        }
+
+        let SyntaxElement::Node(child) = child else {
+            elements += 1;
+            continue;
+        };
+
+        if child.kind == SyntaxKind::Entry {
+            let Some(key) = child.key() else {
+                continue;
+            };
+            let full = key.iter().fold(String::from(prefix), |p, k| join(&p, k));
+
+            if full == path {
+                address.push(i);
+                return true;
+            }
+
+            if within(path, &full) {
+                address.push(i);
+                return locate_entry(child, &full, path, address);
+            }
+        } else {
+            let full = format!("{}[{}]", prefix, elements);
+            elements += 1;
+
+            if within(path, &full) {
+                address.push(i);
+                return locate_in(child, &full, path, address);
+            }
+        }
+    }
+
+    false
+}
+
+fn node_at<'n>(root: &'n mut SyntaxNode, address: &[usize]) -> Option<&'n mut SyntaxNode> {
+    let mut node = root;
+
+    for &i in address {
+        node = match node.children.get_mut(i)? {
+            SyntaxElement::Node(child) => child,
+            SyntaxElement::Token(_) => return None,
+        };
+    }
+
+    Some(node)
+}
+
+fn find_entry<'n>(root: &'n mut SyntaxNode, path: &str) -> Option<&'n mut SyntaxNode> {
+    let address = locate(root, path)?;
+    node_at(root, &address).filter(|node| node.kind == SyntaxKind::Entry)
+}
@@ -0,0 +1,723 @@
+use crate::{path::*, utils::*, value::*};
+use alloc::{
+    boxed::Box,
+    rc::Rc,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{cell::RefCell, fmt, marker::PhantomData};
+use serde::de::{self, Deserialize, IntoDeserializer, Visitor, value::BorrowedStrDeserializer};
+
+const SPANNED: &str = "$__pcf_private_Spanned";
+const SPAN: &str = "$__pcf_private_span";
+const VALUE: &str = "$__pcf_private_value";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeserializeErrorKind {
+    Custom(String),
+    InvalidLength {
+        expected: String,
+        found: usize,
+    },
+    InvalidType {
+        expected: String,
+        found: String,
+    },
+    InvalidValue {
+        expected: String,
+        found: String,
+    },
+    MissingField(&'static str),
+    MissingKey,
+    UnknownField {
+        field: String,
+        suggestion: Option<&'static str>,
+    },
+    UnknownVariant {
+        variant: String,
+        suggestion: Option<&'static str>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeserializeError {
+    pub path: KeyPath,
+    pub span: Span,
+    pub kind: DeserializeErrorKind,
+}
+
+impl DeserializeError {
+    fn new(kind: DeserializeErrorKind) -> Self {
+        Self {
+            path: KeyPath::new(),
+            span: Span::default(),
+            kind,
+        }
+    }
+
+    // serde raises errors without context, so the innermost deserializer that sees one claims it
+    fn locate(mut self, path: &KeyPath, span: Span) -> Self {
+        if self.path.is_empty() && self.span == Span::default() {
+            self.path = path.clone();
+            self.span = span;
+        }
+
+        self
+    }
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}{}] ", self.path, self.span)?;
+
+        match &self.kind {
+            DeserializeErrorKind::Custom(message) => {
+                write!(f, "encountered {} during deserialization", message)
+            }
+            DeserializeErrorKind::InvalidLength { expected, found } => write!(
+                f,
+                "encountered {} elements during deserialization, expected {}",
+                found, expected
+            ),
+            DeserializeErrorKind::InvalidType { expected, found } => write!(
+                f,
+                "encountered {} during deserialization, expected {}",
+                found, expected
+            ),
+            DeserializeErrorKind::InvalidValue { expected, found } => write!(
+                f,
+                "encountered invalid {} during deserialization, expected {}",
+                found, expected
+            ),
+            DeserializeErrorKind::MissingField(field) => {
+                write!(
+                    f,
+                    "encountered missing field `{}` during deserialization",
+                    field
+                )
+            }
+            DeserializeErrorKind::MissingKey => {
+                write!(f, "encountered missing key during deserialization")
+            }
+            DeserializeErrorKind::UnknownField { field, suggestion } => {
+                write!(
+                    f,
+                    "encountered unknown field `{}` during deserialization",
+                    field
+                )?;
+                write_suggestion(f, suggestion)
+            }
+            DeserializeErrorKind::UnknownVariant {
+                variant,
+                suggestion,
+            } => {
+                write!(
+                    f,
+                    "encountered unknown variant `{}` during deserialization",
+                    variant
+                )?;
+                write_suggestion(f, suggestion)
+            }
+        }
+    }
+}
+
+fn write_suggestion(f: &mut fmt::Formatter<'_>, suggestion: &Option<&str>) -> fmt::Result {
+    match suggestion {
+        Some(suggestion) => write!(f, ", did you mean `{}`?", suggestion),
+        None => Ok(()),
+    }
+}
+
+impl core::error::Error for DeserializeError {}
+
+impl de::Error for DeserializeError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Self::new(DeserializeErrorKind::Custom(message.to_string()))
+    }
+
+    fn invalid_type(found: de::Unexpected<'_>, expected: &dyn de::Expected) -> Self {
+        Self::new(DeserializeErrorKind::InvalidType {
+            expected: expected.to_string(),
+            found: found.to_string(),
+        })
+    }
+
+    fn invalid_value(found: de::Unexpected<'_>, expected: &dyn de::Expected) -> Self {
+        Self::new(DeserializeErrorKind::InvalidValue {
+            expected: expected.to_string(),
+            found: found.to_string(),
+        })
+    }
+
+    fn invalid_length(found: usize, expected: &dyn de::Expected) -> Self {
+        Self::new(DeserializeErrorKind::InvalidLength {
+            expected: expected.to_string(),
+            found,
+        })
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        Self::new(DeserializeErrorKind::MissingField(field))
+    }
+
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+        Self::new(DeserializeErrorKind::UnknownField {
+            field: field.into(),
+            suggestion: suggest(field, expected.iter().copied()),
+        })
+    }
+
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+        Self::new(DeserializeErrorKind::UnknownVariant {
+            variant: variant.into(),
+            suggestion: suggest(variant, expected.iter().copied()),
+        })
+    }
+}
+
+type Result<T> = core::result::Result<T, DeserializeError>;
+
+#[derive(Debug, Clone, Copy)]
+enum Node<'de> {
+    Value(&'de Value),
+    Table(&'de Table),
+}
+
+impl<'de> Node<'de> {
+    fn new(value: &'de Value) -> Self {
+        match value {
+            Value::Table(table) => Node::Table(table),
+            _ => Node::Value(value),
+        }
+    }
+}
+
+// unknown keys are shared across the whole tree, so a recovering caller can see all of them
+#[derive(Debug, Clone)]
+pub struct Deserializer<'de> {
+    node: Node<'de>,
+    path: KeyPath,
+    span: Span,
+    unknown: Rc<RefCell<Vec<DeserializeError>>>,
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn new(value: &'de Value, path: KeyPath, span: Span) -> Self {
+        Self {
+            node: Node::new(value),
+            path,
+            span,
+            unknown: Rc::default(),
+        }
+    }
+
+    pub fn root(table: &'de Table) -> Self {
+        Self {
+            node: Node::Table(table),
+            path: KeyPath::new(),
+            span: Span::default(),
+            unknown: Rc::default(),
+        }
+    }
+
+    fn child(&self, segment: PathSegment, value: &'de Value, span: Span) -> Self {
+        let mut path = self.path.clone();
+        path.push(segment);
+
+        Self {
+            node: Node::new(value),
+            path,
+            span,
+            unknown: self.unknown.clone(),
+        }
+    }
+
+    // keys reported by an earlier pass of `from_table_recovering` are passed over
+    fn reported(&self, key: &str) -> bool {
+        let unknown = self.unknown.borrow();
+
+        unknown.iter().any(|error| {
+            error.path.len() == self.path.len() + 1
+                && error.path.starts_with(&self.path)
+                && matches!(error.path.last(), Some(PathSegment::Key(last)) if last == key)
+        })
+    }
+
+    fn table<'a>(
+        &'a self,
+        table: &'de Table,
+        fields: &'static [&'static str],
+    ) -> TableAccess<'a, 'de, impl Iterator<Item = (&'de String, &'de Entry)>> {
+        TableAccess {
+            entries: table.iter(),
+            entry: None,
+            fields,
+            parent: self,
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = DeserializeError;
+
+    // the document owns every string, so they can always be lent out for `'de` without copying
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let result = match self.node {
+            Node::Table(table) => visitor.visit_map(self.table(table, &[])),
+            Node::Value(Value::String(s)) => visitor.visit_borrowed_str(s),
+            Node::Value(Value::Bytes(b)) => visitor.visit_borrowed_bytes(b),
+            Node::Value(Value::Integer(i)) => visitor.visit_i64(*i),
+            Node::Value(Value::Float(f)) => visitor.visit_f64(*f),
+            Node::Value(Value::Bool(b)) => visitor.visit_bool(*b),
+            Node::Value(Value::Array(array)) => visitor.visit_seq(ArrayAccess {
+                elements: array.iter().enumerate(),
+                parent: &self,
+            }),
+            Node::Value(Value::Table(table)) => visitor.visit_map(self.table(table, &[])),
+        };
+
+        result.map_err(|e| e.locate(&self.path, self.span))
+    }
+
+    // a value that is present is always `Some`, absent fields become `None` through serde itself
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let result = match self.node {
+            Node::Table(table) | Node::Value(Value::Table(table)) if table.is_empty() => {
+                visitor.visit_unit::<DeserializeError>()
+            }
+            _ => Err(de::Error::custom("expected an empty table for a unit")),
+        };
+
+        result.map_err(|e| e.locate(&self.path, self.span))
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        if name == SPANNED && fields == [SPAN, VALUE] {
+            return visitor.visit_map(SpannedAccess {
+                span: Some(self.span),
+                value: Some(self),
+            });
+        }
+
+        match self.node {
+            Node::Table(table) => visitor
+                .visit_map(self.table(table, fields))
+                .map_err(|e| e.locate(&self.path, self.span)),
+            Node::Value(_) => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let result = match self.node {
+            Node::Value(Value::String(variant)) => {
+                visitor.visit_enum(BorrowedStrDeserializer::new(variant))
+            }
+            Node::Table(table) if table.len() == 1 => match table.iter().next() {
+                Some((variant, entry)) => visitor.visit_enum(EnumAccess {
+                    variant,
+                    value: self.child(PathSegment::Key(variant.clone()), &entry.value, entry.span),
+                }),
+                None => Err(de::Error::custom("expected a single-entry table")),
+            },
+            _ => Err(de::Error::custom(
+                "expected a string or a single-entry table for an enum",
+            )),
+        };
+
+        result.map_err(|e| e.locate(&self.path, self.span))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf seq tuple tuple_struct map identifier
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DeserializeError> for Deserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+// `fields` is only known for structs, and stays empty for plain maps
+struct TableAccess<'a, 'de, I> {
+    entries: I,
+    entry: Option<(&'de String, &'de Entry)>,
+    fields: &'static [&'static str],
+    parent: &'a Deserializer<'de>,
+}
+
+impl<'de, I: Iterator<Item = (&'de String, &'de Entry)>> TableAccess<'_, 'de, I> {
+    fn locate(&self, error: DeserializeError, key: &str, entry: &Entry) -> DeserializeError {
+        let mut path = self.parent.path.clone();
+        path.push(PathSegment::Key(key.into()));
+        error.locate(&path, entry.key_span)
+    }
+
+    // serde gives up on the first unknown key, so the rest of the table is checked here
+    // while the declared fields are still at hand
+    fn unknown(&mut self, error: &DeserializeError) {
+        let mut unknown = self.parent.unknown.borrow_mut();
+        unknown.push(error.clone());
+
+        if self.fields.is_empty() {
+            return;
+        }
+
+        while let Some((key, entry)) = self.entries.next() {
+            if !self.fields.contains(&key.as_str()) {
+                let error = <DeserializeError as de::Error>::unknown_field(key, self.fields);
+                unknown.push(self.locate(error, key, entry));
+            }
+        }
+    }
+}
+
+impl<'de, I: Iterator<Item = (&'de String, &'de Entry)>> de::MapAccess<'de>
+    for TableAccess<'_, 'de, I>
+{
+    type Error = DeserializeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let Some((key, entry)) = self.entries.find(|(key, _)| !self.parent.reported(key)) else {
+            return Ok(None);
+        };
+
+        self.entry = Some((key, entry));
+
+        match seed.deserialize(BorrowedStrDeserializer::new(key)) {
+            Ok(key) => Ok(Some(key)),
+            Err(error) => {
+                let error = self.locate(error, key, entry);
+
+                if let DeserializeErrorKind::UnknownField { .. } = error.kind {
+                    self.unknown(&error);
+                }
+
+                Err(error)
+            }
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let (key, entry) = self
+            .entry
+            .take()
+            .ok_or_else(|| de::Error::custom("value requested before key"))?;
+
+        seed.deserialize(
+            self.parent
+                .child(PathSegment::Key(key.clone()), &entry.value, entry.span),
+        )
+    }
+}
+
+struct ArrayAccess<'a, 'de, I> {
+    elements: I,
+    parent: &'a Deserializer<'de>,
+}
+
+impl<'de, I: Iterator<Item = (usize, &'de Value)>> de::SeqAccess<'de> for ArrayAccess<'_, 'de, I> {
+    type Error = DeserializeError;
+
+    // elements have no spans of their own, so they report the span of their entry
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>> {
+        let Some((index, value)) = self.elements.next() else {
+            return Ok(None);
+        };
+
+        seed.deserialize(
+            self.parent
+                .child(PathSegment::Index(index), value, self.parent.span),
+        )
+        .map(Some)
+    }
+}
+
+struct EnumAccess<'de> {
+    variant: &'de str,
+    value: Deserializer<'de>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = DeserializeError;
+    type Variant = Deserializer<'de>;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant)> {
+        seed.deserialize(BorrowedStrDeserializer::new(self.variant))
+            .map(|variant| (variant, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer<'de> {
+    type Error = DeserializeError;
+
+    fn unit_variant(self) -> Result<()> {
+        Err(de::Error::custom(
+            "expected a unit variant as a plain string",
+        ))
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+}
+
+// hands `Spanned` its span as plain numbers first, then the value itself
+struct SpannedAccess<'de> {
+    span: Option<Span>,
+    value: Option<Deserializer<'de>>,
+}
+
+impl<'de> de::MapAccess<'de> for SpannedAccess<'de> {
+    type Error = DeserializeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let key = match (self.span, &self.value) {
+            (Some(_), _) => SPAN,
+            (None, Some(_)) => VALUE,
+            (None, None) => return Ok(None),
+        };
+
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        if let Some(Span { begin, end }) = self.span.take() {
+            let numbers = [
+                begin.line,
+                begin.col,
+                begin.offset,
+                end.line,
+                end.col,
+                end.offset,
+            ];
+
+            return seed.deserialize(de::value::SeqDeserializer::new(numbers.into_iter()));
+        }
+
+        match self.value.take() {
+            Some(deserializer) => seed.deserialize(deserializer),
+            None => Err(de::Error::custom("value requested before key")),
+        }
+    }
+}
+
+// the span is that of the entry the value was read from; array elements don't keep spans of
+// their own, so every element of an array gets the whole array's span, while the entries of a
+// table inside an array still get theirs
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(value: T, span: Span) -> Self {
+        Self { value, span }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+// spans describe where a value came from rather than what it is, so they never affect equality
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq> Eq for Spanned<T> {}
+
+impl<T> core::ops::Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> core::ops::DerefMut for Spanned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Spanned<T> {
+    fn deserialize<D: de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> core::result::Result<Self, D::Error> {
+        struct SpannedVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for SpannedVisitor<T> {
+            type Value = Spanned<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a spanned value from the pcf deserializer")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> core::result::Result<Self::Value, A::Error> {
+                if map.next_key::<&str>()? != Some(SPAN) {
+                    return Err(de::Error::custom("expected a span"));
+                }
+
+                let [line, col, offset, end_line, end_col, end_offset] = map.next_value()?;
+
+                let span = Span {
+                    begin: Location::new(line, col, offset),
+                    end: Location::new(end_line, end_col, end_offset),
+                };
+
+                match map.next_key::<&str>()? {
+                    Some(VALUE) => Ok(Spanned::new(map.next_value()?, span)),
+                    _ => Err(de::Error::custom("expected a spanned value")),
+                }
+            }
+        }
+
+        deserializer.deserialize_struct(SPANNED, &[SPAN, VALUE], SpannedVisitor(PhantomData))
+    }
+}
+
+impl<T: serde::Serialize> serde::Serialize for Spanned<T> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+pub fn from_value<'de, T: Deserialize<'de>>(
+    value: &'de Value,
+) -> core::result::Result<T, Box<DeserializeError>> {
+    T::deserialize(Deserializer::new(value, KeyPath::new(), Span::default())).map_err(Box::new)
+}
+
+pub fn from_table<'de, T: Deserialize<'de>>(
+    table: &'de Table,
+) -> core::result::Result<T, Box<DeserializeError>> {
+    T::deserialize(Deserializer::root(table)).map_err(Box::new)
+}
+
+pub fn from_document<'de, T: Deserialize<'de>>(
+    document: &'de Document,
+) -> core::result::Result<T, Box<DeserializeError>> {
+    from_table(&document.root)
+}
+
+pub fn from_document_at<'de, T: Deserialize<'de>>(
+    document: &'de Document,
+    path: impl ToKeyPath,
+) -> core::result::Result<T, Box<DeserializeError>> {
+    let (value, path, span) = document.locate(path).map_err(|error| DeserializeError {
+        path: error.path,
+        span: error.span,
+        kind: DeserializeErrorKind::MissingKey,
+    })?;
+
+    T::deserialize(Deserializer::new(value, path, span)).map_err(Box::new)
+}
+
+// like `from_table`, but reports every unknown key of every `deny_unknown_fields` table;
+// serde stops at the first table with one, so the walk is redone with the keys found so far
+// passed over until it gets through or fails for another reason
+pub fn from_table_recovering<'de, T: Deserialize<'de>>(
+    table: &'de Table,
+) -> core::result::Result<T, Vec<DeserializeError>> {
+    let unknown: Rc<RefCell<Vec<DeserializeError>>> = Rc::default();
+
+    loop {
+        let found = unknown.borrow().len();
+        let mut deserializer = Deserializer::root(table);
+        deserializer.unknown = unknown.clone();
+
+        match T::deserialize(deserializer) {
+            Ok(value) if found == 0 => return Ok(value),
+            Ok(_) => return Err(unknown.take()),
+            Err(error) => {
+                let mut errors = unknown.borrow_mut();
+
+                if matches!(error.kind, DeserializeErrorKind::UnknownField { .. })
+                    && errors.len() > found
+                {
+                    continue;
+                }
+
+                if !errors.contains(&error) {
+                    errors.push(error);
+                }
+
+                drop(errors);
+                return Err(unknown.take());
+            }
+        }
+    }
+}
+
+pub fn from_document_recovering<'de, T: Deserialize<'de>>(
+    document: &'de Document,
+) -> core::result::Result<T, Vec<DeserializeError>> {
+    from_table_recovering(&document.root)
+}
@@ -0,0 +1,548 @@
+use crate::{
+    messages::{self, English, Messages},
+    schema::{SchemaError, SchemaWarning, SchemaWarningKind, Validation},
+    utils::{self, Error, ErrorKind, Hints, OwnedError, RenderOptions, Source, Span},
+};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn style(&self) -> &'static str {
+        match self {
+            Self::Error => utils::ERROR,
+            Self::Warning | Self::Note => utils::NOTE,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(English.severity(*self))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    #[default]
+    Warn,
+    Deny,
+}
+
+// every code that's reported as a warning, which are the only ones a level can be set for
+const LINTS: [&str; ErrorKind::WARNINGS.len() + SchemaWarningKind::ALL.len()] = {
+    let mut lints = [""; ErrorKind::WARNINGS.len() + SchemaWarningKind::ALL.len()];
+    let mut i = 0;
+
+    while i < ErrorKind::WARNINGS.len() {
+        lints[i] = ErrorKind::WARNINGS[i].code();
+        i += 1;
+    }
+
+    while i < lints.len() {
+        lints[i] = SchemaWarningKind::ALL[i - ErrorKind::WARNINGS.len()].code();
+        i += 1;
+    }
+
+    lints
+};
+
+// a fixed table over `LINTS` rather than a map, so the options holding it stay `Copy`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Lints {
+    levels: [Level; LINTS.len()],
+}
+
+impl Lints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // codes that are never reported as warnings have no level to set, so they're ignored
+    pub fn level(mut self, code: &str, level: Level) -> Self {
+        if let Some(i) = LINTS.iter().position(|lint| *lint == code) {
+            self.levels[i] = level;
+        }
+
+        self
+    }
+
+    pub fn allow(self, code: &str) -> Self {
+        self.level(code, Level::Allow)
+    }
+
+    pub fn warn(self, code: &str) -> Self {
+        self.level(code, Level::Warn)
+    }
+
+    pub fn deny(self, code: &str) -> Self {
+        self.level(code, Level::Deny)
+    }
+
+    pub fn get(&self, code: &str) -> Option<Level> {
+        LINTS
+            .iter()
+            .position(|lint| *lint == code)
+            .map(|i| self.levels[i])
+    }
+
+    pub(crate) fn report<'a>(
+        &self,
+        warnings: &mut Vec<Error<'a>>,
+        warning: Error<'a>,
+    ) -> core::result::Result<(), Error<'a>> {
+        debug_assert!(ErrorKind::WARNINGS.iter().any(|k| k.code() == warning.kind.code()));
+
+        match self.get(warning.kind.code()) {
+            Some(Level::Allow) => Ok(()),
+            Some(Level::Deny) => Err(warning),
+            _ => {
+                warnings.push(warning);
+                Ok(())
+            }
+        }
+    }
+}
+
+// the first label is the primary one, the header points at it and any others follow as notes
+// with their own snippet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub labels: Vec<(Span, String)>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, code, message)
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, code, message)
+    }
+
+    pub fn label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push((span, message.into()));
+        self
+    }
+
+    pub fn note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.labels.first().map(|(span, _)| *span)
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    pub fn render(
+        &self,
+        f: &mut impl fmt::Write,
+        src: &Source<'_>,
+        options: &RenderOptions,
+    ) -> fmt::Result {
+        TextRenderer::new(f).options(*options).render(self, src)
+    }
+
+    fn write(
+        &self,
+        f: &mut impl fmt::Write,
+        file: &str,
+        content: Option<&str>,
+        options: &RenderOptions,
+    ) -> fmt::Result {
+        let (on, off) = options.paint(self.severity.style());
+        let mut labels = self.labels.iter();
+
+        // without a source to draw the primary label under, it leads the header the way paths do
+        f.write_str(on)?;
+        if let Some((span, label)) = self.labels.first() {
+            let prefix = match content {
+                Some(_) => file,
+                None => label,
+            };
+
+            write!(f, "[{}{}] ", prefix, span)?;
+        }
+
+        // errors keep the header `Error` renders so converted ones read the same
+        if self.severity != Severity::Error {
+            write!(f, "{} ", options.messages.severity(self.severity))?;
+        }
+
+        write!(f, "{}: {}{}", self.code, self.message, off)?;
+
+        if let (Some((span, label)), Some(content)) = (labels.next(), content) {
+            utils::write_snippet(
+                f,
+                options,
+                self.severity.style(),
+                (content, 1),
+                *span,
+                label,
+            )?;
+        }
+
+        for (span, label) in labels {
+            let (on, off) = options.paint(utils::NOTE);
+            write!(f, "\n{}[{}{}] {}{}", on, file, span, label, off)?;
+
+            if let Some(content) = content {
+                utils::write_snippet(f, options, utils::NOTE, (content, 1), *span, "")?;
+            }
+        }
+
+        for note in &self.notes {
+            let prefix = options.messages.severity(Severity::Note);
+            write!(f, "\n{}: {}", prefix, note)?;
+        }
+
+        if let Some(help) = &self.help {
+            write!(f, "\n{}: {}", options.messages.help(), help)?;
+        }
+
+        Ok(())
+    }
+}
+
+// how a diagnostic is shown; tools that draw errors their own way, in an editor or a web page,
+// implement this over the diagnostic's fields and get every source of diagnostics for free
+pub trait RenderDiagnostic {
+    type Error;
+
+    fn render(&mut self, diagnostic: &Diagnostic, src: &Source<'_>) -> Result<(), Self::Error>;
+}
+
+// the plain text layout, with a blank line between diagnostics rendered one after another
+#[derive(Debug)]
+pub struct TextRenderer<W> {
+    out: W,
+    options: RenderOptions,
+    rendered: bool,
+}
+
+impl<W: fmt::Write> TextRenderer<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            options: RenderOptions::default(),
+            rendered: false,
+        }
+    }
+
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: fmt::Write> RenderDiagnostic for TextRenderer<W> {
+    type Error = fmt::Error;
+
+    fn render(&mut self, diagnostic: &Diagnostic, src: &Source<'_>) -> fmt::Result {
+        if self.rendered {
+            self.out.write_str("\n\n")?;
+        }
+
+        self.rendered = true;
+        diagnostic.write(&mut self.out, src.file, Some(&src.content), &self.options)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, "", None, &RenderOptions::default())
+    }
+}
+
+// builds a diagnostic with its text written by `messages`, the `From` conversions write english
+pub trait IntoDiagnostic {
+    fn into_diagnostic(self, messages: &dyn Messages) -> Diagnostic;
+}
+
+impl IntoDiagnostic for (Span, ErrorKind) {
+    fn into_diagnostic(self, messages: &dyn Messages) -> Diagnostic {
+        let (span, kind) = self;
+        let message = messages::text(|f| messages.error(f, &kind));
+        let diagnostic = Diagnostic::error(kind.code(), message).label(span, String::new());
+
+        match kind.related() {
+            Some((span, label)) => diagnostic.label(span, messages.label(label)),
+            None => diagnostic,
+        }
+    }
+}
+
+fn hints(
+    mut diagnostic: Diagnostic,
+    hints: Option<Box<Hints>>,
+    messages: &dyn Messages,
+) -> Diagnostic {
+    if let Some(hints) = hints {
+        let hint = |hint| messages::text(|f| messages.hint(f, &hint));
+
+        diagnostic.notes.extend(hints.note.map(hint));
+        diagnostic
+            .notes
+            .extend(hints.included.into_iter().map(|included| {
+                messages::text(|f| messages.included(f, &included.file, included.span))
+            }));
+        diagnostic.help = hints.help.map(hint);
+    }
+
+    diagnostic
+}
+
+impl<'a> IntoDiagnostic for Error<'a> {
+    fn into_diagnostic(self, messages: &dyn Messages) -> Diagnostic {
+        let diagnostic = (self.span, self.kind).into_diagnostic(messages);
+        hints(diagnostic, self.hints, messages)
+    }
+}
+
+impl IntoDiagnostic for OwnedError {
+    fn into_diagnostic(self, messages: &dyn Messages) -> Diagnostic {
+        let diagnostic = (self.span, self.kind).into_diagnostic(messages);
+        hints(diagnostic, self.hints, messages)
+    }
+}
+
+fn suggestion(
+    diagnostic: Diagnostic,
+    suggestion: Option<String>,
+    messages: &dyn Messages,
+) -> Diagnostic {
+    match suggestion {
+        Some(suggestion) => {
+            diagnostic.note(messages::text(|f| messages.suggestion(f, &suggestion)))
+        }
+        None => diagnostic,
+    }
+}
+
+impl IntoDiagnostic for SchemaError {
+    fn into_diagnostic(self, messages: &dyn Messages) -> Diagnostic {
+        let message = messages::text(|f| messages.schema_error(f, &self.kind));
+        let diagnostic =
+            Diagnostic::error(self.kind.code(), message).label(self.span, self.path.to_string());
+
+        suggestion(diagnostic, self.suggestion, messages)
+    }
+}
+
+impl IntoDiagnostic for SchemaWarning {
+    fn into_diagnostic(self, messages: &dyn Messages) -> Diagnostic {
+        let message = messages::text(|f| messages.schema_warning(f, &self.kind));
+        let diagnostic =
+            Diagnostic::warning(self.kind.code(), message).label(self.span, self.path.to_string());
+
+        match self.replacement {
+            Some(replacement) => {
+                diagnostic.note(messages::text(|f| messages.replacement(f, &replacement)))
+            }
+            None => suggestion(diagnostic, self.suggestion, messages),
+        }
+    }
+}
+
+impl From<(Span, ErrorKind)> for Diagnostic {
+    fn from(error: (Span, ErrorKind)) -> Self {
+        error.into_diagnostic(&English)
+    }
+}
+
+impl<'a> From<Error<'a>> for Diagnostic {
+    fn from(error: Error<'a>) -> Self {
+        error.into_diagnostic(&English)
+    }
+}
+
+impl From<OwnedError> for Diagnostic {
+    fn from(error: OwnedError) -> Self {
+        error.into_diagnostic(&English)
+    }
+}
+
+impl From<SchemaError> for Diagnostic {
+    fn from(error: SchemaError) -> Self {
+        error.into_diagnostic(&English)
+    }
+}
+
+impl From<SchemaWarning> for Diagnostic {
+    fn from(warning: SchemaWarning) -> Self {
+        warning.into_diagnostic(&English)
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostics {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, diagnostic: impl Into<Diagnostic>) {
+        self.diagnostics.push(diagnostic.into());
+    }
+
+    pub fn len(&self) -> usize {
+        self.diagnostics.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, Diagnostic> {
+        self.diagnostics.iter()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.iter().filter(|d| d.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.iter().filter(|d| d.severity == Severity::Warning)
+    }
+
+    // orders by where each diagnostic points, keeping the order they were reported in otherwise
+    pub fn sort(&mut self) {
+        self.diagnostics.sort_by_key(Diagnostic::span);
+    }
+
+    pub fn into_vec(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+
+    pub fn render(
+        &self,
+        f: &mut impl fmt::Write,
+        src: &Source<'_>,
+        options: &RenderOptions,
+    ) -> fmt::Result {
+        self.render_with(&mut TextRenderer::new(f).options(*options), src)
+    }
+
+    pub fn render_with<R: RenderDiagnostic>(
+        &self,
+        renderer: &mut R,
+        src: &Source<'_>,
+    ) -> Result<(), R::Error> {
+        self.iter()
+            .try_for_each(|diagnostic| renderer.render(diagnostic, src))
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, diagnostic) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+
+            write!(f, "{}", diagnostic)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<D: Into<Diagnostic>> Extend<D> for Diagnostics {
+    fn extend<I: IntoIterator<Item = D>>(&mut self, iter: I) {
+        self.diagnostics.extend(iter.into_iter().map(Into::into));
+    }
+}
+
+impl<D: Into<Diagnostic>> FromIterator<D> for Diagnostics {
+    fn from_iter<I: IntoIterator<Item = D>>(iter: I) -> Self {
+        let mut diagnostics = Self::new();
+        diagnostics.extend(iter);
+        diagnostics
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = Diagnostic;
+    type IntoIter = alloc::vec::IntoIter<Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.diagnostics.into_iter()
+    }
+}
+
+impl<'d> IntoIterator for &'d Diagnostics {
+    type Item = &'d Diagnostic;
+    type IntoIter = core::slice::Iter<'d, Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Validation {
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.diagnostics_with(&English)
+    }
+
+    pub fn diagnostics_with(&self, messages: &dyn Messages) -> Diagnostics {
+        let errors = self.errors.iter().cloned();
+        let warnings = self.warnings.iter().cloned();
+
+        errors
+            .map(|error| error.into_diagnostic(messages))
+            .chain(warnings.map(|warning| warning.into_diagnostic(messages)))
+            .collect()
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{path::*, utils::Span, value::*};
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added {
+        path: KeyPath,
+        value: Value,
+        span: Span,
+    },
+    Removed {
+        path: KeyPath,
+        value: Value,
+        span: Span,
+    },
+    Modified {
+        path: KeyPath,
+        old: Value,
+        new: Value,
+        old_span: Span,
+        new_span: Span,
+    },
+}
+
+impl Change {
+    pub fn path(&self) -> &KeyPath {
+        match self {
+            Self::Added { path, .. } | Self::Removed { path, .. } | Self::Modified { path, .. } => {
+                path
+            }
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added { path, value, .. } => write!(f, "+ {} = {}", path, value),
+            Self::Removed { path, value, .. } => write!(f, "- {} = {}", path, value),
+            Self::Modified { path, old, new, .. } => write!(f, "~ {} = {} -> {}", path, old, new),
+        }
+    }
+}
+
+struct Differ {
+    changes: Vec<Change>,
+    path: KeyPath,
+}
+
+impl Differ {
+    fn table(&mut self, old: &Table, new: &Table) {
+        for (key, entry) in old.iter() {
+            self.path.push(PathSegment::Key(key.clone()));
+
+            match new.get_entry(key) {
+                Some(other) => self.value(&entry.value, &other.value, entry.span, other.span),
+                None => self.changes.push(Change::Removed {
+                    path: self.path.clone(),
+                    value: entry.value.clone(),
+                    span: entry.span,
+                }),
+            }
+
+            self.path.pop();
+        }
+
+        for (key, entry) in new.iter().filter(|(key, _)| !old.contains_key(key)) {
+            self.path.push(PathSegment::Key(key.clone()));
+            self.changes.push(Change::Added {
+                path: self.path.clone(),
+                value: entry.value.clone(),
+                span: entry.span,
+            });
+            self.path.pop();
+        }
+    }
+
+    fn array(&mut self, old: &[Value], new: &[Value], old_span: Span, new_span: Span) {
+        for i in 0..old.len().max(new.len()) {
+            self.path.push(PathSegment::Index(i));
+
+            match (old.get(i), new.get(i)) {
+                (Some(old), Some(new)) => self.value(old, new, old_span, new_span),
+                (Some(old), None) => self.changes.push(Change::Removed {
+                    path: self.path.clone(),
+                    value: old.clone(),
+                    span: old_span,
+                }),
+                (None, Some(new)) => self.changes.push(Change::Added {
+                    path: self.path.clone(),
+                    value: new.clone(),
+                    span: new_span,
+                }),
+                (None, None) => {}
+            }
+
+            self.path.pop();
+        }
+    }
+
+    fn value(&mut self, old: &Value, new: &Value, old_span: Span, new_span: Span) {
+        match (old, new) {
+            _ if old == new => {}
+            (Value::Table(old), Value::Table(new)) => self.table(old, new),
+            (Value::Array(old), Value::Array(new)) => self.array(old, new, old_span, new_span),
+            _ => self.changes.push(Change::Modified {
+                path: self.path.clone(),
+                old: old.clone(),
+                new: new.clone(),
+                old_span,
+                new_span,
+            }),
+        }
+    }
+}
+
+pub fn diff(old: &Document, new: &Document) -> Vec<Change> {
+    let mut differ = Differ {
+        changes: Vec::new(),
+        path: KeyPath::new(),
+    };
+
+    differ.table(&old.root, &new.root);
+    differ.changes
+}
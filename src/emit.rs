@@ -0,0 +1,1071 @@
+use crate::{cst::Cst, lex::is_identifier, path::*, value::*};
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::{
+    cmp::Ordering,
+    fmt::{self, Write},
+    hash::Hasher,
+};
+
+pub(crate) fn write_string(f: &mut impl Write, s: &str) -> fmt::Result {
+    write_quoted(f, s, false)
+}
+
+fn write_quoted(f: &mut impl Write, s: &str, multiline: bool) -> fmt::Result {
+    f.write_char('"')?;
+
+    for chr in s.chars() {
+        match chr {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' if multiline => f.write_char(chr)?,
+            '\n' => f.write_str("\\n")?,
+            '\t' => f.write_str("\\t")?,
+            '\r' => f.write_str("\\r")?,
+            '\0' => f.write_str("\\0")?,
+            _ if chr.is_control() => write!(f, "\\u{{{:x}}}", chr as u32)?,
+            _ => f.write_char(chr)?,
+        }
+    }
+
+    f.write_char('"')
+}
+
+fn escapes(s: &str, multiline: bool) -> usize {
+    s.chars()
+        .filter(|&chr| match chr {
+            '"' | '\\' => true,
+            '\n' => !multiline,
+            _ => chr.is_control(),
+        })
+        .count()
+}
+
+fn is_raw(s: &str) -> bool {
+    !s.chars()
+        .any(|chr| chr.is_control() && !matches!(chr, '\n' | '\t'))
+}
+
+fn write_raw(f: &mut impl Write, s: &str) -> fmt::Result {
+    let hashes = s
+        .match_indices('"')
+        .map(|(i, _)| s[i + 1..].chars().take_while(|&c| c == '#').count() + 1)
+        .max()
+        .unwrap_or(0);
+
+    f.write_char('r')?;
+
+    for _ in 0..hashes {
+        f.write_char('#')?;
+    }
+
+    write!(f, "\"{}\"", s)?;
+
+    for _ in 0..hashes {
+        f.write_char('#')?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn write_bytes(f: &mut impl Write, bytes: &[u8]) -> fmt::Result {
+    f.write_str("b\"")?;
+
+    for byte in bytes {
+        match byte {
+            b'"' => f.write_str("\\\"")?,
+            b'\\' => f.write_str("\\\\")?,
+            b' '..=b'~' => f.write_char(*byte as char)?,
+            _ => write!(f, "\\x{:02x}", byte)?,
+        }
+    }
+
+    f.write_char('"')
+}
+
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+pub(crate) fn write_base64(f: &mut impl Write, bytes: &[u8]) -> fmt::Result {
+    const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - 8 * i));
+
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => f.write_char(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char)?,
+                false => f.write_char('=')?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key != "true"
+        && key != "false"
+        && key.chars().all(is_identifier)
+}
+
+pub(crate) fn write_key(f: &mut impl Write, key: &str) -> fmt::Result {
+    if is_bare_key(key) {
+        f.write_str(key)
+    } else {
+        write_string(f, key)
+    }
+}
+
+// nan payloads can't be written down, so generated nans are the one `0.0 / 0.0` evaluates to
+#[cfg(any(feature = "arbitrary", feature = "proptest"))]
+pub(crate) fn round_trip_float(float: f64) -> f64 {
+    match float.is_nan() {
+        true => core::hint::black_box(0.0_f64) / 0.0,
+        false => float,
+    }
+}
+
+pub(crate) fn write_float(f: &mut impl Write, float: f64) -> fmt::Result {
+    if float.is_nan() {
+        f.write_str("0.0 / 0.0")
+    } else if float.is_infinite() {
+        f.write_str(if float > 0.0 {
+            "1.0 / 0.0"
+        } else {
+            "-1.0 / 0.0"
+        })
+    } else {
+        let mut tracked = Tracked { out: f, dot: false };
+        write!(tracked, "{}", float)?;
+
+        match tracked.dot {
+            true => Ok(()),
+            false => f.write_str(".0"),
+        }
+    }
+}
+
+struct Tracked<'w, W: Write> {
+    out: &'w mut W,
+    dot: bool,
+}
+
+impl<W: Write> Write for Tracked<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.dot |= s.contains('.');
+        self.out.write_str(s)
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableStyle {
+    #[default]
+    Headers,
+    Braces,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayLayout {
+    #[default]
+    Inline,
+    Multiline,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStyle {
+    #[default]
+    Plain,
+    Raw,
+    Multiline,
+    Auto,
+}
+
+impl QuoteStyle {
+    fn choose(s: &str) -> Self {
+        let plain = escapes(s, false);
+        let multiline = escapes(s, true);
+
+        match () {
+            _ if plain == 0 => Self::Plain,
+            _ if multiline == 0 => Self::Multiline,
+            _ if is_raw(s) => Self::Raw,
+            _ if multiline < plain => Self::Multiline,
+            _ => Self::Plain,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone)]
+pub enum KeyOrder {
+    #[default]
+    Preserve,
+    Alphabetical,
+    Priority(Vec<String>),
+    Custom(fn(&str, &str) -> Ordering),
+}
+
+impl PartialEq for KeyOrder {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Preserve, Self::Preserve) | (Self::Alphabetical, Self::Alphabetical) => true,
+            (Self::Priority(a), Self::Priority(b)) => a == b,
+            (Self::Custom(a), Self::Custom(b)) => core::ptr::fn_addr_eq(*a, *b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for KeyOrder {}
+
+impl KeyOrder {
+    pub fn priority<I: IntoIterator<Item = S>, S: Into<String>>(keys: I) -> Self {
+        Self::Priority(keys.into_iter().map(Into::into).collect())
+    }
+
+    fn sort(&self, entries: &mut [(&String, &Entry)]) {
+        match self {
+            Self::Preserve => {}
+            Self::Alphabetical => entries.sort_by_key(|(key, _)| *key),
+            Self::Priority(keys) => entries
+                .sort_by_key(|(key, _)| keys.iter().position(|k| k == *key).unwrap_or(keys.len())),
+            Self::Custom(compare) => entries.sort_by(|(a, _), (b, _)| compare(a, b)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmitOptions {
+    pub indent_width: usize,
+    pub indent_char: char,
+    pub spacing: bool,
+    pub tables: TableStyle,
+    pub arrays: ArrayLayout,
+    pub comments: bool,
+    pub compact: bool,
+    pub keys: KeyOrder,
+    pub quotes: QuoteStyle,
+    pub strings: Vec<(KeyPath, QuoteStyle)>,
+    pub max_width: Option<usize>,
+    pub trailing_commas: bool,
+    pub align: bool,
+}
+
+impl Default for EmitOptions {
+    fn default() -> Self {
+        Self {
+            indent_width: 4,
+            indent_char: ' ',
+            spacing: true,
+            tables: TableStyle::default(),
+            arrays: ArrayLayout::default(),
+            comments: true,
+            compact: false,
+            keys: KeyOrder::default(),
+            quotes: QuoteStyle::default(),
+            strings: Vec::new(),
+            max_width: None,
+            trailing_commas: false,
+            align: false,
+        }
+    }
+}
+
+impl EmitOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn minified() -> Self {
+        Self {
+            spacing: false,
+            tables: TableStyle::Braces,
+            comments: false,
+            compact: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn canonical() -> Self {
+        Self {
+            comments: false,
+            keys: KeyOrder::Alphabetical,
+            ..Self::default()
+        }
+    }
+
+    pub fn indent(mut self, width: usize, chr: char) -> Self {
+        self.indent_width = width;
+        self.indent_char = chr;
+        self
+    }
+
+    pub fn spacing(mut self, spacing: bool) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    pub fn tables(mut self, tables: TableStyle) -> Self {
+        self.tables = tables;
+        self
+    }
+
+    pub fn arrays(mut self, arrays: ArrayLayout) -> Self {
+        self.arrays = arrays;
+        self
+    }
+
+    pub fn comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    pub fn keys(mut self, keys: KeyOrder) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    pub fn quotes(mut self, quotes: QuoteStyle) -> Self {
+        self.quotes = quotes;
+        self
+    }
+
+    pub fn quote(mut self, path: impl ToKeyPath, style: QuoteStyle) -> Self {
+        if let Ok(path) = path.to_key_path() {
+            self.strings.push((path, style));
+        }
+
+        self
+    }
+
+    pub fn max_width(mut self, max_width: usize) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    pub fn trailing_commas(mut self, trailing_commas: bool) -> Self {
+        self.trailing_commas = trailing_commas;
+        self
+    }
+
+    pub fn align(mut self, align: bool) -> Self {
+        self.align = align;
+        self
+    }
+}
+
+struct Width(usize);
+
+impl Write for Width {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.chars().count();
+        Ok(())
+    }
+}
+
+fn is_section(value: &Value) -> bool {
+    match value {
+        Value::Table(_) => true,
+        Value::Array(array) => !array.is_empty() && array.iter().all(Value::is_table),
+        _ => false,
+    }
+}
+
+struct Emitter<'e, W: Write> {
+    out: &'e mut W,
+    options: &'e EmitOptions,
+    written: bool,
+    path: KeyPath,
+    inline: bool,
+    column: usize,
+}
+
+impl<'e, W: Write> Emitter<'e, W> {
+    fn new(out: &'e mut W, options: &'e EmitOptions) -> Self {
+        Self {
+            out,
+            options,
+            written: false,
+            path: KeyPath::new(),
+            inline: false,
+            column: 0,
+        }
+    }
+
+    fn measure(&self, f: impl FnOnce(&mut Emitter<'_, Width>) -> fmt::Result) -> usize {
+        let mut width = Width(0);
+        let _ = f(&mut Emitter {
+            out: &mut width,
+            options: self.options,
+            written: false,
+            path: self.path.clone(),
+            inline: true,
+            column: 0,
+        });
+        width.0
+    }
+
+    fn wrap(&self, f: impl FnOnce(&mut Emitter<'_, Width>) -> fmt::Result, layout: bool) -> bool {
+        if self.options.compact || self.inline {
+            return false;
+        }
+
+        match self.options.max_width {
+            Some(max_width) => self.column + self.measure(f) > max_width,
+            None => layout,
+        }
+    }
+
+    fn locate(&mut self, f: impl FnOnce(&mut Emitter<'_, Width>) -> fmt::Result) {
+        if self.options.max_width.is_some() && !self.inline {
+            self.column = self.measure(f);
+        }
+    }
+
+    fn close(&mut self, last: bool) -> fmt::Result {
+        match !last || self.options.trailing_commas {
+            true => self.out.write_char(','),
+            false => Ok(()),
+        }
+    }
+
+    // paths are only tracked when some string carries an explicit quote style
+    fn enter(&mut self, segment: impl FnOnce() -> PathSegment) {
+        if !self.options.strings.is_empty() {
+            self.path.push(segment());
+        }
+    }
+
+    fn leave(&mut self) {
+        if !self.options.strings.is_empty() {
+            self.path.pop();
+        }
+    }
+
+    fn string(&mut self, s: &str) -> fmt::Result {
+        let style = self
+            .options
+            .strings
+            .iter()
+            .find(|(path, _)| *path == self.path)
+            .map_or(self.options.quotes, |(_, style)| *style);
+        let inline = self.options.compact && s.contains('\n');
+
+        match style {
+            QuoteStyle::Auto => match QuoteStyle::choose(s) {
+                QuoteStyle::Raw if !inline => write_raw(self.out, s),
+                QuoteStyle::Multiline if !inline => write_quoted(self.out, s, true),
+                _ => write_string(self.out, s),
+            },
+            QuoteStyle::Raw if is_raw(s) && !inline => write_raw(self.out, s),
+            QuoteStyle::Multiline if !inline => write_quoted(self.out, s, true),
+            _ => write_string(self.out, s),
+        }
+    }
+
+    fn indent(&mut self, depth: usize) -> fmt::Result {
+        if self.options.compact {
+            return Ok(());
+        }
+
+        for _ in 0..depth * self.options.indent_width {
+            self.out.write_char(self.options.indent_char)?;
+        }
+
+        Ok(())
+    }
+
+    fn commented(&self) -> bool {
+        self.options.comments && !self.options.compact
+    }
+
+    fn inline_comment(&mut self, comment: &Option<String>) -> fmt::Result {
+        match comment {
+            Some(comment) if self.commented() => write!(self.out, " #{}", comment),
+            _ => Ok(()),
+        }
+    }
+
+    fn newline(&mut self) -> fmt::Result {
+        match self.options.compact {
+            true => Ok(()),
+            false => self.out.write_char('\n'),
+        }
+    }
+
+    fn comments(&mut self, comments: &[String], depth: usize) -> fmt::Result {
+        if !self.commented() {
+            return Ok(());
+        }
+
+        for comment in comments {
+            self.indent(depth)?;
+            writeln!(self.out, "#{}", comment)?;
+        }
+
+        Ok(())
+    }
+
+    fn assign(&mut self, key: &str) -> fmt::Result {
+        self.pad(key, 0)
+    }
+
+    fn pad(&mut self, key: &str, width: usize) -> fmt::Result {
+        write_key(self.out, key)?;
+
+        if width > 0 {
+            for _ in self.measure(|e| write_key(e.out, key))..width {
+                self.out.write_char(' ')?;
+            }
+        }
+
+        self.out
+            .write_str(if self.options.spacing { " = " } else { "=" })
+    }
+
+    fn value(&mut self, value: &Value, depth: usize) -> fmt::Result {
+        match value {
+            Value::String(s) => self.string(s),
+            Value::Bytes(b) => write_bytes(self.out, b),
+            Value::Integer(i) => write!(self.out, "{}", i),
+            Value::Float(float) => write_float(self.out, *float),
+            Value::Bool(b) => write!(self.out, "{}", b),
+            Value::Array(array) => self.array(array, depth),
+            Value::Table(table) => self.table(table, depth),
+        }
+    }
+
+    fn sorted<'t>(&self, table: &'t Table) -> Vec<(&'t String, &'t Entry)> {
+        let mut entries: Vec<_> = table.iter().collect();
+        self.options.keys.sort(&mut entries);
+        entries
+    }
+
+    fn separator(&mut self) -> fmt::Result {
+        self.out
+            .write_str(if self.options.compact { "," } else { ", " })
+    }
+
+    fn array(&mut self, array: &[Value], depth: usize) -> fmt::Result {
+        if array.is_empty() {
+            return self.out.write_str("[]");
+        }
+
+        let multiline = self.wrap(
+            |e| e.array(array, 0),
+            self.options.arrays == ArrayLayout::Multiline,
+        );
+
+        match multiline {
+            true => {
+                self.out.write_str("[\n")?;
+
+                for (i, value) in array.iter().enumerate() {
+                    self.indent(depth + 1)?;
+                    self.locate(|e| e.indent(depth + 1));
+                    self.enter(|| PathSegment::Index(i));
+                    self.value(value, depth + 1)?;
+                    self.leave();
+                    self.close(i + 1 == array.len())?;
+                    self.out.write_char('\n')?;
+                }
+
+                self.indent(depth)?;
+            }
+            false => {
+                self.out.write_char('[')?;
+
+                for (i, value) in array.iter().enumerate() {
+                    if i > 0 {
+                        self.separator()?;
+                    }
+
+                    self.enter(|| PathSegment::Index(i));
+                    self.value(value, depth)?;
+                    self.leave();
+                }
+            }
+        }
+
+        self.out.write_char(']')
+    }
+
+    fn table(&mut self, table: &Table, depth: usize) -> fmt::Result {
+        if table.is_empty() {
+            return self.out.write_str("{}");
+        }
+
+        let multiline = self.wrap(
+            |e| e.table(table, 0),
+            self.options.tables == TableStyle::Braces,
+        );
+
+        match () {
+            _ if multiline => {
+                self.out.write_str("{\n")?;
+                self.entries(&self.sorted(table), depth + 1, true)?;
+                self.indent(depth)?;
+                self.out.write_char('}')
+            }
+            _ if self.options.compact => {
+                self.out.write_char('{')?;
+                self.entries(&self.sorted(table), depth + 1, true)?;
+                self.out.write_char('}')
+            }
+            _ => {
+                self.out.write_char('{')?;
+
+                for (i, (key, entry)) in self.sorted(table).into_iter().enumerate() {
+                    self.out.write_str(if i > 0 { ", " } else { " " })?;
+                    self.assign(key)?;
+                    self.enter(|| PathSegment::Key(key.clone()));
+                    self.value(&entry.value, depth)?;
+                    self.leave();
+                }
+
+                self.out.write_str(" }")
+            }
+        }
+    }
+
+    fn entries(
+        &mut self,
+        entries: &[(&String, &Entry)],
+        depth: usize,
+        separated: bool,
+    ) -> fmt::Result {
+        let width = match self.options.align && !self.options.compact {
+            true => entries
+                .iter()
+                .map(|(key, _)| self.measure(|e| write_key(e.out, key)))
+                .max()
+                .unwrap_or(0),
+            false => 0,
+        };
+
+        for (i, (key, entry)) in entries.iter().enumerate() {
+            if self.options.compact && !separated && self.written {
+                self.out.write_char(' ')?;
+            }
+
+            self.comments(&entry.comments.leading, depth)?;
+            self.indent(depth)?;
+            self.pad(key, width)?;
+            self.locate(|e| {
+                e.indent(depth)?;
+                e.pad(key, width)
+            });
+            self.enter(|| PathSegment::Key((*key).clone()));
+            self.value(&entry.value, depth)?;
+            self.leave();
+
+            if separated && !self.options.compact {
+                self.close(i + 1 == entries.len())?;
+            } else if separated && i + 1 < entries.len() {
+                self.out.write_char(',')?;
+            }
+
+            self.inline_comment(&entry.comments.inline)?;
+            self.newline()?;
+            self.comments(&entry.comments.trailing, depth)?;
+            self.written = true;
+        }
+
+        Ok(())
+    }
+
+    fn header(&mut self, path: &[&str], comments: &Comments, array: bool) -> fmt::Result {
+        if self.written {
+            self.out
+                .write_char(if self.options.compact { ' ' } else { '\n' })?;
+        }
+
+        self.comments(&comments.leading, 0)?;
+        self.out.write_str(if array { "[[" } else { "[" })?;
+
+        for (i, key) in path.iter().enumerate() {
+            if i > 0 {
+                self.out.write_char('.')?;
+            }
+
+            write_key(self.out, key)?;
+        }
+
+        self.out.write_str(if array { "]]" } else { "]" })?;
+        self.inline_comment(&comments.inline)?;
+        self.newline()?;
+        self.written = true;
+        Ok(())
+    }
+
+    fn section<'t>(&mut self, table: &'t Table, path: &mut Vec<&'t str>) -> fmt::Result {
+        let (sections, values): (Vec<_>, Vec<_>) = self
+            .sorted(table)
+            .into_iter()
+            .partition(|(_, entry)| is_section(&entry.value));
+
+        self.entries(&values, 0, false)?;
+
+        for (key, entry) in sections {
+            path.push(key);
+            self.enter(|| PathSegment::Key(key.clone()));
+
+            match &entry.value {
+                Value::Table(table) => {
+                    self.header(path, &entry.comments, false)?;
+                    self.section(table, path)?;
+                }
+                Value::Array(array) => {
+                    for (i, table) in array.iter().filter_map(Value::as_table).enumerate() {
+                        let comments = match i {
+                            0 => &entry.comments,
+                            _ => &Comments::default(),
+                        };
+
+                        self.header(path, comments, true)?;
+                        self.enter(|| PathSegment::Index(i));
+                        self.section(table, path)?;
+                        self.leave();
+                    }
+                }
+                _ => {}
+            }
+
+            self.comments(&entry.comments.trailing, 0)?;
+            self.leave();
+            path.pop();
+        }
+
+        Ok(())
+    }
+
+    fn document(&mut self, document: &Document) -> fmt::Result {
+        match self.options.tables {
+            TableStyle::Headers => self.section(&document.root, &mut Vec::new())?,
+            TableStyle::Braces => self.entries(&self.sorted(&document.root), 0, false)?,
+        }
+
+        if self.written && self.commented() && !document.comments.is_empty() {
+            self.out.write_char('\n')?;
+        }
+
+        self.comments(&document.comments, 0)
+    }
+}
+
+pub fn write_document(f: &mut impl Write, document: &Document) -> fmt::Result {
+    write_document_with(f, document, &EmitOptions::default())
+}
+
+pub fn write_document_with(
+    f: &mut impl Write,
+    document: &Document,
+    options: &EmitOptions,
+) -> fmt::Result {
+    Emitter::new(f, options).document(document)
+}
+
+pub struct SliceWriter<'b> {
+    buffer: &'b mut [u8],
+    len: usize,
+}
+
+impl<'b> SliceWriter<'b> {
+    pub fn new(buffer: &'b mut [u8]) -> Self {
+        Self { buffer, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only whole `str`s are ever copied in, so the prefix is always valid utf-8
+        core::str::from_utf8(self.as_bytes()).unwrap_or_default()
+    }
+}
+
+impl Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+
+        match self.buffer.get_mut(self.len..end) {
+            Some(slot) => {
+                slot.copy_from_slice(s.as_bytes());
+                self.len = end;
+                Ok(())
+            }
+            None => Err(fmt::Error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub struct IoWriter<W: std::io::Write> {
+    inner: W,
+    error: Option<std::io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> IoWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, error: None }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    pub fn take_error(&mut self) -> Option<std::io::Error> {
+        self.error.take()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+pub fn write_io(
+    writer: impl std::io::Write,
+    document: &Document,
+    options: &EmitOptions,
+) -> std::io::Result<()> {
+    let mut writer = IoWriter::new(writer);
+
+    match write_document_with(&mut writer, document, options) {
+        Ok(()) => Ok(()),
+        Err(fmt::Error) => Err(writer
+            .take_error()
+            .unwrap_or_else(|| std::io::Error::other("formatter error"))),
+    }
+}
+
+pub fn to_string_preserving(cst: &Cst, document: &Document) -> String {
+    to_string_preserving_with(cst, document, &EmitOptions::default())
+}
+
+// the kept text holds its own layout, so only `align` is applied to it; the other options are
+// used when the document can't be laid over the tree and is written out afresh
+pub fn to_string_preserving_with(cst: &Cst, document: &Document, options: &EmitOptions) -> String {
+    let mut cst = cst.clone();
+
+    if !cst.update(document) {
+        return to_string_with(document, options);
+    }
+
+    if options.align && !options.compact {
+        cst.align();
+    }
+
+    alloc::format!("{}", cst)
+}
+
+pub fn to_string(document: &Document) -> String {
+    to_string_with(document, &EmitOptions::default())
+}
+
+pub fn to_string_with(document: &Document, options: &EmitOptions) -> String {
+    let mut text = String::new();
+    let _ = write_document_with(&mut text, document, options);
+    text
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Emitter::new(f, &EmitOptions::default()).value(self, 0)
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_document(f, self)
+    }
+}
+
+pub trait ToPcf {
+    fn to_pcf(&self) -> Option<Value>;
+}
+
+impl<T: ToPcf + ?Sized> ToPcf for &T {
+    fn to_pcf(&self) -> Option<Value> {
+        (**self).to_pcf()
+    }
+}
+
+impl ToPcf for Value {
+    fn to_pcf(&self) -> Option<Value> {
+        Some(self.clone())
+    }
+}
+
+impl ToPcf for Table {
+    fn to_pcf(&self) -> Option<Value> {
+        Some(Value::Table(self.clone()))
+    }
+}
+
+impl ToPcf for str {
+    fn to_pcf(&self) -> Option<Value> {
+        Some(self.into())
+    }
+}
+
+impl ToPcf for String {
+    fn to_pcf(&self) -> Option<Value> {
+        Some(self.as_str().into())
+    }
+}
+
+impl ToPcf for bool {
+    fn to_pcf(&self) -> Option<Value> {
+        Some((*self).into())
+    }
+}
+
+impl ToPcf for f32 {
+    fn to_pcf(&self) -> Option<Value> {
+        Some((*self).into())
+    }
+}
+
+impl ToPcf for f64 {
+    fn to_pcf(&self) -> Option<Value> {
+        Some((*self).into())
+    }
+}
+
+macro_rules! to_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl ToPcf for $ty {
+                // integers beyond `i64` degrade to floats, like `OverflowPolicy::Float` when lexing
+                fn to_pcf(&self) -> Option<Value> {
+                    Some(match i64::try_from(*self) {
+                        Ok(i) => Value::Integer(i),
+                        Err(_) => Value::Float(*self as f64),
+                    })
+                }
+            }
+        )*
+    };
+}
+
+to_integer!(
+    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize
+);
+
+impl<T: ToPcf> ToPcf for Option<T> {
+    fn to_pcf(&self) -> Option<Value> {
+        self.as_ref()?.to_pcf()
+    }
+}
+
+// a table can leave out an absent value but an array can't without moving every element after
+// it, so one absent element leaves the whole array without a representation
+impl<T: ToPcf> ToPcf for [T] {
+    fn to_pcf(&self) -> Option<Value> {
+        self.iter()
+            .map(T::to_pcf)
+            .collect::<Option<_>>()
+            .map(Value::Array)
+    }
+}
+
+impl<T: ToPcf, const N: usize> ToPcf for [T; N] {
+    fn to_pcf(&self) -> Option<Value> {
+        self.as_slice().to_pcf()
+    }
+}
+
+impl<T: ToPcf> ToPcf for Vec<T> {
+    fn to_pcf(&self) -> Option<Value> {
+        self.as_slice().to_pcf()
+    }
+}
+
+impl<K: AsRef<str>, T: ToPcf> ToPcf for BTreeMap<K, T> {
+    fn to_pcf(&self) -> Option<Value> {
+        let mut table = Table::new();
+
+        for (key, value) in self {
+            if let Some(value) = value.to_pcf() {
+                table.set(key.as_ref(), value);
+            }
+        }
+
+        Some(Value::Table(table))
+    }
+}
+
+macro_rules! to_tuple {
+    ($(($($name:ident),+)),*) => {
+        $(
+            impl<$($name: ToPcf),+> ToPcf for ($($name,)+) {
+                #[allow(non_snake_case)]
+                fn to_pcf(&self) -> Option<Value> {
+                    let ($($name,)+) = self;
+                    [$($name.to_pcf()),+].into_iter().collect::<Option<_>>().map(Value::Array)
+                }
+            }
+        )*
+    };
+}
+
+to_tuple!(
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F)
+);
+
+struct Hashing<'h, H: Hasher>(&'h mut H);
+
+impl<H: Hasher> Write for Hashing<'_, H> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl Document {
+    pub fn hash_canonical<H: Hasher>(&self, state: &mut H) {
+        let _ = write_document_with(&mut Hashing(state), self, &EmitOptions::canonical());
+    }
+
+    pub fn canonical_hash<H: Hasher + Default>(&self) -> u64 {
+        let mut state = H::default();
+        self.hash_canonical(&mut state);
+        state.finish()
+    }
+
+    pub fn from_pcf<T: ToPcf + ?Sized>(value: &T) -> Option<Self> {
+        match value.to_pcf()? {
+            Value::Table(table) => Some(Self::new(table)),
+            _ => None,
+        }
+    }
+}
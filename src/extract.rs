@@ -0,0 +1,331 @@
+use crate::{path::*, utils::Span, value::*};
+use alloc::{collections::btree_map::BTreeMap, string::String, vec::Vec};
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtractErrorKind {
+    Invalid,
+    MissingKey,
+    MismatchedType {
+        expected: ValueKind,
+        found: ValueKind,
+    },
+    OutOfRange,
+    UnknownVariant,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractError {
+    pub path: KeyPath,
+    pub span: Span,
+    pub kind: ExtractErrorKind,
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}{}] ", self.path, self.span)?;
+
+        match self.kind {
+            ExtractErrorKind::Invalid => write!(f, "encountered invalid value during extraction"),
+            ExtractErrorKind::MissingKey => write!(f, "encountered missing key during extraction"),
+            ExtractErrorKind::MismatchedType { expected, found } => write!(
+                f,
+                "encountered {} during extraction, expected {}",
+                found, expected
+            ),
+            ExtractErrorKind::OutOfRange => {
+                write!(f, "encountered out-of-range value during extraction")
+            }
+            ExtractErrorKind::UnknownVariant => {
+                write!(f, "encountered unknown variant during extraction")
+            }
+        }
+    }
+}
+
+impl core::error::Error for ExtractError {}
+
+#[derive(Debug, Clone, Copy)]
+enum Node<'v> {
+    Value(&'v Value),
+    Table(&'v Table),
+}
+
+#[derive(Debug, Clone)]
+pub struct Cursor<'v> {
+    node: Node<'v>,
+    path: KeyPath,
+    span: Span,
+}
+
+impl<'v> Cursor<'v> {
+    pub fn new(value: &'v Value, path: KeyPath, span: Span) -> Self {
+        Self {
+            node: Node::Value(value),
+            path,
+            span,
+        }
+    }
+
+    pub fn root(table: &'v Table) -> Self {
+        Self {
+            node: Node::Table(table),
+            path: KeyPath::new(),
+            span: Span::default(),
+        }
+    }
+
+    pub fn value(&self) -> Option<&'v Value> {
+        match self.node {
+            Node::Value(value) => Some(value),
+            Node::Table(_) => None,
+        }
+    }
+
+    pub fn kind(&self) -> ValueKind {
+        match self.node {
+            Node::Value(value) => value.kind(),
+            Node::Table(_) => ValueKind::Table,
+        }
+    }
+
+    pub fn path(&self) -> &KeyPath {
+        &self.path
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn error(&self, kind: ExtractErrorKind) -> ExtractError {
+        ExtractError {
+            path: self.path.clone(),
+            span: self.span,
+            kind,
+        }
+    }
+
+    pub fn mismatched(&self, expected: ValueKind) -> ExtractError {
+        self.error(ExtractErrorKind::MismatchedType {
+            expected,
+            found: self.kind(),
+        })
+    }
+
+    pub fn table(&self) -> Result<&'v Table, ExtractError> {
+        match self.node {
+            Node::Table(table) | Node::Value(Value::Table(table)) => Ok(table),
+            _ => Err(self.mismatched(ValueKind::Table)),
+        }
+    }
+
+    pub fn str(&self) -> Result<&'v str, ExtractError> {
+        match self.value() {
+            Some(Value::String(s)) => Ok(s),
+            _ => Err(self.mismatched(ValueKind::String)),
+        }
+    }
+
+    pub fn bytes(&self) -> Result<&'v [u8], ExtractError> {
+        match self.value() {
+            Some(Value::Bytes(b)) => Ok(b),
+            _ => Err(self.mismatched(ValueKind::Bytes)),
+        }
+    }
+
+    pub fn array(&self) -> Result<&'v [Value], ExtractError> {
+        match self.value() {
+            Some(Value::Array(array)) => Ok(array),
+            _ => Err(self.mismatched(ValueKind::Array)),
+        }
+    }
+
+    fn join(&self, segment: PathSegment) -> KeyPath {
+        let mut path = self.path.clone();
+        path.push(segment);
+        path
+    }
+
+    pub fn child(&self, key: &str) -> Result<Option<Cursor<'v>>, ExtractError> {
+        Ok(self.table()?.get_entry(key).map(|entry| {
+            Cursor::new(
+                &entry.value,
+                self.join(PathSegment::Key(key.into())),
+                entry.span,
+            )
+        }))
+    }
+
+    // array elements carry no spans of their own, so they borrow the array's
+    pub fn element(&self, index: usize) -> Result<Option<Cursor<'v>>, ExtractError> {
+        Ok(self
+            .array()?
+            .get(index)
+            .map(|value| Cursor::new(value, self.join(PathSegment::Index(index)), self.span)))
+    }
+
+    pub fn field<T: FromValue>(&self, key: &str) -> Result<T, ExtractError> {
+        match self.child(key)? {
+            Some(child) => T::from_value(&child),
+            None => T::from_missing(self.join(PathSegment::Key(key.into())), self.span),
+        }
+    }
+
+    // enums are written as a bare string for unit variants, or as a table with the
+    // variant as its only key otherwise, the same way the serde layer writes them
+    pub fn variant(&self) -> Result<(&'v str, Option<Cursor<'v>>), ExtractError> {
+        if let Ok(variant) = self.str() {
+            return Ok((variant, None));
+        }
+
+        let mut entries = self.table()?.iter();
+
+        match (entries.next(), entries.next()) {
+            (Some((variant, _)), None) => Ok((variant, self.child(variant)?)),
+            _ => Err(self.error(ExtractErrorKind::Invalid)),
+        }
+    }
+
+    pub fn tagged(
+        &self,
+        tag: &str,
+        content: &str,
+    ) -> Result<(&'v str, Option<Cursor<'v>>), ExtractError> {
+        let Some(cursor) = self.child(tag)? else {
+            return Err(ExtractError {
+                path: self.join(PathSegment::Key(tag.into())),
+                span: self.span,
+                kind: ExtractErrorKind::MissingKey,
+            });
+        };
+
+        Ok((cursor.str()?, self.child(content)?))
+    }
+}
+
+pub trait FromValue: Sized {
+    fn from_value(cursor: &Cursor<'_>) -> Result<Self, ExtractError>;
+
+    fn from_missing(path: KeyPath, span: Span) -> Result<Self, ExtractError> {
+        Err(ExtractError {
+            path,
+            span,
+            kind: ExtractErrorKind::MissingKey,
+        })
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(cursor: &Cursor<'_>) -> Result<Self, ExtractError> {
+        match cursor.value() {
+            Some(value) => Ok(value.clone()),
+            None => cursor.table().cloned().map(Value::Table),
+        }
+    }
+}
+
+impl FromValue for Table {
+    fn from_value(cursor: &Cursor<'_>) -> Result<Self, ExtractError> {
+        cursor.table().cloned()
+    }
+}
+
+impl FromValue for String {
+    fn from_value(cursor: &Cursor<'_>) -> Result<Self, ExtractError> {
+        cursor.str().map(String::from)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(cursor: &Cursor<'_>) -> Result<Self, ExtractError> {
+        match cursor.value() {
+            Some(Value::Bool(b)) => Ok(*b),
+            _ => Err(cursor.mismatched(ValueKind::Bool)),
+        }
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(cursor: &Cursor<'_>) -> Result<Self, ExtractError> {
+        match cursor.value() {
+            Some(Value::Float(f)) => Ok(*f),
+            _ => Err(cursor.mismatched(ValueKind::Float)),
+        }
+    }
+}
+
+impl FromValue for f32 {
+    fn from_value(cursor: &Cursor<'_>) -> Result<Self, ExtractError> {
+        f64::from_value(cursor).map(|f| f as f32)
+    }
+}
+
+macro_rules! integer {
+    ($($ty:ty),*) => {
+        $(
+            impl FromValue for $ty {
+                fn from_value(cursor: &Cursor<'_>) -> Result<Self, ExtractError> {
+                    match cursor.value() {
+                        Some(Value::Integer(i)) => (*i)
+                            .try_into()
+                            .map_err(|_| cursor.error(ExtractErrorKind::OutOfRange)),
+                        _ => Err(cursor.mismatched(ValueKind::Integer)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(cursor: &Cursor<'_>) -> Result<Self, ExtractError> {
+        T::from_value(cursor).map(Some)
+    }
+
+    fn from_missing(_path: KeyPath, _span: Span) -> Result<Self, ExtractError> {
+        Ok(None)
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(cursor: &Cursor<'_>) -> Result<Self, ExtractError> {
+        cursor
+            .array()?
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                T::from_value(&Cursor::new(
+                    value,
+                    cursor.join(PathSegment::Index(i)),
+                    cursor.span,
+                ))
+            })
+            .collect()
+    }
+}
+
+impl<T: FromValue> FromValue for BTreeMap<String, T> {
+    fn from_value(cursor: &Cursor<'_>) -> Result<Self, ExtractError> {
+        cursor
+            .table()?
+            .iter()
+            .map(|(key, _)| Ok((key.clone(), cursor.field(key)?)))
+            .collect()
+    }
+}
+
+impl Document {
+    pub fn extract<T: FromValue>(&self) -> Result<T, ExtractError> {
+        T::from_value(&Cursor::root(&self.root))
+    }
+
+    // errors keep the full path from the root, so they still read well out of context
+    pub fn extract_at<T: FromValue>(&self, path: impl ToKeyPath) -> Result<T, ExtractError> {
+        match self.locate(path) {
+            Ok((value, path, span)) => T::from_value(&Cursor::new(value, path, span)),
+            Err(error) => T::from_missing(error.path, error.span),
+        }
+    }
+}
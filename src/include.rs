@@ -0,0 +1,205 @@
+use crate::{
+    lex::*,
+    parse::{Event, Parser, ParserOptions, parse_with_includes},
+    utils::*,
+    value::Document,
+};
+use alloc::{boxed::Box, collections::btree_map::BTreeMap, string::String, vec::Vec};
+use core::fmt;
+
+pub trait Resolver {
+    type Error;
+
+    fn load(&self, path: &str) -> core::result::Result<String, Self::Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotFound;
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("no such file")
+    }
+}
+
+impl core::error::Error for NotFound {}
+
+impl Resolver for BTreeMap<String, String> {
+    type Error = NotFound;
+
+    fn load(&self, path: &str) -> core::result::Result<String, NotFound> {
+        self.get(path).cloned().ok_or(NotFound)
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FsResolver {
+    pub root: std::path::PathBuf,
+}
+
+#[cfg(feature = "std")]
+impl FsResolver {
+    pub fn new(root: impl Into<std::path::PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Resolver for FsResolver {
+    type Error = std::io::Error;
+
+    fn load(&self, path: &str) -> std::io::Result<String> {
+        std::fs::read_to_string(self.root.join(path))
+    }
+}
+
+// a file the resolver couldn't hand over, with the directive that asked for it unless it's
+// the root, and whatever the resolver said went wrong
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadError<E> {
+    pub path: String,
+    pub included: Option<Included>,
+    pub error: E,
+}
+
+impl<E: fmt::Display> fmt::Display for LoadError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(included) = &self.included {
+            write!(f, "[{}{}] ", included.file, included.span)?;
+        }
+
+        write!(f, "failed to load `{}`: {}", self.path, self.error)
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> core::error::Error for LoadError<E> {}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Files {
+    files: Vec<(String, String)>,
+}
+
+impl Files {
+    pub fn load<R: Resolver>(
+        resolver: &R,
+        root: &str,
+    ) -> core::result::Result<Self, Box<LoadError<R::Error>>> {
+        let mut files = Self::default();
+        let mut pending = alloc::vec![(String::from(root), None)];
+
+        while let Some((path, included)) = pending.pop() {
+            if files.files.iter().any(|(p, _)| *p == path) {
+                continue;
+            }
+
+            let content = match resolver.load(&path) {
+                Ok(content) => content,
+                Err(error) => {
+                    return Err(Box::new(LoadError {
+                        path,
+                        included,
+                        error,
+                    }));
+                }
+            };
+
+            let src = Source::new(&path, content);
+            pending.extend(
+                includes(&src)
+                    .into_iter()
+                    .map(|(include, span)| (include, Some(Included::new(&src, span)))),
+            );
+            let content = src.content;
+            files.files.push((path, content));
+        }
+
+        Ok(files)
+    }
+
+    pub fn sources(&self) -> Vec<Source<'_>> {
+        self.files
+            .iter()
+            .map(|(path, content)| Source::new(path, content.clone()))
+            .collect()
+    }
+
+    // the root is loaded first, so it leads the map
+    pub fn source_map(&self) -> SourceMap<'_> {
+        SourceMap {
+            sources: self.sources(),
+        }
+    }
+}
+
+// every file a document is built from, the root first, so that spans reported against any of
+// them can be resolved back to the right file's text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMap<'a> {
+    sources: Vec<Source<'a>>,
+}
+
+impl<'a> SourceMap<'a> {
+    pub fn new(root: Source<'a>) -> Self {
+        Self {
+            sources: alloc::vec![root],
+        }
+    }
+
+    // a file added under a name that's already in the map replaces it
+    pub fn add(&mut self, src: Source<'a>) {
+        match self.sources.iter_mut().find(|s| s.file == src.file) {
+            Some(existing) => *existing = src,
+            None => self.sources.push(src),
+        }
+    }
+
+    pub fn with(mut self, src: Source<'a>) -> Self {
+        self.add(src);
+        self
+    }
+
+    pub fn root(&self) -> &Source<'a> {
+        &self.sources[0]
+    }
+
+    pub fn get(&self, file: &str) -> Option<&Source<'a>> {
+        self.sources.iter().find(|s| s.file == file)
+    }
+
+    pub fn sources(&self) -> &[Source<'a>] {
+        &self.sources
+    }
+
+    pub fn slice(&self, file: &str, span: Span) -> Option<&str> {
+        self.get(file).map(|src| src.slice(span))
+    }
+
+    pub fn snippet(&self, file: &str, span: Span) -> Option<Snippet> {
+        self.get(file).map(|src| Snippet::new(&src.content, span))
+    }
+
+    pub fn parse(&'a self, options: ParserOptions) -> Result<'a, Document> {
+        parse_with_includes(self.root(), &self.sources, options)
+    }
+}
+
+// the parser's own events pick out the directives, so only the ones it would act on are
+// loaded; anything after an error is left for the parse itself to report
+fn includes(src: &Source<'_>) -> Vec<(String, Span)> {
+    let Ok(lexemes) = lex(src) else {
+        return Vec::new();
+    };
+
+    let mut parser = Parser::new(src, lexemes);
+    let mut includes: Vec<_> = core::iter::from_fn(|| parser.next_event())
+        .map_while(core::result::Result::ok)
+        .filter_map(|event| match event {
+            Event::Include { path, span } => Some((path, span)),
+            _ => None,
+        })
+        .collect();
+
+    includes.reverse();
+    includes
+}
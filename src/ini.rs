@@ -0,0 +1,225 @@
+use crate::{utils::*, value::*};
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IniOptions {
+    pub infer: bool,
+    pub nested: bool,
+}
+
+impl IniOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn infer(mut self, infer: bool) -> Self {
+        self.infer = infer;
+        self
+    }
+
+    pub fn nested(mut self, nested: bool) -> Self {
+        self.nested = nested;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IniErrorKind {
+    ConflictingKey,
+    EmptyKey,
+    UnterminatedSection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IniError {
+    pub span: Span,
+    pub kind: IniErrorKind,
+}
+
+impl fmt::Display for IniError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] ", self.span)?;
+
+        match self.kind {
+            IniErrorKind::ConflictingKey => {
+                write!(
+                    f,
+                    "encountered section conflicting with a key during conversion"
+                )
+            }
+            IniErrorKind::EmptyKey => write!(f, "encountered empty key during conversion"),
+            IniErrorKind::UnterminatedSection => {
+                write!(f, "encountered unterminated section during conversion")
+            }
+        }
+    }
+}
+
+impl core::error::Error for IniError {}
+
+fn span(line: usize, text: &str, offset: usize, part: &str) -> Span {
+    let begin = part.as_ptr() as usize - text.as_ptr() as usize;
+    let col = |index: usize| text[..index].chars().count() + 1;
+
+    Span {
+        begin: Location::new(line, col(begin), offset + begin),
+        end: Location::new(line, col(begin + part.len()), offset + begin + part.len()),
+    }
+}
+
+fn unquote(value: &str) -> Option<&str> {
+    ['"', '\'']
+        .into_iter()
+        .find_map(|quote| value.strip_prefix(quote)?.strip_suffix(quote))
+}
+
+// ini has no types, so inference only recognises unambiguous spellings and leaves the rest as text
+fn infer(value: &str) -> Value {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" => return Value::Bool(true),
+        "false" | "no" | "off" => return Value::Bool(false),
+        _ => {}
+    }
+
+    let numeric = value.bytes().any(|b| b.is_ascii_digit())
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.' | b'e' | b'E'));
+
+    match (numeric, value.parse(), value.parse()) {
+        (true, Ok(i), _) => Value::Integer(i),
+        (true, _, Ok(f)) => Value::Float(f),
+        _ => Value::String(value.into()),
+    }
+}
+
+fn section<'t>(root: &'t mut Table, path: &[String], span: Span) -> Option<&'t mut Table> {
+    let Some((key, rest)) = path.split_first() else {
+        return Some(root);
+    };
+
+    if !root.contains_key(key) {
+        root.insert(
+            key.clone(),
+            Entry::new(Value::Table(Table::new()), span, span),
+        );
+    }
+
+    match root.get_mut(key)? {
+        Value::Table(table) => section(table, rest, span),
+        _ => None,
+    }
+}
+
+impl Document {
+    pub fn from_ini_str(content: &str) -> core::result::Result<Self, IniError> {
+        Self::from_ini_str_with(content, &IniOptions::default())
+    }
+
+    pub fn from_ini_str_with(
+        content: &str,
+        options: &IniOptions,
+    ) -> core::result::Result<Self, IniError> {
+        let mut root = Table::new();
+        let mut current = Vec::new();
+        let mut comments = Vec::new();
+        let mut offset = 0;
+
+        for (index, text) in content.split_inclusive('\n').enumerate() {
+            let line = index + 1;
+            let begin = offset;
+            let locate = |part: &str| span(line, text, begin, part);
+            let trimmed = text.trim();
+            offset += text.len();
+
+            if trimmed.is_empty() {
+                continue;
+            }
+
+            if let Some(comment) = trimmed.strip_prefix([';', '#']) {
+                comments.push(comment.into());
+                continue;
+            }
+
+            if let Some(header) = trimmed.strip_prefix('[') {
+                let Some(name) = header.strip_suffix(']') else {
+                    return Err(IniError {
+                        span: locate(trimmed),
+                        kind: IniErrorKind::UnterminatedSection,
+                    });
+                };
+
+                let name = name.trim();
+                let header_span = locate(name);
+
+                current = match options.nested {
+                    true => name.split('.').map(|part| part.trim().into()).collect(),
+                    false => alloc::vec![String::from(name)],
+                };
+
+                if current.iter().any(String::is_empty) {
+                    return Err(IniError {
+                        span: header_span,
+                        kind: IniErrorKind::EmptyKey,
+                    });
+                }
+
+                let conflict = IniError {
+                    span: header_span,
+                    kind: IniErrorKind::ConflictingKey,
+                };
+                let (last, parent) = current.split_last().ok_or(conflict)?;
+                let parent = section(&mut root, parent, header_span).ok_or(conflict)?;
+
+                // repeated sections merge into the first, keeping its position
+                match parent.get_entry_mut(last) {
+                    Some(entry) if matches!(entry.value, Value::Table(_)) => {
+                        entry.comments.leading.append(&mut comments)
+                    }
+                    Some(_) => return Err(conflict),
+                    None => {
+                        let mut entry =
+                            Entry::new(Value::Table(Table::new()), header_span, header_span);
+                        entry.comments.leading = core::mem::take(&mut comments);
+                        parent.insert(last.clone(), entry);
+                    }
+                }
+
+                continue;
+            }
+
+            // keys without a value are kept as empty strings, as most ini readers do
+            let (key, value) = match trimmed.find(['=', ':']) {
+                Some(i) => (trimmed[..i].trim(), trimmed[i + 1..].trim()),
+                None => (trimmed, &trimmed[trimmed.len()..]),
+            };
+
+            if key.is_empty() {
+                return Err(IniError {
+                    span: locate(trimmed),
+                    kind: IniErrorKind::EmptyKey,
+                });
+            }
+
+            let value_span = locate(value);
+            let value = match (unquote(value), options.infer) {
+                (Some(unquoted), _) => Value::String(unquoted.into()),
+                (None, true) => infer(value),
+                (None, false) => Value::String(value.into()),
+            };
+
+            // sections were created when their header was read, so this always finds one
+            let Some(table) = section(&mut root, &current, value_span) else {
+                continue;
+            };
+
+            // later duplicates win, matching the usual ini behaviour
+            let mut entry = Entry::new(value, locate(key), value_span);
+            entry.comments.leading = core::mem::take(&mut comments);
+            table.insert(key.into(), entry);
+        }
+
+        Ok(Document::new(root))
+    }
+}
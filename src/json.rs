@@ -0,0 +1,701 @@
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics, RenderDiagnostic},
+    emit::{write_base64, write_float},
+    lex::OverflowPolicy,
+    path::*,
+    utils::*,
+    value::*,
+};
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt::{self, Write},
+    str::Chars,
+};
+
+const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonErrorKind {
+    DuplicateKey,
+    IntegerOverflow,
+    InvalidEscape,
+    MalformedNumber,
+    NestingTooDeep,
+    NotAnObject,
+    TrailingCharacters,
+    UnexpectedCharacter,
+    UnexpectedEnd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonError {
+    pub span: Span,
+    pub kind: JsonErrorKind,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] ", self.span)?;
+
+        match self.kind {
+            JsonErrorKind::DuplicateKey => write!(f, "encountered duplicate key during conversion"),
+            JsonErrorKind::IntegerOverflow => {
+                write!(f, "encountered out-of-range integer during conversion")
+            }
+            JsonErrorKind::InvalidEscape => {
+                write!(f, "encountered invalid escape during conversion")
+            }
+            JsonErrorKind::MalformedNumber => {
+                write!(f, "encountered malformed number during conversion")
+            }
+            JsonErrorKind::NestingTooDeep => {
+                write!(f, "encountered excessive nesting during conversion")
+            }
+            JsonErrorKind::NotAnObject => {
+                write!(f, "encountered non-object document during conversion")
+            }
+            JsonErrorKind::TrailingCharacters => {
+                write!(f, "encountered trailing characters during conversion")
+            }
+            JsonErrorKind::UnexpectedCharacter => {
+                write!(f, "encountered unexpected character during conversion")
+            }
+            JsonErrorKind::UnexpectedEnd => {
+                write!(f, "encountered unexpected end during conversion")
+            }
+        }
+    }
+}
+
+impl core::error::Error for JsonError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonWarningKind {
+    NonFinite,
+    Null,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonWarning {
+    pub path: KeyPath,
+    pub span: Span,
+    pub kind: JsonWarningKind,
+}
+
+impl fmt::Display for JsonWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}{}] ", self.path, self.span)?;
+
+        match self.kind {
+            JsonWarningKind::NonFinite => {
+                write!(
+                    f,
+                    "encountered non-finite float (written as null) during conversion"
+                )
+            }
+            JsonWarningKind::Null => write!(f, "encountered null (dropped) during conversion"),
+        }
+    }
+}
+
+fn write_string(f: &mut impl Write, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+
+    for chr in s.chars() {
+        match chr {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\t' => f.write_str("\\t")?,
+            '\r' => f.write_str("\\r")?,
+            '\u{8}' => f.write_str("\\b")?,
+            '\u{c}' => f.write_str("\\f")?,
+            _ if (chr as u32) < 0x20 => write!(f, "\\u{:04x}", chr as u32)?,
+            _ => f.write_char(chr)?,
+        }
+    }
+
+    f.write_char('"')
+}
+
+fn write_span(f: &mut impl Write, span: Span) -> fmt::Result {
+    write!(
+        f,
+        "{{\"begin\":{{\"line\":{},\"col\":{},\"offset\":{}}},\"end\":{{\"line\":{},\"col\":{},\"offset\":{}}}}}",
+        span.begin.line,
+        span.begin.col,
+        span.begin.offset,
+        span.end.line,
+        span.end.col,
+        span.end.offset
+    )
+}
+
+fn write_table(f: &mut impl Write, table: &Table, spanned: bool) -> fmt::Result {
+    f.write_char('{')?;
+
+    for (i, (key, entry)) in table.iter().enumerate() {
+        if i > 0 {
+            f.write_char(',')?;
+        }
+
+        write_string(f, key)?;
+        f.write_char(':')?;
+
+        if spanned {
+            f.write_str("{\"key_span\":")?;
+            write_span(f, entry.key_span)?;
+            f.write_str(",\"span\":")?;
+            write_span(f, entry.span)?;
+            f.write_str(",\"value\":")?;
+            write_value(f, &entry.value, spanned)?;
+            f.write_char('}')?;
+        } else {
+            write_value(f, &entry.value, spanned)?;
+        }
+    }
+
+    f.write_char('}')
+}
+
+fn write_value(f: &mut impl Write, value: &Value, spanned: bool) -> fmt::Result {
+    match value {
+        Value::String(s) => write_string(f, s),
+        // json has no byte strings, so bytes become padded standard base64
+        Value::Bytes(b) => {
+            f.write_char('"')?;
+            write_base64(f, b)?;
+            f.write_char('"')
+        }
+        Value::Integer(i) => write!(f, "{}", i),
+        // json cannot represent nan or infinities, `non_finite` reports every one written this way
+        Value::Float(float) if !float.is_finite() => f.write_str("null"),
+        Value::Float(float) => write_float(f, *float),
+        Value::Bool(b) => write!(f, "{}", b),
+        Value::Array(array) => {
+            f.write_char('[')?;
+
+            for (i, value) in array.iter().enumerate() {
+                if i > 0 {
+                    f.write_char(',')?;
+                }
+
+                write_value(f, value, spanned)?;
+            }
+
+            f.write_char(']')
+        }
+        Value::Table(table) => write_table(f, table, spanned),
+    }
+}
+
+fn non_finite(value: &Value, path: &mut KeyPath, span: Span, warnings: &mut Vec<JsonWarning>) {
+    match value {
+        Value::Float(float) if !float.is_finite() => warnings.push(JsonWarning {
+            path: path.clone(),
+            span,
+            kind: JsonWarningKind::NonFinite,
+        }),
+        // elements have no spans of their own, so they report the span of their entry
+        Value::Array(array) => {
+            for (i, value) in array.iter().enumerate() {
+                path.push(PathSegment::Index(i));
+                non_finite(value, path, span, warnings);
+                path.pop();
+            }
+        }
+        Value::Table(table) => {
+            for (key, entry) in table.iter() {
+                path.push(PathSegment::Key(key.clone()));
+                non_finite(&entry.value, path, entry.span, warnings);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+pub fn write_json(f: &mut impl Write, document: &Document) -> fmt::Result {
+    write_table(f, &document.root, false)
+}
+
+pub fn write_json_spanned(f: &mut impl Write, document: &Document) -> fmt::Result {
+    write_table(f, &document.root, true)
+}
+
+// diagnostics don't know which file they came from, so it's written alongside each one to keep
+// every line self-contained
+pub fn write_diagnostic_json(
+    f: &mut impl Write,
+    file: &str,
+    diagnostic: &Diagnostic,
+) -> fmt::Result {
+    write!(f, "{{\"severity\":\"{}\",\"code\":", diagnostic.severity)?;
+    write_string(f, diagnostic.code)?;
+    f.write_str(",\"message\":")?;
+    write_string(f, &diagnostic.message)?;
+    f.write_str(",\"file\":")?;
+    write_string(f, file)?;
+    f.write_str(",\"labels\":[")?;
+
+    for (i, (span, label)) in diagnostic.labels.iter().enumerate() {
+        if i > 0 {
+            f.write_char(',')?;
+        }
+
+        f.write_str("{\"span\":")?;
+        write_span(f, *span)?;
+        f.write_str(",\"message\":")?;
+        write_string(f, label)?;
+        f.write_char('}')?;
+    }
+
+    f.write_str("],\"notes\":[")?;
+
+    for (i, note) in diagnostic.notes.iter().enumerate() {
+        if i > 0 {
+            f.write_char(',')?;
+        }
+
+        write_string(f, note)?;
+    }
+
+    f.write_str("],\"help\":")?;
+
+    match &diagnostic.help {
+        Some(help) => write_string(f, help)?,
+        None => f.write_str("null")?,
+    }
+
+    f.write_char('}')
+}
+
+// one object per line, so tools can stream them without reading the whole output
+pub fn write_diagnostics_json_lines(
+    f: &mut impl Write,
+    file: &str,
+    diagnostics: &Diagnostics,
+) -> fmt::Result {
+    for diagnostic in diagnostics {
+        write_diagnostic_json(f, file, diagnostic)?;
+        f.write_char('\n')?;
+    }
+
+    Ok(())
+}
+
+// renders each diagnostic as one line of json, for tools that take diagnostics as a stream
+#[derive(Debug)]
+pub struct JsonLines<W> {
+    out: W,
+}
+
+impl<W: Write> JsonLines<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> RenderDiagnostic for JsonLines<W> {
+    type Error = fmt::Error;
+
+    fn render(&mut self, diagnostic: &Diagnostic, src: &Source<'_>) -> fmt::Result {
+        write_diagnostic_json(&mut self.out, src.file, diagnostic)?;
+        self.out.write_char('\n')
+    }
+}
+
+struct Reader<'s> {
+    src: &'s str,
+    chars: Chars<'s>,
+    location: Location,
+    overflow: OverflowPolicy,
+    warnings: Vec<JsonWarning>,
+    path: KeyPath,
+}
+
+impl<'s> Reader<'s> {
+    fn error(&self, begin: Location, kind: JsonErrorKind) -> JsonError {
+        JsonError {
+            span: Span {
+                begin,
+                end: self.location,
+            },
+            kind,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.clone().next()
+    }
+
+    fn bump(&mut self) -> core::result::Result<char, JsonError> {
+        let chr = self
+            .chars
+            .next()
+            .ok_or(self.error(self.location, JsonErrorKind::UnexpectedEnd))?;
+        self.location.advance(chr);
+        Ok(chr)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            let _ = self.bump();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> core::result::Result<(), JsonError> {
+        let begin = self.location;
+
+        match self.bump()? {
+            chr if chr == expected => Ok(()),
+            _ => Err(self.error(begin, JsonErrorKind::UnexpectedCharacter)),
+        }
+    }
+
+    fn literal(&mut self, literal: &str) -> core::result::Result<(), JsonError> {
+        literal.chars().try_for_each(|chr| self.expect(chr))
+    }
+
+    fn hex(&mut self) -> core::result::Result<u32, JsonError> {
+        let begin = self.location;
+        let mut n = 0;
+
+        for _ in 0..4 {
+            let digit = self.bump()?.to_digit(16);
+            n = n * 16 + digit.ok_or(self.error(begin, JsonErrorKind::InvalidEscape))?;
+        }
+
+        Ok(n)
+    }
+
+    fn escape(&mut self, begin: Location) -> core::result::Result<char, JsonError> {
+        Ok(match self.bump()? {
+            '"' => '"',
+            '\\' => '\\',
+            '/' => '/',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let high = self.hex()?;
+
+                // characters outside the basic plane arrive as utf-16 surrogate pairs
+                let code = match high {
+                    0xd800..=0xdbff => {
+                        self.literal("\\u")?;
+
+                        match self.hex()? {
+                            low @ 0xdc00..=0xdfff => {
+                                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                            }
+                            _ => return Err(self.error(begin, JsonErrorKind::InvalidEscape)),
+                        }
+                    }
+                    _ => high,
+                };
+
+                char::from_u32(code).ok_or(self.error(begin, JsonErrorKind::InvalidEscape))?
+            }
+            _ => return Err(self.error(begin, JsonErrorKind::InvalidEscape)),
+        })
+    }
+
+    fn string(&mut self) -> core::result::Result<String, JsonError> {
+        self.expect('"')?;
+        let mut string = String::new();
+
+        loop {
+            let begin = self.location;
+
+            match self.bump()? {
+                '"' => return Ok(string),
+                '\\' => string.push(self.escape(begin)?),
+                chr if (chr as u32) < 0x20 => {
+                    return Err(self.error(begin, JsonErrorKind::UnexpectedCharacter));
+                }
+                chr => string.push(chr),
+            }
+        }
+    }
+
+    fn digits(&mut self) -> usize {
+        let mut count = 0;
+
+        while self.peek().is_some_and(|chr| chr.is_ascii_digit()) {
+            let _ = self.bump();
+            count += 1;
+        }
+
+        count
+    }
+
+    // integers stay integers when they fit, anything with a fraction or exponent is a float,
+    // and out-of-range integers follow the overflow policy just like pcf's own lexer
+    fn number(&mut self) -> core::result::Result<Value, JsonError> {
+        let begin = self.location;
+        let malformed = |reader: &Self| reader.error(begin, JsonErrorKind::MalformedNumber);
+
+        if self.peek() == Some('-') {
+            let _ = self.bump();
+        }
+
+        match self.peek() {
+            Some('0') => {
+                let _ = self.bump();
+            }
+            Some('1'..='9') => {
+                self.digits();
+            }
+            _ => return Err(malformed(self)),
+        }
+
+        let mut integer = true;
+
+        if self.peek() == Some('.') {
+            let _ = self.bump();
+            integer = false;
+
+            if self.digits() == 0 {
+                return Err(malformed(self));
+            }
+        }
+
+        if let Some('e' | 'E') = self.peek() {
+            let _ = self.bump();
+            integer = false;
+
+            if let Some('+' | '-') = self.peek() {
+                let _ = self.bump();
+            }
+
+            if self.digits() == 0 {
+                return Err(malformed(self));
+            }
+        }
+
+        let text = &self.src[begin.offset..self.location.offset];
+        let float = || text.parse().map(Value::Float).map_err(|_| malformed(self));
+
+        match (integer, text.parse()) {
+            (true, Ok(i)) => Ok(Value::Integer(i)),
+            (true, Err(_)) => match self.overflow {
+                OverflowPolicy::Error => Err(self.error(begin, JsonErrorKind::IntegerOverflow)),
+                OverflowPolicy::Saturate => Ok(Value::Integer(match text.starts_with('-') {
+                    true => i64::MIN,
+                    false => i64::MAX,
+                })),
+                OverflowPolicy::Float => float(),
+            },
+            (false, _) => float(),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> core::result::Result<Table, JsonError> {
+        self.expect('{')?;
+        let mut table = Table::new();
+        self.skip_whitespace();
+
+        if self.peek() == Some('}') {
+            let _ = self.bump();
+            return Ok(table);
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key_begin = self.location;
+            let key = self.string()?;
+            let key_span = Span {
+                begin: key_begin,
+                end: self.location,
+            };
+
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+
+            let begin = self.location;
+            self.path.push(PathSegment::Key(key.clone()));
+            let value = self.value(depth + 1);
+            self.path.pop();
+            let value = value?;
+            let span = Span {
+                begin,
+                end: self.location,
+            };
+
+            if table.contains_key(&key) {
+                return Err(JsonError {
+                    span: key_span,
+                    kind: JsonErrorKind::DuplicateKey,
+                });
+            }
+
+            if let Some(value) = value {
+                table.insert(key, Entry::new(value, key_span, span));
+            }
+
+            self.skip_whitespace();
+            let begin = self.location;
+
+            match self.bump()? {
+                ',' => continue,
+                '}' => return Ok(table),
+                _ => return Err(self.error(begin, JsonErrorKind::UnexpectedCharacter)),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> core::result::Result<Vec<Value>, JsonError> {
+        self.expect('[')?;
+        let mut array = Vec::new();
+        self.skip_whitespace();
+
+        if self.peek() == Some(']') {
+            let _ = self.bump();
+            return Ok(array);
+        }
+
+        loop {
+            self.skip_whitespace();
+            self.path.push(PathSegment::Index(array.len()));
+            let value = self.value(depth + 1);
+            self.path.pop();
+            array.extend(value?);
+            self.skip_whitespace();
+            let begin = self.location;
+
+            match self.bump()? {
+                ',' => continue,
+                ']' => return Ok(array),
+                _ => return Err(self.error(begin, JsonErrorKind::UnexpectedCharacter)),
+            }
+        }
+    }
+
+    // pcf has no null, so null members and elements are left out entirely, with a warning
+    fn value(&mut self, depth: usize) -> core::result::Result<Option<Value>, JsonError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error(self.location, JsonErrorKind::NestingTooDeep));
+        }
+
+        Ok(Some(match self.peek() {
+            Some('"') => Value::String(self.string()?),
+            Some('{') => Value::Table(self.object(depth)?),
+            Some('[') => Value::Array(self.array(depth)?),
+            Some('t') => {
+                self.literal("true")?;
+                Value::Bool(true)
+            }
+            Some('f') => {
+                self.literal("false")?;
+                Value::Bool(false)
+            }
+            Some('n') => {
+                let begin = self.location;
+                self.literal("null")?;
+                self.warnings.push(JsonWarning {
+                    path: self.path.clone(),
+                    span: Span {
+                        begin,
+                        end: self.location,
+                    },
+                    kind: JsonWarningKind::Null,
+                });
+                return Ok(None);
+            }
+            Some('-' | '0'..='9') => self.number()?,
+            Some(_) => {
+                let begin = self.location;
+                self.bump()?;
+                return Err(self.error(begin, JsonErrorKind::UnexpectedCharacter));
+            }
+            None => return Err(self.error(self.location, JsonErrorKind::UnexpectedEnd)),
+        }))
+    }
+}
+
+impl Document {
+    pub fn from_json_str(
+        content: &str,
+    ) -> core::result::Result<(Self, Vec<JsonWarning>), JsonError> {
+        Self::from_json_str_with(content, OverflowPolicy::Float)
+    }
+
+    pub fn from_json_str_with(
+        content: &str,
+        overflow: OverflowPolicy,
+    ) -> core::result::Result<(Self, Vec<JsonWarning>), JsonError> {
+        let mut reader = Reader {
+            src: content,
+            chars: content.chars(),
+            location: Location::default(),
+            overflow,
+            warnings: Vec::new(),
+            path: KeyPath::new(),
+        };
+
+        reader.skip_whitespace();
+
+        if reader.peek() != Some('{') {
+            let begin = reader.location;
+            let _ = reader.bump();
+            return Err(reader.error(begin, JsonErrorKind::NotAnObject));
+        }
+
+        let root = reader.object(0)?;
+        reader.skip_whitespace();
+
+        match reader.peek() {
+            None => Ok((Document::new(root), reader.warnings)),
+            Some(_) => Err(reader.error(reader.location, JsonErrorKind::TrailingCharacters)),
+        }
+    }
+
+    pub fn to_json_string(&self) -> String {
+        let mut json = String::new();
+        let _ = write_json(&mut json, self);
+        json
+    }
+
+    // like `to_json_string`, but says where nan and the infinities were written as null
+    pub fn to_json_string_with_warnings(&self) -> (String, Vec<JsonWarning>) {
+        let mut warnings = Vec::new();
+
+        for (key, entry) in self.root.iter() {
+            let mut path = KeyPath::new();
+            path.push(PathSegment::Key(key.clone()));
+            non_finite(&entry.value, &mut path, entry.span, &mut warnings);
+        }
+
+        (self.to_json_string(), warnings)
+    }
+
+    pub fn to_json_string_spanned(&self) -> String {
+        let mut json = String::new();
+        let _ = write_json_spanned(&mut json, self);
+        json
+    }
+}
+
+impl Diagnostic {
+    pub fn to_json_string(&self, file: &str) -> String {
+        let mut json = String::new();
+        let _ = write_diagnostic_json(&mut json, file, self);
+        json
+    }
+}
+
+impl Diagnostics {
+    pub fn to_json_lines(&self, file: &str) -> String {
+        let mut json = String::new();
+        let _ = write_diagnostics_json_lines(&mut json, file, self);
+        json
+    }
+}
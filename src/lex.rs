@@ -1,9 +1,17 @@
+#[cfg(feature = "alloc")]
+use crate::diagnostic::{Diagnostic, Diagnostics, Lints, Severity};
 use crate::utils::*;
-use alloc::{collections::vec_deque::VecDeque, string::String};
+#[cfg(feature = "alloc")]
+use alloc::{collections::vec_deque::VecDeque, string::String, vec::Vec};
+use core::fmt;
+#[cfg(feature = "alloc")]
+use core::{iter::Peekable, num::IntErrorKind, str::Chars};
 
+#[cfg(feature = "alloc")]
 #[derive(Debug, Clone, PartialEq)]
 pub enum LexemeKind {
     String(String),
+    Bytes(Vec<u8>),
     Ident(String),
     Integer(i64),
     Float(f64),
@@ -14,149 +22,785 @@ pub enum LexemeKind {
     RBrace,
     Equal,
     Comma,
+    Dot,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Ampersand,
+    Separator,
+    Comment(String),
+    Directive(String),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    String,
+    Bytes,
+    Ident,
+    Integer,
+    Float,
+    Bool,
+    LBrack,
+    RBrack,
+    LBrace,
+    RBrace,
+    Equal,
+    Comma,
+    Dot,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Ampersand,
+    Separator,
+    Comment,
+    Directive,
+}
+
+impl TokenKind {
+    // a sign straight after one of these on the same line is an operator rather than part of a
+    // number, so `5-3` subtracts while `[1, -2]` and a `-2` starting a line stay literals
+    pub(crate) fn ends_operand(&self) -> bool {
+        matches!(
+            self,
+            Self::String
+                | Self::Bytes
+                | Self::Ident
+                | Self::Integer
+                | Self::Float
+                | Self::Bool
+                | Self::RBrack
+                | Self::RBrace
+        )
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::String => "string",
+                Self::Bytes => "bytes",
+                Self::Ident => "identifier",
+                Self::Integer => "integer",
+                Self::Float => "float",
+                Self::Bool => "boolean",
+                Self::LBrack => "`[`",
+                Self::RBrack => "`]`",
+                Self::LBrace => "`{`",
+                Self::RBrace => "`}`",
+                Self::Equal => "`=`",
+                Self::Comma => "`,`",
+                Self::Dot => "`.`",
+                Self::Plus => "`+`",
+                Self::Minus => "`-`",
+                Self::Star => "`*`",
+                Self::Slash => "`/`",
+                Self::Ampersand => "`&`",
+                Self::Separator => "`---`",
+                Self::Comment => "comment",
+                Self::Directive => "directive",
+            }
+        )
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl LexemeKind {
+    pub fn token(&self) -> TokenKind {
+        match self {
+            Self::String(_) => TokenKind::String,
+            Self::Bytes(_) => TokenKind::Bytes,
+            Self::Ident(_) => TokenKind::Ident,
+            Self::Integer(_) => TokenKind::Integer,
+            Self::Float(_) => TokenKind::Float,
+            Self::Bool(_) => TokenKind::Bool,
+            Self::LBrack => TokenKind::LBrack,
+            Self::RBrack => TokenKind::RBrack,
+            Self::LBrace => TokenKind::LBrace,
+            Self::RBrace => TokenKind::RBrace,
+            Self::Equal => TokenKind::Equal,
+            Self::Comma => TokenKind::Comma,
+            Self::Dot => TokenKind::Dot,
+            Self::Plus => TokenKind::Plus,
+            Self::Minus => TokenKind::Minus,
+            Self::Star => TokenKind::Star,
+            Self::Slash => TokenKind::Slash,
+            Self::Ampersand => TokenKind::Ampersand,
+            Self::Separator => TokenKind::Separator,
+            Self::Comment(_) => TokenKind::Comment,
+            Self::Directive(_) => TokenKind::Directive,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
 #[derive(Debug, Clone, PartialEq)]
 pub struct Lexeme {
     pub kind: LexemeKind,
     pub span: Span,
 }
 
+#[cfg(feature = "alloc")]
 impl Lexeme {
     pub fn new(kind: LexemeKind, span: Span) -> Self {
         Self { kind, span }
     }
+
+    pub fn text<'s>(&self, src: &'s Source) -> &'s str {
+        src.slice(self.span)
+    }
 }
 
+#[cfg(feature = "alloc")]
 pub type LexemeStream = VecDeque<Lexeme>;
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    #[default]
+    Error,
+    Saturate,
+    Float,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LexerOptions {
+    pub overflow: OverflowPolicy,
+    pub comments: bool,
+    pub max_size: Option<usize>,
+    pub max_tokens: Option<usize>,
+    pub max_string_len: Option<usize>,
+    #[cfg(feature = "alloc")]
+    pub lints: Lints,
+}
+
+#[cfg(not(feature = "unicode"))]
 pub fn is_identifier(chr: char) -> bool {
     chr.is_ascii_alphanumeric() || chr == '_'
 }
 
+#[cfg(feature = "unicode")]
+pub fn is_identifier(chr: char) -> bool {
+    chr.is_alphanumeric() || chr == '_' || unicode_normalization::char::is_combining_mark(chr)
+}
+
+#[cfg(all(feature = "alloc", not(feature = "unicode")))]
+pub fn normalize_identifier(content: String) -> String {
+    content
+}
+
+#[cfg(all(feature = "alloc", feature = "unicode"))]
+pub fn normalize_identifier(content: String) -> String {
+    use unicode_normalization::{IsNormalized, UnicodeNormalization, is_nfc_quick};
+
+    match is_nfc_quick(content.chars()) {
+        IsNormalized::Yes => content,
+        _ => content.nfc().collect(),
+    }
+}
+
+// the string usually runs to the end of the source, so the help points back at where it opened
+#[cfg(feature = "alloc")]
+fn unterminated<'a>(span: Span, src: &'a Source<'a>, close: &str) -> Error<'a> {
+    Error::lexing(LexingError::UnterminatedString, span, src).with_help(Hint::CloseString {
+        close: close.into(),
+        line: span.begin.line,
+    })
+}
+
+// points at the character the limit falls in, so the snippet shows where the cut would be
+pub(crate) fn too_large(content: &str, limit: usize) -> (Span, LimitError) {
+    let mut span = Span::default();
+
+    for chr in content.chars() {
+        span.begin = span.end;
+        span.end.advance(chr);
+
+        if span.end.offset > limit {
+            break;
+        }
+    }
+
+    let found = content.len();
+    (span, LimitError::DocumentTooLarge { limit, found })
+}
+
+#[cfg(feature = "alloc")]
+fn byte_escape<'a>(span: Span, src: &'a Source<'a>) -> Error<'a> {
+    Error::lexing(LexingError::InvalidEscape, span, src).with_note(Hint::ByteEscapes)
+}
+
+#[cfg(feature = "alloc")]
+fn raw<'a>(
+    chars: &mut Peekable<Chars>,
+    span: &mut Span,
+    src: &'a Source<'a>,
+) -> Result<'a, String> {
+    let mut hashes = 0;
+
+    while let Some(chr) = chars.next_if_eq(&'#') {
+        span.end.advance(chr);
+        hashes += 1;
+    }
+
+    match chars.next() {
+        Some(chr @ '"') => span.end.advance(chr),
+        _ => return Err(Error::lexing(LexingError::UnrecognizedToken, *span, src)),
+    }
+
+    let mut content = String::new();
+
+    loop {
+        let Some(chr) = chars.next() else {
+            let close = alloc::format!("\"{}", "#".repeat(hashes));
+            return Err(unterminated(*span, src, &close));
+        };
+        span.end.advance(chr);
+
+        if chr == '"' {
+            let mut closing = 0;
+
+            while closing < hashes
+                && let Some(chr) = chars.next_if_eq(&'#')
+            {
+                span.end.advance(chr);
+                closing += 1;
+            }
+
+            if closing == hashes {
+                return Ok(content);
+            }
+
+            content.push('"');
+            content.extend(core::iter::repeat_n('#', closing));
+        } else {
+            content.push(chr);
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn bytes<'a>(
+    chars: &mut Peekable<Chars>,
+    span: &mut Span,
+    src: &'a Source<'a>,
+    base64: bool,
+) -> Result<'a, Vec<u8>> {
+    let mut content = Vec::new();
+    let mut buffer = [0; 4];
+
+    if let Some(chr) = chars.next() {
+        span.end.advance(chr);
+    }
+
+    loop {
+        let Some(chr) = chars.next() else {
+            return Err(unterminated(*span, src, "\""));
+        };
+        span.end.advance(chr);
+
+        match chr {
+            '"' => break,
+            '\\' if !base64 => {
+                let Some(escaped) = chars.next() else {
+                    return Err(unterminated(*span, src, "\""));
+                };
+                span.end.advance(escaped);
+
+                content.push(match escaped {
+                    'n' => b'\n',
+                    't' => b'\t',
+                    'r' => b'\r',
+                    '0' => b'\0',
+                    '\\' => b'\\',
+                    '"' => b'"',
+                    'x' => {
+                        let mut hex = String::default();
+
+                        while hex.len() < 2
+                            && let Some(chr) = chars.next_if(char::is_ascii_hexdigit)
+                        {
+                            span.end.advance(chr);
+                            hex.push(chr);
+                        }
+
+                        match u8::from_str_radix(&hex, 16) {
+                            Ok(byte) if hex.len() == 2 => byte,
+                            _ => return Err(byte_escape(*span, src)),
+                        }
+                    }
+                    _ => return Err(byte_escape(*span, src)),
+                });
+            }
+            _ => content.extend_from_slice(chr.encode_utf8(&mut buffer).as_bytes()),
+        }
+    }
+
+    if !base64 {
+        return Ok(content);
+    }
+
+    decode_base64(&content).ok_or_else(|| Error::lexing(LexingError::MalformedBytes, *span, src))
+}
+
+#[cfg(feature = "alloc")]
+fn decode_base64(content: &[u8]) -> Option<Vec<u8>> {
+    let mut digits: Vec<u8> = content
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+
+    for _ in 0..2 {
+        if digits.last() == Some(&b'=') {
+            digits.pop();
+        }
+    }
+
+    let mut decoded = Vec::new();
+    let mut bits = 0u32;
+    let mut count = 0;
+
+    for digit in digits {
+        let value = match digit {
+            b'A'..=b'Z' => digit - b'A',
+            b'a'..=b'z' => digit - b'a' + 26,
+            b'0'..=b'9' => digit - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+
+        bits = (bits << 6) | value as u32;
+        count += 6;
+
+        if count >= 8 {
+            count -= 8;
+            decoded.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+
+    (count < 6).then_some(decoded)
+}
+
+#[cfg(feature = "alloc")]
 pub fn lex<'a>(src: &'a Source<'a>) -> Result<'a, LexemeStream> {
+    lex_with(src, LexerOptions::default())
+}
+
+#[cfg(feature = "alloc")]
+pub fn lex_diagnostics<'a>(
+    src: &'a Source<'a>,
+    options: LexerOptions,
+) -> (Option<LexemeStream>, Diagnostics) {
+    match lex_with_warnings(src, options) {
+        Ok((lexemes, warnings)) => (
+            Some(lexemes),
+            warnings
+                .into_iter()
+                .map(|warning| Diagnostic::from(warning).severity(Severity::Warning))
+                .collect(),
+        ),
+        Err(error) => (None, Diagnostics::from_iter([error])),
+    }
+}
+
+#[cfg(feature = "alloc")]
+pub fn lex_with<'a>(src: &'a Source<'a>, options: LexerOptions) -> Result<'a, LexemeStream> {
+    lex_with_warnings(src, options).map(|(lexemes, _)| lexemes)
+}
+
+// letters from other scripts that are drawn like ascii ones, paired with the letter they pass for
+#[cfg(feature = "alloc")]
+const CONFUSABLES: &[(char, char)] = &[
+    ('\u{430}', 'a'),
+    ('\u{435}', 'e'),
+    ('\u{43e}', 'o'),
+    ('\u{440}', 'p'),
+    ('\u{441}', 'c'),
+    ('\u{443}', 'y'),
+    ('\u{445}', 'x'),
+    ('\u{456}', 'i'),
+    ('\u{458}', 'j'),
+    ('\u{455}', 's'),
+    ('\u{410}', 'A'),
+    ('\u{412}', 'B'),
+    ('\u{415}', 'E'),
+    ('\u{41a}', 'K'),
+    ('\u{41c}', 'M'),
+    ('\u{41d}', 'H'),
+    ('\u{41e}', 'O'),
+    ('\u{420}', 'P'),
+    ('\u{421}', 'C'),
+    ('\u{422}', 'T'),
+    ('\u{425}', 'X'),
+    ('\u{3bf}', 'o'),
+    ('\u{3bd}', 'v'),
+    ('\u{391}', 'A'),
+    ('\u{392}', 'B'),
+    ('\u{395}', 'E'),
+    ('\u{396}', 'Z'),
+    ('\u{397}', 'H'),
+    ('\u{399}', 'I'),
+    ('\u{39a}', 'K'),
+    ('\u{39c}', 'M'),
+    ('\u{39d}', 'N'),
+    ('\u{39f}', 'O'),
+    ('\u{3a1}', 'P'),
+    ('\u{3a4}', 'T'),
+    ('\u{3a5}', 'Y'),
+    ('\u{3a7}', 'X'),
+];
+
+// a lookalike only misleads next to the ascii letters it imitates, keys written wholly in
+// another script are left alone
+#[cfg(feature = "alloc")]
+fn confusable(ident: &str) -> Option<(char, char)> {
+    if !ident.chars().any(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    ident
+        .chars()
+        .find_map(|chr| CONFUSABLES.iter().copied().find(|(found, _)| *found == chr))
+}
+
+#[cfg(feature = "alloc")]
+pub fn lex_with_warnings<'a>(
+    src: &'a Source<'a>,
+    options: LexerOptions,
+) -> Result<'a, (LexemeStream, Vec<Error<'a>>)> {
+    if let Some(limit) = options.max_size.filter(|limit| src.content.len() > *limit) {
+        let (span, kind) = too_large(&src.content, limit);
+        return Err(Error::limit(kind, span, src));
+    }
+
     let mut lexemes = LexemeStream::default();
+    let mut warnings = Vec::new();
     let mut span = Span::default();
     let mut chars = src.chars();
+    // the indentation of the current line so far, until something other than whitespace shows up
+    let mut indent = Some((Location::default(), false, false));
 
     while let Some(tok) = chars.next() {
         span.begin = span.end;
-        span.end.new_col();
-
-        lexemes.push_back(Lexeme::new(
-            match tok {
-                '=' => LexemeKind::Equal,
-                ',' => LexemeKind::Comma,
-                '[' => LexemeKind::LBrack,
-                ']' => LexemeKind::RBrack,
-                '{' => LexemeKind::LBrace,
-                '}' => LexemeKind::RBrace,
-                '"' => {
-                    let mut content = String::default();
-                    let mut prev = '\0';
-
-                    for chr in chars.by_ref() {
-                        if chr == '\n' {
-                            span.end.new_line();
-                        } else {
-                            span.end.new_col();
-                        }
+        span.end.advance(tok);
+
+        match (tok, &mut indent) {
+            ('\n', _) => indent = Some((span.end, false, false)),
+            (' ' | '\t', Some((begin, tabs, spaces))) => {
+                let mixed = *tabs && *spaces;
+                *tabs |= tok == '\t';
+                *spaces |= tok == ' ';
+
+                if !mixed && *tabs && *spaces {
+                    options.lints.report(
+                        &mut warnings,
+                        Error::lexing(
+                            LexingError::MixedIndentation,
+                            Span::new(*begin, span.end),
+                            src,
+                        ),
+                    )?;
+                }
+            }
+            (' ' | '\t' | '\r', None) => {}
+            _ => indent = None,
+        }
+
+        let operand = lexemes
+            .back()
+            .is_some_and(|l| l.span.end.line == span.begin.line && l.kind.token().ends_operand());
+        let kind = match tok {
+            '=' => LexemeKind::Equal,
+            ',' => LexemeKind::Comma,
+            '[' => LexemeKind::LBrack,
+            ']' => LexemeKind::RBrack,
+            '{' => LexemeKind::LBrace,
+            '}' => LexemeKind::RBrace,
+            '.' if !chars.peek().is_some_and(char::is_ascii_digit) => LexemeKind::Dot,
+            '+' if operand
+                || !chars
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_digit() || *c == '.') =>
+            {
+                LexemeKind::Plus
+            }
+            '-' if span.begin.col == 1 && src.content[span.begin.offset..].starts_with("---") => {
+                for chr in chars.by_ref().take(2) {
+                    span.end.advance(chr);
+                }
+
+                LexemeKind::Separator
+            }
+            '-' if operand
+                || !chars
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_digit() || *c == '.') =>
+            {
+                LexemeKind::Minus
+            }
+            '*' => LexemeKind::Star,
+            '/' => LexemeKind::Slash,
+            '&' => LexemeKind::Ampersand,
+            '"' => {
+                let mut content = String::default();
+                let mut terminated = false;
+
+                while let Some(chr) = chars.next() {
+                    span.end.advance(chr);
 
-                        if chr == '"' && prev != '\\' {
-                            prev = chr;
+                    match chr {
+                        '"' => {
+                            terminated = true;
                             break;
                         }
+                        '\\' => {
+                            let Some(escaped) = chars.next() else {
+                                break;
+                            };
+                            span.end.advance(escaped);
 
-                        prev = chr;
-                        content.push(chr);
-                    }
+                            match escaped {
+                                'n' => content.push('\n'),
+                                't' => content.push('\t'),
+                                'r' => content.push('\r'),
+                                '0' => content.push('\0'),
+                                '\\' | '"' => content.push(escaped),
+                                'u' => {
+                                    let mut hex = String::default();
 
-                    if prev != '"' {
-                        return Err(Error::lexing(LexingError::UnterminatedString, span, src));
-                    }
+                                    if chars.peek() == Some(&'{') {
+                                        for chr in chars.by_ref() {
+                                            span.end.advance(chr);
 
-                    LexemeKind::String(content)
-                }
-                _ if tok.is_ascii_numeric() || tok == '-' || tok == '+' || tok == '.' => {
-                    let mut content = String::default();
-                    content.push(tok);
+                                            if chr == '}' {
+                                                break;
+                                            }
 
-                    let mut dot = tok == '.';
+                                            hex.push(chr);
+                                        }
+                                    }
 
-                    while let Some(&chr) = chars.peek() {
-                        if chr == '.' {
-                            if dot {
-                                return Err(Error::lexing(LexingError::MalformedNumber, span, src));
+                                    match u32::from_str_radix(hex.trim_start_matches('{'), 16)
+                                        .ok()
+                                        .and_then(char::from_u32)
+                                    {
+                                        Some(chr) => content.push(chr),
+                                        None => {
+                                            return Err(Error::lexing(
+                                                LexingError::InvalidEscape,
+                                                span,
+                                                src,
+                                            )
+                                            .with_note(Hint::UnicodeEscape));
+                                        }
+                                    }
+                                }
+                                _ => {
+                                    content.push('\\');
+                                    content.push(escaped);
+                                }
                             }
-
-                            dot = true;
                         }
+                        _ => content.push(chr),
+                    }
+                }
 
-                        if !chr.is_ascii_numeric() && chr != '.' {
-                            break;
+                if !terminated {
+                    return Err(unterminated(span, src, "\""));
+                }
+
+                LexemeKind::String(content)
+            }
+            _ if tok.is_ascii_digit() || tok == '-' || tok == '+' || tok == '.' => {
+                let mut content = String::default();
+                content.push(tok);
+
+                let mut dot = tok == '.';
+
+                while let Some(&chr) = chars.peek() {
+                    if chr == '.' {
+                        if dot {
+                            return Err(Error::lexing(LexingError::MalformedNumber, span, src));
                         }
 
-                        chars.next();
-                        content.push(chr);
-                        span.end.new_col();
+                        dot = true;
                     }
 
-                    if dot {
-                        LexemeKind::Float(
-                            content.parse::<f64>().map_err(|_| {
-                                Error::lexing(LexingError::MalformedNumber, span, src)
-                            })?,
-                        )
-                    } else {
-                        LexemeKind::Integer(
-                            content.parse::<i64>().map_err(|_| {
-                                Error::lexing(LexingError::MalformedNumber, span, src)
-                            })?,
-                        )
+                    if !chr.is_ascii_digit() && chr != '.' {
+                        break;
                     }
+
+                    chars.next();
+                    content.push(chr);
+                    span.end.advance(chr);
                 }
-                _ if is_identifier(tok) => {
-                    let mut content = String::default();
-                    content.push(tok);
 
-                    while let Some(&chr) = chars.peek() {
-                        if !is_identifier(chr) {
-                            break;
+                if dot {
+                    LexemeKind::Float(
+                        content
+                            .parse::<f64>()
+                            .map_err(|_| Error::lexing(LexingError::MalformedNumber, span, src))?,
+                    )
+                } else {
+                    match content.parse::<i64>() {
+                        Ok(int) => LexemeKind::Integer(int),
+                        Err(e)
+                            if matches!(
+                                e.kind(),
+                                IntErrorKind::PosOverflow | IntErrorKind::NegOverflow
+                            ) =>
+                        {
+                            match options.overflow {
+                                OverflowPolicy::Error => {
+                                    return Err(Error::lexing(
+                                        LexingError::IntegerOverflow,
+                                        span,
+                                        src,
+                                    )
+                                    .with_note(Hint::IntegerRange));
+                                }
+                                OverflowPolicy::Saturate => LexemeKind::Integer(match e.kind() {
+                                    IntErrorKind::PosOverflow => i64::MAX,
+                                    _ => i64::MIN,
+                                }),
+                                OverflowPolicy::Float => {
+                                    LexemeKind::Float(content.parse::<f64>().map_err(|_| {
+                                        Error::lexing(LexingError::MalformedNumber, span, src)
+                                    })?)
+                                }
+                            }
+                        }
+                        Err(_) => {
+                            return Err(Error::lexing(LexingError::MalformedNumber, span, src));
                         }
-
-                        chars.next();
-                        span.end.new_col();
-                        content.push(chr);
                     }
+                }
+            }
+            _ if is_identifier(tok) => {
+                let mut content = String::default();
+                content.push(tok);
 
-                    match content.as_str() {
-                        "true" => LexemeKind::Bool(true),
-                        "false" => LexemeKind::Bool(false),
-                        _ => LexemeKind::Ident(content),
+                while let Some(&chr) = chars.peek() {
+                    if !is_identifier(chr) {
+                        break;
                     }
+
+                    chars.next();
+                    span.end.advance(chr);
+                    content.push(chr);
                 }
-                '#' => {
-                    for chr in chars.by_ref() {
-                        if chr == '\n' {
-                            span.end.new_line();
-                            break;
+
+                match content.as_str() {
+                    "b" | "base64" if chars.peek() == Some(&'"') => {
+                        LexemeKind::Bytes(bytes(&mut chars, &mut span, src, content == "base64")?)
+                    }
+                    "r" if matches!(chars.peek(), Some('"' | '#')) => {
+                        LexemeKind::String(raw(&mut chars, &mut span, src)?)
+                    }
+                    "true" => LexemeKind::Bool(true),
+                    "false" => LexemeKind::Bool(false),
+                    _ => {
+                        if let Some((found, expected)) = confusable(&content) {
+                            options.lints.report(
+                                &mut warnings,
+                                Error::lexing(
+                                    LexingError::ConfusableCharacter { found, expected },
+                                    span,
+                                    src,
+                                ),
+                            )?;
                         }
+
+                        LexemeKind::Ident(normalize_identifier(content))
                     }
+                }
+            }
+            '#' => {
+                let mut content = String::default();
 
-                    continue;
+                while let Some(&chr) = chars.peek() {
+                    if chr == '\n' {
+                        break;
+                    }
+
+                    chars.next();
+                    span.end.advance(chr);
+                    content.push(chr);
                 }
-                '\n' => {
-                    span.end.new_line();
+
+                if !options.comments {
                     continue;
                 }
-                _ if tok.is_whitespace() => {
-                    continue;
+
+                LexemeKind::Comment(content)
+            }
+            '@' => {
+                let mut content = String::default();
+
+                while let Some(&chr) = chars.peek() {
+                    if !is_identifier(chr) {
+                        break;
+                    }
+
+                    chars.next();
+                    span.end.advance(chr);
+                    content.push(chr);
+                }
+
+                if content.is_empty() {
+                    return Err(Error::lexing(LexingError::UnrecognizedToken, span, src));
+                }
+
+                LexemeKind::Directive(content)
+            }
+            _ if tok.is_whitespace() => {
+                if !matches!(tok, ' ' | '\t' | '\n' | '\r') {
+                    options.lints.report(
+                        &mut warnings,
+                        Error::lexing(LexingError::UnusualWhitespace { found: tok }, span, src),
+                    )?;
                 }
-                _ => return Err(Error::lexing(LexingError::UnrecognizedToken, span, src)),
+
+                continue;
+            }
+            _ => return Err(Error::lexing(LexingError::UnrecognizedToken, span, src)),
+        };
+
+        let len = match &kind {
+            LexemeKind::String(content) => content.len(),
+            LexemeKind::Bytes(content) => content.len(),
+            _ => 0,
+        };
+
+        if let Some(limit) = options.max_string_len.filter(|limit| len > *limit) {
+            return Err(Error::limit(
+                LimitError::StringTooLong { limit, found: len },
+                span,
+                src,
+            ));
+        }
+
+        lexemes.push_back(Lexeme::new(kind, span));
+    }
+
+    // tokens are counted once lexing is done so the error carries the real total, `max_size` is
+    // what bounds the work done before then
+    if let Some(limit) = options.max_tokens.filter(|limit| lexemes.len() > *limit) {
+        return Err(Error::limit(
+            LimitError::TooManyTokens {
+                limit,
+                found: lexemes.len(),
             },
-            span,
+            lexemes[limit].span,
+            src,
         ));
     }
 
-    Ok(lexemes)
+    Ok((lexemes, warnings))
 }
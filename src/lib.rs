@@ -1,12 +1,95 @@
 #![no_std]
+// without `alloc` only the scanner is left, along with the spans and error kinds it reports
+#[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+// lets the derive macros' `::pcf` paths resolve inside this crate as well
+#[cfg(feature = "derive")]
+extern crate self as pcf;
 
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+#[cfg(feature = "alloc")]
+pub mod arena;
+#[cfg(feature = "ariadne")]
+pub mod ariadne;
+#[cfg(feature = "binary")]
+pub mod binary;
+#[cfg(feature = "alloc")]
+pub mod builder;
+#[cfg(feature = "alloc")]
+pub mod check;
+#[cfg(feature = "coerce")]
+pub mod coerce;
+#[cfg(feature = "alloc")]
+pub mod cst;
+#[cfg(feature = "serde")]
+pub mod de;
+#[cfg(feature = "alloc")]
+pub mod diagnostic;
+#[cfg(feature = "alloc")]
+pub mod diff;
+#[cfg(feature = "alloc")]
+pub mod emit;
+#[cfg(feature = "alloc")]
+pub mod extract;
+#[cfg(feature = "alloc")]
+pub mod include;
+#[cfg(feature = "ini")]
+pub mod ini;
+#[cfg(feature = "json")]
+pub mod json;
 pub mod lex;
+#[cfg(feature = "alloc")]
+pub mod merge;
+#[cfg(feature = "alloc")]
+pub mod messages;
+#[cfg(feature = "miette")]
+pub mod miette;
 pub mod utils;
+#[cfg(feature = "alloc")]
 pub mod parse;
+#[cfg(feature = "alloc")]
+pub mod patch;
+#[cfg(feature = "alloc")]
+pub mod path;
+#[cfg(feature = "proptest")]
+pub mod proptest;
+#[cfg(feature = "alloc")]
+pub mod query;
+#[cfg(feature = "alloc")]
+pub mod resolve;
+pub mod scan;
+#[cfg(feature = "alloc")]
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod ser;
+#[cfg(feature = "toml")]
+pub mod toml;
+#[cfg(feature = "alloc")]
+pub mod value;
+#[cfg(feature = "alloc")]
+pub mod visit;
+#[cfg(feature = "yaml")]
+pub mod yaml;
 
-pub use utils::Source;
+#[cfg(feature = "alloc")]
+pub use utils::{OwnedError, Source};
+#[cfg(feature = "alloc")]
+pub use diagnostic::{Diagnostic, Diagnostics};
+#[cfg(feature = "alloc")]
+pub use diff::diff;
+#[cfg(feature = "alloc")]
 pub use lex::lex;
+#[cfg(feature = "alloc")]
+pub use parse::{from_str, parse, parse_diagnostics, parse_documents, parse_str, parse_str_with_warnings};
+#[cfg(feature = "alloc")]
+pub use value::{Document, Value};
+#[cfg(feature = "serde")]
+pub use ser::{to_string, to_string_with};
+#[cfg(feature = "derive")]
+pub use pcf_derive::{FromPcf, ToPcf};
 
-#[cfg(test)]
+#[cfg(all(test, feature = "alloc"))]
 mod tests;
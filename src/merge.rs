@@ -0,0 +1,35 @@
+use crate::value::*;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    #[default]
+    Override,
+    Append,
+    KeepExisting,
+}
+
+impl Table {
+    pub fn merge(&mut self, other: &Table, strategy: MergeStrategy) {
+        for (key, entry) in other.iter() {
+            let Some(existing) = self.get_entry_mut(key) else {
+                self.insert(key.clone(), entry.clone());
+                continue;
+            };
+
+            match (&mut existing.value, &entry.value, strategy) {
+                (Value::Table(base), Value::Table(overlay), _) => base.merge(overlay, strategy),
+                (_, _, MergeStrategy::KeepExisting) => {}
+                (Value::Array(base), Value::Array(overlay), MergeStrategy::Append) => {
+                    base.extend(overlay.iter().cloned())
+                }
+                _ => *existing = entry.clone(),
+            }
+        }
+    }
+}
+
+impl Document {
+    pub fn merge(&mut self, other: &Document, strategy: MergeStrategy) {
+        self.root.merge(&other.root, strategy);
+    }
+}
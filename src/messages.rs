@@ -0,0 +1,270 @@
+use crate::{
+    diagnostic::Severity,
+    schema::{SchemaErrorKind, SchemaWarningKind},
+    utils::*,
+};
+use alloc::string::String;
+use core::fmt;
+
+// every piece of text a diagnostic is made of; each one defaults to english, so a catalog only
+// overrides what it translates and falls back for the rest
+pub trait Messages {
+    fn error(&self, f: &mut dyn fmt::Write, kind: &ErrorKind) -> fmt::Result {
+        match kind {
+            ErrorKind::Lexing(l) => match l {
+                LexingError::ConfusableCharacter { found, expected } => write!(
+                    f,
+                    "encountered `{}` (U+{:04X}) resembling `{}` during lexing",
+                    found, *found as u32, expected
+                ),
+                LexingError::IntegerOverflow => {
+                    write!(f, "encountered out-of-range integer during lexing")
+                }
+                LexingError::InvalidEscape => write!(f, "encountered invalid escape during lexing"),
+                LexingError::MalformedBytes => {
+                    write!(f, "encountered malformed byte string during lexing")
+                }
+                LexingError::MalformedNumber => {
+                    write!(f, "encountered malformed number during lexing")
+                }
+                LexingError::MixedIndentation => write!(
+                    f,
+                    "encountered indentation mixing tabs and spaces during lexing"
+                ),
+                LexingError::UnrecognizedToken => {
+                    write!(f, "encountered unrecognized token during lexing")
+                }
+                LexingError::UnterminatedString => {
+                    write!(f, "encountered unterminated string during lexing")
+                }
+                LexingError::UnusualWhitespace { found } => write!(
+                    f,
+                    "encountered unusual whitespace U+{:04X} during lexing",
+                    *found as u32
+                ),
+            },
+            ErrorKind::Parsing(p) => match p {
+                ParsingError::ArithmeticOverflow => {
+                    write!(f, "encountered arithmetic overflow during parsing")
+                }
+                ParsingError::ConflictingKey { .. } => write!(
+                    f,
+                    "encountered key conflicting with an earlier definition during parsing"
+                ),
+                ParsingError::CyclicExtends => {
+                    write!(f, "encountered cyclic extends during parsing")
+                }
+                ParsingError::DivisionByZero => {
+                    write!(f, "encountered division by zero during parsing")
+                }
+                ParsingError::DuplicateAnchor { .. } => {
+                    write!(f, "encountered duplicate anchor during parsing")
+                }
+                ParsingError::DuplicateKey { .. } => {
+                    write!(f, "encountered duplicate key during parsing")
+                }
+                ParsingError::IncludeCycle => {
+                    write!(f, "encountered cyclic include during parsing")
+                }
+                ParsingError::IncludeNotFound => {
+                    write!(f, "encountered include of unknown source during parsing")
+                }
+                ParsingError::InvalidExtends => {
+                    write!(f, "encountered extends of a non-table value during parsing")
+                }
+                ParsingError::InvalidKey => write!(f, "encountered invalid key during parsing"),
+                ParsingError::InvalidOperands { .. } => {
+                    write!(
+                        f,
+                        "encountered operands of incompatible types during parsing"
+                    )
+                }
+                ParsingError::MixedArray { .. } => {
+                    write!(
+                        f,
+                        "encountered array element of a different type during parsing"
+                    )
+                }
+                ParsingError::TrailingComma => {
+                    write!(f, "encountered trailing comma during parsing")
+                }
+                ParsingError::UnclosedDelimiter { .. } => {
+                    write!(f, "encountered unclosed delimiter during parsing")
+                }
+                ParsingError::UnexpectedEof => {
+                    write!(f, "encountered unexpected end of input during parsing")
+                }
+                ParsingError::UnexpectedToken { found, expected } => write!(
+                    f,
+                    "encountered unexpected {} during parsing, expected {}",
+                    found, expected
+                ),
+                ParsingError::UnknownAnchor => {
+                    write!(f, "encountered alias of undefined anchor during parsing")
+                }
+                ParsingError::UnknownDirective => {
+                    write!(f, "encountered unknown directive during parsing")
+                }
+                ParsingError::UnknownEscape => {
+                    write!(f, "encountered unknown escape during parsing")
+                }
+                ParsingError::UnknownReference => {
+                    write!(f, "encountered reference to undefined key during parsing")
+                }
+            },
+            ErrorKind::Resolving(r) => write!(
+                f,
+                "{}",
+                match r {
+                    ResolvingError::CyclicReference =>
+                        "encountered cyclic reference during resolving",
+                    ResolvingError::InvalidReference =>
+                        "encountered reference to non-scalar value during resolving",
+                    ResolvingError::MissingReference =>
+                        "encountered reference to missing key during resolving",
+                    ResolvingError::UnknownProfile =>
+                        "encountered unknown profile during resolving",
+                    ResolvingError::UnterminatedInterpolation =>
+                        "encountered unterminated interpolation during resolving",
+                }
+            ),
+            ErrorKind::Limit(l) => match l {
+                LimitError::DocumentTooLarge { limit, found } => write!(
+                    f,
+                    "encountered document of {} bytes during lexing, limit is {}",
+                    found, limit
+                ),
+                LimitError::NestingTooDeep { limit, found } => write!(
+                    f,
+                    "encountered nesting {} levels deep during parsing, limit is {}",
+                    found, limit
+                ),
+                LimitError::StringTooLong { limit, found } => write!(
+                    f,
+                    "encountered string of {} bytes during lexing, limit is {}",
+                    found, limit
+                ),
+                LimitError::TooManyTokens { limit, found } => write!(
+                    f,
+                    "encountered {} tokens during lexing, limit is {}",
+                    found, limit
+                ),
+            },
+        }
+    }
+
+    fn schema_error(&self, f: &mut dyn fmt::Write, kind: &SchemaErrorKind) -> fmt::Result {
+        match *kind {
+            SchemaErrorKind::MissingKey => write!(f, "encountered missing key during validation"),
+            SchemaErrorKind::MismatchedType { expected, found } => write!(
+                f,
+                "encountered {} during validation, expected {}",
+                found, expected
+            ),
+            SchemaErrorKind::OutOfRange => {
+                write!(f, "encountered out-of-range value during validation")
+            }
+            SchemaErrorKind::InvalidChoice => {
+                write!(
+                    f,
+                    "encountered value outside the allowed choices during validation"
+                )
+            }
+            SchemaErrorKind::UnknownKey => {
+                write!(
+                    f,
+                    "encountered key not declared in the schema during validation"
+                )
+            }
+            SchemaErrorKind::DeprecatedKey => {
+                write!(f, "encountered deprecated key during validation")
+            }
+        }
+    }
+
+    fn schema_warning(&self, f: &mut dyn fmt::Write, kind: &SchemaWarningKind) -> fmt::Result {
+        match kind {
+            SchemaWarningKind::DeprecatedKey => {
+                write!(f, "encountered deprecated key during validation")
+            }
+            SchemaWarningKind::UnknownKey => write!(
+                f,
+                "encountered key not declared in the schema during validation"
+            ),
+        }
+    }
+
+    fn hint(&self, f: &mut dyn fmt::Write, hint: &Hint) -> fmt::Result {
+        match hint {
+            Hint::Text(text) => f.write_str(text),
+            Hint::ByteEscapes => f.write_str(
+                "byte strings only accept `\\n`, `\\t`, `\\r`, `\\0`, `\\\\`, `\\\"` and `\\x` \
+                 followed by two hex digits",
+            ),
+            Hint::UnicodeEscape => f.write_str(
+                "unicode escapes are written `\\u{..}` around the hex code of a character",
+            ),
+            Hint::IntegerRange => {
+                write!(f, "integers must be between {} and {}", i64::MIN, i64::MAX)
+            }
+            Hint::CloseString { close, line } => write!(
+                f,
+                "strings must be closed with `{}`, did you forget a closing quote on line {}?",
+                close, line
+            ),
+            Hint::CloseDelimiter { close, line } => write!(
+                f,
+                "add a `{}` to match the one opened on line {}",
+                close, line
+            ),
+        }
+    }
+
+    fn suggestion(&self, f: &mut dyn fmt::Write, suggestion: &str) -> fmt::Result {
+        write!(f, "did you mean `{}`?", suggestion)
+    }
+
+    fn replacement(&self, f: &mut dyn fmt::Write, replacement: &str) -> fmt::Result {
+        write!(f, "rename it to `{}`", replacement)
+    }
+
+    fn included(&self, f: &mut dyn fmt::Write, file: &str, span: Span) -> fmt::Result {
+        write!(f, "included from {}{}", file, span)
+    }
+
+    fn label(&self, label: Label) -> &str {
+        match label {
+            Label::Here => "here",
+            Label::PreviousDefinition => "previously defined here",
+            Label::OpenDelimiter => "delimiter opened here",
+            Label::LeftOperand => "left operand here",
+            Label::FirstElement => "first element here",
+            Label::IncludedHere => "included from here",
+        }
+    }
+
+    fn severity(&self, severity: Severity) -> &str {
+        match severity {
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    fn help(&self) -> &str {
+        "help"
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct English;
+
+impl Messages for English {}
+
+// collects one message into a string, for the places that keep text rather than write it out
+pub(crate) fn text(write: impl FnOnce(&mut dyn fmt::Write) -> fmt::Result) -> String {
+    let mut text = String::new();
+    // writing into a string never fails
+    let _ = write(&mut text);
+    text
+}
@@ -0,0 +1,145 @@
+use crate::utils::{Error, ErrorKind, Hints, OwnedError, Snippet, Source, Span};
+use ::miette::{
+    LabeledSpan, MietteDiagnostic, MietteError, MietteSpanContents, NamedSource, Report,
+    SourceCode, SourceSpan, SpanContents,
+};
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+fn source_span(span: Span) -> SourceSpan {
+    (
+        span.begin.offset,
+        span.end.offset.saturating_sub(span.begin.offset),
+    )
+        .into()
+}
+
+// errors already render their snippet in `Display`, so reports carry a diagnostic with just the
+// message and leave drawing the source to miette; miette has nowhere to put a note, so only the
+// help carries over
+fn diagnostic(span: Span, kind: ErrorKind, hints: Option<&Hints>) -> MietteDiagnostic {
+    let mut labels = alloc::vec![LabeledSpan::new_primary_with_span(None, source_span(span))];
+
+    if let Some((related, label)) = kind.related() {
+        labels.push(LabeledSpan::new_with_span(
+            Some(alloc::format!("{}", label)),
+            source_span(related),
+        ));
+    }
+
+    let diagnostic = MietteDiagnostic::new(alloc::format!("{}", kind))
+        .with_code(kind.code())
+        .with_labels(labels);
+
+    match hints.and_then(|hints| hints.help.as_ref()) {
+        Some(help) => diagnostic.with_help(alloc::format!("{}", help)),
+        None => diagnostic,
+    }
+}
+
+impl SourceCode for Source<'_> {
+    fn read_span<'a>(
+        &'a self,
+        span: &SourceSpan,
+        before: usize,
+        after: usize,
+    ) -> Result<Box<dyn SpanContents<'a> + 'a>, MietteError> {
+        let contents = self.content.read_span(span, before, after)?;
+
+        Ok(Box::new(MietteSpanContents::new_named(
+            self.file.into(),
+            contents.data(),
+            *contents.span(),
+            contents.line(),
+            contents.column(),
+            contents.line_count(),
+        )))
+    }
+}
+
+impl<'a> From<Error<'a>> for Report {
+    fn from(error: Error<'a>) -> Self {
+        Report::new(diagnostic(error.span, error.kind, error.hints.as_deref()))
+            .with_source_code(NamedSource::new(error.src.file, error.src.content.clone()))
+    }
+}
+
+// owned errors only keep the lines around their spans, so spans are read from whichever snippet
+// holds them and shifted back to where that snippet sits in the source
+#[derive(Debug)]
+struct Snippets {
+    file: String,
+    snippets: Vec<Snippet>,
+}
+
+impl SourceCode for Snippets {
+    fn read_span<'a>(
+        &'a self,
+        span: &SourceSpan,
+        before: usize,
+        after: usize,
+    ) -> Result<Box<dyn SpanContents<'a> + 'a>, MietteError> {
+        let snippet = self
+            .snippets
+            .iter()
+            .find(|s| {
+                s.offset <= span.offset() && span.offset() + span.len() <= s.offset + s.text.len()
+            })
+            .ok_or(MietteError::OutOfBounds)?;
+
+        let shifted = (span.offset() - snippet.offset, span.len()).into();
+        let contents = snippet.text.read_span(&shifted, before, after)?;
+
+        Ok(Box::new(MietteSpanContents::new_named(
+            self.file.clone(),
+            contents.data(),
+            (
+                contents.span().offset() + snippet.offset,
+                contents.span().len(),
+            )
+                .into(),
+            contents.line() + snippet.line - 1,
+            contents.column(),
+            contents.line_count(),
+        )))
+    }
+}
+
+// miette reads nearby labels as a single span, so snippets that touch are joined into one
+fn merge(snippets: impl IntoIterator<Item = Snippet>) -> Vec<Snippet> {
+    let mut snippets: Vec<Snippet> = snippets.into_iter().collect();
+    snippets.sort_by_key(|s| s.line);
+
+    let mut merged: Vec<Snippet> = Vec::new();
+    for snippet in snippets {
+        match merged.last_mut() {
+            Some(last) if snippet.line <= last.line + last.text.split('\n').count() => {
+                let end = last.line + last.text.split('\n').count();
+
+                for line in snippet.text.split('\n').skip(end - snippet.line) {
+                    last.text.push('\n');
+                    last.text.push_str(line);
+                }
+            }
+            _ => merged.push(snippet),
+        }
+    }
+
+    merged
+}
+
+impl From<OwnedError> for Report {
+    fn from(error: OwnedError) -> Self {
+        Report::new(diagnostic(error.span, error.kind, error.hints.as_deref())).with_source_code(
+            Snippets {
+                file: error.file,
+                snippets: merge(core::iter::once(error.snippet).chain(error.related)),
+            },
+        )
+    }
+}
+
+impl From<Box<OwnedError>> for Report {
+    fn from(error: Box<OwnedError>) -> Self {
+        (*error).into()
+    }
+}
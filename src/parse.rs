@@ -0,0 +1,2032 @@
+use crate::{
+    arena::{Arena, ROOT},
+    diagnostic::{Diagnostic, Diagnostics, Lints, Severity},
+    lex::*,
+    merge::MergeStrategy,
+    path::*,
+    utils::*,
+    value::*,
+};
+use alloc::{
+    boxed::Box,
+    collections::{btree_map::BTreeMap, vec_deque::VecDeque},
+    string::String,
+    vec::Vec,
+};
+use core::ops::Range;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    #[default]
+    Error,
+    LastWins,
+    FirstWins,
+    Merge,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayPolicy {
+    #[default]
+    Mixed,
+    Homogeneous,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    Strict,
+    #[default]
+    Lenient,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParserOptions {
+    pub mode: ParseMode,
+    // duplicates are rejected by default in either mode, a policy chosen here is always followed
+    pub duplicates: DuplicatePolicy,
+    pub arrays: ArrayPolicy,
+    pub overflow: OverflowPolicy,
+    pub max_depth: usize,
+    pub max_size: Option<usize>,
+    pub max_tokens: Option<usize>,
+    pub max_string_len: Option<usize>,
+    pub lints: Lints,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        Self {
+            mode: ParseMode::default(),
+            duplicates: DuplicatePolicy::default(),
+            arrays: ArrayPolicy::default(),
+            overflow: OverflowPolicy::default(),
+            max_depth: 128,
+            max_size: None,
+            max_tokens: None,
+            max_string_len: None,
+            lints: Lints::default(),
+        }
+    }
+}
+
+impl ParserOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mode(mut self, mode: ParseMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn strict(self) -> Self {
+        self.mode(ParseMode::Strict)
+    }
+
+    pub fn duplicates(mut self, duplicates: DuplicatePolicy) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+
+    pub fn arrays(mut self, arrays: ArrayPolicy) -> Self {
+        self.arrays = arrays;
+        self
+    }
+
+    pub fn overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    pub fn max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn max_string_len(mut self, max_string_len: usize) -> Self {
+        self.max_string_len = Some(max_string_len);
+        self
+    }
+
+    pub fn lints(mut self, lints: Lints) -> Self {
+        self.lints = lints;
+        self
+    }
+
+    pub fn lexer(&self) -> LexerOptions {
+        LexerOptions {
+            overflow: self.overflow,
+            comments: true,
+            max_size: self.max_size,
+            max_tokens: self.max_tokens,
+            max_string_len: self.max_string_len,
+            lints: self.lints,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Section {
+        path: KeyPath,
+        array: bool,
+        span: Span,
+    },
+    Include {
+        path: String,
+        span: Span,
+    },
+    Key {
+        path: KeyPath,
+        span: Span,
+    },
+    Scalar(Value, Span),
+    ArrayStart(Span),
+    ArrayEnd(Span),
+    TableStart(Span),
+    TableEnd(Span),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Frame {
+    table: bool,
+    first: bool,
+    open: Span,
+    element: Option<(ValueKind, Span)>,
+}
+
+// a key path with the span of each of its keys
+type Keys = Vec<(String, Span)>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Extends {
+    path: KeyPath,
+    target: KeyPath,
+    key: Span,
+    span: Span,
+}
+
+pub struct Parser<'a> {
+    src: &'a Source<'a>,
+    lexemes: LexemeStream,
+    comments: VecDeque<(String, Span)>,
+    options: ParserOptions,
+    prev: Span,
+    last: Span,
+    includes: &'a [Source<'a>],
+    stack: Vec<&'a str>,
+    anchors: BTreeMap<String, (Value, Span)>,
+    frames: Vec<Frame>,
+    pending: bool,
+    depth: usize,
+    checked: usize,
+    warnings: Vec<Error<'a>>,
+    extends: Vec<Extends>,
+    expected: (usize, ExpectedSet),
+    // the span of each segment of the last key or header an event was made for, which the event
+    // only spans as a whole
+    segments: Vec<Span>,
+    // what the events have read so far, kept for references and `extends` to resolve against:
+    // the section they're in, the key waiting on its value and the inline arrays and tables still
+    // open, each with the key it goes at; a document without any is streamed without it
+    recording: bool,
+    tree: Table,
+    section: Vec<(String, Span)>,
+    key: Option<Keys>,
+    nest: Vec<(Option<Keys>, Value, Span)>,
+    queued: VecDeque<Event>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(src: &'a Source<'a>, lexemes: LexemeStream) -> Self {
+        Self::with_options(src, lexemes, ParserOptions::default())
+    }
+
+    pub fn with_options(
+        src: &'a Source<'a>,
+        lexemes: LexemeStream,
+        options: ParserOptions,
+    ) -> Self {
+        let (comments, lexemes): (LexemeStream, LexemeStream) = lexemes
+            .into_iter()
+            .partition(|l| matches!(l.kind, LexemeKind::Comment(_)));
+
+        Self {
+            src,
+            recording: references(&lexemes),
+            lexemes,
+            comments: comments
+                .into_iter()
+                .filter_map(|l| match l.kind {
+                    LexemeKind::Comment(comment) => Some((comment, l.span)),
+                    _ => None,
+                })
+                .collect(),
+            options,
+            prev: Span::default(),
+            last: Span::default(),
+            includes: &[],
+            stack: alloc::vec![src.file],
+            anchors: BTreeMap::new(),
+            frames: Vec::new(),
+            pending: false,
+            depth: 0,
+            checked: 0,
+            warnings: Vec::new(),
+            extends: Vec::new(),
+            expected: (usize::MAX, ExpectedSet::new()),
+            segments: Vec::new(),
+            tree: Table::new(),
+            section: Vec::new(),
+            key: None,
+            nest: Vec::new(),
+            queued: VecDeque::new(),
+        }
+    }
+
+    pub fn with_includes(mut self, includes: &'a [Source<'a>]) -> Self {
+        self.includes = includes;
+        self
+    }
+
+    // seeds the warnings with ones reported before parsing, usually the lexer's
+    pub fn with_warnings(mut self, warnings: Vec<Error<'a>>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    pub fn parse(self) -> Result<'a, Document> {
+        self.parse_with_warnings().map(|(document, _)| document)
+    }
+
+    pub fn parse_with_warnings(mut self) -> Result<'a, (Document, Vec<Error<'a>>)> {
+        let mut root = Table::new();
+        let mut section = Vec::new();
+
+        while !self.lexemes.is_empty() {
+            self.step(&mut root, &mut section)?;
+        }
+
+        self.inherit(&mut root)?;
+        let document = self.finish(root, &section);
+        Ok((document, self.warnings))
+    }
+
+    pub fn parse_recovering(self) -> (Document, Vec<Error<'a>>) {
+        let (document, errors, _) = self.recover();
+        (document, errors)
+    }
+
+    pub fn parse_diagnostics(self) -> (Document, Diagnostics) {
+        let (document, errors, warnings) = self.recover();
+        let mut diagnostics: Diagnostics = errors.into_iter().collect();
+
+        diagnostics.extend(
+            warnings
+                .into_iter()
+                .map(|warning| Diagnostic::from(warning).severity(Severity::Warning)),
+        );
+        diagnostics.sort();
+
+        (document, diagnostics)
+    }
+
+    fn recover(mut self) -> (Document, Vec<Error<'a>>, Vec<Error<'a>>) {
+        let mut root = Table::new();
+        let mut section = Vec::new();
+        let mut errors = Vec::new();
+
+        while !self.lexemes.is_empty() {
+            if let Err(error) = self.step(&mut root, &mut section) {
+                errors.push(error);
+                self.synchronize();
+            }
+        }
+
+        if let Err(error) = self.inherit(&mut root) {
+            errors.push(error);
+        }
+
+        (self.finish(root, &section), errors, self.warnings)
+    }
+
+    pub fn next_event(&mut self) -> Option<Result<'a, Event>> {
+        let event = self.event();
+
+        match &event {
+            Ok(Some(event)) if self.recording => self.record(event),
+            Ok(_) => {}
+            Err(_) => {
+                self.lexemes.clear();
+                self.frames.clear();
+                self.nest.clear();
+                self.extends.clear();
+                self.queued.clear();
+                self.pending = false;
+            }
+        }
+
+        event.transpose()
+    }
+
+    fn event(&mut self) -> Result<'a, Option<Event>> {
+        if let Some(event) = self.queued.pop_front() {
+            if let Event::Key { path, span } = &event {
+                self.segments.clear();
+                self.segments.resize(path.len(), *span);
+            }
+
+            return Ok(Some(event));
+        }
+
+        if self.pending {
+            self.pending = false;
+            return self.value_event().map(Some);
+        }
+
+        let Some(frame) = self.frames.last().copied() else {
+            return match self.peek() {
+                None if !self.extends.is_empty() => {
+                    self.inherited()?;
+                    self.event()
+                }
+                None => Ok(None),
+                Some(LexemeKind::LBrack) => {
+                    let begin = self.front_span();
+                    let (path, array) = self.parse_header()?;
+                    self.segments.clear();
+                    self.segments.extend(path.iter().map(|(_, span)| *span));
+
+                    Ok(Some(Event::Section {
+                        path: key_path(path),
+                        array,
+                        span: Span::new(begin.begin, self.last.end),
+                    }))
+                }
+                Some(LexemeKind::Directive(name)) if name == "include" => {
+                    let directive = self.next()?;
+                    let lexeme = self.next()?;
+
+                    match lexeme.kind {
+                        LexemeKind::String(path) => Ok(Some(Event::Include {
+                            path,
+                            span: Span::new(directive.span.begin, lexeme.span.end),
+                        })),
+                        _ => Err(self.unexpected(lexeme, Expected::Token(TokenKind::String))),
+                    }
+                }
+                Some(LexemeKind::Directive(_)) => {
+                    let span = self.next()?.span;
+                    Err(self.error(ParsingError::UnknownDirective, span))
+                }
+                Some(_) if self.is_extends() => {
+                    let tree = core::mem::take(&mut self.tree);
+                    let section = core::mem::take(&mut self.section);
+                    let result = self.parse_extends(&tree, &section);
+                    (self.tree, self.section) = (tree, section);
+                    result?;
+                    self.event()
+                }
+                Some(_) => self.key_event().map(Some),
+            };
+        };
+
+        let close = if frame.table {
+            LexemeKind::RBrace
+        } else {
+            LexemeKind::RBrack
+        };
+
+        let event = self.delimited(frame.open, |p| {
+            if !frame.first && !p.check(&close) {
+                let comma = p.expect(LexemeKind::Comma)?;
+
+                if p.check(&close) {
+                    p.lenient(ParsingError::TrailingComma, comma)?;
+                }
+            }
+
+            if p.check(&close) {
+                let span = p.next()?.span;
+                p.frames.pop();
+
+                return Ok(if frame.table {
+                    Event::TableEnd(span)
+                } else {
+                    Event::ArrayEnd(span)
+                });
+            }
+
+            if let Some(frame) = p.frames.last_mut() {
+                frame.first = false;
+            }
+
+            if frame.table {
+                return p.key_event();
+            }
+
+            let parent = p.frames.len() - 1;
+            let event = p.value_event()?;
+            let (kind, span) = match &event {
+                Event::Scalar(value, span) => (value.kind(), *span),
+                Event::ArrayStart(span) => (ValueKind::Array, *span),
+                Event::TableStart(span) => (ValueKind::Table, *span),
+                _ => return Ok(event),
+            };
+
+            let mut element = p.frames[parent].element;
+            p.element(&mut element, kind, span)?;
+            p.frames[parent].element = element;
+            Ok(event)
+        })?;
+
+        Ok(Some(event))
+    }
+
+    // the document goes straight into one arena rather than a tree of separate allocations; it's
+    // read through the event stream, so the tokens and events along the way still allocate as
+    // they do there, and comments aren't kept
+    pub fn parse_arena(mut self) -> Result<'a, Arena> {
+        let mut arena = Arena::new();
+        self.build_arena(&mut arena, ROOT)?;
+        Ok(arena)
+    }
+
+    fn build_arena(&mut self, arena: &mut Arena, root: usize) -> Result<'a, ()> {
+        let mut section = root;
+        // the inline arrays and tables still open, with the span each was opened at
+        let mut open: Vec<(usize, Span)> = Vec::new();
+        let mut key = None;
+
+        while let Some(event) = self.next_event() {
+            let (node, span, opened) = match event? {
+                Event::Section { path, array, span } => {
+                    section = self.arena_section(arena, root, path, array, span)?;
+                    continue;
+                }
+                Event::Include { path, span } => {
+                    self.arena_include(arena, section, &path, span)?;
+                    continue;
+                }
+                Event::Key { mut path, span } => {
+                    let Some(PathSegment::Key(last)) = path.pop() else {
+                        continue;
+                    };
+
+                    let table = open.last().map_or(section, |(node, _)| *node);
+                    let table = self.arena_descend(arena, table, &path, span)?;
+                    let span = self.segments.last().copied().unwrap_or(span);
+                    key = Some((table, arena.key(&last), span));
+                    continue;
+                }
+                Event::Scalar(value, span) => (arena.value(&value, span), span, false),
+                Event::ArrayStart(span) => (arena.container(false, span), span, true),
+                Event::TableStart(span) => (arena.container(true, span), span, true),
+                Event::ArrayEnd(span) | Event::TableEnd(span) => {
+                    if let Some((node, open)) = open.pop() {
+                        arena.close(node, open, span);
+                    }
+
+                    continue;
+                }
+            };
+
+            let node = match (key.take(), open.last()) {
+                (Some((table, key, key_span)), _) => {
+                    self.arena_insert(arena, table, key, key_span, node)?
+                }
+                (None, Some(&(array, _))) => {
+                    arena.push(array, node);
+                    node
+                }
+                (None, None) => node,
+            };
+
+            if opened {
+                open.push((node, span));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn arena_section(
+        &self,
+        arena: &mut Arena,
+        root: usize,
+        mut path: KeyPath,
+        array: bool,
+        span: Span,
+    ) -> Result<'a, usize> {
+        if !array {
+            return self.arena_descend(arena, root, &path, span);
+        }
+
+        let Some(PathSegment::Key(key)) = path.pop() else {
+            return Ok(root);
+        };
+        let table = self.arena_descend(arena, root, &path, span)?;
+        let span = self.segments.last().copied().unwrap_or(span);
+
+        let node = match arena.find(table, &key) {
+            Some(entry) => match arena.entry(entry) {
+                (_, _, node) if arena.is_array(node) => node,
+                (_, first, _) => return Err(self.conflict(first, span)),
+            },
+            None => {
+                let node = arena.container(false, span);
+                let key = arena.key(&key);
+                arena.add(table, key, span, node);
+                node
+            }
+        };
+
+        let element = arena.container(true, span);
+        arena.push(node, element);
+        Ok(element)
+    }
+
+    fn arena_descend(
+        &self,
+        arena: &mut Arena,
+        mut table: usize,
+        path: &KeyPath,
+        span: Span,
+    ) -> Result<'a, usize> {
+        let mut segments = path.iter().peekable();
+        let mut index = 0;
+
+        while let Some(segment) = segments.next() {
+            let PathSegment::Key(key) = segment else {
+                continue;
+            };
+            let span = self.segments.get(index).copied().unwrap_or(span);
+            index += 1;
+            let (first, node) = match arena.find(table, key) {
+                Some(entry) => {
+                    let (_, first, node) = arena.entry(entry);
+                    (first, node)
+                }
+                None => {
+                    let node = arena.container(true, span);
+                    let key = arena.key(key);
+                    arena.add(table, key, span, node);
+                    (span, node)
+                }
+            };
+
+            // only what `extends` brings in is put into an element other than the last
+            let element = match segments.peek() {
+                Some(PathSegment::Index(index)) => arena.element(node, *index),
+                _ => arena.last(node),
+            };
+
+            table = match element {
+                _ if arena.is_table(node) => node,
+                Some(last) if arena.is_array(node) && arena.is_table(last) => last,
+                _ => return Err(self.conflict(first, span)),
+            };
+        }
+
+        Ok(table)
+    }
+
+    // gives back the node the value's contents belong in, which when tables are merged is the
+    // table already there
+    fn arena_insert(
+        &mut self,
+        arena: &mut Arena,
+        table: usize,
+        key: Range<usize>,
+        key_span: Span,
+        value: usize,
+    ) -> Result<'a, usize> {
+        let Some(existing) = arena.find(table, arena.text(key.clone())) else {
+            arena.add(table, key, key_span, value);
+            return Ok(value);
+        };
+        let (_, first, old) = arena.entry(existing);
+        let duplicate = self.error(ParsingError::DuplicateKey { first }, key_span);
+        match self.options.duplicates {
+            DuplicatePolicy::Error => Err(duplicate),
+            DuplicatePolicy::LastWins => {
+                self.options.lints.report(&mut self.warnings, duplicate)?;
+                arena.replace(existing, key_span, value);
+                Ok(value)
+            }
+            // the value is still read, just into a node nothing points at
+            DuplicatePolicy::FirstWins => {
+                self.options.lints.report(&mut self.warnings, duplicate)?;
+                Ok(value)
+            }
+            DuplicatePolicy::Merge if arena.is_table(old) && arena.is_table(value) => {
+                let mut next = arena.first_entry(value);
+
+                while let Some(entry) = next {
+                    next = arena.next_entry(entry);
+                    let (key, key_span, value) = arena.entry(entry);
+                    self.arena_insert(arena, old, key, key_span, value)?;
+                }
+
+                Ok(old)
+            }
+            DuplicatePolicy::Merge => Err(duplicate),
+        }
+    }
+
+    fn arena_include(
+        &mut self,
+        arena: &mut Arena,
+        table: usize,
+        path: &str,
+        span: Span,
+    ) -> Result<'a, ()> {
+        let Some(src) = self.includes.iter().find(|s| s.file == path) else {
+            return Err(self.error(ParsingError::IncludeNotFound, span));
+        };
+
+        if self.stack.contains(&src.file) {
+            return Err(self.error(ParsingError::IncludeCycle, span));
+        }
+
+        let included = |error: Error<'a>| error.included_from(self.src, span);
+        let (lexemes, warnings) = lex_with_warnings(src, self.options.lexer()).map_err(included)?;
+        let mut parser = Parser::with_options(src, lexemes, self.options)
+            .with_includes(self.includes)
+            .with_warnings(warnings);
+        parser.stack = self.stack.clone();
+        parser.stack.push(src.file);
+
+        // the included file is read into a table of its own first, so its entries are merged in
+        // the same way an included document's are
+        let root = arena.container(true, span);
+        parser.build_arena(arena, root).map_err(included)?;
+        self.warnings
+            .extend(parser.warnings.into_iter().map(included));
+
+        let merged = self.warnings.len();
+        let mut next = arena.first_entry(root);
+        let mut result = Ok(());
+
+        while let Some(entry) = next {
+            next = arena.next_entry(entry);
+            let (key, key_span, value) = arena.entry(entry);
+
+            if let Err(error) = self.arena_insert(arena, table, key, key_span, value) {
+                result = Err(error);
+                break;
+            }
+        }
+
+        for warning in &mut self.warnings[merged..] {
+            warning.span = span;
+        }
+
+        result.map_err(|error| Error { span, ..error })
+    }
+
+    fn front_span(&self) -> Span {
+        self.lexemes.front().map_or(self.last, |l| l.span)
+    }
+
+    fn key_event(&mut self) -> Result<'a, Event> {
+        let path = self.parse_key_path()?;
+        self.segments.clear();
+        self.segments.extend(path.iter().map(|(_, span)| *span));
+        let span = Span::new(
+            path.first().map_or(self.last, |(_, s)| *s).begin,
+            self.last.end,
+        );
+
+        self.expect(LexemeKind::Equal)?;
+        self.pending = true;
+
+        Ok(Event::Key {
+            path: key_path(path),
+            span,
+        })
+    }
+
+    fn value_event(&mut self) -> Result<'a, Event> {
+        // a value that has to be worked out is read whole, the same way `parse` reads it, and
+        // handed on as the scalar it comes to
+        if self.computed() {
+            let (value, span) = self.evaluate()?;
+            return Ok(Event::Scalar(value, span));
+        }
+
+        let lexeme = self.next()?;
+        let span = lexeme.span;
+
+        Ok(match lexeme.kind {
+            LexemeKind::String(s) => Event::Scalar(Value::String(s), span),
+            LexemeKind::Bytes(b) => Event::Scalar(Value::Bytes(b), span),
+            LexemeKind::Integer(i) => Event::Scalar(Value::Integer(i), span),
+            LexemeKind::Float(f) => Event::Scalar(Value::Float(f), span),
+            LexemeKind::Bool(b) => Event::Scalar(Value::Bool(b), span),
+            LexemeKind::LBrack | LexemeKind::LBrace => {
+                if self.frames.len() >= self.options.max_depth {
+                    return Err(self.too_deep(self.frames.len(), span));
+                }
+
+                let table = lexeme.kind == LexemeKind::LBrace;
+                self.frames.push(Frame {
+                    table,
+                    first: true,
+                    open: span,
+                    element: None,
+                });
+
+                if table {
+                    Event::TableStart(span)
+                } else {
+                    Event::ArrayStart(span)
+                }
+            }
+            kind => return Err(self.unexpected(Lexeme::new(kind, span), Expected::Value)),
+        })
+    }
+
+    fn computed(&self) -> bool {
+        let operator = |lexeme: Option<&Lexeme>| {
+            lexeme.is_some_and(|l| {
+                matches!(
+                    l.kind,
+                    LexemeKind::Plus | LexemeKind::Minus | LexemeKind::Star | LexemeKind::Slash
+                )
+            })
+        };
+        let Some(front) = self.lexemes.front() else {
+            return false;
+        };
+
+        match front.kind {
+            LexemeKind::Ampersand | LexemeKind::Star => true,
+            LexemeKind::Ident(_) => front.span.begin.line == self.last.end.line,
+            LexemeKind::LBrack | LexemeKind::LBrace => {
+                let mut depth = 0usize;
+                let close = self.lexemes.iter().position(|l| {
+                    match l.kind {
+                        LexemeKind::LBrack | LexemeKind::LBrace => depth += 1,
+                        LexemeKind::RBrack | LexemeKind::RBrace => depth -= 1,
+                        _ => {}
+                    }
+
+                    depth == 0
+                });
+
+                close.is_some_and(|close| operator(self.lexemes.get(close + 1)))
+            }
+            _ => operator(self.lexemes.get(1)),
+        }
+    }
+
+    // references resolve against the innermost table being read, as they do in `parse`
+    fn evaluate(&mut self) -> Result<'a, (Value, Span)> {
+        let mut tree = core::mem::take(&mut self.tree);
+        let nest = core::mem::take(&mut self.nest);
+        self.depth = self.frames.len();
+
+        let result = match nest.iter().rev().find_map(|(_, value, _)| value.as_table()) {
+            Some(scope) => self.parse_value(scope),
+            None => self
+                .descend(&mut tree, &self.section)
+                .and_then(|scope| self.parse_value(scope)),
+        };
+
+        self.depth = 0;
+        (self.tree, self.nest) = (tree, nest);
+        result
+    }
+
+    // what's recorded follows `parse`, but anything it would reject is left for whoever reads
+    // the events to report
+    fn record(&mut self, event: &Event) {
+        let segments = |path: &KeyPath, spans: &[Span]| -> Keys {
+            path.iter()
+                .filter_map(PathSegment::key)
+                .zip(spans.iter().copied())
+                .map(|(key, span)| (key.into(), span))
+                .collect()
+        };
+
+        match event {
+            Event::Section { path, array, .. } => {
+                let path = segments(path, &self.segments);
+                let mut tree = core::mem::take(&mut self.tree);
+
+                let _ = match array {
+                    true => self.append(&mut tree, &path),
+                    false => self.descend(&mut tree, &path).map(|_| ()),
+                };
+
+                (self.tree, self.section) = (tree, path);
+            }
+            Event::Include { path, span } => {
+                let mut tree = core::mem::take(&mut self.tree);
+                let warnings = self.warnings.len();
+
+                if let Ok(table) = self.descend(&mut tree, &self.section) {
+                    let _ = self.include(table, path, *span);
+                }
+
+                self.warnings.truncate(warnings);
+                self.tree = tree;
+            }
+            Event::Key { path, .. } => self.key = Some(segments(path, &self.segments)),
+            Event::Scalar(value, span) => self.place(value.clone(), *span),
+            Event::ArrayStart(span) => {
+                let key = self.key.take();
+                self.nest.push((key, Value::Array(Vec::new()), *span));
+            }
+            Event::TableStart(span) => {
+                let key = self.key.take();
+                self.nest.push((key, Value::Table(Table::new()), *span));
+            }
+            Event::ArrayEnd(span) | Event::TableEnd(span) => {
+                if let Some((key, value, open)) = self.nest.pop() {
+                    self.key = key;
+                    self.place(value, Span::new(open.begin, span.end));
+                }
+            }
+        }
+    }
+
+    fn place(&mut self, value: Value, span: Span) {
+        if let Some((_, Value::Array(array), _)) = self.nest.last_mut() {
+            array.push(value);
+            return;
+        }
+
+        let Some(mut path) = self.key.take() else {
+            return;
+        };
+        let Some((key, key_span)) = path.pop() else {
+            return;
+        };
+
+        let mut tree = core::mem::take(&mut self.tree);
+        let mut nest = core::mem::take(&mut self.nest);
+        let warnings = self.warnings.len();
+
+        let table = match nest.last_mut() {
+            Some((_, Value::Table(table), _)) => Ok(table),
+            _ => self.descend(&mut tree, &self.section),
+        };
+
+        if let Ok(table) = table.and_then(|table| self.descend(table, &path)) {
+            let _ = self.insert(table, key, Entry::new(value, key_span, span));
+        }
+
+        self.warnings.truncate(warnings);
+        (self.tree, self.nest) = (tree, nest);
+    }
+
+    // `extends` can only be resolved once everything's been read, so what it brings in comes
+    // after the rest of the events, each key spelled out from the root
+    fn inherited(&mut self) -> Result<'a, ()> {
+        let mut resolved = self.tree.clone();
+        self.inherit(&mut resolved)?;
+        self.extends.clear();
+        added(&self.tree, &resolved, &mut KeyPath::new(), &mut self.queued);
+
+        if !self.queued.is_empty() {
+            let span = Span::new(self.last.end, self.last.end);
+            let path = KeyPath::new();
+            self.queued.push_front(Event::Section {
+                path,
+                array: false,
+                span,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self, mut root: Table, section: &[(String, Span)]) -> Document {
+        let mut rest = self.comments_before(usize::MAX);
+        let last = self
+            .descend(&mut root, section)
+            .ok()
+            .and_then(|table| table.iter_mut().last());
+
+        if let Some((_, entry)) = last {
+            entry.comments.trailing.append(&mut rest);
+        }
+
+        let mut document = Document::new(root);
+        document.comments = rest;
+        document
+    }
+
+    fn step(&mut self, root: &mut Table, section: &mut Vec<(String, Span)>) -> Result<'a, ()> {
+        if self.check(&LexemeKind::LBrack) {
+            let leading = self.comments_before(self.front_offset());
+            let (path, array) = self.parse_header()?;
+
+            if array {
+                self.append(root, &path)?;
+            } else {
+                self.descend(root, &path)?;
+            }
+
+            let inline = self.inline_comment(self.last.end.line);
+
+            if let Some(entry) = self.header_entry(root, &path) {
+                entry.comments.leading.extend(leading);
+                entry.comments.inline = inline.or(entry.comments.inline.take());
+            }
+
+            *section = path;
+            Ok(())
+        } else if let Some(LexemeKind::Directive(_)) = self.peek() {
+            let table = self.descend(root, section)?;
+            self.parse_directive(table)
+        } else if self.is_extends() {
+            self.descend(root, section)?;
+            self.parse_extends(root, section)
+        } else {
+            let table = self.descend(root, section)?;
+            self.parse_entry(table)
+        }
+    }
+
+    fn is_extends(&self) -> bool {
+        match (
+            self.lexemes.front(),
+            self.lexemes.get(1),
+            self.lexemes.get(2),
+        ) {
+            (Some(key), Some(equal), Some(target)) => {
+                key.kind == LexemeKind::Ident("extends".into())
+                    && equal.kind == LexemeKind::Equal
+                    && matches!(target.kind, LexemeKind::Ident(_))
+                    && target.span.begin.line == equal.span.end.line
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_extends(&mut self, root: &Table, section: &[(String, Span)]) -> Result<'a, ()> {
+        let key = self.next()?.span;
+        self.expect(LexemeKind::Equal)?;
+        let target = self.parse_key_path()?;
+        let span = Span::new(
+            target.first().map_or(self.last, |(_, s)| *s).begin,
+            self.last.end,
+        );
+
+        let mut path = KeyPath::new();
+        let mut table = Some(root);
+
+        for (key, _) in section {
+            path.push(PathSegment::Key(key.clone()));
+
+            table = match table.and_then(|t| t.get(key)) {
+                Some(Value::Array(array)) => {
+                    path.push(PathSegment::Index(array.len().saturating_sub(1)));
+                    array.last().and_then(Value::as_table)
+                }
+                value => value.and_then(Value::as_table),
+            };
+        }
+
+        if let Some(first) = self.extends.iter().find(|e| e.path == path) {
+            return Err(self.error(ParsingError::DuplicateKey { first: first.key }, key));
+        }
+
+        self.extends.push(Extends {
+            path,
+            target: key_path(target),
+            key,
+            span,
+        });
+        Ok(())
+    }
+
+    fn inherit(&self, root: &mut Table) -> Result<'a, ()> {
+        let mut resolved = alloc::vec![false; self.extends.len()];
+        let mut stack = Vec::new();
+
+        for i in 0..self.extends.len() {
+            self.inherit_one(root, i, &mut resolved, &mut stack)?;
+        }
+
+        Ok(())
+    }
+
+    fn inherit_one(
+        &self,
+        root: &mut Table,
+        i: usize,
+        resolved: &mut [bool],
+        stack: &mut Vec<usize>,
+    ) -> Result<'a, ()> {
+        if resolved[i] {
+            return Ok(());
+        }
+
+        let extends = &self.extends[i];
+
+        if stack.contains(&i) || extends.path.starts_with(&extends.target) {
+            return Err(self.error(ParsingError::CyclicExtends, extends.span));
+        }
+
+        // each link of an extends chain is resolved a level further down, so long chains are held
+        // to the same limit as nesting
+        if stack.len() >= self.options.max_depth {
+            let limit = self.options.max_depth;
+            let found = stack.len() + 1;
+            let kind = LimitError::NestingTooDeep { limit, found };
+            return Err(Error::limit(kind, extends.span, self.src));
+        }
+
+        stack.push(i);
+
+        for j in 0..self.extends.len() {
+            let other = &self.extends[j];
+
+            // what the target inherits has to be in it first, and so does whatever an ancestor of
+            // the target inherits, since that can be where the target itself comes from; an
+            // ancestor already being resolved is further up this chain and is left to it
+            let ancestor = j != i && extends.target.starts_with(&other.path) && !stack.contains(&j);
+
+            if other.path.starts_with(&extends.target) || ancestor {
+                self.inherit_one(root, j, resolved, stack)?;
+            }
+        }
+
+        let mut base = match root.lookup(&extends.target) {
+            Some(Value::Table(table)) => table.clone(),
+            Some(_) => return Err(self.error(ParsingError::InvalidExtends, extends.span)),
+            None => return Err(self.error(ParsingError::UnknownReference, extends.span)),
+        };
+
+        let table = match extends.path.is_empty() {
+            true => Some(root),
+            false => root.lookup_mut(&extends.path).and_then(Value::as_table_mut),
+        };
+
+        if let Some(table) = table {
+            base.merge(table, MergeStrategy::Override);
+            *table = base;
+        }
+
+        stack.pop();
+        resolved[i] = true;
+        Ok(())
+    }
+
+    fn parse_directive(&mut self, table: &mut Table) -> Result<'a, ()> {
+        let lexeme = self.next()?;
+
+        match lexeme.kind {
+            LexemeKind::Directive(name) if name == "include" => {}
+            _ => return Err(self.error(ParsingError::UnknownDirective, lexeme.span)),
+        }
+
+        let lexeme = self.next()?;
+        let LexemeKind::String(path) = lexeme.kind else {
+            return Err(self.unexpected(lexeme, Expected::Token(TokenKind::String)));
+        };
+
+        self.include(table, &path, Span::new(self.prev.begin, lexeme.span.end))
+    }
+
+    fn include(&mut self, table: &mut Table, path: &str, span: Span) -> Result<'a, ()> {
+        let Some(src) = self.includes.iter().find(|s| s.file == path) else {
+            return Err(self.error(ParsingError::IncludeNotFound, span));
+        };
+
+        if self.stack.contains(&src.file) {
+            return Err(self.error(ParsingError::IncludeCycle, span));
+        }
+
+        let included = |error: Error<'a>| error.included_from(self.src, span);
+        let (lexemes, warnings) = lex_with_warnings(src, self.options.lexer()).map_err(included)?;
+        let mut parser = Parser::with_options(src, lexemes, self.options)
+            .with_includes(self.includes)
+            .with_warnings(warnings);
+        parser.stack = self.stack.clone();
+        parser.stack.push(src.file);
+
+        let (document, warnings) = parser.parse_with_warnings().map_err(included)?;
+        self.warnings.extend(warnings.into_iter().map(included));
+
+        // the entries' spans point into the included file, so anything reported while merging
+        // them in is moved to the directive, which is in this one
+        let merged = self.warnings.len();
+        let result = document
+            .root
+            .into_iter()
+            .try_for_each(|(key, entry)| self.insert(table, key, entry));
+
+        for warning in &mut self.warnings[merged..] {
+            warning.span = span;
+        }
+
+        result.map_err(|error| Error { span, ..error })
+    }
+
+    fn synchronize(&mut self) {
+        let line = self.last.end.line;
+
+        while let Some(lexeme) = self.lexemes.front() {
+            if lexeme.span.begin.line > line
+                && matches!(
+                    lexeme.kind,
+                    LexemeKind::Ident(_)
+                        | LexemeKind::String(_)
+                        | LexemeKind::LBrack
+                        | LexemeKind::Directive(_)
+                )
+            {
+                break;
+            }
+
+            self.next().ok();
+        }
+    }
+
+    fn error(&self, kind: ParsingError, span: Span) -> Error<'a> {
+        Error::parsing(kind, span, self.src)
+    }
+
+    // the brackets still ahead are counted so the error reports how deep the value really goes,
+    // not just the level the limit was hit at; anchors nest without a closing token to stop at,
+    // so those only report the level they were hit at
+    fn too_deep(&self, depth: usize, open: Span) -> Error<'a> {
+        let (mut current, mut found) = (depth + 1, depth + 1);
+        let bracketed = matches!(self.src.slice(open), "[" | "{");
+
+        for lexeme in self.lexemes.iter().take_while(|_| bracketed) {
+            match lexeme.kind {
+                LexemeKind::LBrack | LexemeKind::LBrace => {
+                    current += 1;
+                    found = found.max(current);
+                }
+                LexemeKind::RBrack | LexemeKind::RBrace if current == depth + 1 => break,
+                LexemeKind::RBrack | LexemeKind::RBrace => current -= 1,
+                _ => {}
+            }
+        }
+
+        Error::limit(
+            LimitError::NestingTooDeep {
+                limit: self.options.max_depth,
+                found,
+            },
+            open,
+            self.src,
+        )
+    }
+
+    fn lenient(&mut self, kind: ParsingError, span: Span) -> Result<'a, ()> {
+        let error = self.error(kind, span);
+
+        match self.options.mode {
+            ParseMode::Strict => Err(error),
+            ParseMode::Lenient => self.options.lints.report(&mut self.warnings, error),
+        }
+    }
+
+    fn element(
+        &self,
+        first: &mut Option<(ValueKind, Span)>,
+        kind: ValueKind,
+        span: Span,
+    ) -> Result<'a, ()> {
+        match *first {
+            None => *first = Some((kind, span)),
+            Some((expected, first))
+                if expected != kind && self.options.arrays == ArrayPolicy::Homogeneous =>
+            {
+                return Err(self.error(ParsingError::MixedArray { first }, span));
+            }
+            Some(_) => {}
+        }
+
+        Ok(())
+    }
+
+    fn conflict(&self, first: Span, span: Span) -> Error<'a> {
+        self.error(ParsingError::ConflictingKey { first }, span)
+    }
+
+    fn unclosed(&self, error: Error<'a>, open: Span) -> Error<'a> {
+        match error.kind {
+            ErrorKind::Parsing(ParsingError::UnexpectedEof) => {
+                let error = self.error(ParsingError::UnclosedDelimiter { open }, error.span);
+
+                let close = match self.src.slice(open).chars().next() {
+                    Some('[') => ']',
+                    Some('{') => '}',
+                    _ => return error,
+                };
+
+                error.with_help(Hint::CloseDelimiter {
+                    close,
+                    line: open.begin.line,
+                })
+            }
+            _ => error,
+        }
+    }
+
+    fn front_offset(&self) -> usize {
+        self.lexemes
+            .front()
+            .map_or(usize::MAX, |l| l.span.begin.offset)
+    }
+
+    fn comments_before(&mut self, offset: usize) -> Vec<String> {
+        let mut comments = Vec::new();
+
+        while let Some((_, span)) = self.comments.front() {
+            if span.begin.offset >= offset {
+                break;
+            }
+
+            comments.extend(self.comments.pop_front().map(|(c, _)| c));
+        }
+
+        comments
+    }
+
+    fn inline_comment(&mut self, line: usize) -> Option<String> {
+        let (_, span) = self.comments.front()?;
+
+        let next = self
+            .lexemes
+            .iter()
+            .find(|l| l.kind != LexemeKind::Comma)
+            .map_or(usize::MAX, |l| l.span.begin.offset);
+
+        if span.begin.line == line && span.begin.offset < next {
+            self.comments.pop_front().map(|(c, _)| c)
+        } else {
+            None
+        }
+    }
+
+    fn peek(&self) -> Option<&LexemeKind> {
+        self.lexemes.front().map(|l| &l.kind)
+    }
+
+    fn next(&mut self) -> Result<'a, Lexeme> {
+        match self.lexemes.pop_front() {
+            Some(lexeme) => {
+                self.prev = self.last;
+                self.last = lexeme.span;
+
+                if lexeme.span.begin.offset >= self.checked {
+                    self.checked = lexeme.span.end.offset;
+
+                    if let LexemeKind::String(_) = lexeme.kind
+                        && let Some(span) = unknown_escape(self.src, lexeme.span)
+                    {
+                        self.lenient(ParsingError::UnknownEscape, span)?;
+                    }
+                }
+
+                Ok(lexeme)
+            }
+            None => Err(self.error(
+                ParsingError::UnexpectedEof,
+                Span::new(self.last.end, self.last.end),
+            )),
+        }
+    }
+
+    // notes a token that would have been accepted at the front of the stream, so that an error
+    // there lists every alternative that was tried rather than only the last
+    fn expecting(&mut self, expected: Expected) {
+        let offset = self.front_offset();
+
+        if self.expected.0 != offset {
+            self.expected = (offset, ExpectedSet::new());
+        }
+
+        self.expected.1.insert(expected);
+    }
+
+    fn check(&mut self, kind: &LexemeKind) -> bool {
+        self.expecting(Expected::Token(kind.token()));
+        self.peek() == Some(kind)
+    }
+
+    fn unexpected(&mut self, lexeme: Lexeme, expected: Expected) -> Error<'a> {
+        let tried = match self.expected {
+            (offset, tried) if offset == lexeme.span.begin.offset => tried,
+            _ => ExpectedSet::new(),
+        };
+        let error = self.error(
+            ParsingError::UnexpectedToken {
+                found: lexeme.kind.token(),
+                expected: tried.with(expected),
+            },
+            lexeme.span,
+        );
+        self.last = self.prev;
+        self.lexemes.push_front(lexeme);
+        error
+    }
+
+    fn expect(&mut self, kind: LexemeKind) -> Result<'a, Span> {
+        self.expecting(Expected::Token(kind.token()));
+        let lexeme = self.next()?;
+
+        if lexeme.kind == kind {
+            Ok(lexeme.span)
+        } else {
+            Err(self.unexpected(lexeme, Expected::Token(kind.token())))
+        }
+    }
+
+    fn parse_key(&mut self) -> Result<'a, (String, Span)> {
+        let lexeme = self.next()?;
+
+        match lexeme.kind {
+            LexemeKind::Ident(key) | LexemeKind::String(key) => Ok((key, lexeme.span)),
+            LexemeKind::Integer(_) | LexemeKind::Float(_) | LexemeKind::Bool(_) => {
+                Err(self.error(ParsingError::InvalidKey, lexeme.span))
+            }
+            kind => Err(self.unexpected(Lexeme::new(kind, lexeme.span), Expected::Key)),
+        }
+    }
+
+    fn parse_key_path(&mut self) -> Result<'a, Vec<(String, Span)>> {
+        let mut path = Vec::new();
+        path.push(self.parse_key()?);
+
+        while self.check(&LexemeKind::Dot) {
+            self.next()?;
+            path.push(self.parse_key()?);
+        }
+
+        Ok(path)
+    }
+
+    fn parse_header(&mut self) -> Result<'a, (Vec<(String, Span)>, bool)> {
+        let open = self.expect(LexemeKind::LBrack)?;
+
+        self.delimited(open, |p| {
+            let array = p.check(&LexemeKind::LBrack);
+
+            if array {
+                p.next()?;
+            }
+
+            let path = p.parse_key_path()?;
+            p.expect(LexemeKind::RBrack)?;
+
+            if array {
+                p.expect(LexemeKind::RBrack)?;
+            }
+
+            Ok((path, array))
+        })
+    }
+
+    fn descend<'t>(
+        &self,
+        mut table: &'t mut Table,
+        path: &[(String, Span)],
+    ) -> Result<'a, &'t mut Table> {
+        for (key, span) in path {
+            let first = match table.get_entry(key) {
+                Some(entry) => entry.key_span,
+                None => {
+                    table.insert(
+                        key.clone(),
+                        Entry::new(Value::Table(Table::new()), *span, *span),
+                    );
+                    *span
+                }
+            };
+
+            table = match table.get_mut(key) {
+                Some(Value::Table(table)) => table,
+                Some(Value::Array(array)) => match array.last_mut() {
+                    Some(Value::Table(table)) => table,
+                    _ => return Err(self.conflict(first, *span)),
+                },
+                _ => return Err(self.conflict(first, *span)),
+            };
+        }
+
+        Ok(table)
+    }
+
+    fn header_entry<'t>(
+        &self,
+        root: &'t mut Table,
+        path: &[(String, Span)],
+    ) -> Option<&'t mut Entry> {
+        let ((key, _), parent) = path.split_last()?;
+        self.descend(root, parent).ok()?.get_entry_mut(key)
+    }
+
+    fn append(&self, root: &mut Table, path: &[(String, Span)]) -> Result<'a, ()> {
+        let Some(((key, span), parent)) = path.split_last() else {
+            return Ok(());
+        };
+        let table = self.descend(root, parent)?;
+
+        if !table.contains_key(key) {
+            table.insert(
+                key.clone(),
+                Entry::new(Value::Array(Vec::new()), *span, *span),
+            );
+        }
+
+        match table.get_entry_mut(key) {
+            Some(Entry {
+                value: Value::Array(array),
+                ..
+            }) => {
+                array.push(Value::Table(Table::new()));
+                Ok(())
+            }
+            Some(entry) => Err(self.conflict(entry.key_span, *span)),
+            None => Ok(()),
+        }
+    }
+
+    fn parse_entry(&mut self, table: &mut Table) -> Result<'a, ()> {
+        let leading = self.comments_before(self.front_offset());
+        let mut path = self.parse_key_path()?;
+        self.expect(LexemeKind::Equal)?;
+        let (value, span) = self.parse_value(table)?;
+
+        let Some((key, key_span)) = path.pop() else {
+            return Ok(());
+        };
+        let mut entry = Entry::new(value, key_span, span);
+        entry.comments = Comments {
+            leading,
+            trailing: self.comments_before(span.end.offset),
+            inline: self.inline_comment(span.end.line),
+        };
+
+        let table = self.descend(table, &path)?;
+        self.insert(table, key, entry)
+    }
+
+    fn insert(&mut self, table: &mut Table, key: String, entry: Entry) -> Result<'a, ()> {
+        let Some(existing) = table.get_entry_mut(&key) else {
+            table.insert(key, entry);
+            return Ok(());
+        };
+        let duplicate = self.error(
+            ParsingError::DuplicateKey {
+                first: existing.key_span,
+            },
+            entry.key_span,
+        );
+        match self.options.duplicates {
+            DuplicatePolicy::Error => Err(duplicate),
+            DuplicatePolicy::LastWins => {
+                self.options.lints.report(&mut self.warnings, duplicate)?;
+                *existing = entry;
+                Ok(())
+            }
+            DuplicatePolicy::FirstWins => self.options.lints.report(&mut self.warnings, duplicate),
+            DuplicatePolicy::Merge => match (&mut existing.value, entry.value) {
+                (Value::Table(old), Value::Table(new)) => {
+                    for (key, entry) in new {
+                        self.insert(old, key, entry)?;
+                    }
+
+                    Ok(())
+                }
+                _ => Err(duplicate),
+            },
+        }
+    }
+
+    fn parse_value(&mut self, scope: &Table) -> Result<'a, (Value, Span)> {
+        let mut left = self.parse_term(scope)?;
+
+        loop {
+            let operator = match self.lexemes.front() {
+                Some(l) if l.kind == LexemeKind::Plus => Operator::Add,
+                Some(l) if l.kind == LexemeKind::Minus => Operator::Sub,
+                // a signed literal is a value of its own, so `[1 -2]` is missing a comma rather
+                // than subtracting
+                _ => break,
+            };
+
+            self.next()?;
+            let right = self.parse_term(scope)?;
+            left = self.apply(operator, left, right)?;
+        }
+
+        Ok(left)
+    }
+
+    fn parse_term(&mut self, scope: &Table) -> Result<'a, (Value, Span)> {
+        let mut left = self.parse_operand(scope)?;
+
+        loop {
+            let operator = match self.peek() {
+                Some(LexemeKind::Star) => Operator::Mul,
+                Some(LexemeKind::Slash) => Operator::Div,
+                _ => break,
+            };
+
+            self.next()?;
+            let right = self.parse_operand(scope)?;
+            left = self.apply(operator, left, right)?;
+        }
+
+        Ok(left)
+    }
+
+    fn parse_reference(&mut self, scope: &Table) -> Result<'a, (Value, Span)> {
+        let path = self.parse_key_path()?;
+        let span = Span::new(
+            path.first().map_or(self.last, |(_, s)| *s).begin,
+            self.last.end,
+        );
+        let mut value: Option<&Value> = None;
+
+        for (i, (key, _)) in path.iter().enumerate() {
+            value = match (i, value) {
+                (0, _) => scope.get(key),
+                (_, Some(Value::Table(table))) => table.get(key),
+                _ => None,
+            };
+        }
+
+        match value {
+            Some(value) => Ok((value.clone(), span)),
+            None => Err(self.error(ParsingError::UnknownReference, span)),
+        }
+    }
+
+    fn anchor_name(&mut self) -> Result<'a, (String, Span)> {
+        let lexeme = self.next()?;
+
+        match lexeme.kind {
+            LexemeKind::Ident(name) => Ok((name, lexeme.span)),
+            kind => Err(self.unexpected(
+                Lexeme::new(kind, lexeme.span),
+                Expected::Token(TokenKind::Ident),
+            )),
+        }
+    }
+
+    fn parse_anchor(&mut self, scope: &Table) -> Result<'a, (Value, Span)> {
+        let (name, span) = self.anchor_name()?;
+
+        if let Some((_, first)) = self.anchors.get(&name) {
+            return Err(self.error(ParsingError::DuplicateAnchor { first: *first }, span));
+        }
+
+        let (value, value_span) = self.nested(span, |p| p.parse_operand(scope))?;
+        self.anchors.insert(name, (value.clone(), span));
+        Ok((value, value_span))
+    }
+
+    fn apply(
+        &self,
+        operator: Operator,
+        (left, left_span): (Value, Span),
+        (right, right_span): (Value, Span),
+    ) -> Result<'a, (Value, Span)> {
+        let span = Span::new(left_span.begin, right_span.end);
+        let invalid = self.error(
+            ParsingError::InvalidOperands { left: left_span },
+            right_span,
+        );
+
+        let value = match (left, right) {
+            (Value::Integer(a), Value::Integer(b)) => {
+                if operator == Operator::Div && b == 0 {
+                    return Err(self.error(ParsingError::DivisionByZero, right_span));
+                }
+
+                Value::Integer(
+                    match operator {
+                        Operator::Add => a.checked_add(b),
+                        Operator::Sub => a.checked_sub(b),
+                        Operator::Mul => a.checked_mul(b),
+                        Operator::Div => a.checked_div(b),
+                    }
+                    .ok_or_else(|| self.error(ParsingError::ArithmeticOverflow, span))?,
+                )
+            }
+            (
+                a @ (Value::Integer(_) | Value::Float(_)),
+                b @ (Value::Integer(_) | Value::Float(_)),
+            ) => {
+                let number = |value| match value {
+                    Value::Integer(i) => i as f64,
+                    Value::Float(f) => f,
+                    _ => 0.0,
+                };
+                let (a, b) = (number(a), number(b));
+
+                Value::Float(match operator {
+                    Operator::Add => a + b,
+                    Operator::Sub => a - b,
+                    Operator::Mul => a * b,
+                    Operator::Div => a / b,
+                })
+            }
+            (Value::String(mut a), Value::String(b)) if operator == Operator::Add => {
+                a.push_str(&b);
+                Value::String(a)
+            }
+            (Value::Bytes(mut a), Value::Bytes(b)) if operator == Operator::Add => {
+                a.extend(b);
+                Value::Bytes(a)
+            }
+            (Value::Array(mut a), Value::Array(b)) if operator == Operator::Add => {
+                a.extend(b);
+                Value::Array(a)
+            }
+            _ => return Err(invalid),
+        };
+
+        Ok((value, span))
+    }
+
+    fn parse_operand(&mut self, scope: &Table) -> Result<'a, (Value, Span)> {
+        let lexeme = self.next()?;
+
+        Ok((
+            match lexeme.kind {
+                LexemeKind::String(s) => Value::String(s),
+                LexemeKind::Bytes(b) => Value::Bytes(b),
+                LexemeKind::Integer(i) => Value::Integer(i),
+                LexemeKind::Float(f) => Value::Float(f),
+                LexemeKind::Bool(b) => Value::Bool(b),
+                LexemeKind::LBrack => return self.parse_array(lexeme.span, scope),
+                LexemeKind::LBrace => return self.parse_table(lexeme.span),
+                LexemeKind::Ampersand => return self.parse_anchor(scope),
+                LexemeKind::Star => {
+                    let (name, span) = self.anchor_name()?;
+
+                    return match self.anchors.get(&name) {
+                        Some((value, _)) => {
+                            Ok((value.clone(), Span::new(lexeme.span.begin, span.end)))
+                        }
+                        None => Err(self.error(ParsingError::UnknownAnchor, span)),
+                    };
+                }
+                LexemeKind::Ident(_) if lexeme.span.begin.line == self.prev.end.line => {
+                    self.last = self.prev;
+                    self.lexemes.push_front(lexeme);
+                    return self.parse_reference(scope);
+                }
+                kind => {
+                    return Err(self.unexpected(Lexeme::new(kind, lexeme.span), Expected::Value));
+                }
+            },
+            lexeme.span,
+        ))
+    }
+
+    fn delimited<T>(
+        &mut self,
+        open: Span,
+        f: impl FnOnce(&mut Self) -> Result<'a, T>,
+    ) -> Result<'a, T> {
+        let result = f(self);
+        result.map_err(|e| self.unclosed(e, open))
+    }
+
+    fn nested<T>(
+        &mut self,
+        open: Span,
+        f: impl FnOnce(&mut Self) -> Result<'a, T>,
+    ) -> Result<'a, T> {
+        if self.depth >= self.options.max_depth {
+            return Err(self.too_deep(self.depth, open));
+        }
+
+        self.depth += 1;
+        let result = self.delimited(open, f);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_array(&mut self, begin: Span, scope: &Table) -> Result<'a, (Value, Span)> {
+        self.nested(begin, |p| {
+            let mut array = Vec::new();
+            let mut first = None;
+
+            while !p.check(&LexemeKind::RBrack) {
+                let (value, span) = p.parse_value(scope)?;
+                p.element(&mut first, value.kind(), span)?;
+                array.push(value);
+
+                if !p.check(&LexemeKind::Comma) {
+                    break;
+                }
+
+                let comma = p.next()?.span;
+
+                if p.check(&LexemeKind::RBrack) {
+                    p.lenient(ParsingError::TrailingComma, comma)?;
+                }
+            }
+
+            let end = p.expect(LexemeKind::RBrack)?;
+            Ok((Value::Array(array), Span::new(begin.begin, end.end)))
+        })
+    }
+
+    fn parse_table(&mut self, begin: Span) -> Result<'a, (Value, Span)> {
+        self.nested(begin, |p| {
+            let mut table = Table::new();
+
+            while !p.check(&LexemeKind::RBrace) {
+                p.parse_entry(&mut table)?;
+
+                if !p.check(&LexemeKind::Comma) {
+                    break;
+                }
+
+                let comma = p.next()?.span;
+
+                if p.check(&LexemeKind::RBrace) {
+                    p.lenient(ParsingError::TrailingComma, comma)?;
+                }
+            }
+
+            if let Some((_, entry)) = table.iter_mut().last() {
+                let trailing = p.comments_before(p.front_offset());
+                entry.comments.trailing.extend(trailing);
+            }
+
+            let end = p.expect(LexemeKind::RBrace)?;
+            Ok((Value::Table(table), Span::new(begin.begin, end.end)))
+        })
+    }
+}
+
+fn unknown_escape(src: &Source, span: Span) -> Option<Span> {
+    if src.slice(span).starts_with('r') {
+        return None;
+    }
+
+    let mut location = span.begin;
+    let mut chars = src.slice(span).chars();
+
+    while let Some(chr) = chars.next() {
+        let begin = location;
+        location.advance(chr);
+
+        if chr != '\\' {
+            continue;
+        }
+
+        let escaped = chars.next()?;
+        location.advance(escaped);
+
+        if !matches!(escaped, 'n' | 't' | 'r' | '0' | '\\' | '"' | 'u') {
+            return Some(Span::new(begin, location));
+        }
+    }
+
+    None
+}
+
+// whether there's an identifier where a value could be, which is every reference and `extends`
+// target and, since what follows a comma isn't told apart, every key after the first in an inline
+// table too
+fn references(lexemes: &LexemeStream) -> bool {
+    let mut prev: Option<&Lexeme> = None;
+    // whether the last `[` opened an array rather than a header
+    let mut array = false;
+
+    lexemes.iter().any(|lexeme| {
+        let after = prev.filter(|p| p.span.end.line == lexeme.span.begin.line);
+        let value = prev.is_some_and(|p| {
+            matches!(
+                p.kind,
+                LexemeKind::Equal
+                    | LexemeKind::Comma
+                    | LexemeKind::Plus
+                    | LexemeKind::Minus
+                    | LexemeKind::Star
+                    | LexemeKind::Slash
+            ) || (p.kind == LexemeKind::LBrack && array)
+        });
+        let found = matches!(lexeme.kind, LexemeKind::Ident(_)) && after.is_some() && value;
+
+        if lexeme.kind == LexemeKind::LBrack {
+            array = value;
+        }
+
+        prev = Some(lexeme);
+        found
+    })
+}
+
+// the events adding whatever `resolved` has that `read` doesn't
+fn added(read: &Table, resolved: &Table, path: &mut KeyPath, events: &mut VecDeque<Event>) {
+    for (key, entry) in resolved.iter() {
+        path.push(PathSegment::Key(key.clone()));
+
+        match (read.get(key), &entry.value) {
+            (None, value) => {
+                let span = entry.key_span;
+                events.push_back(Event::Key {
+                    path: path.clone(),
+                    span,
+                });
+                events.push_back(Event::Scalar(value.clone(), entry.span));
+            }
+            (Some(Value::Table(read)), Value::Table(resolved)) => {
+                added(read, resolved, path, events);
+            }
+            (Some(Value::Array(read)), Value::Array(resolved)) => {
+                for (index, pair) in read.iter().zip(resolved).enumerate() {
+                    if let (Value::Table(read), Value::Table(resolved)) = pair {
+                        path.push(PathSegment::Index(index));
+                        added(read, resolved, path, events);
+                        path.pop();
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        path.pop();
+    }
+}
+
+fn key_path(path: Vec<(String, Span)>) -> KeyPath {
+    let mut key_path = KeyPath::new();
+    key_path.extend(path.into_iter().map(|(key, _)| PathSegment::Key(key)));
+    key_path
+}
+
+pub fn parse<'a>(src: &'a Source<'a>) -> Result<'a, Document> {
+    parse_with(src, ParserOptions::default())
+}
+
+pub fn parse_arena<'a>(src: &'a Source<'a>) -> Result<'a, Arena> {
+    parse_arena_with(src, ParserOptions::default())
+}
+
+pub fn parse_arena_with<'a>(src: &'a Source<'a>, options: ParserOptions) -> Result<'a, Arena> {
+    Parser::with_options(src, lex_with(src, options.lexer())?, options).parse_arena()
+}
+
+pub fn parse_source<'a>(src: &'a Source<'a>) -> Result<'a, Document> {
+    parse(src)
+}
+
+pub fn parse_tokens<'a>(lexemes: LexemeStream, src: &'a Source<'a>) -> Result<'a, Document> {
+    Parser::new(src, lexemes).parse()
+}
+
+pub fn parse_str(file: &str, content: &str) -> core::result::Result<Document, Box<OwnedError>> {
+    let src = Source::new(file, content.into());
+    parse(&src).map_err(|e| Box::new(e.into()))
+}
+
+pub fn parse_str_with_warnings(
+    file: &str,
+    content: &str,
+) -> core::result::Result<(Document, Vec<OwnedError>), Box<OwnedError>> {
+    let src = Source::new(file, content.into());
+    let (document, warnings) =
+        parse_with_warnings(&src, ParserOptions::default()).map_err(|e| Box::new(e.into()))?;
+
+    Ok((
+        document,
+        warnings.into_iter().map(Error::into_owned).collect(),
+    ))
+}
+
+pub fn from_str(content: &str) -> core::result::Result<Document, Box<OwnedError>> {
+    parse_str("<string>", content)
+}
+
+impl core::str::FromStr for Document {
+    type Err = Box<OwnedError>;
+
+    fn from_str(content: &str) -> core::result::Result<Self, Self::Err> {
+        from_str(content)
+    }
+}
+
+pub fn parse_with<'a>(src: &'a Source<'a>, options: ParserOptions) -> Result<'a, Document> {
+    Parser::with_options(src, lex_with(src, options.lexer())?, options).parse()
+}
+
+pub fn parse_with_warnings<'a>(
+    src: &'a Source<'a>,
+    options: ParserOptions,
+) -> Result<'a, (Document, Vec<Error<'a>>)> {
+    let (lexemes, warnings) = lex_with_warnings(src, options.lexer())?;
+
+    Parser::with_options(src, lexemes, options)
+        .with_warnings(warnings)
+        .parse_with_warnings()
+}
+
+pub fn parse_with_includes<'a>(
+    src: &'a Source<'a>,
+    includes: &'a [Source<'a>],
+    options: ParserOptions,
+) -> Result<'a, Document> {
+    Parser::with_options(src, lex_with(src, options.lexer())?, options)
+        .with_includes(includes)
+        .parse()
+}
+
+pub struct Documents<'a> {
+    src: &'a Source<'a>,
+    chunks: VecDeque<LexemeStream>,
+    options: ParserOptions,
+    error: Option<Error<'a>>,
+}
+
+impl<'a> Iterator for Documents<'a> {
+    type Item = Result<'a, Document>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+
+        let chunk = self.chunks.pop_front()?;
+        Some(Parser::with_options(self.src, chunk, self.options).parse())
+    }
+}
+
+pub fn parse_documents<'a>(src: &'a Source<'a>) -> Documents<'a> {
+    parse_documents_with(src, ParserOptions::default())
+}
+
+pub fn parse_documents_with<'a>(src: &'a Source<'a>, options: ParserOptions) -> Documents<'a> {
+    let mut chunks = VecDeque::new();
+    let mut error = None;
+
+    match lex_with(src, options.lexer()) {
+        Ok(lexemes) => {
+            let mut chunk = LexemeStream::new();
+
+            for lexeme in lexemes {
+                match lexeme.kind {
+                    LexemeKind::Separator if chunk.is_empty() && chunks.is_empty() => {}
+                    LexemeKind::Separator => chunks.push_back(core::mem::take(&mut chunk)),
+                    _ => chunk.push_back(lexeme),
+                }
+            }
+
+            chunks.push_back(chunk);
+        }
+        Err(e) => error = Some(e),
+    }
+
+    Documents {
+        src,
+        chunks,
+        options,
+        error,
+    }
+}
+
+pub fn parse_recovering<'a>(src: &'a Source<'a>) -> (Document, Vec<Error<'a>>) {
+    match lex_with(src, ParserOptions::default().lexer()) {
+        Ok(lexemes) => Parser::new(src, lexemes).parse_recovering(),
+        Err(error) => (Document::default(), alloc::vec![error]),
+    }
+}
+
+pub fn parse_diagnostics<'a>(
+    src: &'a Source<'a>,
+    options: ParserOptions,
+) -> (Document, Diagnostics) {
+    match lex_with_warnings(src, options.lexer()) {
+        Ok((lexemes, warnings)) => Parser::with_options(src, lexemes, options)
+            .with_warnings(warnings)
+            .parse_diagnostics(),
+        Err(error) => (Document::default(), Diagnostics::from_iter([error])),
+    }
+}
@@ -0,0 +1,331 @@
+use crate::{diff::Change, extract::*, path::*, utils::Span, value::*};
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Add { path: KeyPath, value: Value },
+    Remove { path: KeyPath },
+    Replace { path: KeyPath, value: Value },
+    Test { path: KeyPath, value: Value },
+}
+
+impl Operation {
+    pub fn path(&self) -> &KeyPath {
+        match self {
+            Self::Add { path, .. }
+            | Self::Remove { path }
+            | Self::Replace { path, .. }
+            | Self::Test { path, .. } => path,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Add { .. } => "add",
+            Self::Remove { .. } => "remove",
+            Self::Replace { .. } => "replace",
+            Self::Test { .. } => "test",
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub operations: Vec<Operation>,
+}
+
+impl Patch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, operation: Operation) {
+        self.operations.push(operation);
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, Operation> {
+        self.operations.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+fn is_removal(change: &Change) -> bool {
+    matches!(change, Change::Removed { path, .. } if matches!(path.last(), Some(PathSegment::Index(_))))
+}
+
+fn siblings(a: &KeyPath, b: &KeyPath) -> bool {
+    a.len() == b.len() && a.segments()[..a.len() - 1] == b.segments()[..b.len() - 1]
+}
+
+impl From<&[Change]> for Patch {
+    fn from(changes: &[Change]) -> Self {
+        let mut patch = Patch::new();
+
+        // array elements are removed back to front so earlier indices stay valid
+        for run in
+            changes.chunk_by(|a, b| is_removal(a) && is_removal(b) && siblings(a.path(), b.path()))
+        {
+            let removals = is_removal(&run[0]);
+
+            for change in run
+                .iter()
+                .rev()
+                .filter(|_| removals)
+                .chain(run.iter().filter(|_| !removals))
+            {
+                match change.clone() {
+                    Change::Added { path, value, .. } => patch.push(Operation::Add { path, value }),
+                    Change::Removed { path, value, .. } => {
+                        patch.push(Operation::Test {
+                            path: path.clone(),
+                            value,
+                        });
+                        patch.push(Operation::Remove { path });
+                    }
+                    Change::Modified { path, old, new, .. } => {
+                        patch.push(Operation::Test {
+                            path: path.clone(),
+                            value: old,
+                        });
+                        patch.push(Operation::Replace { path, value: new });
+                    }
+                }
+            }
+        }
+
+        patch
+    }
+}
+
+impl From<Vec<Change>> for Patch {
+    fn from(changes: Vec<Change>) -> Self {
+        Self::from(changes.as_slice())
+    }
+}
+
+impl FromIterator<Operation> for Patch {
+    fn from_iter<I: IntoIterator<Item = Operation>>(iter: I) -> Self {
+        Self {
+            operations: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for Patch {
+    type Item = Operation;
+    type IntoIter = alloc::vec::IntoIter<Operation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.operations.into_iter()
+    }
+}
+
+impl From<&Operation> for Value {
+    fn from(operation: &Operation) -> Self {
+        let mut table = Table::new();
+        table.set("op", operation.name().into());
+        table.set("path", format!("{}", operation.path()).into());
+
+        if let Operation::Add { value, .. }
+        | Operation::Replace { value, .. }
+        | Operation::Test { value, .. } = operation
+        {
+            table.set("value", value.clone());
+        }
+
+        Value::Table(table)
+    }
+}
+
+impl From<&Patch> for Value {
+    fn from(patch: &Patch) -> Self {
+        Value::Array(patch.iter().map(Value::from).collect())
+    }
+}
+
+impl From<&Patch> for Document {
+    fn from(patch: &Patch) -> Self {
+        let mut root = Table::new();
+        root.set("operations", patch.into());
+        Document::new(root)
+    }
+}
+
+impl FromValue for Operation {
+    fn from_value(cursor: &Cursor<'_>) -> core::result::Result<Self, ExtractError> {
+        let path = cursor.field::<String>("path")?;
+        let path = KeyPath::parse(&path).map_err(|_| cursor.error(ExtractErrorKind::Invalid))?;
+
+        match cursor.field::<String>("op")?.as_str() {
+            "add" => Ok(Self::Add {
+                path,
+                value: cursor.field("value")?,
+            }),
+            "remove" => Ok(Self::Remove { path }),
+            "replace" => Ok(Self::Replace {
+                path,
+                value: cursor.field("value")?,
+            }),
+            "test" => Ok(Self::Test {
+                path,
+                value: cursor.field("value")?,
+            }),
+            _ => Err(cursor.error(ExtractErrorKind::Invalid)),
+        }
+    }
+}
+
+impl FromValue for Patch {
+    fn from_value(cursor: &Cursor<'_>) -> core::result::Result<Self, ExtractError> {
+        match cursor.value() {
+            Some(_) => Vec::from_value(cursor).map(|operations| Self { operations }),
+            None => cursor
+                .field("operations")
+                .map(|operations| Self { operations }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchErrorKind {
+    AlreadyExists,
+    Missing,
+    Mismatched,
+    Path(PathError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchError {
+    pub index: usize,
+    pub path: KeyPath,
+    pub kind: PatchErrorKind,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{} #{}] ", self.path, self.index)?;
+
+        match self.kind {
+            PatchErrorKind::AlreadyExists => {
+                write!(f, "encountered existing value during patching")
+            }
+            PatchErrorKind::Missing => write!(f, "encountered missing value during patching"),
+            PatchErrorKind::Mismatched => write!(f, "encountered mismatched value during patching"),
+            PatchErrorKind::Path(error) => write!(f, "encountered {} during patching", error),
+        }
+    }
+}
+
+impl core::error::Error for PatchError {}
+
+enum Parent<'t> {
+    Table(&'t mut Table),
+    Array(&'t mut Vec<Value>),
+}
+
+fn parent<'t>(
+    root: &'t mut Table,
+    path: &[PathSegment],
+) -> core::result::Result<Parent<'t>, PatchErrorKind> {
+    if path.is_empty() {
+        return Ok(Parent::Table(root));
+    }
+
+    match root.lookup_mut(&path.iter().cloned().collect()) {
+        Some(Value::Table(table)) => Ok(Parent::Table(table)),
+        Some(Value::Array(array)) => Ok(Parent::Array(array)),
+        Some(_) => Err(PatchErrorKind::Path(PathError::NotATable)),
+        None => Err(PatchErrorKind::Missing),
+    }
+}
+
+fn test(found: Option<&Value>, expected: &Value) -> core::result::Result<(), PatchErrorKind> {
+    match found {
+        Some(found) if found == expected => Ok(()),
+        Some(_) => Err(PatchErrorKind::Mismatched),
+        None => Err(PatchErrorKind::Missing),
+    }
+}
+
+fn apply(root: &mut Table, operation: &Operation) -> core::result::Result<(), PatchErrorKind> {
+    let Some((last, path)) = operation.path().segments().split_last() else {
+        return Err(PatchErrorKind::Path(PathError::Malformed));
+    };
+
+    match (parent(root, path)?, last) {
+        (Parent::Table(table), PathSegment::Key(key)) => match operation {
+            Operation::Add { value, .. } => match table.contains_key(key) {
+                true => Err(PatchErrorKind::AlreadyExists),
+                false => {
+                    table.insert(
+                        key.clone(),
+                        Entry::new(value.clone(), Span::default(), Span::default()),
+                    );
+                    Ok(())
+                }
+            },
+            Operation::Remove { .. } => table.remove(key).map(drop).ok_or(PatchErrorKind::Missing),
+            Operation::Replace { value, .. } => match table.get_mut(key) {
+                Some(existing) => {
+                    *existing = value.clone();
+                    Ok(())
+                }
+                None => Err(PatchErrorKind::Missing),
+            },
+            Operation::Test { value, .. } => test(table.get(key), value),
+        },
+        (Parent::Array(array), PathSegment::Index(index)) => match operation {
+            Operation::Add { value, .. } => match *index <= array.len() {
+                true => {
+                    array.insert(*index, value.clone());
+                    Ok(())
+                }
+                false => Err(PatchErrorKind::Path(PathError::OutOfBounds)),
+            },
+            Operation::Remove { .. } => match *index < array.len() {
+                true => {
+                    array.remove(*index);
+                    Ok(())
+                }
+                false => Err(PatchErrorKind::Missing),
+            },
+            Operation::Replace { value, .. } => match array.get_mut(*index) {
+                Some(existing) => {
+                    *existing = value.clone();
+                    Ok(())
+                }
+                None => Err(PatchErrorKind::Missing),
+            },
+            Operation::Test { value, .. } => test(array.get(*index), value),
+        },
+        (Parent::Table(_), PathSegment::Index(_)) => {
+            Err(PatchErrorKind::Path(PathError::NotAnArray))
+        }
+        (Parent::Array(_), PathSegment::Key(_)) => Err(PatchErrorKind::Path(PathError::NotATable)),
+    }
+}
+
+impl Document {
+    pub fn apply(&mut self, patch: &Patch) -> core::result::Result<(), PatchError> {
+        let mut root = self.root.clone();
+
+        for (index, operation) in patch.iter().enumerate() {
+            apply(&mut root, operation).map_err(|kind| PatchError {
+                index,
+                path: operation.path().clone(),
+                kind,
+            })?;
+        }
+
+        self.root = root;
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+use alloc::{
+    string::String,
+    vec::{self, Vec},
+};
+use core::{fmt, slice, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+impl PathSegment {
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            Self::Key(key) => Some(key),
+            Self::Index(_) => None,
+        }
+    }
+
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            Self::Key(_) => None,
+            Self::Index(index) => Some(*index),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathError {
+    Malformed,
+    Missing,
+    NotATable,
+    NotAnArray,
+    OutOfBounds,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Malformed => "malformed key path",
+                Self::Missing => "key path does not exist",
+                Self::NotATable => "key path traverses a value that is not a table",
+                Self::NotAnArray => "key path indexes a value that is not an array",
+                Self::OutOfBounds => "key path index is out of bounds",
+            }
+        )
+    }
+}
+
+impl core::error::Error for PathError {}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KeyPath {
+    segments: Vec<PathSegment>,
+}
+
+impl KeyPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(path: &str) -> Result<Self, PathError> {
+        let mut segments = Vec::new();
+        let mut chars = path.chars().peekable();
+
+        loop {
+            match chars.peek() {
+                Some('[') if segments.is_empty() => {}
+                Some('"') => {
+                    chars.next();
+                    let mut key = String::new();
+
+                    loop {
+                        match chars.next().ok_or(PathError::Malformed)? {
+                            '"' => break,
+                            '\\' => match chars.next() {
+                                Some(chr @ ('"' | '\\')) => key.push(chr),
+                                _ => return Err(PathError::Malformed),
+                            },
+                            chr => key.push(chr),
+                        }
+                    }
+
+                    segments.push(PathSegment::Key(key));
+                }
+                _ => {
+                    let mut key = String::new();
+
+                    while let Some(chr) = chars.next_if(|c| !matches!(c, '.' | '[' | ']' | '"')) {
+                        key.push(chr);
+                    }
+
+                    if key.is_empty() {
+                        return Err(PathError::Malformed);
+                    }
+
+                    segments.push(PathSegment::Key(key));
+                }
+            }
+
+            while chars.next_if_eq(&'[').is_some() {
+                let mut index = String::new();
+
+                while let Some(chr) = chars.next_if(char::is_ascii_digit) {
+                    index.push(chr);
+                }
+
+                if chars.next() != Some(']') {
+                    return Err(PathError::Malformed);
+                }
+
+                segments.push(PathSegment::Index(
+                    index.parse().map_err(|_| PathError::Malformed)?,
+                ));
+            }
+
+            match chars.next() {
+                None => return Ok(Self { segments }),
+                Some('.') => {}
+                Some(_) => return Err(PathError::Malformed),
+            }
+        }
+    }
+
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, PathSegment> {
+        self.segments.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn push(&mut self, segment: PathSegment) {
+        self.segments.push(segment);
+    }
+
+    pub fn pop(&mut self) -> Option<PathSegment> {
+        self.segments.pop()
+    }
+
+    pub fn last(&self) -> Option<&PathSegment> {
+        self.segments.last()
+    }
+
+    pub fn starts_with(&self, prefix: &KeyPath) -> bool {
+        self.segments.starts_with(&prefix.segments)
+    }
+}
+
+impl FromStr for KeyPath {
+    type Err = PathError;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        Self::parse(path)
+    }
+}
+
+impl Extend<PathSegment> for KeyPath {
+    fn extend<I: IntoIterator<Item = PathSegment>>(&mut self, iter: I) {
+        self.segments.extend(iter);
+    }
+}
+
+impl FromIterator<PathSegment> for KeyPath {
+    fn from_iter<I: IntoIterator<Item = PathSegment>>(iter: I) -> Self {
+        Self {
+            segments: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for KeyPath {
+    type Item = PathSegment;
+    type IntoIter = vec::IntoIter<PathSegment>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.segments.into_iter()
+    }
+}
+
+impl<'p> IntoIterator for &'p KeyPath {
+    type Item = &'p PathSegment;
+    type IntoIter = slice::Iter<'p, PathSegment>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Display for KeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) => {
+                    if i > 0 {
+                        write!(f, ".")?;
+                    }
+
+                    if key.is_empty() || key.contains(['.', '[', ']', '"', '\\']) {
+                        write!(f, "\"")?;
+
+                        for chr in key.chars() {
+                            if matches!(chr, '"' | '\\') {
+                                write!(f, "\\")?;
+                            }
+
+                            write!(f, "{}", chr)?;
+                        }
+
+                        write!(f, "\"")?;
+                    } else {
+                        write!(f, "{}", key)?;
+                    }
+                }
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub trait ToKeyPath {
+    fn to_key_path(&self) -> Result<KeyPath, PathError>;
+}
+
+impl ToKeyPath for str {
+    fn to_key_path(&self) -> Result<KeyPath, PathError> {
+        KeyPath::parse(self)
+    }
+}
+
+impl ToKeyPath for String {
+    fn to_key_path(&self) -> Result<KeyPath, PathError> {
+        KeyPath::parse(self)
+    }
+}
+
+impl ToKeyPath for KeyPath {
+    fn to_key_path(&self) -> Result<KeyPath, PathError> {
+        Ok(self.clone())
+    }
+}
+
+impl<T: ToKeyPath + ?Sized> ToKeyPath for &T {
+    fn to_key_path(&self) -> Result<KeyPath, PathError> {
+        (**self).to_key_path()
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{
+    emit::{self, ArrayLayout, EmitOptions, QuoteStyle, TableStyle},
+    lex::normalize_identifier,
+    value::*,
+};
+use ::proptest::{
+    collection::vec,
+    option,
+    prelude::*,
+    sample::{Index, select},
+};
+use alloc::{string::String, vec::Vec};
+
+// nesting is capped so that larger cases grow documents wider rather than deeper
+const MAX_DEPTH: u32 = 4;
+const MAX_LEN: usize = 8;
+
+// edits that break the structure of the source rather than just its contents
+const STRUCTURAL: &[char] = &['=', ',', '.', '[', ']', '{', '}', '"', '#', '\n'];
+
+fn text() -> impl Strategy<Value = String> {
+    vec(any::<char>(), 0..16).prop_map(String::from_iter)
+}
+
+// pcf has no empty keys, and bare keys are normalized when they're read back
+pub fn key() -> impl Strategy<Value = String> {
+    text()
+        .prop_map(normalize_identifier)
+        .prop_filter("pcf has no empty keys", |key| !key.is_empty())
+}
+
+fn entries(value: impl Strategy<Value = Value>) -> impl Strategy<Value = Table> {
+    vec((key(), value), 0..MAX_LEN).prop_map(|entries| {
+        let mut table = Table::new();
+
+        for (key, value) in entries {
+            table.set(&key, value);
+        }
+
+        table
+    })
+}
+
+pub fn value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        text().prop_map(Value::String),
+        vec(any::<u8>(), 0..16).prop_map(Value::Bytes),
+        any::<i64>().prop_map(Value::Integer),
+        any::<f64>().prop_map(|float| Value::Float(emit::round_trip_float(float))),
+        any::<bool>().prop_map(Value::Bool),
+    ];
+
+    leaf.prop_recursive(MAX_DEPTH, 64, MAX_LEN as u32, |inner| {
+        prop_oneof![
+            vec(inner.clone(), 0..MAX_LEN).prop_map(Value::Array),
+            entries(inner).prop_map(Value::Table),
+        ]
+    })
+}
+
+pub fn table() -> impl Strategy<Value = Table> {
+    entries(value())
+}
+
+// comments end at the line break, so they are generated without any
+fn comment() -> impl Strategy<Value = String> {
+    text().prop_map(|comment| comment.replace(['\n', '\r'], " "))
+}
+
+pub fn document() -> impl Strategy<Value = Document> {
+    (table(), vec(comment(), 0..4)).prop_map(|(root, comments)| {
+        let mut document = Document::new(root);
+        document.comments = comments;
+        document
+    })
+}
+
+pub fn emit_options() -> impl Strategy<Value = EmitOptions> {
+    (
+        prop_oneof![
+            Just(EmitOptions::new()),
+            Just(EmitOptions::minified()),
+            Just(EmitOptions::canonical()),
+        ],
+        select(&[TableStyle::Headers, TableStyle::Braces][..]),
+        select(&[ArrayLayout::Inline, ArrayLayout::Multiline][..]),
+        select(
+            &[
+                QuoteStyle::Plain,
+                QuoteStyle::Raw,
+                QuoteStyle::Multiline,
+                QuoteStyle::Auto,
+            ][..],
+        ),
+        any::<(bool, bool)>(),
+        option::of(1..120_usize),
+    )
+        .prop_map(
+            |(options, tables, arrays, quotes, (trailing_commas, align), max_width)| {
+                let options = options
+                    .tables(tables)
+                    .arrays(arrays)
+                    .quotes(quotes)
+                    .trailing_commas(trailing_commas)
+                    .align(align);
+
+                match max_width {
+                    Some(max_width) => options.max_width(max_width),
+                    None => options,
+                }
+            },
+        )
+}
+
+// the text is a document emitted with arbitrary options, so it always parses back to the document
+pub fn source() -> impl Strategy<Value = (String, Document)> {
+    (document(), emit_options())
+        .prop_map(|(document, options)| (emit::to_string_with(&document, &options), document))
+}
+
+#[derive(Debug, Clone)]
+enum Edit {
+    Delete,
+    Insert(char),
+    Replace(char),
+    Truncate,
+}
+
+fn edit() -> impl Strategy<Value = Edit> {
+    prop_oneof![
+        Just(Edit::Delete),
+        select(STRUCTURAL).prop_map(Edit::Insert),
+        select(STRUCTURAL).prop_map(Edit::Replace),
+        Just(Edit::Truncate),
+    ]
+}
+
+// a valid source with a single edit applied, paired with the document the unedited text held;
+// the edit may or may not keep the text valid, so this is meant for checking that errors are
+// reported cleanly and that whatever does parse still resembles the original
+pub fn near_valid_source() -> impl Strategy<Value = (String, Document)> {
+    (source(), any::<Index>(), edit()).prop_map(|((text, document), index, edit)| {
+        let mut chars: Vec<char> = text.chars().collect();
+        let at = index.index(chars.len() + 1);
+
+        match edit {
+            Edit::Delete if at < chars.len() => {
+                chars.remove(at);
+            }
+            Edit::Replace(chr) if at < chars.len() => chars[at] = chr,
+            Edit::Insert(chr) | Edit::Replace(chr) => chars.insert(at, chr),
+            Edit::Truncate | Edit::Delete => chars.truncate(at),
+        }
+
+        (String::from_iter(chars), document)
+    })
+}
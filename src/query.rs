@@ -0,0 +1,132 @@
+use crate::{path::*, value::*};
+use alloc::{string::String, vec::Vec};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Pattern {
+    Segment(PathSegment),
+    Any,
+    Recursive,
+}
+
+impl Pattern {
+    fn matches(&self, segment: &PathSegment) -> bool {
+        match self {
+            Self::Segment(expected) => expected == segment,
+            Self::Any | Self::Recursive => true,
+        }
+    }
+}
+
+fn patterns(query: &str) -> Option<Vec<Pattern>> {
+    let mut pieces = Vec::new();
+    let mut piece = String::new();
+    let mut quoted = false;
+    let mut chars = query.chars();
+
+    while let Some(chr) = chars.next() {
+        match chr {
+            '.' if !quoted => pieces.push(core::mem::take(&mut piece)),
+            '\\' if quoted => {
+                piece.push(chr);
+                piece.extend(chars.next());
+            }
+            '"' => {
+                quoted = !quoted;
+                piece.push(chr);
+            }
+            _ => piece.push(chr),
+        }
+    }
+
+    pieces.push(piece);
+
+    let mut patterns = Vec::new();
+
+    for piece in pieces {
+        match piece.as_str() {
+            "**" if patterns.last() == Some(&Pattern::Recursive) => {}
+            "**" => patterns.push(Pattern::Recursive),
+            "*" => patterns.push(Pattern::Any),
+            _ => patterns.extend(
+                KeyPath::parse(&piece)
+                    .ok()?
+                    .into_iter()
+                    .map(Pattern::Segment),
+            ),
+        }
+    }
+
+    Some(patterns)
+}
+
+#[derive(Clone, Copy)]
+enum Node<'v> {
+    Table(&'v Table),
+    Value(&'v Value),
+}
+
+fn children(node: Node<'_>) -> Vec<(PathSegment, &Value)> {
+    match node {
+        Node::Table(table) | Node::Value(Value::Table(table)) => table
+            .iter()
+            .map(|(key, entry)| (PathSegment::Key(key.clone()), &entry.value))
+            .collect(),
+        Node::Value(Value::Array(array)) => array
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (PathSegment::Index(i), value))
+            .collect(),
+        Node::Value(_) => Vec::new(),
+    }
+}
+
+fn visit<'v>(
+    patterns: &[Pattern],
+    node: Node<'v>,
+    path: &mut KeyPath,
+    matches: &mut Vec<(KeyPath, &'v Value)>,
+) {
+    let Some((pattern, rest)) = patterns.split_first() else {
+        if let Node::Value(value) = node {
+            matches.push((path.clone(), value));
+        }
+
+        return;
+    };
+
+    if *pattern == Pattern::Recursive {
+        visit(rest, node, path, matches);
+    }
+
+    for (segment, child) in children(node) {
+        if !pattern.matches(&segment) {
+            continue;
+        }
+
+        path.push(segment);
+
+        match pattern {
+            Pattern::Recursive => visit(patterns, Node::Value(child), path, matches),
+            _ => visit(rest, Node::Value(child), path, matches),
+        }
+
+        path.pop();
+    }
+}
+
+impl Document {
+    pub fn query(&self, query: &str) -> Vec<(KeyPath, &Value)> {
+        let mut matches = Vec::new();
+
+        if let Some(patterns) = patterns(query) {
+            visit(
+                &patterns,
+                Node::Table(&self.root),
+                &mut KeyPath::new(),
+                &mut matches,
+            );
+        }
+
+        matches
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{merge::MergeStrategy, path::*, utils::*, value::*};
+use alloc::{collections::btree_map::BTreeMap, format, string::String, vec::Vec};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolveOptions<'o> {
+    pub profile: Option<&'o str>,
+    pub interpolate: bool,
+}
+
+impl Default for ResolveOptions<'_> {
+    fn default() -> Self {
+        Self {
+            profile: None,
+            interpolate: true,
+        }
+    }
+}
+
+struct Interpolator<'a, 'd> {
+    src: &'a Source<'a>,
+    document: &'d Document,
+    strings: BTreeMap<KeyPath, (String, Span)>,
+    resolved: BTreeMap<KeyPath, String>,
+    stack: Vec<KeyPath>,
+}
+
+impl<'a, 'd> Interpolator<'a, 'd> {
+    fn new(src: &'a Source<'a>, document: &'d Document) -> Self {
+        let mut strings = BTreeMap::new();
+
+        document.walk(|path, value, span| {
+            if let Value::String(s) = value {
+                strings.insert(path.clone(), (s.clone(), span));
+            }
+        });
+
+        Self {
+            src,
+            document,
+            strings,
+            resolved: BTreeMap::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    fn error(&self, kind: ResolvingError, span: Span) -> Error<'a> {
+        Error::resolving(kind, span, self.src)
+    }
+
+    fn resolve(&mut self, path: &KeyPath, site: Span) -> Result<'a, String> {
+        if let Some(resolved) = self.resolved.get(path) {
+            return Ok(resolved.clone());
+        }
+
+        if self.stack.iter().any(|p| p == path) {
+            return Err(self.error(ResolvingError::CyclicReference, site));
+        }
+
+        let Some((content, span)) = self.strings.get(path).cloned() else {
+            return Ok(String::new());
+        };
+
+        self.stack.push(path.clone());
+        let resolved = self.interpolate(&content, span)?;
+        self.stack.pop();
+
+        self.resolved.insert(path.clone(), resolved.clone());
+        Ok(resolved)
+    }
+
+    fn interpolate(&mut self, content: &str, span: Span) -> Result<'a, String> {
+        let mut out = String::new();
+        let mut offset = 0;
+        let mut nth = 0;
+
+        while let Some(begin) = content[offset..].find("${").map(|i| offset + i) {
+            out.push_str(&content[offset..begin]);
+
+            let site = site(self.src, span, nth);
+            nth += 1;
+
+            let Some(end) = content[begin..].find('}').map(|i| begin + i + 1) else {
+                return Err(self.error(ResolvingError::UnterminatedInterpolation, site));
+            };
+            let Ok(reference) = KeyPath::parse(content[begin + 2..end - 1].trim()) else {
+                return Err(self.error(ResolvingError::MissingReference, site));
+            };
+
+            match self.document.get(&reference) {
+                Some(Value::String(_)) => out.push_str(&self.resolve(&reference, site)?),
+                Some(Value::Integer(i)) => out.push_str(&format!("{}", i)),
+                Some(Value::Float(f)) => out.push_str(&format!("{}", f)),
+                Some(Value::Bool(b)) => out.push_str(&format!("{}", b)),
+                Some(_) => return Err(self.error(ResolvingError::InvalidReference, site)),
+                None => return Err(self.error(ResolvingError::MissingReference, site)),
+            }
+
+            offset = end;
+        }
+
+        out.push_str(&content[offset..]);
+        Ok(out)
+    }
+}
+
+fn site(src: &Source, span: Span, nth: usize) -> Span {
+    let raw = src.slice(span);
+    let Some(begin) = raw.match_indices("${").nth(nth).map(|(i, _)| i) else {
+        return span;
+    };
+    let end = raw[begin..].find('}').map_or(raw.len(), |i| begin + i + 1);
+    let mut location = span.begin;
+
+    for chr in raw[..begin].chars() {
+        location.advance(chr);
+    }
+
+    let start = location;
+
+    for chr in raw[begin..end].chars() {
+        location.advance(chr);
+    }
+
+    Span::new(start, location)
+}
+
+pub fn interpolate<'a>(src: &'a Source<'a>, document: &mut Document) -> Result<'a, ()> {
+    let mut interpolator = Interpolator::new(src, document);
+    let paths: Vec<(KeyPath, Span)> = interpolator
+        .strings
+        .iter()
+        .filter(|(_, (content, _))| content.contains("${"))
+        .map(|(path, (_, span))| (path.clone(), *span))
+        .collect();
+    let mut resolved = Vec::new();
+
+    for (path, span) in paths {
+        let value = interpolator.resolve(&path, span)?;
+        resolved.push((path, value));
+    }
+
+    for (path, value) in resolved {
+        document.set(&path, Value::String(value)).ok();
+    }
+
+    Ok(())
+}
+
+pub fn apply_profile<'a>(
+    src: &'a Source<'a>,
+    document: &mut Document,
+    profile: Option<&str>,
+) -> Result<'a, ()> {
+    let Some(name) = profile else {
+        return Ok(());
+    };
+    let unknown = |span| Error::resolving(ResolvingError::UnknownProfile, span, src);
+
+    let Some(profiles) = document.root.get_entry("profile") else {
+        // there's nothing to point at, so the error goes at the end, where a profile would be added
+        let mut end = Location::default();
+        src.content.chars().for_each(|chr| end.advance(chr));
+        return Err(unknown(Span::new(end, end)));
+    };
+
+    // `profile` is only taken out once it's known to hold the profile being applied
+    let overrides = match profiles.value.as_table().map(|t| t.get_entry(name)) {
+        Some(Some(Entry {
+            value: Value::Table(overrides),
+            ..
+        })) => overrides.clone(),
+        Some(Some(entry)) => return Err(unknown(entry.key_span)),
+        _ => return Err(unknown(profiles.key_span)),
+    };
+
+    document.root.remove("profile");
+    document.root.merge(&overrides, MergeStrategy::Override);
+    Ok(())
+}
+
+pub fn resolve<'a>(
+    src: &'a Source<'a>,
+    document: &mut Document,
+    options: ResolveOptions,
+) -> Result<'a, ()> {
+    apply_profile(src, document, options.profile)?;
+
+    if options.interpolate {
+        interpolate(src, document)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,594 @@
+use crate::{
+    lex::{LexerOptions, OverflowPolicy, TokenKind, is_identifier, too_large},
+    utils::{ErrorKind, LexingError, LimitError, Span},
+};
+use core::{iter::Peekable, num::IntErrorKind, slice, str::Chars};
+
+// the same tokens `lex` makes, borrowing their text from the source instead of copying it out;
+// strings and bytes keep their escapes as written, `chars` and `bytes` decode them on the fly
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BorrowedKind<'s> {
+    String(&'s str),
+    RawString(&'s str),
+    Bytes(&'s str),
+    Base64(&'s str),
+    // as written, normalizing a key would need somewhere to put the result
+    Ident(&'s str),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    LBrack,
+    RBrack,
+    LBrace,
+    RBrace,
+    Equal,
+    Comma,
+    Dot,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Ampersand,
+    Separator,
+    Comment(&'s str),
+    Directive(&'s str),
+}
+
+impl<'s> BorrowedKind<'s> {
+    pub fn token(&self) -> TokenKind {
+        match self {
+            Self::String(_) | Self::RawString(_) => TokenKind::String,
+            Self::Bytes(_) | Self::Base64(_) => TokenKind::Bytes,
+            Self::Ident(_) => TokenKind::Ident,
+            Self::Integer(_) => TokenKind::Integer,
+            Self::Float(_) => TokenKind::Float,
+            Self::Bool(_) => TokenKind::Bool,
+            Self::LBrack => TokenKind::LBrack,
+            Self::RBrack => TokenKind::RBrack,
+            Self::LBrace => TokenKind::LBrace,
+            Self::RBrace => TokenKind::RBrace,
+            Self::Equal => TokenKind::Equal,
+            Self::Comma => TokenKind::Comma,
+            Self::Dot => TokenKind::Dot,
+            Self::Plus => TokenKind::Plus,
+            Self::Minus => TokenKind::Minus,
+            Self::Star => TokenKind::Star,
+            Self::Slash => TokenKind::Slash,
+            Self::Ampersand => TokenKind::Ampersand,
+            Self::Separator => TokenKind::Separator,
+            Self::Comment(_) => TokenKind::Comment,
+            Self::Directive(_) => TokenKind::Directive,
+        }
+    }
+
+    pub fn chars(&self) -> Option<Unescape<'s>> {
+        match *self {
+            Self::String(text) => Some(Unescape::new(text, false)),
+            Self::RawString(text) => Some(Unescape::new(text, true)),
+            _ => None,
+        }
+    }
+
+    pub fn bytes(&self) -> Option<Decode<'s>> {
+        match *self {
+            Self::Bytes(text) => Some(Decode::new(text, false)),
+            Self::Base64(text) => Some(Decode::new(text, true)),
+            _ => None,
+        }
+    }
+
+    // what `max_string_len` is held against, the length once decoded just like `lex` counts it
+    fn decoded_len(&self) -> usize {
+        match (self.chars(), self.bytes()) {
+            (Some(chars), _) => chars.map(char::len_utf8).sum(),
+            (_, Some(bytes)) => bytes.count(),
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BorrowedLexeme<'s> {
+    pub kind: BorrowedKind<'s>,
+    pub span: Span,
+}
+
+fn unicode(hex: &str) -> Option<char> {
+    u32::from_str_radix(hex.trim_start_matches('{'), 16)
+        .ok()
+        .and_then(char::from_u32)
+}
+
+fn base64_digit(digit: u8) -> Option<u8> {
+    match digit {
+        b'A'..=b'Z' => Some(digit - b'A'),
+        b'a'..=b'z' => Some(digit - b'a' + 26),
+        b'0'..=b'9' => Some(digit - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    }
+}
+
+// the same rules as decoding the digits into a buffer: whitespace is skipped, up to two `=` pad
+// the end, and a lone digit past the last whole group is a malformed tail
+fn valid_base64(text: &str) -> bool {
+    let digits = || text.bytes().filter(|b| !b.is_ascii_whitespace());
+    let pad = digits().rev().take(2).take_while(|b| *b == b'=').count();
+    let len = digits().count() - pad;
+
+    len % 4 != 1 && digits().take(len).all(|b| base64_digit(b).is_some())
+}
+
+#[derive(Debug, Clone)]
+pub struct Unescape<'s> {
+    chars: Chars<'s>,
+    raw: bool,
+    // an unknown escape is kept as written, so its backslash comes out first
+    pending: Option<char>,
+}
+
+impl<'s> Unescape<'s> {
+    fn new(text: &'s str, raw: bool) -> Self {
+        Self {
+            chars: text.chars(),
+            raw,
+            pending: None,
+        }
+    }
+}
+
+impl Iterator for Unescape<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if let Some(chr) = self.pending.take() {
+            return Some(chr);
+        }
+
+        let chr = self.chars.next()?;
+        if self.raw || chr != '\\' {
+            return Some(chr);
+        }
+
+        Some(match self.chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('u') => {
+                let rest = self.chars.as_str();
+                let (hex, after) = match rest.strip_prefix('{').and_then(|s| s.find('}')) {
+                    Some(close) => (&rest[..close + 1], &rest[close + 2..]),
+                    None => ("", rest),
+                };
+                self.chars = after.chars();
+
+                // spans the scanner made never hold a bad escape, but a kind can be built by hand
+                unicode(hex).unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+            Some(escaped @ ('\\' | '"')) => escaped,
+            Some(escaped) => {
+                self.pending = Some(escaped);
+                '\\'
+            }
+            None => '\\',
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Decode<'s> {
+    bytes: slice::Iter<'s, u8>,
+    base64: bool,
+    bits: u32,
+    count: u32,
+}
+
+impl<'s> Decode<'s> {
+    fn new(text: &'s str, base64: bool) -> Self {
+        Self {
+            bytes: text.as_bytes().iter(),
+            base64,
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    fn hex(&mut self) -> Option<u8> {
+        let digit = |byte: Option<&u8>| byte.and_then(|b| char::from(*b).to_digit(16));
+        let (high, low) = (digit(self.bytes.next())?, digit(self.bytes.next())?);
+
+        Some((high << 4 | low) as u8)
+    }
+}
+
+impl Iterator for Decode<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if !self.base64 {
+            // escapes are all ascii, so a backslash byte is never inside a wider character
+            let byte = *self.bytes.next()?;
+            if byte != b'\\' {
+                return Some(byte);
+            }
+
+            return match *self.bytes.next()? {
+                b'n' => Some(b'\n'),
+                b't' => Some(b'\t'),
+                b'r' => Some(b'\r'),
+                b'0' => Some(b'\0'),
+                b'x' => self.hex(),
+                escaped => Some(escaped),
+            };
+        }
+
+        loop {
+            let digit = *self.bytes.next()?;
+            if digit.is_ascii_whitespace() {
+                continue;
+            }
+
+            self.bits = (self.bits << 6) | base64_digit(digit)? as u32;
+            self.count += 6;
+
+            if self.count >= 8 {
+                self.count -= 8;
+                let byte = (self.bits >> self.count) as u8;
+                self.bits &= (1 << self.count) - 1;
+                return Some(byte);
+            }
+        }
+    }
+}
+
+type Scanned<'s> = Result<BorrowedLexeme<'s>, (Span, ErrorKind)>;
+
+// lexes one token at a time straight out of the source, without allocating; lints are never
+// reported since there's nowhere to keep a warning, and stops after the first error
+#[derive(Debug, Clone)]
+pub struct Scanner<'s> {
+    src: &'s str,
+    chars: Peekable<Chars<'s>>,
+    span: Span,
+    options: LexerOptions,
+    count: usize,
+    error: Option<(Span, ErrorKind)>,
+    done: bool,
+    // the line the last token ended on, when that token can be the left side of a `+` or `-`
+    operand: Option<usize>,
+}
+
+pub fn scan(src: &str) -> Scanner<'_> {
+    scan_with(src, LexerOptions::default())
+}
+
+pub fn scan_with(src: &str, options: LexerOptions) -> Scanner<'_> {
+    let error = options
+        .max_size
+        .filter(|limit| src.len() > *limit)
+        .map(|limit| {
+            let (span, kind) = too_large(src, limit);
+            (span, ErrorKind::Limit(kind))
+        });
+
+    Scanner {
+        src,
+        chars: src.chars().peekable(),
+        span: Span::default(),
+        options,
+        count: 0,
+        error,
+        done: false,
+        operand: None,
+    }
+}
+
+impl<'s> Scanner<'s> {
+    fn next_char(&mut self) -> Option<char> {
+        let chr = self.chars.next()?;
+        self.span.end.advance(chr);
+        Some(chr)
+    }
+
+    fn next_if(&mut self, f: impl FnOnce(&char) -> bool) -> Option<char> {
+        let chr = self.chars.next_if(f)?;
+        self.span.end.advance(chr);
+        Some(chr)
+    }
+
+    fn peek_is(&mut self, f: impl FnOnce(&char) -> bool) -> bool {
+        self.chars.peek().is_some_and(f)
+    }
+
+    fn text(&self, begin: usize, end: usize) -> &'s str {
+        self.src.get(begin..end).unwrap_or_default()
+    }
+
+    fn error(&self, kind: LexingError) -> (Span, ErrorKind) {
+        (self.span, ErrorKind::Lexing(kind))
+    }
+
+    fn string(&mut self) -> Result<BorrowedKind<'s>, (Span, ErrorKind)> {
+        let begin = self.span.end.offset;
+
+        loop {
+            match self.next_char() {
+                None => return Err(self.error(LexingError::UnterminatedString)),
+                Some('"') => {
+                    let end = self.span.end.offset - 1;
+                    return Ok(BorrowedKind::String(self.text(begin, end)));
+                }
+                Some('\\') => match self.next_char() {
+                    None => return Err(self.error(LexingError::UnterminatedString)),
+                    Some('u') => {
+                        let from = self.span.end.offset;
+
+                        if self.chars.peek() == Some(&'{') {
+                            while self.next_char().is_some_and(|chr| chr != '}') {}
+                        }
+
+                        let hex = self.text(from, self.span.end.offset);
+                        if unicode(hex.strip_suffix('}').unwrap_or(hex)).is_none() {
+                            return Err(self.error(LexingError::InvalidEscape));
+                        }
+                    }
+                    Some(_) => {}
+                },
+                Some(_) => {}
+            }
+        }
+    }
+
+    fn raw(&mut self) -> Result<BorrowedKind<'s>, (Span, ErrorKind)> {
+        let mut hashes = 0;
+
+        while self.next_if(|c| *c == '#').is_some() {
+            hashes += 1;
+        }
+
+        if self.next_if(|c| *c == '"').is_none() {
+            return Err(self.error(LexingError::UnrecognizedToken));
+        }
+
+        let begin = self.span.end.offset;
+
+        loop {
+            match self.next_char() {
+                None => return Err(self.error(LexingError::UnterminatedString)),
+                Some('"') => {
+                    let end = self.span.end.offset - 1;
+                    let mut closing = 0;
+
+                    while closing < hashes && self.next_if(|c| *c == '#').is_some() {
+                        closing += 1;
+                    }
+
+                    if closing == hashes {
+                        return Ok(BorrowedKind::RawString(self.text(begin, end)));
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    fn bytes(&mut self, base64: bool) -> Result<BorrowedKind<'s>, (Span, ErrorKind)> {
+        self.next_char();
+        let begin = self.span.end.offset;
+
+        loop {
+            match self.next_char() {
+                None => return Err(self.error(LexingError::UnterminatedString)),
+                Some('"') => break,
+                Some('\\') if !base64 => match self.next_char() {
+                    None => return Err(self.error(LexingError::UnterminatedString)),
+                    Some('n' | 't' | 'r' | '0' | '\\' | '"') => {}
+                    Some('x') => {
+                        let mut digits = 0;
+
+                        while digits < 2 && self.next_if(char::is_ascii_hexdigit).is_some() {
+                            digits += 1;
+                        }
+
+                        if digits < 2 {
+                            return Err(self.error(LexingError::InvalidEscape));
+                        }
+                    }
+                    Some(_) => return Err(self.error(LexingError::InvalidEscape)),
+                },
+                Some(_) => {}
+            }
+        }
+
+        let text = self.text(begin, self.span.end.offset - 1);
+
+        match base64 {
+            false => Ok(BorrowedKind::Bytes(text)),
+            true if valid_base64(text) => Ok(BorrowedKind::Base64(text)),
+            true => Err(self.error(LexingError::MalformedBytes)),
+        }
+    }
+
+    fn number(&mut self, tok: char) -> Result<BorrowedKind<'s>, (Span, ErrorKind)> {
+        let mut dot = tok == '.';
+
+        while let Some(&chr) = self.chars.peek() {
+            if chr == '.' {
+                if dot {
+                    return Err(self.error(LexingError::MalformedNumber));
+                }
+
+                dot = true;
+            }
+
+            if !chr.is_ascii_digit() && chr != '.' {
+                break;
+            }
+
+            self.next_char();
+        }
+
+        let text = self.text(self.span.begin.offset, self.span.end.offset);
+        let float = |text: &str| {
+            text.parse::<f64>()
+                .map(BorrowedKind::Float)
+                .map_err(|_| self.error(LexingError::MalformedNumber))
+        };
+
+        if dot {
+            return float(text);
+        }
+
+        match text.parse::<i64>() {
+            Ok(int) => Ok(BorrowedKind::Integer(int)),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    IntErrorKind::PosOverflow | IntErrorKind::NegOverflow
+                ) =>
+            {
+                match self.options.overflow {
+                    OverflowPolicy::Error => Err(self.error(LexingError::IntegerOverflow)),
+                    OverflowPolicy::Saturate => Ok(BorrowedKind::Integer(match e.kind() {
+                        IntErrorKind::PosOverflow => i64::MAX,
+                        _ => i64::MIN,
+                    })),
+                    OverflowPolicy::Float => float(text),
+                }
+            }
+            Err(_) => Err(self.error(LexingError::MalformedNumber)),
+        }
+    }
+
+    // `None` for whatever is skipped over rather than yielded
+    fn kind(&mut self, tok: char) -> Result<Option<BorrowedKind<'s>>, (Span, ErrorKind)> {
+        let begin = self.span.begin.offset;
+        let operand = self.operand == Some(self.span.begin.line);
+        let sign = |c: &char| !operand && (c.is_ascii_digit() || *c == '.');
+
+        Ok(Some(match tok {
+            '=' => BorrowedKind::Equal,
+            ',' => BorrowedKind::Comma,
+            '[' => BorrowedKind::LBrack,
+            ']' => BorrowedKind::RBrack,
+            '{' => BorrowedKind::LBrace,
+            '}' => BorrowedKind::RBrace,
+            '.' if !self.peek_is(char::is_ascii_digit) => BorrowedKind::Dot,
+            '+' if !self.peek_is(sign) => BorrowedKind::Plus,
+            '-' if self.span.begin.col == 1
+                && self.text(begin, self.src.len()).starts_with("---") =>
+            {
+                self.next_char();
+                self.next_char();
+                BorrowedKind::Separator
+            }
+            '-' if !self.peek_is(sign) => BorrowedKind::Minus,
+            '*' => BorrowedKind::Star,
+            '/' => BorrowedKind::Slash,
+            '&' => BorrowedKind::Ampersand,
+            '"' => self.string()?,
+            _ if tok.is_ascii_digit() || tok == '-' || tok == '+' || tok == '.' => {
+                self.number(tok)?
+            }
+            _ if is_identifier(tok) => {
+                while self.next_if(|c| is_identifier(*c)).is_some() {}
+
+                match self.text(begin, self.span.end.offset) {
+                    kind @ ("b" | "base64") if self.chars.peek() == Some(&'"') => {
+                        self.bytes(kind == "base64")?
+                    }
+                    "r" if matches!(self.chars.peek(), Some('"' | '#')) => self.raw()?,
+                    "true" => BorrowedKind::Bool(true),
+                    "false" => BorrowedKind::Bool(false),
+                    ident => BorrowedKind::Ident(ident),
+                }
+            }
+            '#' => {
+                while self.next_if(|c| *c != '\n').is_some() {}
+
+                if !self.options.comments {
+                    return Ok(None);
+                }
+
+                BorrowedKind::Comment(self.text(begin + 1, self.span.end.offset))
+            }
+            '@' => {
+                while self.next_if(|c| is_identifier(*c)).is_some() {}
+
+                if self.span.end.offset == begin + 1 {
+                    return Err(self.error(LexingError::UnrecognizedToken));
+                }
+
+                BorrowedKind::Directive(self.text(begin + 1, self.span.end.offset))
+            }
+            _ if tok.is_whitespace() => return Ok(None),
+            _ => return Err(self.error(LexingError::UnrecognizedToken)),
+        }))
+    }
+
+    fn scan(&mut self) -> Option<Scanned<'s>> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+
+        loop {
+            self.span.begin = self.span.end;
+            let tok = self.next_char()?;
+
+            match self.kind(tok) {
+                Ok(None) => continue,
+                Ok(Some(kind)) => {
+                    self.operand = kind.token().ends_operand().then_some(self.span.end.line);
+
+                    return Some(self.limit(BorrowedLexeme {
+                        kind,
+                        span: self.span,
+                    }));
+                }
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+
+    // nothing is kept to count the rest of the source by, so too many tokens is reported at the
+    // first one over the limit, with that one counted as all that was found
+    fn limit(&mut self, lexeme: BorrowedLexeme<'s>) -> Scanned<'s> {
+        if let Some(limit) = self.options.max_string_len {
+            let len = lexeme.kind.decoded_len();
+
+            if len > limit {
+                let kind = LimitError::StringTooLong { limit, found: len };
+                return Err((lexeme.span, ErrorKind::Limit(kind)));
+            }
+        }
+
+        self.count += 1;
+
+        match self.options.max_tokens.filter(|limit| self.count > *limit) {
+            Some(limit) => {
+                let kind = LimitError::TooManyTokens {
+                    limit,
+                    found: self.count,
+                };
+                Err((lexeme.span, ErrorKind::Limit(kind)))
+            }
+            None => Ok(lexeme),
+        }
+    }
+}
+
+impl<'s> Iterator for Scanner<'s> {
+    type Item = Scanned<'s>;
+
+    fn next(&mut self) -> Option<Scanned<'s>> {
+        if self.done {
+            return None;
+        }
+
+        let scanned = self.scan();
+        self.done = !matches!(scanned, Some(Ok(_)));
+        scanned
+    }
+}
@@ -0,0 +1,591 @@
+use crate::{
+    diagnostic::{Level, Lints},
+    messages::{English, Messages},
+    path::*,
+    utils::*,
+    value::*,
+};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{cmp::Ordering, fmt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Any,
+    String,
+    Bytes,
+    Integer,
+    Float,
+    Bool,
+    Array(Box<Type>),
+    Table(Schema),
+}
+
+impl Type {
+    pub fn kind(&self) -> Option<ValueKind> {
+        match self {
+            Self::Any => None,
+            Self::String => Some(ValueKind::String),
+            Self::Bytes => Some(ValueKind::Bytes),
+            Self::Integer => Some(ValueKind::Integer),
+            Self::Float => Some(ValueKind::Float),
+            Self::Bool => Some(ValueKind::Bool),
+            Self::Array(_) => Some(ValueKind::Array),
+            Self::Table(_) => Some(ValueKind::Table),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Integer(i64),
+    Float(f64),
+}
+
+macro_rules! from_integer {
+    ($($ty:ty),+) => {
+        $(
+            impl From<$ty> for Bound {
+                fn from(value: $ty) -> Self {
+                    Self::Integer(value.into())
+                }
+            }
+        )+
+    };
+}
+
+from_integer!(i8, i16, i32, i64, u8, u16, u32);
+
+impl From<f32> for Bound {
+    fn from(value: f32) -> Self {
+        Self::Float(value.into())
+    }
+}
+
+impl From<f64> for Bound {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl Bound {
+    // None means the two are unordered, which only happens with a NaN.
+    fn compare(value: &Value, bound: Self) -> Option<Ordering> {
+        match (value, bound) {
+            (Value::Integer(value), Self::Integer(bound)) => Some(value.cmp(&bound)),
+            (Value::Integer(value), Self::Float(bound)) => integer_float(*value, bound),
+            (Value::Float(value), Self::Integer(bound)) => {
+                integer_float(bound, *value).map(Ordering::reverse)
+            }
+            (Value::Float(value), Self::Float(bound)) => value.partial_cmp(&bound),
+            _ => Some(Ordering::Equal),
+        }
+    }
+}
+
+// Casting the integer to f64 rounds above 2^53, so the float is truncated
+// instead and only the fractional part decides a tie.
+fn integer_float(integer: i64, float: f64) -> Option<Ordering> {
+    if float.is_nan() {
+        None
+    } else if float >= 9223372036854775808.0 {
+        Some(Ordering::Less)
+    } else if float < -9223372036854775808.0 {
+        Some(Ordering::Greater)
+    } else {
+        let truncated = float as i64;
+
+        match integer.cmp(&truncated) {
+            Ordering::Equal => (truncated as f64).partial_cmp(&float),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub ty: Type,
+    pub required: bool,
+    pub min: Option<Bound>,
+    pub max: Option<Bound>,
+    pub choices: Vec<Value>,
+    pub default: Option<Value>,
+    pub deprecated: bool,
+    pub replacement: Option<String>,
+}
+
+impl Field {
+    pub fn new(ty: Type) -> Self {
+        Self {
+            ty,
+            required: false,
+            min: None,
+            max: None,
+            choices: Vec::new(),
+            default: None,
+            deprecated: false,
+            replacement: None,
+        }
+    }
+
+    pub fn any() -> Self {
+        Self::new(Type::Any)
+    }
+
+    pub fn string() -> Self {
+        Self::new(Type::String)
+    }
+
+    pub fn bytes() -> Self {
+        Self::new(Type::Bytes)
+    }
+
+    pub fn integer() -> Self {
+        Self::new(Type::Integer)
+    }
+
+    pub fn float() -> Self {
+        Self::new(Type::Float)
+    }
+
+    pub fn bool() -> Self {
+        Self::new(Type::Bool)
+    }
+
+    pub fn array(ty: Type) -> Self {
+        Self::new(Type::Array(Box::new(ty)))
+    }
+
+    pub fn table(schema: Schema) -> Self {
+        Self::new(Type::Table(schema))
+    }
+
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    pub fn min(mut self, min: impl Into<Bound>) -> Self {
+        self.min = Some(min.into());
+        self
+    }
+
+    pub fn max(mut self, max: impl Into<Bound>) -> Self {
+        self.max = Some(max.into());
+        self
+    }
+
+    pub fn range(self, min: impl Into<Bound>, max: impl Into<Bound>) -> Self {
+        self.min(min).max(max)
+    }
+
+    pub fn choices<V: Into<Value>>(mut self, choices: impl IntoIterator<Item = V>) -> Self {
+        self.choices = choices.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn default(mut self, value: impl Into<Value>) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    pub fn deprecated(mut self) -> Self {
+        self.deprecated = true;
+        self
+    }
+
+    pub fn replaced_by(mut self, path: &str) -> Self {
+        self.deprecated = true;
+        self.replacement = Some(path.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaErrorKind {
+    MissingKey,
+    MismatchedType {
+        expected: ValueKind,
+        found: ValueKind,
+    },
+    OutOfRange,
+    InvalidChoice,
+    UnknownKey,
+    DeprecatedKey,
+}
+
+impl SchemaErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingKey => "PCF0301",
+            Self::MismatchedType { .. } => "PCF0302",
+            Self::OutOfRange => "PCF0303",
+            Self::InvalidChoice => "PCF0304",
+            Self::UnknownKey => "PCF0305",
+            Self::DeprecatedKey => "PCF0306",
+        }
+    }
+}
+
+impl fmt::Display for SchemaErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        English.schema_error(f, self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+    pub path: KeyPath,
+    pub span: Span,
+    pub kind: SchemaErrorKind,
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}{}] {}", self.path, self.span, self.kind)?;
+
+        match &self.suggestion {
+            Some(suggestion) => {
+                f.write_str(", ")?;
+                English.suggestion(f, suggestion)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl core::error::Error for SchemaError {}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownKeyPolicy {
+    #[default]
+    Allow,
+    Warn,
+    Deny,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationOptions {
+    pub unknown_keys: UnknownKeyPolicy,
+    pub lints: Lints,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaWarningKind {
+    DeprecatedKey,
+    UnknownKey,
+}
+
+impl SchemaWarningKind {
+    pub(crate) const ALL: [Self; 2] = [Self::DeprecatedKey, Self::UnknownKey];
+
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::DeprecatedKey => "PCF0401",
+            Self::UnknownKey => "PCF0402",
+        }
+    }
+}
+
+impl fmt::Display for SchemaWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        English.schema_warning(f, self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaWarning {
+    pub path: KeyPath,
+    pub span: Span,
+    pub kind: SchemaWarningKind,
+    pub replacement: Option<String>,
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for SchemaWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}{}] {}", self.path, self.span, self.kind)?;
+
+        match (&self.replacement, &self.suggestion) {
+            (Some(replacement), _) => {
+                f.write_str(", ")?;
+                English.replacement(f, replacement)
+            }
+            (None, Some(suggestion)) => {
+                f.write_str(", ")?;
+                English.suggestion(f, suggestion)
+            }
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+// a denied warning is reported as the error of the same name, and the key it should be renamed
+// to is offered as the suggestion
+impl From<SchemaWarning> for SchemaError {
+    fn from(warning: SchemaWarning) -> Self {
+        Self {
+            path: warning.path,
+            span: warning.span,
+            kind: match warning.kind {
+                SchemaWarningKind::DeprecatedKey => SchemaErrorKind::DeprecatedKey,
+                SchemaWarningKind::UnknownKey => SchemaErrorKind::UnknownKey,
+            },
+            suggestion: warning.replacement.or(warning.suggestion),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Validation {
+    pub errors: Vec<SchemaError>,
+    pub warnings: Vec<SchemaWarning>,
+    pub defaulted: Vec<KeyPath>,
+}
+
+impl Validation {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn is_defaulted(&self, path: &KeyPath) -> bool {
+        self.defaulted.contains(path)
+    }
+
+    fn warn(&mut self, lints: Lints, warning: SchemaWarning) {
+        match lints.get(warning.kind.code()) {
+            Some(Level::Allow) => {}
+            Some(Level::Deny) => self.errors.push(warning.into()),
+            _ => self.warnings.push(warning),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Schema {
+    fields: Vec<(String, Field)>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn field(mut self, key: &str, field: Field) -> Self {
+        match self.fields.iter_mut().find(|(k, _)| k == key) {
+            Some((_, existing)) => *existing = field,
+            None => self.fields.push((key.into(), field)),
+        }
+
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Field> {
+        self.fields.iter().find(|(k, _)| k == key).map(|(_, f)| f)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &Field)> {
+        self.fields.iter().map(|(k, f)| (k.as_str(), f))
+    }
+
+    pub fn validate(&self, document: &Document) -> Vec<SchemaError> {
+        self.check(document).errors
+    }
+
+    pub fn check(&self, document: &Document) -> Validation {
+        self.check_with(document, ValidationOptions::default())
+    }
+
+    pub fn check_with(&self, document: &Document, options: ValidationOptions) -> Validation {
+        let mut validation = Validation::default();
+        self.validate_table(
+            &document.root,
+            &mut KeyPath::new(),
+            Span::default(),
+            options,
+            &mut validation,
+        );
+        validation
+    }
+
+    pub fn validate_and_fill(&self, document: &mut Document) -> Validation {
+        let mut defaulted = Vec::new();
+        self.fill_table(&mut document.root, &mut KeyPath::new(), &mut defaulted);
+
+        Validation {
+            defaulted,
+            ..self.check(document)
+        }
+    }
+
+    fn fill_table(&self, table: &mut Table, path: &mut KeyPath, defaulted: &mut Vec<KeyPath>) {
+        for (key, field) in &self.fields {
+            path.push(PathSegment::Key(key.clone()));
+
+            match (table.get_mut(key), &field.ty) {
+                (Some(Value::Table(table)), Type::Table(schema)) => {
+                    schema.fill_table(table, path, defaulted)
+                }
+                (Some(_), _) => {}
+                (None, ty) => {
+                    if let Some(default) = &field.default {
+                        table.set(key, default.clone());
+                        defaulted.push(path.clone());
+                    } else if let Type::Table(schema) = ty {
+                        let mut nested = Table::new();
+                        schema.fill_table(&mut nested, path, defaulted);
+
+                        if !nested.is_empty() {
+                            table.set(key, Value::Table(nested));
+                        }
+                    }
+                }
+            }
+
+            path.pop();
+        }
+    }
+
+    fn validate_table(
+        &self,
+        table: &Table,
+        path: &mut KeyPath,
+        span: Span,
+        options: ValidationOptions,
+        validation: &mut Validation,
+    ) {
+        if options.unknown_keys != UnknownKeyPolicy::Allow {
+            for (key, entry) in table.iter() {
+                if self.get(key).is_some() {
+                    continue;
+                }
+
+                path.push(PathSegment::Key(key.clone()));
+
+                // keys the table already has aren't what a typo was aiming for
+                let suggestion = suggest(
+                    key,
+                    self.fields
+                        .iter()
+                        .map(|(k, _)| k.as_str())
+                        .filter(|k| table.get(k).is_none()),
+                )
+                .map(String::from);
+
+                if options.unknown_keys == UnknownKeyPolicy::Deny {
+                    validation.errors.push(SchemaError {
+                        path: path.clone(),
+                        span: entry.key_span,
+                        kind: SchemaErrorKind::UnknownKey,
+                        suggestion,
+                    });
+                } else {
+                    validation.warn(
+                        options.lints,
+                        SchemaWarning {
+                            path: path.clone(),
+                            span: entry.key_span,
+                            kind: SchemaWarningKind::UnknownKey,
+                            replacement: None,
+                            suggestion,
+                        },
+                    );
+                }
+
+                path.pop();
+            }
+        }
+
+        for (key, field) in &self.fields {
+            path.push(PathSegment::Key(key.clone()));
+
+            match table.get_entry(key) {
+                Some(entry) => {
+                    if field.deprecated {
+                        validation.warn(
+                            options.lints,
+                            SchemaWarning {
+                                path: path.clone(),
+                                span: entry.key_span,
+                                kind: SchemaWarningKind::DeprecatedKey,
+                                replacement: field.replacement.clone(),
+                                suggestion: None,
+                            },
+                        );
+                    }
+
+                    field.validate(&entry.value, path, entry.span, options, validation)
+                }
+                None if field.required => validation.errors.push(SchemaError {
+                    path: path.clone(),
+                    span,
+                    kind: SchemaErrorKind::MissingKey,
+                    suggestion: None,
+                }),
+                None => {}
+            }
+
+            path.pop();
+        }
+    }
+}
+
+impl Field {
+    fn validate(
+        &self,
+        value: &Value,
+        path: &mut KeyPath,
+        span: Span,
+        options: ValidationOptions,
+        validation: &mut Validation,
+    ) {
+        let mut error = |kind| {
+            validation.errors.push(SchemaError {
+                path: path.clone(),
+                span,
+                kind,
+                suggestion: None,
+            })
+        };
+
+        if let Some(expected) = self.ty.kind()
+            && expected != value.kind()
+        {
+            return error(SchemaErrorKind::MismatchedType {
+                expected,
+                found: value.kind(),
+            });
+        }
+
+        let below = self.min.is_some_and(|min| {
+            Bound::compare(value, min).is_none_or(|ordering| ordering == Ordering::Less)
+        });
+        let above = self.max.is_some_and(|max| {
+            Bound::compare(value, max).is_none_or(|ordering| ordering == Ordering::Greater)
+        });
+
+        if below || above {
+            error(SchemaErrorKind::OutOfRange);
+        }
+
+        if !self.choices.is_empty() && !self.choices.contains(value) {
+            error(SchemaErrorKind::InvalidChoice);
+        }
+
+        match (&self.ty, value) {
+            (Type::Array(ty), Value::Array(array)) => {
+                let element = Field::new((**ty).clone());
+
+                for (i, value) in array.iter().enumerate() {
+                    path.push(PathSegment::Index(i));
+                    element.validate(value, path, span, options, validation);
+                    path.pop();
+                }
+            }
+            (Type::Table(schema), Value::Table(table)) => {
+                schema.validate_table(table, path, span, options, validation)
+            }
+            _ => {}
+        }
+    }
+}
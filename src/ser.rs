@@ -0,0 +1,597 @@
+use crate::{emit::EmitOptions, path::*, utils::Span, value::*};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+use serde::ser::{self, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SerializeErrorKind {
+    Custom(String),
+    IntegerOutOfRange,
+    KeyMustBeAString,
+    MissingValue,
+    NotATable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerializeError {
+    pub path: KeyPath,
+    pub kind: SerializeErrorKind,
+}
+
+impl SerializeError {
+    fn new(kind: SerializeErrorKind) -> Self {
+        Self {
+            path: KeyPath::new(),
+            kind,
+        }
+    }
+
+    // errors bubble up from the innermost value, so each level prefixes its own segment
+    fn within(self, segment: PathSegment) -> Self {
+        Self {
+            path: core::iter::once(segment).chain(self.path).collect(),
+            kind: self.kind,
+        }
+    }
+}
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] ", self.path)?;
+
+        match &self.kind {
+            SerializeErrorKind::Custom(message) => {
+                write!(f, "encountered {} during serialization", message)
+            }
+            SerializeErrorKind::IntegerOutOfRange => {
+                write!(f, "encountered integer out of range during serialization")
+            }
+            SerializeErrorKind::KeyMustBeAString => {
+                write!(f, "encountered non-string key during serialization")
+            }
+            SerializeErrorKind::MissingValue => {
+                write!(f, "encountered missing value during serialization")
+            }
+            SerializeErrorKind::NotATable => {
+                write!(f, "encountered non-table document during serialization")
+            }
+        }
+    }
+}
+
+impl core::error::Error for SerializeError {}
+
+impl ser::Error for SerializeError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Self::new(SerializeErrorKind::Custom(message.to_string()))
+    }
+}
+
+type Result<T> = core::result::Result<T, SerializeError>;
+
+fn entry(value: Value) -> Entry {
+    Entry::new(value, Span::default(), Span::default())
+}
+
+// integers beyond `i64` are rejected rather than degraded to floats, which wouldn't deserialize
+// back into the type they came from
+fn integer<T: TryInto<i64>>(n: T) -> Result<Option<Value>> {
+    match n.try_into() {
+        Ok(i) => Ok(Some(Value::Integer(i))),
+        Err(_) => Err(SerializeError::new(SerializeErrorKind::IntegerOutOfRange)),
+    }
+}
+
+fn variant(name: &str, value: Value) -> Option<Value> {
+    let mut table = Table::new();
+    table.insert(name.into(), entry(value));
+    Some(Value::Table(table))
+}
+
+// values serialize to `None` when they are absent, since pcf has no null, which lets tables
+// leave them out the same way `ToPcf` does; an array can't without moving every element after
+// it, so there it's an error. `()` is present rather than absent, so it becomes an empty table
+// that deserializes back, where leaving it out would make it a missing field
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<Value>;
+    type Error = SerializeError;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeVariant<SerializeArray>;
+    type SerializeMap = SerializeTable;
+    type SerializeStruct = SerializeTable;
+    type SerializeStructVariant = SerializeVariant<SerializeTable>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        Ok(Some(Value::Bool(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        Ok(Some(Value::Integer(v.into())))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        Ok(Some(Value::Integer(v.into())))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        Ok(Some(Value::Integer(v.into())))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        Ok(Some(Value::Integer(v)))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok> {
+        integer(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        Ok(Some(Value::Integer(v.into())))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        Ok(Some(Value::Integer(v.into())))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        Ok(Some(Value::Integer(v.into())))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        integer(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok> {
+        integer(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        Ok(Some(Value::Float(v.into())))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        Ok(Some(Value::Float(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        Ok(Some(Value::String(v.into())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        Ok(Some(Value::String(v.into())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+        Ok(Some(Value::Bytes(v.into())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Ok(Some(Value::Table(Table::new())))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        Ok(Some(Value::Table(Table::new())))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok> {
+        Ok(Some(Value::String(variant.into())))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        let value = value
+            .serialize(self)
+            .map_err(|e| e.within(PathSegment::Key(variant.into())))?
+            .ok_or(SerializeError::new(SerializeErrorKind::MissingValue))?;
+
+        Ok(self::variant(variant, value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SerializeArray {
+            array: Vec::with_capacity(len.unwrap_or(0)),
+            index: 0,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(SerializeTable {
+            table: Table::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+pub struct SerializeArray {
+    array: Vec<Value>,
+    index: usize,
+}
+
+impl SerializeArray {
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let value = value
+            .serialize(Serializer)
+            .and_then(|value| value.ok_or(SerializeError::new(SerializeErrorKind::MissingValue)))
+            .map_err(|e| e.within(PathSegment::Index(self.index)))?;
+
+        self.index += 1;
+        self.array.push(value);
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Option<Value>;
+    type Error = SerializeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(Some(Value::Array(self.array)))
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Option<Value>;
+    type Error = SerializeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Option<Value>;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+pub struct SerializeTable {
+    table: Table,
+    key: Option<String>,
+}
+
+impl SerializeTable {
+    fn field<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<()> {
+        let value = value
+            .serialize(Serializer)
+            .map_err(|e| e.within(PathSegment::Key(key.clone())))?;
+
+        if let Some(value) = value {
+            self.table.insert(key, entry(value));
+        }
+
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for SerializeTable {
+    type Ok = Option<Value>;
+    type Error = SerializeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self.key.take().unwrap_or_default();
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(Some(Value::Table(self.table)))
+    }
+}
+
+impl ser::SerializeStruct for SerializeTable {
+    type Ok = Option<Value>;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.field(key.into(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+pub struct SerializeVariant<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeArray> {
+    type Ok = Option<Value>;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.inner
+            .element(value)
+            .map_err(|e| e.within(PathSegment::Key(self.variant.into())))
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(variant(self.variant, Value::Array(self.inner.array)))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeTable> {
+    type Ok = Option<Value>;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.inner
+            .field(key.into(), value)
+            .map_err(|e| e.within(PathSegment::Key(self.variant.into())))
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(variant(self.variant, Value::Table(self.inner.table)))
+    }
+}
+
+// table keys must be strings, though scalar keys are stringified the way json serializers do
+struct KeySerializer;
+
+impl KeySerializer {
+    fn error<T>() -> Result<T> {
+        Err(SerializeError::new(SerializeErrorKind::KeyMustBeAString))
+    }
+}
+
+macro_rules! stringify_key {
+    ($($method:ident: $ty:ty),*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<String> {
+                Ok(v.to_string())
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = SerializeError;
+    type SerializeSeq = ser::Impossible<String, SerializeError>;
+    type SerializeTuple = ser::Impossible<String, SerializeError>;
+    type SerializeTupleStruct = ser::Impossible<String, SerializeError>;
+    type SerializeTupleVariant = ser::Impossible<String, SerializeError>;
+    type SerializeMap = ser::Impossible<String, SerializeError>;
+    type SerializeStruct = ser::Impossible<String, SerializeError>;
+    type SerializeStructVariant = ser::Impossible<String, SerializeError>;
+
+    stringify_key!(
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+        serialize_char: char,
+        serialize_str: &str
+    );
+
+    fn serialize_f32(self, _v: f32) -> Result<String> {
+        Self::error()
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String> {
+        Self::error()
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String> {
+        Self::error()
+    }
+
+    fn serialize_none(self) -> Result<String> {
+        Self::error()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<String> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String> {
+        Self::error()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String> {
+        Self::error()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<String> {
+        Ok(variant.into())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String> {
+        Self::error()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Self::error()
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Self::error()
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Self::error()
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Self::error()
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Self::error()
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Self::error()
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Self::error()
+    }
+}
+
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value> {
+    value
+        .serialize(Serializer)?
+        .ok_or(SerializeError::new(SerializeErrorKind::MissingValue))
+}
+
+pub fn to_document<T: Serialize + ?Sized>(value: &T) -> Result<Document> {
+    match to_value(value)? {
+        Value::Table(root) => Ok(Document::new(root)),
+        _ => Err(SerializeError::new(SerializeErrorKind::NotATable)),
+    }
+}
+
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    to_string_with(value, &EmitOptions::default())
+}
+
+pub fn to_string_with<T: Serialize + ?Sized>(value: &T, options: &EmitOptions) -> Result<String> {
+    to_document(value).map(|document| crate::emit::to_string_with(&document, options))
+}
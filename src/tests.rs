@@ -1 +1,4454 @@
+use crate::{lex::*, parse::*, path::*, utils::*, value::*, visit::*};
 
+fn kinds(content: &str) -> alloc::vec::Vec<LexemeKind> {
+    let src = Source::new("test", content.into());
+    lex(&src).unwrap().into_iter().map(|l| l.kind).collect()
+}
+
+#[test]
+fn lexes_basic_assignment() {
+    assert_eq!(
+        kinds("name = \"pcf\""),
+        [
+            LexemeKind::Ident("name".into()),
+            LexemeKind::Equal,
+            LexemeKind::String("pcf".into())
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "unicode")]
+fn identifiers_are_nfc_normalized() {
+    assert_eq!(kinds("caf\u{e9}"), kinds("cafe\u{301}"));
+    assert_eq!(
+        kinds("cafe\u{301}"),
+        [LexemeKind::Ident("caf\u{e9}".into())]
+    );
+}
+
+#[test]
+fn lexeme_text_matches_source_spelling() {
+    let src = Source::new("test", "\"h\u{e9}\" = 0.50 # note\nnext".into());
+    let texts: alloc::vec::Vec<_> = lex(&src).unwrap().iter().map(|l| l.text(&src)).collect();
+
+    assert_eq!(texts, ["\"h\u{e9}\"", "=", "0.50", "next"]);
+}
+
+#[test]
+fn string_escapes_are_decoded() {
+    assert_eq!(
+        kinds("\"a\\nb\\t\\r\\0 \\\\ \\\" \\u{41}\\u{1F600}\""),
+        [LexemeKind::String("a\nb\t\r\0 \\ \" A\u{1F600}".into())]
+    );
+
+    // an escape pcf doesn't know is kept as written, the parser warns about it
+    assert_eq!(kinds("\"\\q\""), [LexemeKind::String("\\q".into())]);
+
+    for content in ["\"\\u{zz}\"", "\"\\u{110000}\"", "\"\\u41\""] {
+        let src = Source::new("", content.into());
+        let error = lex(&src).unwrap_err();
+        assert_eq!(
+            error.kind,
+            ErrorKind::Lexing(LexingError::InvalidEscape),
+            "{}",
+            content
+        );
+        assert_eq!(error.span.begin.offset, 0, "{}", content);
+    }
+}
+
+#[test]
+fn integer_overflow_follows_policy() {
+    let src = Source::new("test", "18446744073709551615\n-9223372036854775809".into());
+    let with = |overflow| {
+        lex_with(
+            &src,
+            LexerOptions {
+                overflow,
+                ..Default::default()
+            },
+        )
+        .map(|ls| {
+            ls.into_iter()
+                .map(|l| l.kind)
+                .collect::<alloc::vec::Vec<_>>()
+        })
+        .map_err(|e| e.kind)
+    };
+
+    assert_eq!(
+        with(OverflowPolicy::Error),
+        Err(ErrorKind::Lexing(LexingError::IntegerOverflow))
+    );
+    assert_eq!(
+        with(OverflowPolicy::Saturate),
+        Ok(alloc::vec![
+            LexemeKind::Integer(i64::MAX),
+            LexemeKind::Integer(i64::MIN)
+        ])
+    );
+    assert_eq!(
+        with(OverflowPolicy::Float),
+        Ok(alloc::vec![
+            LexemeKind::Float(18446744073709551615.0),
+            LexemeKind::Float(-9223372036854775809.0)
+        ])
+    );
+}
+
+fn document(content: &str) -> Document {
+    let src = Source::new("test", content.into());
+    parse(&src).unwrap()
+}
+
+fn parse_error(content: &str) -> ErrorKind {
+    let src = Source::new("test", content.into());
+    parse(&src).unwrap_err().kind
+}
+
+#[test]
+fn parses_scalars_arrays_and_tables() {
+    let doc = document(
+        "name = \"pcf\" version = 1 ratio = 0.5 stable = false\n\
+         tags = [\"a\", [1, 2]] server = { host = \"localhost\", port = 8080 }",
+    );
+
+    assert_eq!(doc.root.get("name"), Some(&Value::String("pcf".into())));
+    assert_eq!(doc.root.get("version"), Some(&Value::Integer(1)));
+    assert_eq!(doc.root.get("ratio"), Some(&Value::Float(0.5)));
+    assert_eq!(doc.root.get("stable"), Some(&Value::Bool(false)));
+    assert_eq!(
+        doc.root.get("tags"),
+        Some(&Value::Array(alloc::vec![
+            Value::String("a".into()),
+            Value::Array(alloc::vec![Value::Integer(1), Value::Integer(2)])
+        ]))
+    );
+
+    let Some(Value::Table(server)) = doc.root.get("server") else {
+        panic!("expected server table");
+    };
+    assert_eq!(server.get("port"), Some(&Value::Integer(8080)));
+}
+
+#[test]
+fn reports_parsing_errors() {
+    assert!(matches!(
+        parse_error("a = 1 a = 2"),
+        ErrorKind::Parsing(ParsingError::DuplicateKey { .. })
+    ));
+    assert_eq!(
+        parse_error("a ="),
+        ErrorKind::Parsing(ParsingError::UnexpectedEof)
+    );
+    assert_eq!(
+        parse_error("a = ="),
+        ErrorKind::Parsing(ParsingError::UnexpectedToken {
+            found: TokenKind::Equal,
+            expected: Expected::Value.into()
+        })
+    );
+    assert_eq!(
+        parse_error("a 1"),
+        ErrorKind::Parsing(ParsingError::UnexpectedToken {
+            found: TokenKind::Integer,
+            expected: ExpectedSet::from_iter([
+                Expected::Token(TokenKind::Equal),
+                Expected::Token(TokenKind::Dot)
+            ])
+        })
+    );
+    // an empty key can only be written quoted, which is how it's emitted
+    let doc = document("\"\" = 1\nt.\"\".\"\" = 2");
+    assert_eq!(doc.get_i64("\"\""), Ok(1));
+    assert_eq!(doc.get_i64("t.\"\".\"\""), Ok(2));
+    assert_eq!(document(&crate::emit::to_string(&doc)).root, doc.root);
+    assert_eq!(
+        parse_error("true = 1"),
+        ErrorKind::Parsing(ParsingError::InvalidKey)
+    );
+    assert_eq!(
+        parse_error("a = { b = [1, 2"),
+        ErrorKind::Parsing(ParsingError::UnclosedDelimiter {
+            open: Span::new(Location::new(1, 11, 10), Location::new(1, 12, 11))
+        })
+    );
+}
+
+#[test]
+fn sections_fold_into_nested_tables() {
+    let doc = document("title = \"x\"\n[server]\nhost = \"a\"\n[server.http]\nport = 80\n[db]\n");
+
+    let Some(Value::Table(server)) = doc.root.get("server") else {
+        panic!("expected server table");
+    };
+    let Some(Value::Table(http)) = server.get("http") else {
+        panic!("expected server.http table");
+    };
+    assert_eq!(server.get("host"), Some(&Value::String("a".into())));
+    assert_eq!(http.get("port"), Some(&Value::Integer(80)));
+    assert_eq!(doc.root.get("db"), Some(&Value::Table(Table::new())));
+    assert!(matches!(
+        parse_error("a = 1\n[a]"),
+        ErrorKind::Parsing(ParsingError::ConflictingKey { .. })
+    ));
+}
+
+#[test]
+fn arrays_nest_and_tolerate_trailing_commas() {
+    let doc =
+        document("empty = [] nested = [[1, 2,], [],] servers = [{ port = 1, }, { port = 2 }]");
+
+    assert_eq!(doc.root.get("empty"), Some(&Value::Array(alloc::vec![])));
+    assert_eq!(
+        doc.root.get("nested"),
+        Some(&Value::Array(alloc::vec![
+            Value::Array(alloc::vec![Value::Integer(1), Value::Integer(2)]),
+            Value::Array(alloc::vec![])
+        ]))
+    );
+
+    let Some(Value::Array(servers)) = doc.root.get("servers") else {
+        panic!("expected servers array");
+    };
+    assert_eq!(servers.len(), 2);
+    assert!(matches!(&servers[1], Value::Table(t) if t.get("port") == Some(&Value::Integer(2))));
+    assert!(matches!(
+        parse_error("a = [,]"),
+        ErrorKind::Parsing(ParsingError::UnexpectedToken { .. })
+    ));
+    assert_eq!(
+        parse_error("a = [1 2]"),
+        ErrorKind::Parsing(ParsingError::UnexpectedToken {
+            found: TokenKind::Integer,
+            expected: ExpectedSet::from_iter([
+                Expected::Token(TokenKind::RBrack),
+                Expected::Token(TokenKind::Comma)
+            ])
+        })
+    );
+}
+
+#[test]
+fn array_of_tables_headers_aggregate() {
+    let doc = document(
+        "[[servers]]\nhost = \"a\"\n[servers.tls]\nenabled = true\n[[servers]]\nhost = \"b\"\n",
+    );
+
+    let Some(Value::Array(servers)) = doc.root.get("servers") else {
+        panic!("expected servers array");
+    };
+    assert_eq!(servers.len(), 2);
+
+    let (Value::Table(first), Value::Table(second)) = (&servers[0], &servers[1]) else {
+        panic!("expected tables in servers");
+    };
+    assert_eq!(first.get("host"), Some(&Value::String("a".into())));
+    assert!(first.contains_key("tls"));
+    assert_eq!(second.get("host"), Some(&Value::String("b".into())));
+    assert!(!second.contains_key("tls"));
+}
+
+#[test]
+fn dotted_keys_create_tables_and_detect_conflicts() {
+    let doc = document("a.b.c = 1 a.b.d = 2 t = { x.y = true }");
+
+    let Some(Value::Table(a)) = doc.root.get("a") else {
+        panic!("expected a table");
+    };
+    let Some(Value::Table(b)) = a.get("b") else {
+        panic!("expected a.b table");
+    };
+    assert_eq!(b.get("c"), Some(&Value::Integer(1)));
+    assert_eq!(b.get("d"), Some(&Value::Integer(2)));
+
+    let src = Source::new("test", "a.b = 1\na.b.c = 2".into());
+    let error = parse(&src).unwrap_err();
+    assert_eq!(
+        error.kind,
+        ErrorKind::Parsing(ParsingError::ConflictingKey {
+            first: Span::new(Location::new(1, 3, 2), Location::new(1, 4, 3))
+        })
+    );
+    assert_eq!(src.slice(error.span), "b");
+    assert_eq!(error.span.begin.line, 2);
+    assert!(alloc::format!("{error}").ends_with("previously defined here\n1 | a.b = 1\n  |   ^"));
+}
+
+#[test]
+fn duplicate_keys_follow_policy() {
+    let src = Source::new("test", "a = 1 t = { x = 1 } a = 2 t = { y = 2 }".into());
+    let with = |duplicates| {
+        parse_with(
+            &src,
+            ParserOptions {
+                duplicates,
+                ..Default::default()
+            },
+        )
+        .map(|doc| doc.root)
+        .map_err(|e| e.kind)
+    };
+
+    assert_eq!(
+        with(DuplicatePolicy::Error),
+        Err(ErrorKind::Parsing(ParsingError::DuplicateKey {
+            first: Span::new(Location::new(1, 1, 0), Location::new(1, 2, 1))
+        }))
+    );
+
+    let last = with(DuplicatePolicy::LastWins).unwrap();
+    assert_eq!(last.get("a"), Some(&Value::Integer(2)));
+    assert!(
+        matches!(last.get("t"), Some(Value::Table(t)) if t.contains_key("y") && !t.contains_key("x"))
+    );
+
+    let first = with(DuplicatePolicy::FirstWins).unwrap();
+    assert_eq!(first.get("a"), Some(&Value::Integer(1)));
+
+    let src = Source::new("test", "t = { x = 1 } t = { y = 2 }".into());
+    let merged = parse_with(
+        &src,
+        ParserOptions {
+            duplicates: DuplicatePolicy::Merge,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(matches!(merged.root.get("t"), Some(Value::Table(t)) if t.len() == 2));
+}
+
+#[test]
+fn parse_modes_gate_lenient_syntax() {
+    let src = Source::new("test", "a = [1, 2,]\nb = \"\\q\"\na = 3".into());
+    let with = |mode| {
+        parse_with_warnings(
+            &src,
+            ParserOptions {
+                mode,
+                duplicates: DuplicatePolicy::LastWins,
+                ..Default::default()
+            },
+        )
+        .map(|(doc, warnings)| {
+            let kinds: alloc::vec::Vec<_> = warnings.into_iter().map(|w| w.kind).collect();
+            (doc.root, kinds)
+        })
+        .map_err(|e| (e.kind, e.span.begin.line))
+    };
+
+    let (root, warnings) = with(ParseMode::Lenient).unwrap();
+    assert_eq!(root.get("a"), Some(&Value::Integer(3)));
+    assert_eq!(root.get("b"), Some(&Value::from("\\q")));
+    assert!(matches!(
+        warnings[..],
+        [
+            ErrorKind::Parsing(ParsingError::TrailingComma),
+            ErrorKind::Parsing(ParsingError::UnknownEscape),
+            ErrorKind::Parsing(ParsingError::DuplicateKey { .. }),
+        ]
+    ));
+
+    assert_eq!(
+        with(ParseMode::Strict),
+        Err((ErrorKind::Parsing(ParsingError::TrailingComma), 1))
+    );
+
+    let src = Source::new("test", "b = { x = \"\\q\" }".into());
+    let strict = ParserOptions {
+        mode: ParseMode::Strict,
+        ..Default::default()
+    };
+    let error = parse_with(&src, strict).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Parsing(ParsingError::UnknownEscape));
+    assert_eq!((error.span.begin.col, error.span.end.col), (12, 14));
+
+    // duplicates are an error in either mode until a policy is chosen, which the mode never
+    // overrides
+    let src = Source::new("test", "a = 1\na = 2".into());
+    for mode in [ParseMode::Strict, ParseMode::Lenient] {
+        assert!(matches!(
+            parse_with(&src, ParserOptions::new().mode(mode)),
+            Err(Error {
+                kind: ErrorKind::Parsing(ParsingError::DuplicateKey { .. }),
+                ..
+            })
+        ));
+    }
+    let last = parse_with(&src, strict.duplicates(DuplicatePolicy::LastWins)).unwrap();
+    assert_eq!(last.get_i64("a"), Ok(2));
+    let first = parse_with(&src, strict.duplicates(DuplicatePolicy::FirstWins)).unwrap();
+    assert_eq!(first.get_i64("a"), Ok(1));
+}
+
+#[test]
+fn array_policy_controls_mixed_elements() {
+    let src = Source::new("test", "a = [1, 2]\nb = [1, \"two\", true]".into());
+    let homogeneous = ParserOptions {
+        arrays: ArrayPolicy::Homogeneous,
+        ..Default::default()
+    };
+
+    assert!(parse(&src).is_ok());
+
+    let error = parse_with(&src, homogeneous).unwrap_err();
+    assert_eq!(
+        error.kind,
+        ErrorKind::Parsing(ParsingError::MixedArray {
+            first: Span::new(Location::new(2, 6, 16), Location::new(2, 7, 17))
+        })
+    );
+    assert_eq!(error.span.begin.col, 9);
+    assert!(alloc::format!("{error}").contains("first element here"));
+
+    let mut parser = Parser::with_options(&src, lex(&src).unwrap(), homogeneous);
+    let events: core::result::Result<alloc::vec::Vec<_>, _> =
+        core::iter::from_fn(|| parser.next_event()).collect();
+    assert!(matches!(
+        events.map_err(|e| e.kind),
+        Err(ErrorKind::Parsing(ParsingError::MixedArray { .. }))
+    ));
+}
+
+#[test]
+fn tables_preserve_declaration_order() {
+    let mut doc = document("zeta = 1 alpha = 2 [mid] x = 1 [beta]");
+    let keys: alloc::vec::Vec<_> = doc.root.keys().map(|k| k.as_str()).collect();
+    assert_eq!(keys, ["zeta", "alpha", "mid", "beta"]);
+
+    doc.root.remove("alpha");
+    let keys: alloc::vec::Vec<_> = doc.root.keys().map(|k| k.as_str()).collect();
+    assert_eq!(keys, ["zeta", "mid", "beta"]);
+    assert_eq!(doc.root.position("beta"), Some(2));
+}
+
+#[test]
+fn recovery_collects_every_error() {
+    let src = Source::new(
+        "test",
+        "a = \nb = 1\nc = = 2\nd = [1 2]\n[t]\ne = true\nf = 1 f = 2\n".into(),
+    );
+    let (doc, errors) = parse_recovering(&src);
+
+    let kinds: alloc::vec::Vec<_> = errors.iter().map(|e| (e.kind, e.span.begin.line)).collect();
+    assert_eq!(
+        kinds,
+        [
+            (
+                ErrorKind::Parsing(ParsingError::UnexpectedToken {
+                    found: TokenKind::Ident,
+                    expected: Expected::Value.into()
+                }),
+                2
+            ),
+            (
+                ErrorKind::Parsing(ParsingError::UnexpectedToken {
+                    found: TokenKind::Equal,
+                    expected: Expected::Value.into()
+                }),
+                3
+            ),
+            (
+                ErrorKind::Parsing(ParsingError::UnexpectedToken {
+                    found: TokenKind::Integer,
+                    expected: ExpectedSet::from_iter([
+                        Expected::Token(TokenKind::RBrack),
+                        Expected::Token(TokenKind::Comma)
+                    ])
+                }),
+                4
+            ),
+            (
+                ErrorKind::Parsing(ParsingError::DuplicateKey {
+                    first: Span::new(Location::new(7, 1, 42), Location::new(7, 2, 43))
+                }),
+                7
+            ),
+        ]
+    );
+    assert_eq!(doc.root.get("b"), Some(&Value::Integer(1)));
+    assert!(
+        matches!(doc.root.get("t"), Some(Value::Table(t)) if t.get("e") == Some(&Value::Bool(true)))
+    );
+}
+
+#[test]
+fn parsing_errors_render_expectations() {
+    let src = Source::new("test.pcf", "a = [1, 2".into());
+    assert_eq!(
+        alloc::format!("{}", parse(&src).unwrap_err()),
+        "[test.pcf:1:10] PCF0115: encountered unclosed delimiter during parsing\n\
+         1 | a = [1, 2\n  |          ^\n\
+         [test.pcf:1 5..6] delimiter opened here\n\
+         1 | a = [1, 2\n  |     ^\n\
+         help: add a `]` to match the one opened on line 1"
+    );
+
+    let src = Source::new("test.pcf", "a b".into());
+    assert_eq!(
+        alloc::format!("{}", parse(&src).unwrap_err()),
+        "[test.pcf:1 3..4] PCF0117: encountered unexpected identifier during parsing, expected `=` or `.`\n\
+         1 | a b\n  |   ^"
+    );
+}
+
+#[test]
+fn unexpected_tokens_list_every_alternative_tried() {
+    let expected = |content| match parse_error(content) {
+        ErrorKind::Parsing(ParsingError::UnexpectedToken { expected, .. }) => {
+            alloc::format!("{}", expected)
+        }
+        kind => panic!("expected an unexpected token, found {:?}", kind),
+    };
+
+    assert_eq!(expected("= 1"), "`[` or key");
+    assert_eq!(expected("a b"), "`=` or `.`");
+    assert_eq!(expected("[a b]"), "`]` or `.`");
+    assert_eq!(expected("a = [1 2]"), "`]` or `,`");
+    assert_eq!(expected("a = [,]"), "`]` or value");
+    assert_eq!(expected("a = {x = 1 y = 2}"), "`}` or `,`");
+    assert_eq!(expected("a = ="), "value");
+
+    let set = ExpectedSet::from_iter([
+        Expected::Value,
+        Expected::Token(TokenKind::LBrace),
+        Expected::Token(TokenKind::Equal),
+        Expected::Token(TokenKind::LBrack),
+    ]);
+    assert_eq!(set.len(), 4);
+    assert!(set.contains(Expected::Token(TokenKind::Equal)));
+    assert!(!set.contains(Expected::Key));
+    assert_eq!(alloc::format!("{}", set), "`[`, `{`, `=`, or value");
+    assert_eq!(
+        alloc::format!("{:?}", ExpectedSet::from(Expected::Key)),
+        "{Key}"
+    );
+}
+
+#[test]
+fn errors_carry_notes_and_help() {
+    let src = Source::new("test.pcf", "a = b\"\\q\"".into());
+    let error = parse(&src).unwrap_err();
+    assert_eq!(error.note(), Some(&Hint::ByteEscapes));
+    assert_eq!(
+        alloc::format!("{}", Hint::ByteEscapes),
+        "byte strings only accept `\\n`, `\\t`, `\\r`, `\\0`, `\\\\`, `\\\"` and `\\x` followed by two hex digits"
+    );
+    assert_eq!(error.help(), None);
+
+    let src = Source::new("test.pcf", "a = 9223372036854775808".into());
+    let error = parse(&src).unwrap_err().into_owned();
+    assert_eq!(
+        error
+            .note()
+            .map(|note| alloc::format!("{}", note))
+            .as_deref(),
+        Some("integers must be between -9223372036854775808 and 9223372036854775807")
+    );
+
+    let src = Source::new("test.pcf", "a = 1".into());
+    let error = Error::parsing(ParsingError::UnexpectedEof, Span::default(), &src)
+        .with_help("finish the entry")
+        .with_note("the source ended early");
+    assert_eq!(
+        alloc::format!("{}", error),
+        "[test.pcf:1:1] PCF0116: encountered unexpected end of input during parsing\n\
+         1 | a = 1\n  | ^\n\
+         note: the source ended early\n\
+         help: finish the entry"
+    );
+    assert_eq!(
+        alloc::format!("{}", error.clone().into_owned()),
+        alloc::format!("{}", error)
+    );
+
+    let diagnostic = crate::Diagnostic::from(error);
+    assert_eq!(diagnostic.notes, ["the source ended early"]);
+    assert_eq!(diagnostic.help.as_deref(), Some("finish the entry"));
+}
+
+#[test]
+fn errors_render_context_and_multiline_spans() {
+    let src = Source::new("test.pcf", "x = 1\n\tb = \"open\ny = 2".into());
+    let error = parse(&src).unwrap_err();
+    let render = |context| {
+        let mut out = alloc::string::String::new();
+        error
+            .render(&mut out, &RenderOptions::new().context(context))
+            .unwrap();
+        out
+    };
+
+    assert_eq!(
+        render(false),
+        "[test.pcf 2:6..3:6] PCF0003: encountered unterminated string during lexing\n\
+         2 | \tb = \"open\n  | \t    ^^^^^\n\
+         3 | y = 2\n  | ^^^^^\n\
+         help: strings must be closed with `\"`, did you forget a closing quote on line 2?"
+    );
+    assert_eq!(
+        render(true),
+        "[test.pcf 2:6..3:6] PCF0003: encountered unterminated string during lexing\n\
+         1 | x = 1\n\
+         2 | \tb = \"open\n  | \t    ^^^^^\n\
+         3 | y = 2\n  | ^^^^^\n\
+         help: strings must be closed with `\"`, did you forget a closing quote on line 2?"
+    );
+    let (expected, mut owned) = (render(true), alloc::string::String::new());
+    error
+        .into_owned()
+        .render(&mut owned, &RenderOptions::new().context(true))
+        .unwrap();
+    assert_eq!(owned, expected);
+}
+
+#[test]
+fn errors_render_in_color() {
+    let src = Source::new("test.pcf", "a = 1\na = 2".into());
+    let mut out = alloc::string::String::new();
+    parse(&src)
+        .unwrap_err()
+        .render(&mut out, &RenderOptions::new().color(true))
+        .unwrap();
+
+    assert_eq!(
+        out,
+        "\x1b[1;31m[test.pcf:2 1..2] PCF0106: encountered duplicate key during parsing\x1b[0m\n\
+         2 | a = 2\n  | \x1b[1;31m^\x1b[0m\n\
+         \x1b[1;33m[test.pcf:1 1..2] previously defined here\x1b[0m\n\
+         1 | a = 1\n  | \x1b[1;33m^\x1b[0m"
+    );
+}
+
+#[test]
+fn error_kinds_have_stable_codes() {
+    use alloc::vec::Vec;
+
+    let kinds = [
+        ErrorKind::Lexing(LexingError::UnterminatedString),
+        ErrorKind::Parsing(ParsingError::DuplicateKey {
+            first: Span::default(),
+        }),
+        ErrorKind::Resolving(ResolvingError::CyclicReference),
+    ];
+    let codes: Vec<_> = kinds.iter().map(ErrorKind::code).collect();
+    assert_eq!(codes, ["PCF0003", "PCF0106", "PCF0201"]);
+
+    let src = Source::new("test", "a = \"open".into());
+    let error = parse(&src).unwrap_err();
+    assert!(alloc::format!("{error}").starts_with("[test:1 5..10] PCF0003: "));
+}
+
+#[test]
+#[cfg(feature = "miette")]
+fn errors_convert_into_miette_reports() {
+    use ::miette::{NarratableReportHandler, Report};
+    use alloc::string::String;
+
+    let render = |report: Report| {
+        let mut out = String::new();
+        NarratableReportHandler::new()
+            .render_report(&mut out, report.as_ref())
+            .unwrap();
+        out
+    };
+
+    let content = "x = 1\ny = 2\na = 3\nb = 4\na = 5";
+    let owned = render(crate::parse_str("app.pcf", content).unwrap_err().into());
+
+    let src = Source::new("app.pcf", content.into());
+    let borrowed = render(parse(&src).unwrap_err().into());
+
+    assert_eq!(owned, borrowed);
+    assert!(owned.starts_with("encountered duplicate key during parsing\n"));
+    assert!(owned.contains("Begin snippet for app.pcf starting at line 2, column 1"));
+    assert!(owned.contains("label at line 3, column 1: previously defined here"));
+    assert!(owned.contains("label at line 5, column 1\ndiagnostic code: PCF0106"));
+}
+
+#[test]
+#[cfg(feature = "ariadne")]
+fn errors_build_ariadne_reports() {
+    use ::ariadne::Config;
+    use alloc::{string::String, vec::Vec};
+
+    let src = Source::new("app.pcf", "\"h\u{e9}\" = 1\n\"h\u{e9}\" = 2".into());
+    let error = parse(&src).unwrap_err();
+
+    let mut out = Vec::new();
+    error
+        .report()
+        .with_config(Config::default().with_color(false))
+        .finish()
+        .write(error.cache(), &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.starts_with("[PCF0106] Error: encountered duplicate key during parsing"));
+    assert!(out.contains("app.pcf:2:1"));
+    assert!(out.contains("previously defined here"));
+    assert!(out.contains("\"h\u{e9}\" = 1"));
+    assert!(out.contains("\"h\u{e9}\" = 2"));
+}
+
+#[test]
+fn parsing_collects_errors_and_warnings_as_diagnostics() {
+    use crate::{
+        diagnostic::*,
+        schema::{Field, Schema},
+    };
+    use alloc::{string::String, vec::Vec};
+
+    let src = Source::new("app.pcf", "a = 1\na = 2\nb =\nc = 3".into());
+    let options = ParserOptions::new().duplicates(DuplicatePolicy::LastWins);
+    let (doc, diagnostics) = crate::parse_diagnostics(&src, options);
+
+    assert_eq!(doc.get_i64("a"), Ok(2));
+    assert_eq!(doc.get_i64("c"), Ok(3));
+    assert!(diagnostics.has_errors());
+    assert_eq!(
+        diagnostics
+            .iter()
+            .map(|d| (d.severity, d.code))
+            .collect::<Vec<_>>(),
+        [(Severity::Warning, "PCF0106"), (Severity::Error, "PCF0117")]
+    );
+
+    let mut out = String::new();
+    diagnostics
+        .render(&mut out, &src, &RenderOptions::new())
+        .unwrap();
+    assert_eq!(
+        out,
+        "[app.pcf:2 1..2] warning PCF0106: encountered duplicate key during parsing\n\
+         2 | a = 2\n  | ^\n\
+         [app.pcf:1 1..2] previously defined here\n1 | a = 1\n  | ^\n\n\
+         [app.pcf:4 1..2] PCF0117: encountered unexpected identifier during parsing, \
+         expected value\n4 | c = 3\n  | ^"
+    );
+
+    let src = Source::new("app.pcf", "port = \"x\"".into());
+    let schema = Schema::new().field("port", Field::integer());
+    let diagnostics = schema.check(&parse(&src).unwrap()).diagnostics();
+    assert_eq!(
+        alloc::format!("{diagnostics}"),
+        "[port:1 8..11] PCF0302: encountered string during validation, expected integer"
+    );
+
+    let mut out = String::new();
+    diagnostics
+        .render(&mut out, &src, &RenderOptions::new())
+        .unwrap();
+    assert_eq!(
+        out,
+        "[app.pcf:1 8..11] PCF0302: encountered string during validation, expected integer\n\
+         1 | port = \"x\"\n  |        ^^^ port"
+    );
+}
+
+#[test]
+fn lexer_warnings_do_not_fail_the_parse() {
+    use alloc::vec::Vec;
+
+    let (doc, warnings) =
+        crate::parse_str_with_warnings("app.pcf", "a = 1\n \t b = 2\nc\u{a0}= 3").unwrap();
+    let warnings: Vec<_> = warnings.iter().map(|w| (w.kind, w.span)).collect();
+
+    assert_eq!(doc.get_i64("b"), Ok(2));
+    assert_eq!(doc.get_i64("c"), Ok(3));
+    assert_eq!(
+        warnings,
+        [
+            (
+                ErrorKind::Lexing(LexingError::MixedIndentation),
+                Span::new(Location::new(2, 1, 6), Location::new(2, 3, 8))
+            ),
+            (
+                ErrorKind::Lexing(LexingError::UnusualWhitespace { found: '\u{a0}' }),
+                Span::new(Location::new(3, 2, 16), Location::new(3, 3, 18))
+            ),
+        ]
+    );
+    assert_eq!(warnings[1].0.code(), "PCF0008");
+
+    let src = Source::new("app.pcf", "a = 1\n\tb = 2\n    c = 3".into());
+    let (_, diagnostics) = crate::parse_diagnostics(&src, ParserOptions::new());
+    assert!(diagnostics.is_empty());
+}
+
+#[test]
+fn lint_levels_allow_or_deny_warnings() {
+    use crate::{
+        diagnostic::{Level, Lints},
+        schema::*,
+    };
+
+    let src = Source::new("app.pcf", "a = 1\n \t b = 2\na = 3".into());
+    let options = ParserOptions::new().duplicates(DuplicatePolicy::LastWins);
+    let codes = |lints| {
+        parse_with_warnings(&src, options.lints(lints))
+            .map(|(doc, warnings)| {
+                assert_eq!(doc.get_i64("a"), Ok(3));
+                warnings
+                    .iter()
+                    .map(|w| w.kind.code())
+                    .collect::<alloc::vec::Vec<_>>()
+            })
+            .map_err(|e| e.kind.code())
+    };
+
+    assert_eq!(codes(Lints::new()), Ok(alloc::vec!["PCF0007", "PCF0106"]));
+    assert_eq!(
+        codes(Lints::new().allow("PCF0007").warn("PCF0106")),
+        Ok(alloc::vec!["PCF0106"])
+    );
+    assert_eq!(codes(Lints::new().deny("PCF0007")), Err("PCF0007"));
+    assert_eq!(codes(Lints::new().deny("PCF0106")), Err("PCF0106"));
+
+    let src = Source::new("app.pcf", "a = [1, 2,]".into());
+    let commas = |lints| {
+        parse_with_warnings(&src, ParserOptions::new().lints(lints))
+            .map(|(_, warnings)| warnings.len())
+            .map_err(|e| e.kind.code())
+    };
+    assert_eq!(commas(Lints::new()), Ok(1));
+    assert_eq!(commas(Lints::new().allow("PCF0114")), Ok(0));
+    assert_eq!(commas(Lints::new().deny("PCF0114")), Err("PCF0114"));
+
+    let lints = Lints::new().level("PCF0402", Level::Deny).allow("PCF0117");
+    assert_eq!(lints.get("PCF0402"), Some(Level::Deny));
+    assert_eq!(lints.get("PCF0401"), Some(Level::Warn));
+    // errors aren't lints, so they have no level to set
+    assert_eq!(lints.get("PCF0117"), None);
+
+    let schema = Schema::new()
+        .field("port", Field::integer())
+        .field("hostname", Field::string().replaced_by("host"));
+    let options = ValidationOptions {
+        unknown_keys: UnknownKeyPolicy::Warn,
+        lints: Lints::new().deny("PCF0401").allow("PCF0402"),
+    };
+    let validation = schema.check_with(&document("prot = 1\nhostname = \"a\""), options);
+
+    assert!(validation.warnings.is_empty());
+    assert_eq!(
+        validation.errors,
+        [SchemaError {
+            path: KeyPath::parse("hostname").unwrap(),
+            span: Span::new(Location::new(2, 1, 9), Location::new(2, 9, 17)),
+            kind: SchemaErrorKind::DeprecatedKey,
+            suggestion: Some("host".into()),
+        }]
+    );
+    assert_eq!(validation.errors[0].kind.code(), "PCF0306");
+}
+
+#[test]
+#[cfg(feature = "unicode")]
+fn lexer_warns_about_lookalike_characters() {
+    let src = Source::new(
+        "app.pcf",
+        "p\u{43e}rt = 1\n\u{43f}\u{43e}\u{440}\u{442} = 2".into(),
+    );
+    let (_, warnings) = parse_with_warnings(&src, ParserOptions::new()).unwrap();
+
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+        warnings[0].kind,
+        ErrorKind::Lexing(LexingError::ConfusableCharacter {
+            found: '\u{43e}',
+            expected: 'o'
+        })
+    );
+    assert_eq!(
+        alloc::format!("{}", warnings[0].kind),
+        "encountered `\u{43e}` (U+043E) resembling `o` during lexing"
+    );
+}
+
+#[test]
+fn typed_accessors_match_variants() {
+    let doc = document("s = \"x\" i = 1 f = 1.5 b = true a = [1] t = { k = 1 }");
+    let get = |key| doc.root.get(key).unwrap();
+
+    assert_eq!(get("s").as_str(), Some("x"));
+    assert_eq!(get("i").as_i64(), Some(1));
+    assert_eq!(get("f").as_f64(), Some(1.5));
+    assert_eq!(get("b").as_bool(), Some(true));
+    assert_eq!(get("a").as_array().map(|a| a.len()), Some(1));
+    assert!(get("t").as_table().is_some_and(|t| t.contains_key("k")));
+    assert_eq!(get("i").as_str(), None);
+    assert!(get("f").is_f64() && !get("f").is_i64());
+    assert_eq!(get("t").kind(), ValueKind::Table);
+    assert_eq!(alloc::format!("{}", get("b").kind()), "boolean");
+}
+
+#[test]
+fn document_resolves_dotted_paths() {
+    let doc = document(
+        "[server.http]\nport = 8080\n[[servers]]\nhost = \"a\"\n[[servers]]\nhost = \"b\"\n\
+         [grid]\ncells = [[1, 2], [3, 4]]",
+    );
+
+    assert_eq!(doc.get_i64("server.http.port"), Ok(8080));
+    assert_eq!(doc.get_str("servers[1].host"), Ok("b"));
+    assert_eq!(doc.get_i64("grid.cells[1][0]"), Ok(3));
+    assert!(doc.get_table("server").is_ok());
+    assert_eq!(doc.get("servers[2].host"), None);
+    assert_eq!(doc.get("server.http.port.x"), None);
+    assert_eq!(doc.get("servers[x]"), None);
+    assert!(doc.get_str("server.http.port").is_err());
+}
+
+#[test]
+fn key_paths_parse_and_display() {
+    let path = KeyPath::parse("servers[1].\"host.name\"[0][2].x").unwrap();
+    assert_eq!(
+        path.iter().cloned().collect::<alloc::vec::Vec<_>>(),
+        [
+            PathSegment::Key("servers".into()),
+            PathSegment::Index(1),
+            PathSegment::Key("host.name".into()),
+            PathSegment::Index(0),
+            PathSegment::Index(2),
+            PathSegment::Key("x".into()),
+        ]
+    );
+    assert_eq!(
+        alloc::format!("{}", path),
+        "servers[1].\"host.name\"[0][2].x"
+    );
+    assert_eq!("servers[1]".parse::<KeyPath>().unwrap().len(), 2);
+
+    for malformed in ["", "a..b", "a[x]", "a[1", "\"a", "a.", "a]"] {
+        assert_eq!(KeyPath::parse(malformed), Err(PathError::Malformed));
+    }
+
+    let mut doc = document("\"a.b\" = { \"c\\\"d\" = 1 }\nlist = [{ x = 2 }]");
+    let quoted = KeyPath::parse("\"a.b\".\"c\\\"d\"").unwrap();
+    assert_eq!(doc.get(&quoted), Some(&Value::Integer(1)));
+    assert_eq!(doc.get_i64("list[0].x"), Ok(2));
+    assert_eq!(
+        KeyPath::parse(&alloc::format!("{}", quoted)),
+        Ok(quoted.clone())
+    );
+
+    assert_eq!(
+        doc.set(&quoted, Value::Integer(3)),
+        Ok(Some(Value::Integer(1)))
+    );
+    assert_eq!(
+        doc.set("a..b", Value::Integer(3)),
+        Err(PathError::Malformed)
+    );
+    assert_eq!(doc.remove(quoted), Some(Value::Integer(3)));
+    assert!(doc.get("a.b").is_none());
+}
+
+#[test]
+fn typed_accessors_report_spans_and_types() {
+    use crate::extract::*;
+
+    let doc = document("[server]\nport = \"80\"\nhosts = [\"a\", 1]");
+
+    let error = doc.get_i64("server.port").unwrap_err();
+    assert_eq!(alloc::format!("{}", error.path), "server.port");
+    assert_eq!(error.span.begin.line, 2);
+    assert_eq!(
+        error.kind,
+        ExtractErrorKind::MismatchedType {
+            expected: ValueKind::Integer,
+            found: ValueKind::String,
+        }
+    );
+    assert_eq!(
+        alloc::format!("{}", error),
+        "[server.port:2 8..12] encountered string during extraction, expected integer"
+    );
+
+    let error = doc.get_str("server.hosts[1]").unwrap_err();
+    assert_eq!(alloc::format!("{}", error.path), "server.hosts[1]");
+    assert_eq!(error.span.begin.line, 3);
+
+    let error = doc.get_bool("server.tls.enabled").unwrap_err();
+    assert_eq!(error.kind, ExtractErrorKind::MissingKey);
+    assert_eq!(alloc::format!("{}", error.path), "server.tls");
+}
+
+#[test]
+fn documents_can_be_edited() {
+    let mut doc = document("[server]\nhost = \"a\"\nports = [1, 2]\nname = \"x\"");
+
+    assert_eq!(
+        doc.set("server.host", Value::String("b".into())),
+        Ok(Some(Value::String("a".into())))
+    );
+    assert_eq!(doc.set("server.ports[2]", Value::Integer(3)), Ok(None));
+    assert_eq!(
+        doc.set("server.ports[9]", Value::Integer(3)),
+        Err(PathError::OutOfBounds)
+    );
+    assert_eq!(doc.set("cache.size.max", Value::Integer(64)), Ok(None));
+    assert_eq!(
+        doc.set("server.host.x", Value::Integer(1)),
+        Err(PathError::NotATable)
+    );
+    assert_eq!(doc.get_i64("cache.size.max"), Ok(64));
+    assert_eq!(doc.get_i64("server.ports[2]"), Ok(3));
+
+    // a set that fails leaves the document as it was
+    let before = doc.clone();
+    assert_eq!(
+        doc.set("x.y[3].z", Value::Integer(1)),
+        Err(PathError::NotAnArray)
+    );
+    assert_eq!(
+        doc.set("server.tls.ports[0]", Value::Integer(1)),
+        Err(PathError::NotAnArray)
+    );
+    assert_eq!(
+        doc.set("server.ports[1].x", Value::Integer(1)),
+        Err(PathError::NotATable)
+    );
+    assert_eq!(doc, before);
+    assert!(doc.get("x").is_none());
+
+    assert_eq!(doc.remove("server.ports[0]"), Some(Value::Integer(1)));
+    assert_eq!(doc.remove("server.name"), Some(Value::String("x".into())));
+    assert_eq!(doc.remove("server.name"), None);
+
+    doc.insert_after("server.host", "tls", Value::Bool(true))
+        .unwrap();
+    let keys: alloc::vec::Vec<_> = doc
+        .get_table("server")
+        .unwrap()
+        .keys()
+        .map(|k| k.as_str())
+        .collect();
+    assert_eq!(keys, ["host", "tls", "ports"]);
+
+    if let Some(Value::Integer(port)) = doc.get_mut("server.ports[0]") {
+        *port = 20;
+    }
+    assert_eq!(doc.get_i64("server.ports[0]"), Ok(20));
+}
+
+#[test]
+fn comments_attach_to_nearest_entries() {
+    let doc = document(
+        "# about a\na = 1 # inline a\n# about server\n[server] # main\n# about port\n\
+         port = 80\nt = { x = 1, # inline x\n# dangling x\n}\n# end\n",
+    );
+    let entry = |table: &Table, key| table.get_entry(key).unwrap().comments.clone();
+
+    assert_eq!(
+        entry(&doc.root, "a"),
+        Comments {
+            leading: alloc::vec![" about a".into()],
+            inline: Some(" inline a".into()),
+            trailing: alloc::vec![],
+        }
+    );
+
+    let server = entry(&doc.root, "server");
+    assert_eq!(server.leading, [" about server"]);
+    assert_eq!(server.inline.as_deref(), Some(" main"));
+
+    let server = doc.get_table("server").unwrap();
+    assert_eq!(entry(server, "port").leading, [" about port"]);
+    assert_eq!(entry(server, "t").trailing, [" end"]);
+
+    let x = entry(doc.get_table("server.t").unwrap(), "x");
+    assert_eq!(x.inline.as_deref(), Some(" inline x"));
+    assert_eq!(x.trailing, [" dangling x"]);
+
+    assert_eq!(
+        document("# only\n# comments").comments,
+        [" only", " comments"]
+    );
+}
+
+#[test]
+fn cst_round_trips_and_edits_in_place() {
+    use crate::cst::*;
+
+    let content = "# config\r\ntitle   =  \"pcf\"   # name\n\n[server]\n  port = 8080\n  hosts = [ \"a\",\n    { name = \"b\",  weight = 2 }, # second\n  ]\n[[jobs]]\nid = 1\n[[jobs]]\nid = 2\n";
+    let src = Source::new("test", content.into());
+    let mut cst = Cst::parse(&src).unwrap();
+    assert_eq!(alloc::format!("{cst}"), content);
+
+    assert!(cst.set("server.port", &Value::Integer(9090)));
+    assert!(cst.set("server.hosts[1].weight", &Value::Integer(5)));
+    assert!(cst.set("jobs[1].id", &Value::String("two".into())));
+    assert!(!cst.set("server.missing", &Value::Integer(1)));
+    assert_eq!(
+        alloc::format!("{cst}"),
+        content
+            .replace("8080", "9090")
+            .replace("weight = 2", "weight = 5")
+            .replace("id = 2", "id = \"two\"")
+    );
+}
+
+#[test]
+fn walk_visits_every_value_with_its_path() {
+    let doc = document("a = 1\n[s]\nlist = [true, { x = 2 }]");
+    let mut seen = alloc::vec::Vec::new();
+    doc.walk(|path, value, span| {
+        seen.push((alloc::format!("{path}"), value.kind(), span.begin.line))
+    });
+
+    assert_eq!(
+        seen,
+        [
+            ("a".into(), ValueKind::Integer, 1),
+            ("s".into(), ValueKind::Table, 2),
+            ("s.list".into(), ValueKind::Array, 3),
+            ("s.list[0]".into(), ValueKind::Bool, 3),
+            ("s.list[1]".into(), ValueKind::Table, 3),
+            ("s.list[1].x".into(), ValueKind::Integer, 3),
+        ]
+    );
+
+    #[derive(Default)]
+    struct Depth {
+        current: usize,
+        max: usize,
+    }
+
+    impl Visit for Depth {
+        fn enter_table(&mut self, _: &KeyPath, _: &Table, _: Span) {
+            self.current += 1;
+            self.max = self.max.max(self.current);
+        }
+
+        fn exit_table(&mut self, _: &KeyPath, _: &Table, _: Span) {
+            self.current -= 1;
+        }
+    }
+
+    let mut depth = Depth::default();
+    doc.visit(&mut depth);
+    assert_eq!((depth.current, depth.max), (0, 3));
+}
+
+#[test]
+fn builder_constructs_documents() {
+    use crate::builder::*;
+
+    let doc = Document::builder()
+        .set("name", "pcf")
+        .table("server", |t| {
+            t.set("port", 8080)
+                .array("hosts", ["a", "b"])
+                .table("tls", |t| t.set("enabled", true))
+        })
+        .build()
+        .unwrap();
+
+    assert_eq!(doc.get_str("name"), Ok("pcf"));
+    assert_eq!(doc.get_i64("server.port"), Ok(8080));
+    assert_eq!(doc.get_str("server.hosts[1]"), Ok("b"));
+    assert_eq!(doc.get_bool("server.tls.enabled"), Ok(true));
+
+    let error = Document::builder()
+        .table("server", |t| t.set("port", 1).set("port", 2))
+        .build()
+        .unwrap_err();
+    assert_eq!(error.kind, BuildErrorKind::DuplicateKey);
+    assert_eq!(alloc::format!("{}", error.path), "server.port");
+
+    let error = Document::builder().set("a\nb", 1).build().unwrap_err();
+    assert_eq!(error.kind, BuildErrorKind::InvalidKey);
+}
+
+#[test]
+fn documents_deep_merge_with_strategies() {
+    use crate::merge::*;
+
+    let base = document("[server]\nhost = \"a\"\nport = 80\ntags = [1]\n[log]\nlevel = \"info\"");
+    let overlay = document("[server]\nport = 443\ntags = [2]\ntls = true");
+
+    let mut merged = base.clone();
+    merged.merge(&overlay, MergeStrategy::Override);
+    assert_eq!(merged.get_str("server.host"), Ok("a"));
+    assert_eq!(merged.get_i64("server.port"), Ok(443));
+    assert_eq!(
+        merged.get("server.tags"),
+        Some(&Value::Array(alloc::vec![Value::Integer(2)]))
+    );
+    assert_eq!(merged.get_bool("server.tls"), Ok(true));
+    assert_eq!(merged.get_str("log.level"), Ok("info"));
+
+    let mut appended = base.clone();
+    appended.merge(&overlay, MergeStrategy::Append);
+    assert_eq!(appended.get_array("server.tags").map(|a| a.len()), Ok(2));
+
+    let mut kept = base.clone();
+    kept.merge(&overlay, MergeStrategy::KeepExisting);
+    assert_eq!(kept.get_i64("server.port"), Ok(80));
+    assert_eq!(kept.get_bool("server.tls"), Ok(true));
+}
+
+#[test]
+fn includes_are_spliced_and_cycles_rejected() {
+    use crate::include::*;
+    use alloc::{collections::btree_map::BTreeMap, string::String};
+
+    let mut resolver = BTreeMap::new();
+    let mut add = |path: &str, content: &str| {
+        resolver.insert(String::from(path), String::from(content));
+    };
+    add("main", "name = \"app\"\n[db]\n@include \"db\"\n");
+    add("db", "host = \"localhost\"\n@include \"port\"");
+    add("port", "port = 5432");
+    add("loop", "@include \"loop\"");
+    add("broken", "@include \"missing\"\n@include \"bad\"");
+    add("bad", "x = ");
+    add(
+        "text",
+        "a = \"x\n@include \\\"missing\\\"\"\nb = 1 @include \"port\"",
+    );
+    add("value", "a = [@include \"missing\"]");
+
+    let files = Files::load(&resolver, "main").unwrap();
+    let sources = files.sources();
+    let doc = parse_with_includes(&sources[0], &sources, ParserOptions::default()).unwrap();
+    assert_eq!(doc.get_str("name"), Ok("app"));
+    assert_eq!(doc.get_str("db.host"), Ok("localhost"));
+    assert_eq!(doc.get_i64("db.port"), Ok(5432));
+
+    let files = Files::load(&resolver, "loop").unwrap();
+    let sources = files.sources();
+    let error = parse_with_includes(&sources[0], &sources, ParserOptions::default()).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Parsing(ParsingError::IncludeCycle));
+    assert_eq!(error.src.slice(error.span), "@include \"loop\"");
+
+    let error = Files::load(&resolver, "broken").unwrap_err();
+    assert_eq!((error.path.as_str(), error.error), ("missing", NotFound));
+    assert_eq!(
+        alloc::format!("{}", error),
+        "[broken:1 1..19] failed to load `missing`: no such file"
+    );
+
+    // sources put together by hand can still leave an include out
+    let bad = [Source::new("bad", "x = ".into())];
+    let src = Source::new("root", "@include \"missing\"\n@include \"bad\"".into());
+    let error = parse_with_includes(&src, &bad, ParserOptions::default()).unwrap_err();
+    assert_eq!(
+        error.kind,
+        ErrorKind::Parsing(ParsingError::IncludeNotFound)
+    );
+
+    let src = Source::new("root", "@include \"bad\"".into());
+    let error = parse_with_includes(&src, &bad, ParserOptions::default()).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Parsing(ParsingError::UnexpectedEof));
+    assert_eq!(error.src.file, "bad");
+
+    // only directives the parser would act on are loaded, so a path in a string or in a value
+    // doesn't count, while one sharing a line with another statement still does
+    let files = Files::load(&resolver, "text").unwrap();
+    let sources = files.sources();
+    assert_eq!(
+        sources
+            .iter()
+            .map(|s| s.file)
+            .collect::<alloc::vec::Vec<_>>(),
+        ["text", "port"]
+    );
+    let doc = parse_with_includes(&sources[0], &sources, ParserOptions::default()).unwrap();
+    assert_eq!(doc.get_str("a"), Ok("x\n@include \"missing\""));
+    assert_eq!(doc.get_i64("port"), Ok(5432));
+
+    let files = Files::load(&resolver, "value").unwrap();
+    let sources = files.sources();
+    assert_eq!(sources.len(), 1);
+    let error = parse_with_includes(&sources[0], &sources, ParserOptions::default()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ErrorKind::Parsing(ParsingError::UnexpectedToken { .. })
+    ));
+
+    let error = Files::load(&resolver, "missing").unwrap_err();
+    assert_eq!(error.included, None);
+    assert_eq!(
+        alloc::format!("{}", error),
+        "failed to load `missing`: no such file"
+    );
+}
+
+#[test]
+fn errors_in_included_files_report_the_include_chain() {
+    use crate::include::*;
+    use alloc::vec::Vec;
+
+    let map = SourceMap::new(Source::new(
+        "main",
+        "name = \"app\"\n@include \"db\"".into(),
+    ))
+    .with(Source::new("db", "host = \"x\"\n@include \"port\"".into()))
+    .with(Source::new("port", "port = ]".into()));
+    let error = map.parse(ParserOptions::default()).unwrap_err();
+
+    assert_eq!(error.src.file, "port");
+    assert_eq!(map.slice(error.src.file, error.span), Some("]"));
+    assert_eq!(
+        error
+            .included()
+            .iter()
+            .map(|included| (
+                included.file.as_str(),
+                map.slice(&included.file, included.span)
+            ))
+            .collect::<Vec<_>>(),
+        [
+            ("db", Some("@include \"port\"")),
+            ("main", Some("@include \"db\"")),
+        ]
+    );
+    assert_eq!(
+        alloc::format!("{}", error),
+        "[port:1 8..9] PCF0117: encountered unexpected `]` during parsing, expected value\n\
+         1 | port = ]\n  |        ^\n\
+         [db:2 1..16] included from here\n\
+         2 | @include \"port\"\n  | ^^^^^^^^^^^^^^^\n\
+         [main:2 1..14] included from here\n\
+         2 | @include \"db\"\n  | ^^^^^^^^^^^^^"
+    );
+    assert_eq!(
+        alloc::format!("{}", error.clone().into_owned()),
+        alloc::format!("{}", error)
+    );
+    assert_eq!(
+        crate::Diagnostic::from(error).notes,
+        ["included from db:2 1..16", "included from main:2 1..14"]
+    );
+
+    // keys clashing with ones from an included file are reported at the directive
+    let map = SourceMap::new(Source::new("main", "a = 1\n@include \"dup\"".into()))
+        .with(Source::new("dup", "a = 2".into()));
+    let error = map.parse(ParserOptions::default()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ErrorKind::Parsing(ParsingError::DuplicateKey { .. })
+    ));
+    assert_eq!(
+        map.slice(error.src.file, error.span),
+        Some("@include \"dup\"")
+    );
+    assert!(error.included().is_empty());
+
+    let options = ParserOptions::new().duplicates(DuplicatePolicy::LastWins);
+    let (doc, warnings) = Parser::with_options(map.root(), lex(map.root()).unwrap(), options)
+        .with_includes(map.sources())
+        .parse_with_warnings()
+        .unwrap();
+    assert_eq!(doc.get_i64("a"), Ok(2));
+    assert_eq!(warnings[0].src.slice(warnings[0].span), "@include \"dup\"");
+
+    let mut resolver = alloc::collections::btree_map::BTreeMap::new();
+    resolver.insert("main".into(), "@include \"dup\"".into());
+    resolver.insert("dup".into(), "a = 2".into());
+    let files = Files::load(&resolver, "main").unwrap();
+    let map = files.source_map();
+    assert_eq!(map.root().file, "main");
+    assert_eq!(
+        map.get("dup").map(|src| src.content.as_str()),
+        Some("a = 2")
+    );
+    assert_eq!(
+        map.parse(ParserOptions::default()).unwrap().get_i64("a"),
+        Ok(2)
+    );
+}
+
+#[test]
+fn strings_interpolate_other_keys() {
+    use crate::resolve::*;
+
+    let src = Source::new(
+        "test",
+        "url = \"http://${server.host}:${server.port}/${path}\"\npath = \"v${version}\"\nversion = 2\n[server]\nhost = \"localhost\"\nport = 8080".into(),
+    );
+    let mut doc = parse(&src).unwrap();
+    interpolate(&src, &mut doc).unwrap();
+    assert_eq!(doc.get_str("url"), Ok("http://localhost:8080/v2"));
+    assert_eq!(doc.get_str("path"), Ok("v2"));
+
+    let failure = |content: &str| {
+        let src = Source::new("test", content.into());
+        let mut doc = parse(&src).unwrap();
+        let error = interpolate(&src, &mut doc).unwrap_err();
+        (
+            error.kind,
+            alloc::string::String::from(src.slice(error.span)),
+        )
+    };
+
+    assert_eq!(
+        failure("a = \"x ${missing} y\""),
+        (
+            ErrorKind::Resolving(ResolvingError::MissingReference),
+            "${missing}".into()
+        )
+    );
+    assert_eq!(
+        failure("a = \"${t}\"\nt = {}"),
+        (
+            ErrorKind::Resolving(ResolvingError::InvalidReference),
+            "${t}".into()
+        )
+    );
+    assert_eq!(
+        failure("a = \"${b}\"\nb = \"${a}\"").0,
+        ErrorKind::Resolving(ResolvingError::CyclicReference)
+    );
+    assert_eq!(
+        failure("a = \"${b\"").0,
+        ErrorKind::Resolving(ResolvingError::UnterminatedInterpolation)
+    );
+}
+
+#[test]
+fn constant_expressions_are_evaluated() {
+    let doc = document(
+        "workers = 2 * 4\nbase = 90\nlimit = base + 10\nratio = limit / 8.0 - 1\nname = \"pc\" + \"f\"\nall = [1] + [2, 3]",
+    );
+    assert_eq!(doc.get_i64("workers"), Ok(8));
+    assert_eq!(doc.get_i64("limit"), Ok(100));
+    assert_eq!(doc.get_f64("ratio"), Ok(11.5));
+    assert_eq!(doc.get_str("name"), Ok("pcf"));
+    assert_eq!(doc.get_array("all").map(|a| a.len()), Ok(3));
+
+    let src = Source::new("test", "a = \"x\" * 2".into());
+    let error = parse(&src).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ErrorKind::Parsing(ParsingError::InvalidOperands { .. })
+    ));
+    assert_eq!(src.slice(error.span), "2");
+    assert_eq!(
+        error.kind.related().map(|(span, _)| src.slice(span)),
+        Some("\"x\"")
+    );
+
+    assert_eq!(
+        parse_error("a = 1 / 0"),
+        ErrorKind::Parsing(ParsingError::DivisionByZero)
+    );
+    assert_eq!(
+        parse_error("a = missing + 1"),
+        ErrorKind::Parsing(ParsingError::UnknownReference)
+    );
+    assert_eq!(
+        parse_error("a = 9223372036854775807 + 1"),
+        ErrorKind::Parsing(ParsingError::ArithmeticOverflow)
+    );
+
+    // a sign straight after an operand on the same line is an operator, spaced or not, while one
+    // that opens a line or follows a comma is part of the number
+    let doc = document("a = 5-3\nbase = 90\nlimit = base+10\nb = 1 -2\nc = [1, -2]\nd = [1]+[2]");
+    assert_eq!(doc.get_i64("a"), Ok(2));
+    assert_eq!(doc.get_i64("limit"), Ok(100));
+    assert_eq!(doc.get_i64("b"), Ok(-1));
+    assert_eq!(
+        doc.get("c"),
+        Some(&Value::Array(alloc::vec![1.into(), (-2).into()]))
+    );
+    assert_eq!(doc.get_array("d").map(|a| a.len()), Ok(2));
+    assert!(matches!(
+        parse_error("a = [1\n-2]"),
+        ErrorKind::Parsing(ParsingError::UnexpectedToken {
+            found: TokenKind::Integer,
+            ..
+        })
+    ));
+    assert_eq!(
+        parse_error("a = 1\n-2"),
+        ErrorKind::Parsing(ParsingError::InvalidKey)
+    );
+
+    let content = "base = 2
+limit = base * 4 - 1 # note
+next = 1
+";
+    let src = Source::new("test", content.into());
+    let mut cst = crate::cst::Cst::parse(&src).unwrap();
+    assert!(cst.set("limit", &Value::Integer(7)));
+    assert_eq!(
+        alloc::format!("{cst}"),
+        content.replace("base * 4 - 1", "7")
+    );
+}
+
+#[test]
+fn profiles_flatten_into_the_effective_document() {
+    use crate::resolve::*;
+
+    let src = Source::new(
+        "test",
+        "url = \"http://${host}\"\nhost = \"localhost\"\ndebug = true\n[profile.production]\nhost = \"example.com\"\ndebug = false".into(),
+    );
+    let resolved = |profile| {
+        let mut doc = parse(&src).unwrap();
+        resolve(
+            &src,
+            &mut doc,
+            ResolveOptions {
+                profile,
+                ..Default::default()
+            },
+        )
+        .map(|_| doc)
+        .map_err(|e| e.kind)
+    };
+
+    let doc = resolved(None).unwrap();
+    assert_eq!(doc.get_str("url"), Ok("http://localhost"));
+    assert_eq!(doc.get_str("profile.production.host"), Ok("example.com"));
+
+    let doc = resolved(Some("production")).unwrap();
+    assert_eq!(doc.get_str("url"), Ok("http://example.com"));
+    assert_eq!(doc.get_bool("debug"), Ok(false));
+    assert_eq!(doc.get("profile"), None);
+
+    assert_eq!(
+        resolved(Some("staging")).unwrap_err(),
+        ErrorKind::Resolving(ResolvingError::UnknownProfile)
+    );
+
+    // a `profile` that isn't a table is just another key, and stays put
+    let src = Source::new("test", "profile = \"fast\"".into());
+    let mut doc = parse(&src).unwrap();
+    assert!(apply_profile(&src, &mut doc, None).is_ok());
+    assert_eq!(doc.get_str("profile"), Ok("fast"));
+
+    let unknown = |content: &str| {
+        let src = Source::new("test", content.into());
+        let mut doc = parse(&src).unwrap();
+        let error = apply_profile(&src, &mut doc, Some("production")).unwrap_err();
+        assert_eq!(doc, parse(&src).unwrap());
+        let (line, col) = (error.span.begin.line, error.span.begin.col);
+        (
+            alloc::string::String::from(src.slice(error.span)),
+            line,
+            col,
+        )
+    };
+    assert_eq!(unknown("profile = \"fast\""), ("profile".into(), 1, 1));
+    assert_eq!(
+        unknown("[profile]\nproduction = 1"),
+        ("production".into(), 2, 1)
+    );
+    assert_eq!(unknown("a = 1\nb = 2\n"), ("".into(), 3, 1));
+}
+
+#[test]
+fn anchors_are_copied_into_aliases() {
+    let content = "defaults = &base { retries = 3, timeout = 1.5 }\n[a]\nhttp = *base\n[b]\nlimits = [*base, 2 * 2]\n";
+    let doc = document(content);
+    assert_eq!(doc.get_i64("a.http.retries"), Ok(3));
+    assert_eq!(doc.get_f64("b.limits[0].timeout"), Ok(1.5));
+    assert_eq!(doc.get("defaults"), doc.get("a.http"));
+
+    let src = Source::new("test", content.into());
+    let cst = crate::cst::Cst::parse(&src).unwrap();
+    assert_eq!(alloc::format!("{cst}"), content);
+
+    assert_eq!(
+        parse_error("a = *missing"),
+        ErrorKind::Parsing(ParsingError::UnknownAnchor)
+    );
+    assert!(matches!(
+        parse_error("a = &x 1\nb = &x 2"),
+        ErrorKind::Parsing(ParsingError::DuplicateAnchor { .. })
+    ));
+}
+
+#[test]
+#[cfg(feature = "coerce")]
+fn values_coerce_from_compatible_representations() {
+    assert_eq!(Value::from("8080").coerce_i64(), Some(8080));
+    assert_eq!(Value::from(2.0).coerce_i64(), Some(2));
+    assert_eq!(Value::from(2.5).coerce_i64(), None);
+    assert_eq!(Value::from(3).coerce_f64(), Some(3.0));
+    assert_eq!(Value::from(1).coerce_bool(), Some(true));
+    assert_eq!(Value::from("Off").coerce_bool(), Some(false));
+    assert_eq!(Value::from(2).coerce_bool(), None);
+    assert_eq!(Value::from(false).coerce_string(), Some("false".into()));
+    assert_eq!(Value::Array(alloc::vec![]).coerce_i64(), None);
+}
+
+#[test]
+fn schemas_validate_documents() {
+    use crate::schema::*;
+
+    let schema = Schema::new()
+        .field("name", Field::string().required())
+        .field("level", Field::string().choices(["debug", "info"]))
+        .field(
+            "server",
+            Field::table(
+                Schema::new()
+                    .field("port", Field::integer().required().range(1, 65535))
+                    .field("host", Field::string().required()),
+            )
+            .required(),
+        )
+        .field("ratios", Field::array(Type::Float));
+
+    let doc = document("name = \"pcf\"\nlevel = \"info\"\n[server]\nport = 80\nhost = \"a\"");
+    assert_eq!(schema.validate(&doc), []);
+
+    let doc = document("level = \"trace\"\nratios = [0.5, 1]\n[server]\nport = 0");
+    let errors: alloc::vec::Vec<_> = schema
+        .validate(&doc)
+        .into_iter()
+        .map(|e| (alloc::format!("{}", e.path), e.kind, e.span.begin.line))
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            ("name".into(), SchemaErrorKind::MissingKey, 1),
+            ("level".into(), SchemaErrorKind::InvalidChoice, 1),
+            ("server.port".into(), SchemaErrorKind::OutOfRange, 4),
+            ("server.host".into(), SchemaErrorKind::MissingKey, 3),
+            (
+                "ratios[1]".into(),
+                SchemaErrorKind::MismatchedType {
+                    expected: ValueKind::Float,
+                    found: ValueKind::Integer
+                },
+                2
+            ),
+        ]
+    );
+}
+
+#[test]
+fn schema_bounds_compare_exactly() {
+    use crate::schema::*;
+
+    let check = |field: Field, value: Value| {
+        let mut doc = document("");
+        doc.set("x", value).unwrap();
+        let errors = Schema::new().field("x", field).validate(&doc);
+        errors
+            .into_iter()
+            .map(|e| e.kind)
+            .collect::<alloc::vec::Vec<_>>()
+    };
+    let out = [SchemaErrorKind::OutOfRange];
+
+    let max = 1_i64 << 53;
+    assert_eq!(check(Field::integer().max(max), Value::Integer(max)), []);
+    assert_eq!(
+        check(Field::integer().max(max), Value::Integer(max + 1)),
+        out
+    );
+    assert_eq!(
+        check(
+            Field::integer().range(i64::MIN, i64::MAX - 1),
+            Value::Integer(i64::MAX)
+        ),
+        out
+    );
+    assert_eq!(
+        check(Field::integer().min(i64::MAX), Value::Integer(i64::MAX)),
+        []
+    );
+
+    assert_eq!(check(Field::integer().max(0.5), Value::Integer(0)), []);
+    assert_eq!(check(Field::integer().max(0.5), Value::Integer(1)), out);
+    assert_eq!(check(Field::integer().min(-0.5), Value::Integer(-1)), out);
+    assert_eq!(
+        check(Field::integer().max(9.3e18), Value::Integer(i64::MAX)),
+        []
+    );
+    assert_eq!(
+        check(Field::float().max(i64::MAX), Value::Float(9.3e18)),
+        out
+    );
+    assert_eq!(check(Field::float().min(1), Value::Float(0.5)), out);
+
+    assert_eq!(
+        check(Field::float().range(0, 1), Value::Float(f64::NAN)),
+        out
+    );
+    assert_eq!(check(Field::float().max(1.0), Value::Float(f64::NAN)), out);
+    assert_eq!(
+        check(
+            Field::float().min(f64::NEG_INFINITY),
+            Value::Float(f64::INFINITY)
+        ),
+        []
+    );
+    assert_eq!(check(Field::float(), Value::Float(f64::NAN)), []);
+}
+
+#[test]
+fn typed_extraction_reports_spanned_errors() {
+    use crate::extract::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Server {
+        host: alloc::string::String,
+        port: u16,
+        tags: alloc::vec::Vec<alloc::string::String>,
+        tls: Option<bool>,
+    }
+
+    impl FromValue for Server {
+        fn from_value(cursor: &Cursor<'_>) -> core::result::Result<Self, ExtractError> {
+            Ok(Self {
+                host: cursor.field("host")?,
+                port: cursor.field("port")?,
+                tags: cursor.field("tags")?,
+                tls: cursor.field("tls")?,
+            })
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Config {
+        server: Server,
+        workers: Option<i64>,
+    }
+
+    impl FromValue for Config {
+        fn from_value(cursor: &Cursor<'_>) -> core::result::Result<Self, ExtractError> {
+            Ok(Self {
+                server: cursor.field("server")?,
+                workers: cursor.field("workers")?,
+            })
+        }
+    }
+
+    let config: Config = document("[server]\nhost = \"a\"\nport = 80\ntags = [\"x\"]")
+        .extract()
+        .unwrap();
+    assert_eq!(
+        config,
+        Config {
+            server: Server {
+                host: "a".into(),
+                port: 80,
+                tags: alloc::vec!["x".into()],
+                tls: None,
+            },
+            workers: None,
+        }
+    );
+
+    let extract = |content: &str| {
+        let error = document(content).extract::<Config>().unwrap_err();
+        (
+            alloc::format!("{}", error.path),
+            error.kind,
+            error.span.begin.line,
+        )
+    };
+
+    assert_eq!(
+        extract("[server]\nhost = \"a\"\nport = 70000\ntags = []"),
+        ("server.port".into(), ExtractErrorKind::OutOfRange, 3)
+    );
+    assert_eq!(
+        extract("[server]\nhost = \"a\"\nport = 1\ntags = [1]"),
+        (
+            "server.tags[0]".into(),
+            ExtractErrorKind::MismatchedType {
+                expected: ValueKind::String,
+                found: ValueKind::Integer
+            },
+            4
+        )
+    );
+    assert_eq!(
+        extract("\n[server]\nport = 1"),
+        ("server.host".into(), ExtractErrorKind::MissingKey, 2)
+    );
+}
+
+#[test]
+fn schema_defaults_fill_the_effective_document() {
+    use crate::schema::*;
+
+    let schema = Schema::new()
+        .field("name", Field::string().required())
+        .field("workers", Field::integer().default(4))
+        .field(
+            "server",
+            Field::table(
+                Schema::new()
+                    .field("port", Field::integer().default(8080))
+                    .field("host", Field::string().required().default("localhost")),
+            ),
+        );
+
+    let mut doc = document("name = \"pcf\"\nworkers = 2");
+    let validation = schema.validate_and_fill(&mut doc);
+    assert!(validation.is_valid());
+    assert_eq!(doc.get_i64("workers"), Ok(2));
+    assert_eq!(doc.get_i64("server.port"), Ok(8080));
+    assert_eq!(doc.get_str("server.host"), Ok("localhost"));
+
+    let defaulted: alloc::vec::Vec<_> = validation
+        .defaulted
+        .iter()
+        .map(|p| alloc::format!("{p}"))
+        .collect();
+    assert_eq!(defaulted, ["server.port", "server.host"]);
+    let mut workers = KeyPath::new();
+    workers.push(PathSegment::Key("workers".into()));
+    assert!(!validation.is_defaulted(&workers));
+
+    let mut doc = document("");
+    let validation = schema.validate_and_fill(&mut doc);
+    assert_eq!(validation.errors.len(), 1);
+    assert_eq!(doc.get_i64("workers"), Ok(4));
+}
+
+#[test]
+fn deprecated_keys_warn_with_a_suggested_rename() {
+    use crate::schema::*;
+
+    let schema = Schema::new()
+        .field("hostname", Field::string().replaced_by("server.host"))
+        .field("legacy", Field::bool().deprecated())
+        .field("port", Field::integer());
+
+    let validation = schema.check(&document("port = 1\nhostname = \"a\"\nlegacy = true"));
+    assert!(validation.is_valid());
+
+    let warnings: alloc::vec::Vec<_> = validation
+        .warnings
+        .iter()
+        .map(|w| alloc::format!("{w}"))
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            "[hostname:2 1..9] encountered deprecated key during validation, rename it to `server.host`",
+            "[legacy:3 1..7] encountered deprecated key during validation",
+        ]
+    );
+}
+
+#[test]
+fn unknown_keys_follow_the_configured_policy() {
+    use crate::schema::*;
+
+    let schema = Schema::new().field("port", Field::integer()).field(
+        "server",
+        Field::table(Schema::new().field("host", Field::string())),
+    );
+    let doc = document("port = 1\nprot = 2\n[server]\nhots = \"a\"");
+    let with = |unknown_keys| {
+        schema.check_with(
+            &doc,
+            ValidationOptions {
+                unknown_keys,
+                ..Default::default()
+            },
+        )
+    };
+
+    let allowed = with(UnknownKeyPolicy::Allow);
+    assert!(allowed.is_valid() && allowed.warnings.is_empty());
+
+    let warned = with(UnknownKeyPolicy::Warn);
+    assert!(warned.is_valid());
+    let paths: alloc::vec::Vec<_> = warned
+        .warnings
+        .iter()
+        .map(|w| (alloc::format!("{}", w.path), w.kind, w.span.begin.line))
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            ("prot".into(), SchemaWarningKind::UnknownKey, 2),
+            ("server.hots".into(), SchemaWarningKind::UnknownKey, 4)
+        ]
+    );
+
+    let denied = with(UnknownKeyPolicy::Deny);
+    assert_eq!(denied.errors.len(), 2);
+    assert!(
+        denied
+            .errors
+            .iter()
+            .all(|e| e.kind == SchemaErrorKind::UnknownKey)
+    );
+}
+
+#[test]
+fn unknown_keys_suggest_the_closest_field() {
+    use crate::schema::*;
+
+    let schema = Schema::new()
+        .field("port", Field::integer())
+        .field("timeout", Field::integer())
+        .field(
+            "server",
+            Field::table(Schema::new().field("host", Field::string())),
+        );
+    let doc = document("prot = 1\nport = 2\ntimout = 3\n[server]\nhost = \"a\"\nhots = \"b\"");
+
+    let with = |doc, unknown_keys| {
+        schema.check_with(
+            doc,
+            ValidationOptions {
+                unknown_keys,
+                ..Default::default()
+            },
+        )
+    };
+
+    let warnings: alloc::vec::Vec<_> = with(&doc, UnknownKeyPolicy::Warn)
+        .warnings
+        .iter()
+        .map(|w| w.suggestion.clone())
+        .collect();
+    // `prot` isn't pointed at `port` since that's already set, and neither is `hots` at `host`
+    assert_eq!(warnings, [None, Some("timeout".into()), None]);
+
+    let denied = with(
+        &document("timout = 1\nport = 2\n[server]\nhots = \"a\""),
+        UnknownKeyPolicy::Deny,
+    );
+    let errors: alloc::vec::Vec<_> = denied
+        .errors
+        .iter()
+        .map(|e| alloc::format!("{e}"))
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            "[timout:1 1..7] encountered key not declared in the schema during validation, \
+             did you mean `timeout`?",
+            "[server.hots:4 1..5] encountered key not declared in the schema during validation, \
+             did you mean `host`?",
+        ]
+    );
+
+    let notes: alloc::vec::Vec<_> = denied
+        .diagnostics()
+        .into_iter()
+        .flat_map(|d| d.notes)
+        .collect();
+    assert_eq!(notes, ["did you mean `timeout`?", "did you mean `host`?"]);
+}
+
+#[test]
+fn documents_parse_straight_from_str() {
+    let doc = crate::from_str("a = 1").unwrap();
+    assert_eq!(doc.get_i64("a"), Ok(1));
+
+    let doc: Document = "[t]\nb = true".parse().unwrap();
+    assert_eq!(doc.get_bool("t.b"), Ok(true));
+
+    let error = crate::parse_str("app.pcf", "a = 1\na = 2").unwrap_err();
+    assert_eq!(error.file, "app.pcf");
+    assert_eq!(
+        alloc::format!("{error}"),
+        "[app.pcf:2 1..2] PCF0106: encountered duplicate key during parsing\n2 | a = 2\n  | ^\n\
+         [app.pcf:1 1..2] previously defined here\n1 | a = 1\n  | ^"
+    );
+}
+
+#[test]
+fn parser_reuses_an_existing_token_stream() {
+    let src = Source::new("test", "# lead\na = [1, 2] # tail".into());
+    let lexemes = lex_with(
+        &src,
+        LexerOptions {
+            comments: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(lexemes.len(), 9);
+
+    let doc = parse_tokens(lexemes, &src).unwrap();
+    assert_eq!(doc, parse_source(&src).unwrap());
+    assert_eq!(
+        doc.root.get_entry("a").unwrap().comments.inline.as_deref(),
+        Some(" tail")
+    );
+}
+
+#[test]
+fn pull_parser_streams_events() {
+    let src = Source::new(
+        "test",
+        "a = [1, [true], {}]\n[s]\nt = { x = \"y\", }\n@include \"more\"".into(),
+    );
+    let mut parser = Parser::new(&src, lex(&src).unwrap());
+    let mut events = alloc::vec::Vec::new();
+
+    while let Some(event) = parser.next_event() {
+        events.push(match event.unwrap() {
+            Event::Section { path, array, .. } => alloc::format!("section {path} {array}"),
+            Event::Include { path, .. } => alloc::format!("include {path}"),
+            Event::Key { path, .. } => alloc::format!("key {path}"),
+            Event::Scalar(value, _) => alloc::format!("scalar {:?}", value),
+            Event::ArrayStart(_) => "[".into(),
+            Event::ArrayEnd(_) => "]".into(),
+            Event::TableStart(_) => "{".into(),
+            Event::TableEnd(_) => "}".into(),
+        });
+    }
+
+    assert_eq!(
+        events,
+        [
+            "key a",
+            "[",
+            "scalar Integer(1)",
+            "[",
+            "scalar Bool(true)",
+            "]",
+            "{",
+            "}",
+            "]",
+            "section s false",
+            "key t",
+            "{",
+            "key x",
+            "scalar String(\"y\")",
+            "}",
+            "include more",
+        ]
+    );
+
+    let src = Source::new("test", "a = [1 2]".into());
+    let mut parser = Parser::new(&src, lex(&src).unwrap());
+    let kinds: alloc::vec::Vec<_> = core::iter::from_fn(|| parser.next_event())
+        .map(|e| e.map_err(|e| e.kind).err())
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            None,
+            None,
+            None,
+            Some(ErrorKind::Parsing(ParsingError::UnexpectedToken {
+                found: TokenKind::Integer,
+                expected: ExpectedSet::from_iter([
+                    Expected::Token(TokenKind::RBrack),
+                    Expected::Token(TokenKind::Comma)
+                ])
+            }))
+        ]
+    );
+
+    let src = Source::new("test", "a = [1".into());
+    let mut parser = Parser::new(&src, lex(&src).unwrap());
+    let last = core::iter::from_fn(|| parser.next_event()).last();
+    assert!(matches!(
+        last.map(|e| e.map_err(|e| e.kind)),
+        Some(Err(ErrorKind::Parsing(
+            ParsingError::UnclosedDelimiter { .. }
+        )))
+    ));
+}
+
+#[test]
+fn pull_parser_works_out_values_like_parse() {
+    let src = Source::new(
+        "test",
+        "a = 2 * 4 - 1\nb = &x [1] + [2]\nc = [*x, 1.0 / 0.0]\n[s]\nd = 1\ne = { f = 1 + 1, g = f }\nh = [d]\n\
+         [t]\nextends = s\nd = 2"
+            .into(),
+    );
+    let mut parser = Parser::new(&src, lex(&src).unwrap());
+    let events: alloc::vec::Vec<_> = core::iter::from_fn(|| parser.next_event())
+        .map(|event| match event.unwrap() {
+            Event::Section { path, .. } => alloc::format!("section {path}"),
+            Event::Key { path, .. } => alloc::format!("key {path}"),
+            Event::Scalar(value, span) => alloc::format!("scalar {} {}", value, src.slice(span)),
+            event => alloc::format!("{:?}", event)
+                .split('(')
+                .next()
+                .unwrap()
+                .into(),
+        })
+        .collect();
+
+    assert_eq!(
+        events,
+        [
+            "key a",
+            "scalar 7 2 * 4 - 1",
+            "key b",
+            "scalar [1, 2] [1] + [2]",
+            "key c",
+            "ArrayStart",
+            "scalar [1] *x",
+            "scalar 1.0 / 0.0 1.0 / 0.0",
+            "ArrayEnd",
+            "section s",
+            "key d",
+            "scalar 1 1",
+            "key e",
+            "TableStart",
+            "key f",
+            "scalar 2 1 + 1",
+            "key g",
+            "scalar 2 f",
+            "TableEnd",
+            "key h",
+            "ArrayStart",
+            "scalar 1 d",
+            "ArrayEnd",
+            "section t",
+            "key d",
+            "scalar 2 2",
+            "section ",
+            "key t.e",
+            "scalar { f = 2, g = 2 } { f = 1 + 1, g = f }",
+            "key t.h",
+            "scalar [1] [d]",
+        ]
+    );
+
+    let src = Source::new("test", "a = [1 2]".into());
+    let mut parser = Parser::new(&src, lex(&src).unwrap());
+    let last = core::iter::from_fn(|| parser.next_event()).last();
+    assert!(matches!(
+        last.map(|e| e.map_err(|e| e.kind)),
+        Some(Err(ErrorKind::Parsing(ParsingError::UnexpectedToken {
+            found: TokenKind::Integer,
+            ..
+        })))
+    ));
+}
+
+#[test]
+fn nesting_beyond_the_depth_limit_is_rejected() {
+    let deep = alloc::format!("a = {}{}", "[".repeat(100_000), "]".repeat(100_000));
+    assert_eq!(
+        parse_error(&deep),
+        ErrorKind::Limit(LimitError::NestingTooDeep {
+            limit: 128,
+            found: 100_000
+        })
+    );
+
+    let src = Source::new("test", "a = [[{ b = [1] }]]".into());
+    let with = |max_depth| {
+        parse_with(
+            &src,
+            ParserOptions {
+                max_depth,
+                ..Default::default()
+            },
+        )
+        .map_err(|e| (e.kind, e.span.begin.col))
+    };
+    assert!(with(4).is_ok());
+    assert_eq!(
+        with(3).unwrap_err(),
+        (
+            ErrorKind::Limit(LimitError::NestingTooDeep { limit: 3, found: 4 }),
+            13
+        )
+    );
+
+    let mut parser = Parser::with_options(
+        &src,
+        lex(&src).unwrap(),
+        ParserOptions {
+            max_depth: 1,
+            ..Default::default()
+        },
+    );
+    let last = core::iter::from_fn(|| parser.next_event()).last();
+    assert!(matches!(
+        last.map(|e| e.map_err(|e| e.kind)),
+        Some(Err(ErrorKind::Limit(LimitError::NestingTooDeep {
+            limit: 1,
+            found: 4
+        })))
+    ));
+}
+
+#[test]
+fn limit_errors_report_the_limit_and_what_was_found() {
+    let src = Source::new("test", "a = \"hello\"\nb = [1, 2, 3]".into());
+    let with = |options: ParserOptions| parse_with(&src, options).map_err(|e| (e.kind, e.span));
+
+    let (kind, span) = with(ParserOptions::new().max_size(16)).unwrap_err();
+    assert_eq!(
+        kind,
+        ErrorKind::Limit(LimitError::DocumentTooLarge {
+            limit: 16,
+            found: 25
+        })
+    );
+    assert_eq!((span.begin.line, span.begin.col), (2, 5));
+
+    let (kind, span) = with(ParserOptions::new().max_string_len(4)).unwrap_err();
+    assert_eq!(
+        kind,
+        ErrorKind::Limit(LimitError::StringTooLong { limit: 4, found: 5 })
+    );
+    assert_eq!(src.slice(span), "\"hello\"");
+
+    let (kind, span) = with(ParserOptions::new().max_tokens(8)).unwrap_err();
+    assert_eq!(
+        kind,
+        ErrorKind::Limit(LimitError::TooManyTokens {
+            limit: 8,
+            found: 12
+        })
+    );
+    assert_eq!(src.slice(span), "2");
+
+    assert!(
+        with(
+            ParserOptions::new()
+                .max_size(25)
+                .max_string_len(5)
+                .max_tokens(12)
+        )
+        .is_ok()
+    );
+    assert_eq!(
+        alloc::format!("{}", kind),
+        "encountered 12 tokens during lexing, limit is 8"
+    );
+    assert_eq!(kind.code(), "PCF0504");
+}
+
+#[test]
+fn messages_can_be_translated() {
+    use crate::{
+        diagnostic::{Diagnostic, IntoDiagnostic},
+        messages::{English, Messages},
+    };
+    use core::fmt;
+
+    // overrides a few messages and falls back to english for everything else
+    struct Pirate;
+
+    impl Messages for Pirate {
+        fn error(&self, f: &mut dyn fmt::Write, kind: &ErrorKind) -> fmt::Result {
+            match kind {
+                ErrorKind::Parsing(ParsingError::UnclosedDelimiter { .. }) => {
+                    f.write_str("ye left a bracket open")
+                }
+                kind => English.error(f, kind),
+            }
+        }
+
+        fn hint(&self, f: &mut dyn fmt::Write, hint: &Hint) -> fmt::Result {
+            match hint {
+                Hint::CloseDelimiter { close, .. } => write!(f, "nail a `{}` on the end", close),
+                hint => English.hint(f, hint),
+            }
+        }
+
+        fn label(&self, label: Label) -> &str {
+            match label {
+                Label::OpenDelimiter => "opened here, matey",
+                label => English.label(label),
+            }
+        }
+
+        fn help(&self) -> &str {
+            "advice"
+        }
+    }
+
+    static PIRATE: Pirate = Pirate;
+
+    let src = Source::new("test", "a = [1".into());
+    let error = parse(&src).unwrap_err();
+    let mut rendered = alloc::string::String::new();
+    error
+        .render(&mut rendered, &RenderOptions::new().messages(&PIRATE))
+        .unwrap();
+    assert_eq!(
+        rendered,
+        "[test:1:7] PCF0115: ye left a bracket open\n\
+         1 | a = [1\n  |       ^\n\
+         [test:1 5..6] opened here, matey\n\
+         1 | a = [1\n  |     ^\n\
+         advice: nail a `]` on the end"
+    );
+
+    let diagnostic = error.clone().into_diagnostic(&PIRATE);
+    assert_eq!(diagnostic.message, "ye left a bracket open");
+    assert_eq!(diagnostic.labels[1].1, "opened here, matey");
+    assert_eq!(diagnostic.help.as_deref(), Some("nail a `]` on the end"));
+
+    assert_eq!(
+        Diagnostic::from(error).message,
+        "encountered unclosed delimiter during parsing"
+    );
+    let kind = parse_error("a = ]");
+    assert_eq!(
+        crate::messages::text(|f| PIRATE.error(f, &kind)),
+        alloc::format!("{}", kind)
+    );
+}
+
+#[test]
+fn checks_report_outcomes_instead_of_panicking() {
+    use crate::check::{self, Outcome};
+
+    assert_eq!(check::never_panics("a = [1, { b = 2 }]"), Outcome::Accepted);
+    assert!(matches!(
+        check::never_panics("a = [1"),
+        Outcome::Rejected {
+            kind: ErrorKind::Parsing(ParsingError::UnclosedDelimiter { .. }),
+            ..
+        }
+    ));
+
+    // each section extends one further down, so resolving the first walks the whole chain
+    let mut chain = alloc::string::String::new();
+    for i in (1..200).rev() {
+        chain += &alloc::format!("[s{}]\nextends = s{}\n", i, i - 1);
+    }
+    chain += "[s0]\nv = 1\n";
+    assert!(matches!(
+        check::never_panics(&chain),
+        Outcome::Rejected {
+            kind: ErrorKind::Limit(LimitError::NestingTooDeep { limit: 128, .. }),
+            ..
+        }
+    ));
+
+    // spans that point past the source, or run backwards once two of them are joined
+    let at = |line, offset| {
+        Span::new(
+            Location::new(line, 1, offset),
+            Location::new(line, 2, offset + 1),
+        )
+    };
+    let tokens = |kinds: alloc::vec::Vec<(LexemeKind, Span)>| {
+        kinds
+            .into_iter()
+            .map(|(kind, span)| Lexeme::new(kind, span))
+            .collect()
+    };
+
+    let unclosed = tokens(alloc::vec![
+        (LexemeKind::Ident("a".into()), at(1, 0)),
+        (LexemeKind::Equal, at(1, 2)),
+        (LexemeKind::LBrack, at(40, 900)),
+    ]);
+    assert!(!check::tokens("a = [", unclosed).is_bug());
+
+    let overflow = tokens(alloc::vec![
+        (LexemeKind::Ident("a".into()), at(1, 0)),
+        (LexemeKind::Equal, at(1, 2)),
+        (LexemeKind::Integer(i64::MAX), at(9, 4)),
+        (LexemeKind::Plus, at(9, 6)),
+        (LexemeKind::Integer(1), at(1, 8)),
+    ]);
+    assert!(matches!(
+        check::tokens("a = 1 + 1", overflow),
+        Outcome::Rejected {
+            kind: ErrorKind::Parsing(ParsingError::ArithmeticOverflow),
+            ..
+        }
+    ));
+}
+
+#[test]
+fn diagnostics_render_through_any_renderer() {
+    use crate::diagnostic::{Diagnostic, RenderDiagnostic, Severity, TextRenderer};
+    use alloc::{string::String, vec::Vec};
+
+    // what an editor would put in its gutter: where, how bad, and what
+    #[derive(Default)]
+    struct Gutter(Vec<(usize, Severity, String)>);
+
+    impl RenderDiagnostic for Gutter {
+        type Error = core::convert::Infallible;
+
+        fn render(
+            &mut self,
+            diagnostic: &Diagnostic,
+            _: &Source<'_>,
+        ) -> core::result::Result<(), Self::Error> {
+            let line = diagnostic.span().map_or(0, |span| span.begin.line);
+            self.0
+                .push((line, diagnostic.severity, diagnostic.message.clone()));
+            Ok(())
+        }
+    }
+
+    let src = Source::new("test", "a = 1\na = 2\nb = [".into());
+    let (_, diagnostics) = crate::parse_diagnostics(
+        &src,
+        ParserOptions::new().duplicates(DuplicatePolicy::LastWins),
+    );
+
+    let mut gutter = Gutter::default();
+    diagnostics.render_with(&mut gutter, &src).unwrap();
+    assert_eq!(
+        gutter.0,
+        [
+            (
+                2,
+                Severity::Warning,
+                "encountered duplicate key during parsing".into()
+            ),
+            (
+                3,
+                Severity::Error,
+                "encountered unclosed delimiter during parsing".into()
+            ),
+        ]
+    );
+
+    let mut text = TextRenderer::new(String::new());
+    diagnostics.render_with(&mut text, &src).unwrap();
+    let mut rendered = String::new();
+    diagnostics
+        .render(&mut rendered, &src, &RenderOptions::default())
+        .unwrap();
+    assert_eq!(text.into_inner(), rendered);
+}
+
+#[test]
+fn spans_across_lines_slice_the_offending_text() {
+    let cases = [
+        ("a = \"one\ntwo\\u{zz}\"", "\"one\ntwo\\u{zz}", (2, 10)),
+        ("a = r#\"one\ntwo", "r#\"one\ntwo", (2, 4)),
+        ("a = b\"one\r\ntwo\\q\"", "b\"one\r\ntwo\\q", (2, 6)),
+        (
+            "# caf\u{e9}\na = \"\u{e9}\n\u{e9}\\u{110000}\"",
+            "\"\u{e9}\n\u{e9}\\u{110000}",
+            (3, 12),
+        ),
+        ("a = \"x\ny\"\n  b = 1 =", "=", (3, 10)),
+    ];
+
+    for (content, text, end) in cases {
+        let src = Source::new("test", content.into());
+        let span = parse(&src).unwrap_err().span;
+
+        assert_eq!(src.slice(span), text, "{:?}", content);
+        assert_eq!((span.end.line, span.end.col), end, "{:?}", content);
+        assert_eq!(src.extract_offset(span.begin), span.begin.offset);
+        assert_eq!(src.extract_offset(span.end), span.end.offset);
+    }
+
+    let src = Source::new("test", "# one\n# tw\u{f6}\na = 1".into());
+    let lexemes = lex_with(
+        &src,
+        LexerOptions {
+            comments: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(lexemes[1].text(&src), "# tw\u{f6}");
+    assert_eq!(src.extract_offset(lexemes[2].span.begin), 13);
+}
+
+#[test]
+fn values_have_a_total_order_and_consistent_hash() {
+    use alloc::collections::btree_set::BTreeSet;
+    use core::hash::{Hash, Hasher};
+
+    #[derive(Default)]
+    struct Fnv(u64);
+
+    impl Hasher for Fnv {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+            }
+        }
+    }
+
+    let hash = |value: &Value| {
+        let mut hasher = Fnv::default();
+        value.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    assert_eq!(Value::Float(f64::NAN), Value::Float(f64::NAN));
+    assert_ne!(Value::Float(0.0), Value::Float(-0.0));
+    assert!(Value::Float(-0.0) < Value::Float(0.0));
+    assert!(Value::from("z") < Value::from(0));
+
+    let a = document("x = 1\ny = [1.5]").root;
+    let b = document("y = [1.5]\nx = 1").root;
+    assert_eq!(a, b);
+    assert_eq!(hash(&Value::Table(a.clone())), hash(&Value::Table(b)));
+    assert!(Value::Table(a) < Value::Table(document("x = 2").root));
+
+    let set: BTreeSet<_> = [Value::from(2), Value::from(1), Value::from(2)]
+        .into_iter()
+        .collect();
+    assert_eq!(
+        set.into_iter().collect::<alloc::vec::Vec<_>>(),
+        [Value::from(1), Value::from(2)]
+    );
+}
+
+#[test]
+fn values_and_documents_display_as_pcf() {
+    let doc = document(
+        "name = \"a \\\"quoted\\\"\\n\\ttab\"\n\"odd key\" = [1.0, 0.5, -2]\n[server.http]\nport = 80\n[[users]]\nid = 1\n[[users]]\nid = 2\ntags = { a = true, \"1x\" = {} }",
+    );
+    let text = alloc::format!("{}", doc);
+    assert_eq!(document(&text), doc);
+    assert!(text.contains("[server.http]\nport = 80\n"));
+    assert!(text.contains("[[users]]\nid = 2\n\n[users.tags]\na = true\n\n[users.tags.\"1x\"]\n"));
+
+    assert_eq!(alloc::format!("{}", Value::Float(1.0)), "1.0");
+    assert_eq!(
+        alloc::format!(
+            "{}",
+            Value::from(alloc::vec![Value::from("x"), Value::from(3)])
+        ),
+        "[\"x\", 3]"
+    );
+
+    let nan = document(&alloc::format!("x = {}", Value::Float(f64::NAN)));
+    assert!(nan.get("x").and_then(Value::as_f64).unwrap().is_nan());
+}
+
+#[test]
+fn byte_strings_decode_hex_and_base64() {
+    let doc = document(
+        "key = b\"ab\\x00\\xff\"\ntoken = base64\"aGVsbG8=\"\nb = 1\nlong = b\"a\" + base64\"Yg\"",
+    );
+    assert_eq!(doc.get("key"), Some(&Value::from(&b"ab\x00\xff"[..])));
+    assert_eq!(
+        doc.get("token").and_then(Value::as_bytes),
+        Some(&b"hello"[..])
+    );
+    assert_eq!(doc.get("b"), Some(&Value::Integer(1)));
+    assert_eq!(doc.get("long"), Some(&Value::from(&b"ab"[..])));
+
+    let text = alloc::format!("{}", doc);
+    assert!(text.contains("key = b\"ab\\x00\\xff\"\n"));
+    assert_eq!(document(&text), doc);
+
+    let error = |content: &str| lex(&Source::new("", content.into())).map_err(|e| e.kind);
+    assert_eq!(
+        error("b\"\\x4\""),
+        Err(ErrorKind::Lexing(LexingError::InvalidEscape))
+    );
+    assert_eq!(
+        error("base64\"a\""),
+        Err(ErrorKind::Lexing(LexingError::MalformedBytes))
+    );
+    assert_eq!(
+        error("b\"abc"),
+        Err(ErrorKind::Lexing(LexingError::UnterminatedString))
+    );
+}
+
+#[test]
+fn sections_extend_other_tables() {
+    let doc = document(
+        "[server.backup]\nextends = server.primary\nport = 8081\n[server.primary]\nhost = \"a\"\nport = 80\ntls = { on = true, cert = \"x\" }\n[server.edge]\nextends = server.backup\ntls = { on = false }\n[[pool]]\nextends = server.primary\nhost = \"b\"",
+    );
+    let backup = doc.get_table("server.backup").unwrap();
+    assert_eq!(backup.get("host"), Some(&Value::from("a")));
+    assert_eq!(backup.get("port"), Some(&Value::Integer(8081)));
+    assert!(!backup.contains_key("extends"));
+
+    assert_eq!(doc.get_i64("server.edge.port"), Ok(8081));
+    assert_eq!(doc.get_bool("server.edge.tls.on"), Ok(false));
+    assert_eq!(doc.get_str("server.edge.tls.cert"), Ok("x"));
+    assert_eq!(doc.get_str("pool[0].host"), Ok("b"));
+    assert_eq!(doc.get_i64("pool[0].port"), Ok(80));
+
+    assert!(matches!(
+        parse_error("[a]\nextends = b\n[b]\nextends = a"),
+        ErrorKind::Parsing(ParsingError::CyclicExtends)
+    ));
+    assert!(matches!(
+        parse_error("[a.b]\nextends = a"),
+        ErrorKind::Parsing(ParsingError::CyclicExtends)
+    ));
+    assert!(matches!(
+        parse_error("x = 1\n[a]\nextends = x"),
+        ErrorKind::Parsing(ParsingError::InvalidExtends)
+    ));
+    assert!(matches!(
+        parse_error("[a]\nextends = missing"),
+        ErrorKind::Parsing(ParsingError::UnknownReference)
+    ));
+    assert!(matches!(
+        parse_error("[b]\n[a]\nextends = b\nextends = b"),
+        ErrorKind::Parsing(ParsingError::DuplicateKey { .. })
+    ));
+}
+
+#[test]
+fn extends_resolve_the_same_in_any_section_order() {
+    let sections = [
+        "[c]\nextends = a.inner\n",
+        "[base.inner]\nx = 1\n",
+        "[a]\nextends = base\n",
+    ];
+
+    for order in [
+        [0, 1, 2],
+        [0, 2, 1],
+        [1, 0, 2],
+        [1, 2, 0],
+        [2, 0, 1],
+        [2, 1, 0],
+    ] {
+        let content: alloc::string::String = order.iter().map(|i| sections[*i]).collect();
+        let doc = document(&content);
+        assert_eq!(doc.get_i64("c.x"), Ok(1), "{}", content);
+        assert_eq!(doc.get_i64("a.inner.x"), Ok(1), "{}", content);
+
+        let src = Source::new("test", content.clone());
+        let arena = parse_arena(&src).unwrap();
+        assert_eq!(arena.root().to_table(), doc.root, "{}", content);
+    }
+}
+
+#[test]
+fn streams_split_into_documents() {
+    let src = Source::new(
+        "test",
+        "---\nname = \"first\"\n---\n# snapshot two\nname = \"second\"\nn = 1 - -1\n---\nbad =\n---\n".into(),
+    );
+    let documents: alloc::vec::Vec<_> = parse_documents(&src).collect();
+    assert_eq!(documents.len(), 4);
+    assert_eq!(documents[0].as_ref().unwrap().get_str("name"), Ok("first"));
+    assert_eq!(documents[1].as_ref().unwrap().get_i64("n"), Ok(2));
+    assert!(documents[2].is_err());
+    assert!(documents[3].as_ref().unwrap().root.is_empty());
+
+    assert!(matches!(
+        parse_error("a = 1\n---\nb = 2"),
+        ErrorKind::Parsing(ParsingError::UnexpectedToken {
+            found: TokenKind::Separator,
+            ..
+        })
+    ));
+
+    let src = Source::new("test", "a = \"x".into());
+    assert_eq!(parse_documents(&src).count(), 1);
+}
+
+#[test]
+fn parser_options_build_fluently() {
+    let options = ParserOptions::new()
+        .strict()
+        .duplicates(DuplicatePolicy::LastWins)
+        .arrays(ArrayPolicy::Homogeneous)
+        .overflow(OverflowPolicy::Saturate)
+        .max_depth(4);
+
+    assert_eq!(
+        options,
+        ParserOptions {
+            mode: ParseMode::Strict,
+            duplicates: DuplicatePolicy::LastWins,
+            arrays: ArrayPolicy::Homogeneous,
+            overflow: OverflowPolicy::Saturate,
+            max_depth: 4,
+            max_size: None,
+            max_tokens: None,
+            max_string_len: None,
+            lints: crate::diagnostic::Lints::default(),
+        }
+    );
+    assert!(options.lexer().comments);
+
+    let src = Source::new("test", "big = 99999999999999999999".into());
+    assert_eq!(
+        parse_with(&src, options).unwrap().get_i64("big"),
+        Ok(i64::MAX)
+    );
+    assert!(parse_with(&src, ParserOptions::new()).is_err());
+}
+
+#[test]
+fn documents_answer_glob_queries() {
+    let doc = document(
+        "password = \"root\"\n[[servers]]\nhost = \"a\"\n[[servers]]\nhost = \"b\"\n[db.primary]\nhost = \"c\"\npassword = \"x\"\n[db.replica]\nauth = { password = \"y\" }",
+    );
+    let paths = |query| {
+        doc.query(query)
+            .into_iter()
+            .map(|(path, _)| alloc::format!("{}", path))
+            .collect::<alloc::vec::Vec<_>>()
+    };
+
+    assert_eq!(
+        paths("servers.*.host"),
+        ["servers[0].host", "servers[1].host"]
+    );
+    assert_eq!(paths("db.*.host"), ["db.primary.host"]);
+    assert_eq!(
+        paths("**.password"),
+        [
+            "password",
+            "db.primary.password",
+            "db.replica.auth.password"
+        ]
+    );
+    assert_eq!(paths("servers[1].host"), ["servers[1].host"]);
+    assert_eq!(paths("db.**.**.auth"), ["db.replica.auth"]);
+    assert_eq!(paths("db.replica.**").len(), 3);
+    assert!(paths("missing.*").is_empty());
+    assert!(paths("a..b").is_empty());
+
+    let values: alloc::vec::Vec<_> = doc.query("**.host").into_iter().map(|(_, v)| v).collect();
+    assert_eq!(values.len(), 3);
+    assert_eq!(values[2], &Value::from("c"));
+}
+
+#[test]
+fn documents_diff_structurally() {
+    use crate::diff::*;
+
+    let old = document("name = \"a\"\nports = [1, 2, 3]\n[db]\nhost = \"x\"\nuser = \"u\"");
+    let new = document("name = \"a\"\nports = [1, 5]\n[db]\nhost = \"y\"\npool = 4");
+    let changes = crate::diff(&old, &new);
+
+    assert_eq!(
+        changes
+            .iter()
+            .map(|c| alloc::format!("{}", c))
+            .collect::<alloc::vec::Vec<_>>(),
+        [
+            "~ ports[1] = 2 -> 5",
+            "- ports[2] = 3",
+            "~ db.host = \"x\" -> \"y\"",
+            "- db.user = \"u\"",
+            "+ db.pool = 4",
+        ]
+    );
+
+    let Change::Modified {
+        old_span, new_span, ..
+    } = &changes[2]
+    else {
+        panic!("expected a modification");
+    };
+    assert_eq!((old_span.begin.line, new_span.begin.line), (4, 4));
+    assert_eq!(alloc::format!("{}", changes[4].path()), "db.pool");
+    assert!(crate::diff(&old, &old).is_empty());
+}
+
+#[test]
+fn patches_apply_with_conflict_detection() {
+    use crate::{extract::*, patch::*};
+
+    let old = document("name = \"a\"\nports = [1, 2, 3, 4]\n[db]\nhost = \"x\"\nuser = \"u\"");
+    let new = document("name = \"b\"\nports = [1]\n[db]\nhost = \"x\"\npool = 4");
+    let patch = Patch::from(crate::diff(&old, &new));
+
+    let mut applied = old.clone();
+    applied.apply(&patch).unwrap();
+    assert_eq!(applied.root, new.root);
+
+    let text = alloc::format!("{}", Document::from(&patch));
+    assert_eq!(document(&text).extract::<Patch>(), Ok(patch.clone()));
+
+    let error = new.clone().apply(&patch).unwrap_err();
+    assert_eq!((error.index, error.kind), (0, PatchErrorKind::Mismatched));
+    assert_eq!(alloc::format!("{}", error.path), "name");
+
+    let mut conflicted = new.clone();
+    let add = Patch::from_iter([
+        Operation::Replace {
+            path: KeyPath::parse("name").unwrap(),
+            value: "c".into(),
+        },
+        Operation::Add {
+            path: KeyPath::parse("db.pool").unwrap(),
+            value: 8.into(),
+        },
+    ]);
+    assert_eq!(
+        conflicted.apply(&add).unwrap_err().kind,
+        PatchErrorKind::AlreadyExists
+    );
+    assert_eq!(conflicted, new);
+
+    let bad = document("[[operations]]\nop = \"move\"\npath = \"a\"");
+    assert_eq!(
+        bad.extract::<Patch>().unwrap_err().kind,
+        ExtractErrorKind::Invalid
+    );
+}
+
+#[test]
+fn emit_round_trips_documents() {
+    use crate::emit::*;
+
+    let doc = document(
+        "# leading\nname = \"tab\\tquote\\\"\" # inline\nratio = 0.1\nbig = 12345678901234567890.5\n# header\n[server] # section\nport = 80\n[[users]]\nid = 1\n[[users]]\nid = 2\n# the end",
+    );
+    let text = to_string(&doc);
+    let reparsed = document(&text);
+
+    assert_eq!(reparsed, doc);
+    assert_eq!(to_string(&reparsed), text);
+    assert!(text.starts_with("# leading\nname = \"tab\\tquote\\\"\" # inline\n"));
+    assert!(text.contains("\n# header\n[server] # section\nport = 80\n"));
+    assert!(text.ends_with("\n# the end\n"));
+    assert_eq!(reparsed.get_f64("ratio"), Ok(0.1));
+    assert_eq!(reparsed.get_f64("big"), doc.get_f64("big"));
+    assert_eq!(alloc::format!("{}", doc), text);
+}
+
+#[test]
+fn emit_options_control_layout() {
+    use crate::emit::*;
+
+    let doc = document(
+        "name = \"a\"\nports = [1, [2, 3]]\n[server]\n# bind\nhost = \"x\"\ntls = { on = true }",
+    );
+
+    let braces = EmitOptions::new()
+        .indent(2, ' ')
+        .spacing(false)
+        .tables(TableStyle::Braces);
+    let text = to_string_with(&doc, &braces);
+    assert_eq!(
+        text,
+        "name=\"a\"\nports=[1, [2, 3]]\nserver={\n  # bind\n  host=\"x\",\n  tls={\n    on=true\n  }\n}\n"
+    );
+    assert_eq!(document(&text), doc);
+
+    let lines = EmitOptions::new()
+        .indent(1, '\t')
+        .arrays(ArrayLayout::Multiline);
+    let text = to_string_with(&doc, &lines);
+    assert!(
+        text.starts_with("name = \"a\"\nports = [\n\t1,\n\t[\n\t\t2,\n\t\t3\n\t]\n]\n\n[server]\n")
+    );
+    assert!(text.contains("[server]\n# bind\nhost = \"x\"\n\n[server.tls]\non = true\n"));
+    assert_eq!(document(&text), doc);
+}
+
+#[test]
+fn minified_emission_fits_one_line() {
+    use crate::emit::*;
+
+    let doc = document(
+        "# top\nname = \"a\" # note\nkey = \"k\"\nports = [1, 2]\n[server]\nhost = \"x\"\ntls = { on = true, ca = b\"\\x01\" }\n[[users]]\nid = 1\n[[users]]\nid = 2",
+    );
+    let text = to_string_with(&doc, &EmitOptions::minified());
+    assert_eq!(
+        text,
+        "name=\"a\" key=\"k\" ports=[1,2] server={host=\"x\",tls={on=true,ca=b\"\\x01\"}} users=[{id=1},{id=2}]"
+    );
+    assert_eq!(document(&text).root, doc.root);
+
+    let headers = EmitOptions::minified().tables(TableStyle::Headers);
+    let text = to_string_with(&doc, &headers);
+    assert!(!text.contains('\n') && !text.contains('#'));
+    assert_eq!(document(&text).root, doc.root);
+}
+
+#[test]
+fn canonical_emission_is_deterministic() {
+    use crate::emit::*;
+
+    let a = document(
+        "# a\nb = 1\na = { y = 2.50, x = \"\\u{41}\" }\n[c]\nz = -0.0\nn = [1, { q = 1, p = 2 }]",
+    );
+    let b = document(
+        "b = 1 # one\n[c]\nn = [1, { p = 2, q = 1 }]\nz = -0.0 # zero\n[a]\nx = \"A\"\ny = 2.5",
+    );
+
+    let canonical = EmitOptions::canonical();
+    let text = to_string_with(&a, &canonical);
+    assert_eq!(text, to_string_with(&b, &canonical));
+    assert_eq!(
+        text,
+        "b = 1\n\n[a]\nx = \"A\"\ny = 2.5\n\n[c]\nn = [1, { p = 2, q = 1 }]\nz = -0.0\n"
+    );
+}
+
+#[test]
+fn emit_writes_into_fixed_buffers() {
+    use crate::emit::*;
+
+    let doc = document("name = \"a\"\nratio = 2.0\n[db]\nport = 80");
+    let mut buffer = [0u8; 64];
+    let mut writer = SliceWriter::new(&mut buffer);
+    write_document(&mut writer, &doc).unwrap();
+    assert_eq!(writer.as_str(), to_string(&doc));
+    assert_eq!(
+        writer.as_str(),
+        "name = \"a\"\nratio = 2.0\n\n[db]\nport = 80\n"
+    );
+
+    let mut small = [0u8; 8];
+    let mut writer = SliceWriter::new(&mut small);
+    assert!(write_document(&mut writer, &doc).is_err());
+    assert!(writer.len() <= 8);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn emit_writes_through_io() {
+    use crate::emit::*;
+
+    let doc = document("a = [1, 2]");
+    let mut bytes = alloc::vec::Vec::new();
+    write_io(&mut bytes, &doc, &EmitOptions::minified()).unwrap();
+    assert_eq!(bytes, b"a=[1,2]");
+}
+
+#[test]
+fn preserving_emission_keeps_untouched_formatting() {
+    use crate::{cst::*, emit::*};
+
+    let text = "# service\nname   = \"api\"   # keep\nports = [80,  443]\n\n[db]\n# connection\nhost = \"x\"\nuser = \"u\" # drop\ntls = { on = true }\n\n[cache]\nttl = 5\n";
+    let src = Source::new("", text.into());
+    let cst = Cst::parse(&src).unwrap();
+    let mut doc = document(text);
+
+    doc.set("db.host", "y".into()).unwrap();
+    doc.remove("db.user");
+    doc.set("db.pool", 4.into()).unwrap();
+    doc.set("db.tls.ca", "root".into()).unwrap();
+    doc.set("debug", false.into()).unwrap();
+    doc.get_mut("ports")
+        .and_then(Value::as_array_mut)
+        .unwrap()
+        .push(8080.into());
+
+    let emitted = to_string_preserving(&cst, &doc);
+    assert_eq!(
+        emitted,
+        "# service\nname   = \"api\"   # keep\nports = [80, 443, 8080]\ndebug = false\n\n[db]\n# connection\nhost = \"y\"\ntls = { on = true, ca = \"root\" }\npool = 4\n\n[cache]\nttl = 5\n"
+    );
+    assert_eq!(document(&emitted).root, doc.root);
+    assert_eq!(to_string_preserving(&cst, &document(text)), text);
+
+    let edited = |text: &str, edit: &dyn Fn(&mut Document)| {
+        let cst = Cst::parse(&Source::new("", text.into())).unwrap();
+        let mut doc = document(text);
+        edit(&mut doc);
+        let emitted = to_string_preserving(&cst, &doc);
+        assert_eq!(document(&emitted).root, doc.root, "{}", emitted);
+        emitted
+    };
+
+    // the anchor is kept, and an alias that no longer repeats it is written out in full
+    assert_eq!(
+        edited("a = &x {k = 1}\nb = *x\n", &|doc| {
+            doc.set("a.k", 5.into()).unwrap();
+        }),
+        "a = &x {k = 5}\nb = { k = 1 }\n"
+    );
+    assert_eq!(
+        edited("a = &x {k = 1}\nb = *x\n", &|doc| {
+            doc.set("a.k", 5.into()).unwrap();
+            doc.set("b.k", 5.into()).unwrap();
+        }),
+        "a = &x {k = 5}\nb = { k = 5 }\n"
+    );
+
+    // a table left empty with no text of its own still needs writing out
+    assert_eq!(
+        edited("a.b = 1\n", &|doc| {
+            doc.remove("a.b");
+        }),
+        "a = {}\n"
+    );
+}
+
+#[test]
+fn emit_orders_keys() {
+    use crate::emit::*;
+
+    let doc = document(
+        "zeta = 1\nversion = \"1.0\"\nalpha = [3, 1, 2]\nname = \"pcf\"\n[deps]\nb = 1\na = 2",
+    );
+
+    let priority = EmitOptions::new().keys(KeyOrder::priority(["name", "version"]));
+    assert_eq!(
+        to_string_with(&doc, &priority),
+        "name = \"pcf\"\nversion = \"1.0\"\nzeta = 1\nalpha = [3, 1, 2]\n\n[deps]\nb = 1\na = 2\n"
+    );
+
+    let alphabetical = EmitOptions::new().keys(KeyOrder::Alphabetical);
+    assert_eq!(
+        to_string_with(&doc, &alphabetical),
+        "alpha = [3, 1, 2]\nname = \"pcf\"\nversion = \"1.0\"\nzeta = 1\n\n[deps]\na = 2\nb = 1\n"
+    );
+
+    let reversed = EmitOptions::new().keys(KeyOrder::Custom(|a, b| b.cmp(a)));
+    assert!(to_string_with(&doc, &reversed).starts_with("zeta = 1\nversion = \"1.0\"\nname"));
+}
+
+#[test]
+fn rust_values_convert_to_pcf() {
+    use crate::emit::*;
+    use alloc::{collections::BTreeMap, vec};
+
+    assert_eq!(42u8.to_pcf(), Some(Value::Integer(42)));
+    assert_eq!(u64::MAX.to_pcf(), Some(Value::Float(u64::MAX as f64)));
+    assert_eq!(None::<i32>.to_pcf(), None);
+    assert_eq!(
+        ("a", 1, Some(true)).to_pcf(),
+        Some(Value::from(vec![
+            Value::from("a"),
+            Value::from(1),
+            Value::from(true)
+        ]))
+    );
+    assert_eq!(("a", 1, Some(true), None::<f64>).to_pcf(), None);
+
+    let mut users = BTreeMap::new();
+    users.insert("ada", vec![Some(1), None, Some(3)]);
+    users.insert("bob", vec![Some(1), Some(3)]);
+
+    let mut map = BTreeMap::new();
+    map.insert("name", Some(Value::from("pcf")));
+    map.insert("missing", None);
+    map.insert("users", users.to_pcf());
+
+    let doc = Document::from_pcf(&map).unwrap();
+    assert_eq!(to_string(&doc), "name = \"pcf\"\n\n[users]\nbob = [1, 3]\n");
+    assert!(Document::from_pcf(&[1, 2]).is_none());
+}
+
+#[test]
+fn emit_chooses_quote_styles() {
+    use crate::emit::*;
+
+    assert_eq!(
+        kinds("r\"C:\\dir\" r#\"say \"hi\"\"#"),
+        [
+            LexemeKind::String("C:\\dir".into()),
+            LexemeKind::String("say \"hi\"".into())
+        ]
+    );
+    assert!(document("a = r\"\\q\"").get("a").is_some());
+
+    let doc = document(
+        "plain = \"abc\"\npath = \"C:\\\\dir\\\\file\"\nquote = \"\\\"#\"\ntext = \"one\\ntwo\"\nbell = \"\\u{7}\\\\\"",
+    );
+
+    let auto = EmitOptions::new().quotes(QuoteStyle::Auto);
+    let text = to_string_with(&doc, &auto);
+    assert_eq!(
+        text,
+        "plain = \"abc\"\npath = r\"C:\\dir\\file\"\nquote = r##\"\"#\"##\ntext = \"one\ntwo\"\nbell = \"\\u{7}\\\\\"\n"
+    );
+    assert_eq!(document(&text).root, doc.root);
+
+    let annotated = EmitOptions::new()
+        .quote("text", QuoteStyle::Raw)
+        .quote("bell", QuoteStyle::Raw);
+    let text = to_string_with(&doc, &annotated);
+    assert!(text.contains("path = \"C:\\\\dir\\\\file\"\n"));
+    assert!(text.contains("text = r\"one\ntwo\"\n"));
+    assert!(text.contains("bell = \"\\u{7}\\\\\"\n"));
+    assert_eq!(document(&text).root, doc.root);
+
+    let compact = EmitOptions::minified().quotes(QuoteStyle::Auto);
+    assert!(!to_string_with(&doc, &compact).contains('\n'));
+}
+
+#[test]
+fn emit_wraps_to_max_width() {
+    use crate::emit::*;
+
+    let doc = document(
+        "short = [1, 2]\nlong = [\"alpha\", \"beta\", \"gamma\", [1, 2], { deep = true }]\nmeta = { a = 1 }",
+    );
+    let options = EmitOptions::new()
+        .indent(2, ' ')
+        .max_width(30)
+        .trailing_commas(true);
+    let text = to_string_with(&doc, &options);
+
+    assert_eq!(
+        text,
+        "short = [1, 2]\nlong = [\n  \"alpha\",\n  \"beta\",\n  \"gamma\",\n  [1, 2],\n  { deep = true },\n]\n\n[meta]\na = 1\n"
+    );
+    assert_eq!(document(&text).root, doc.root);
+
+    let braces = EmitOptions::new().tables(TableStyle::Braces).max_width(20);
+    let text = to_string_with(&doc, &braces);
+    assert!(text.contains("\nmeta = { a = 1 }\n"));
+    assert!(text.contains("    { deep = true }\n]"));
+    assert_eq!(document(&text).root, doc.root);
+}
+
+#[test]
+fn comments_attach_programmatically() {
+    use crate::emit::*;
+
+    let mut doc = document("name = \"a\"\n[server]\nport = 80");
+    doc.set_comment(
+        "name",
+        CommentPosition::Leading,
+        "service name\n\nkeep short",
+    )
+    .unwrap();
+    doc.set_comment("server", CommentPosition::Inline, "network")
+        .unwrap();
+    doc.set_comment("server.port", CommentPosition::Trailing, "end")
+        .unwrap();
+    doc.set_comment("name", CommentPosition::Inline, "")
+        .unwrap();
+
+    assert_eq!(
+        to_string(&doc),
+        "# service name\n#\n# keep short\nname = \"a\"\n\n[server] # network\nport = 80\n# end\n"
+    );
+    assert_eq!(
+        doc.set_comment("server.host", CommentPosition::Leading, "x"),
+        Err(PathError::Missing)
+    );
+    assert_eq!(
+        doc.set_comment("name.x", CommentPosition::Leading, "x"),
+        Err(PathError::NotATable)
+    );
+
+    let reparsed = document(&to_string(&doc));
+    assert_eq!(to_string(&reparsed), to_string(&doc));
+}
+
+#[test]
+fn emit_aligns_assignments() {
+    use crate::emit::*;
+
+    let mut doc = document(
+        "a = 1\nlonger = 2\n[server]\nhost = \"x\"\n\"odd key\" = { id = 1, identifier = 2 }",
+    );
+    let options = EmitOptions::new().align(true).tables(TableStyle::Braces);
+    assert_eq!(
+        to_string_with(&doc, &options),
+        "a      = 1\nlonger = 2\nserver = {\n    host      = \"x\",\n    \"odd key\" = {\n        id         = 1,\n        identifier = 2\n    }\n}\n"
+    );
+
+    doc.set("a_much_longer_key", 3.into()).unwrap();
+    doc.remove("server");
+    assert_eq!(
+        to_string_with(&doc, &options),
+        "a                 = 1\nlonger            = 2\na_much_longer_key = 3\n"
+    );
+
+    // edits laid over the source are realigned along with the lines kept from it
+    let text = "a = 1 # one\nlonger   =  2\n\n# note\n\"odd key\" = 3\n[server]\nhost = \"x\"\n";
+    let cst = crate::cst::Cst::parse(&Source::new("", text.into())).unwrap();
+    let mut doc = document(text);
+    doc.set("a_much_longer_key", 4.into()).unwrap();
+    doc.set("server.port", 80.into()).unwrap();
+    assert_eq!(
+        to_string_preserving_with(&cst, &doc, &options),
+        "a                 = 1 # one\nlonger            =  2\n\n# note\n\"odd key\"         = 3\n\
+         a_much_longer_key = 4\n[server]\nhost = \"x\"\nport = 80\n"
+    );
+    assert_eq!(to_string_preserving(&cst, &document(text)), text);
+}
+
+#[cfg(feature = "binary")]
+#[test]
+fn binary_encoding_round_trips() {
+    use crate::binary::*;
+
+    let doc = document(
+        "name = \"pcf\"\nblob = b\"\\x00\\xff\"\nneg = -300\nmax = 9223372036854775807\nmin = -9223372036854775807 - 1\nratio = 0.25\non = true\nlist = [1, \"a\", [false]]\n[nested.deep]\nkey = {}",
+    );
+    let bytes = encode(&doc);
+    assert_eq!(&bytes[..4], b"PCF\x01");
+    assert_eq!(decode(&bytes), Ok(Document::new(doc.root.clone())));
+    assert!(bytes.len() < alloc::format!("{}", doc).len());
+
+    assert_eq!(decode(b"TOML"), Err(DecodeError::InvalidMagic));
+    assert_eq!(
+        decode(&bytes[..bytes.len() - 1]),
+        Err(DecodeError::UnexpectedEnd)
+    );
+    assert_eq!(
+        decode(&[b'P', b'C', b'F', 1, 1, 1, b'k', 9]),
+        Err(DecodeError::InvalidTag(9))
+    );
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(decode(&trailing), Err(DecodeError::TrailingBytes));
+}
+
+#[test]
+fn canonical_hashes_ignore_formatting() {
+    #[derive(Default)]
+    struct Fnv(u64);
+
+    impl core::hash::Hasher for Fnv {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x100000001b3);
+            }
+        }
+    }
+
+    let a = document("# config\nname = \"a\"\n[db]\nport = 80\nhost = \"x\"");
+    let b = document("db = { host = \"x\", port = 80 } # inline\nname   =   \"a\"");
+    let c = document("name = \"a\"\n[db]\nport = 81\nhost = \"x\"");
+
+    assert_eq!(a.canonical_hash::<Fnv>(), b.canonical_hash::<Fnv>());
+    assert_ne!(a.canonical_hash::<Fnv>(), c.canonical_hash::<Fnv>());
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn documents_export_to_json() {
+    let doc = document(
+        "name = \"a\\\"b\"\nratio = 2.0\nbytes = b\"hi!\"\ntags = [1, true]\n[db]\nport = 80",
+    );
+
+    assert_eq!(
+        doc.to_json_string(),
+        "{\"name\":\"a\\\"b\",\"ratio\":2.0,\"bytes\":\"aGkh\",\"tags\":[1,true],\"db\":{\"port\":80}}"
+    );
+
+    let spanned = document("port = 80").to_json_string_spanned();
+    assert_eq!(
+        spanned,
+        "{\"port\":{\"key_span\":{\"begin\":{\"line\":1,\"col\":1,\"offset\":0},\"end\":{\"line\":1,\"col\":5,\"offset\":4}},\"span\":{\"begin\":{\"line\":1,\"col\":8,\"offset\":7},\"end\":{\"line\":1,\"col\":10,\"offset\":9}},\"value\":80}}"
+    );
+
+    let doc = document("ok = 1.5\nnan = 0.0 / 0.0\n[t]\nlimits = [1.0, -1.0 / 0.0]");
+    let (json, warnings) = doc.to_json_string_with_warnings();
+    assert_eq!(json, doc.to_json_string());
+    assert_eq!(
+        json,
+        "{\"ok\":1.5,\"nan\":null,\"t\":{\"limits\":[1.0,null]}}"
+    );
+    assert_eq!(
+        warnings
+            .iter()
+            .map(|warning| alloc::format!("{}", warning))
+            .collect::<alloc::vec::Vec<_>>(),
+        [
+            "[nan:2 7..16] encountered non-finite float (written as null) during conversion",
+            "[t.limits[1]:4 10..27] encountered non-finite float (written as null) during conversion",
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn diagnostics_export_as_json_lines() {
+    use crate::diagnostic::*;
+
+    let src = Source::new("app.pcf", "a = 1\na = \"\\q\"".into());
+    let (_, diagnostics) = crate::parse_diagnostics(&src, ParserOptions::new());
+    let lines = diagnostics.to_json_lines(src.file);
+
+    assert_eq!(
+        lines,
+        "{\"severity\":\"error\",\"code\":\"PCF0106\",\"message\":\"encountered duplicate key \
+         during parsing\",\"file\":\"app.pcf\",\"labels\":[{\"span\":{\"begin\":{\"line\":2,\
+         \"col\":1,\"offset\":6},\"end\":{\"line\":2,\"col\":2,\"offset\":7}},\"message\":\"\"},\
+         {\"span\":{\"begin\":{\"line\":1,\"col\":1,\"offset\":0},\"end\":{\"line\":1,\"col\":2,\
+         \"offset\":1}},\"message\":\"previously defined here\"}],\"notes\":[],\"help\":null}\n\
+         {\"severity\":\"warning\",\"code\":\"PCF0120\",\"message\":\"encountered unknown escape \
+         during parsing\",\"file\":\"app.pcf\",\"labels\":[{\"span\":{\"begin\":{\"line\":2,\
+         \"col\":6,\"offset\":11},\"end\":{\"line\":2,\"col\":8,\"offset\":13}},\"message\":\"\"}],\
+         \"notes\":[],\"help\":null}\n"
+    );
+
+    let diagnostic = Diagnostic::warning("PCF0401", "encountered \"x\"")
+        .label(Span::default(), "here")
+        .note("did you mean `y`?");
+    assert_eq!(
+        diagnostic.to_json_string("a\\b.pcf"),
+        "{\"severity\":\"warning\",\"code\":\"PCF0401\",\"message\":\"encountered \\\"x\\\"\",\
+         \"file\":\"a\\\\b.pcf\",\"labels\":[{\"span\":{\"begin\":{\"line\":1,\"col\":1,\
+         \"offset\":0},\"end\":{\"line\":1,\"col\":1,\"offset\":0}},\"message\":\"here\"}],\
+         \"notes\":[\"did you mean `y`?\"],\"help\":null}"
+    );
+}
+
+#[test]
+#[cfg(feature = "toml")]
+fn documents_convert_to_and_from_toml() {
+    let doc = document("name = \"app\"\nratio = 0.5\ntags = [\"a\", \"b\"]\n[db]\nport = 80");
+    let toml = doc.to_toml_string().unwrap();
+
+    assert_eq!(
+        toml,
+        "name = \"app\"\nratio = 0.5\ntags = [\"a\", \"b\"]\n\n[db]\nport = 80\n"
+    );
+    assert_eq!(Document::from_toml_str(&toml).unwrap().root, doc.root);
+
+    let imported =
+        Document::from_toml_str("when = 1979-05-27T07:32:00Z\n[[servers]]\nhost = \"x\"").unwrap();
+    assert_eq!(
+        imported.root.lookup(&"when".parse().unwrap()),
+        Some(&Value::String("1979-05-27T07:32:00Z".into()))
+    );
+    assert_eq!(
+        imported.root.lookup(&"servers[0].host".parse().unwrap()),
+        Some(&Value::String("x".into()))
+    );
+    assert!(Document::from_toml_str("a = ").is_err());
+}
+
+#[test]
+#[cfg(feature = "yaml")]
+fn documents_convert_to_and_from_yaml() {
+    use crate::yaml::*;
+
+    let doc = document("name = \"app\"\nports = [80, 443]\n[db]\nratio = 0.5");
+    let yaml = doc.to_yaml_string();
+
+    assert_eq!(
+        yaml,
+        "---\nname: app\nports:\n  - 80\n  - 443\ndb:\n  ratio: 0.5"
+    );
+
+    let (imported, warnings) = Document::from_yaml_str(&yaml).unwrap();
+    assert_eq!(imported.root, doc.root);
+    assert!(warnings.is_empty());
+
+    let (imported, warnings) = Document::from_yaml_str(
+        "base: &b\n  ü: 1\nderived: *b\n1: one\nnothing: ~\nquoted: \"true\"",
+    )
+    .unwrap();
+
+    assert_eq!(
+        warnings
+            .iter()
+            .map(|w| w.kind)
+            .collect::<alloc::vec::Vec<_>>(),
+        [
+            YamlWarningKind::Anchor,
+            YamlWarningKind::Alias,
+            YamlWarningKind::NonStringKey,
+            YamlWarningKind::Null
+        ]
+    );
+    assert_eq!(
+        alloc::format!("{}", warnings[1]),
+        "[derived:3:10] encountered alias (copied) during conversion"
+    );
+    assert_eq!(
+        imported.root.lookup(&"derived.ü".parse().unwrap()),
+        Some(&Value::Integer(1))
+    );
+    assert_eq!(imported.root.get("1"), Some(&"one".into()));
+    assert_eq!(imported.root.get("quoted"), Some(&"true".into()));
+    assert!(imported.root.get("nothing").is_none());
+
+    let error = Document::from_yaml_str("- 1\n- 2").unwrap_err();
+    assert_eq!(error.kind, YamlErrorKind::NotATable);
+    assert!(Document::from_yaml_str("a: [1").is_err());
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn documents_import_from_json() {
+    use crate::{json::*, lex::OverflowPolicy};
+
+    let (doc, warnings) = Document::from_json_str(
+        "{\"name\": \"a\\u00e9\\ud83d\\ude00\", \"port\": 80, \"ratio\": 1.0, \"big\": 1e3,\n \"id\": 18446744073709551615, \"gone\": null, \"tags\": [true, null, -0]}",
+    )
+    .unwrap();
+
+    assert_eq!(doc.root.get("name"), Some(&"aé😀".into()));
+    assert_eq!(doc.root.get("port"), Some(&Value::Integer(80)));
+    assert_eq!(doc.root.get("ratio"), Some(&Value::Float(1.0)));
+    assert_eq!(doc.root.get("big"), Some(&Value::Float(1000.0)));
+    assert_eq!(
+        doc.root.get("id"),
+        Some(&Value::Float(18446744073709551615.0))
+    );
+    assert!(doc.root.get("gone").is_none());
+    let dropped: alloc::vec::Vec<_> = warnings.iter().map(|w| alloc::format!("{w}")).collect();
+    assert_eq!(
+        dropped,
+        [
+            "[gone:2 38..42] encountered null (dropped) during conversion",
+            "[tags[1]:2 59..63] encountered null (dropped) during conversion"
+        ]
+    );
+    assert_eq!(
+        doc.root.get("tags"),
+        Some(&Value::Array(alloc::vec![true.into(), Value::Integer(0)]))
+    );
+    assert_eq!(
+        Document::from_json_str(&doc.to_json_string())
+            .unwrap()
+            .0
+            .root,
+        doc.root
+    );
+
+    let (empty, _) = Document::from_json_str("{\"\": {\"\": 1}}").unwrap();
+    assert_eq!(document(&crate::emit::to_string(&empty)).root, empty.root);
+
+    let error = |content| Document::from_json_str(content).unwrap_err().kind;
+    assert_eq!(error("[1]"), JsonErrorKind::NotAnObject);
+    assert_eq!(error("{\"a\": 1, \"a\": 2}"), JsonErrorKind::DuplicateKey);
+    assert_eq!(error("{\"a\": 01}"), JsonErrorKind::UnexpectedCharacter);
+    assert_eq!(error("{\"a\": 1.}"), JsonErrorKind::MalformedNumber);
+    assert_eq!(error("{\"a\": 1,}"), JsonErrorKind::UnexpectedCharacter);
+    assert_eq!(error("{\"a\": 1} x"), JsonErrorKind::TrailingCharacters);
+    assert_eq!(error("{\"a\": \"\\x\"}"), JsonErrorKind::InvalidEscape);
+    assert_eq!(error("{\"a\": "), JsonErrorKind::UnexpectedEnd);
+
+    let overflow =
+        Document::from_json_str_with("{\"a\": -9223372036854775809}", OverflowPolicy::Error);
+    assert_eq!(overflow.unwrap_err().kind, JsonErrorKind::IntegerOverflow);
+
+    let saturated =
+        Document::from_json_str_with("{\"a\": -9223372036854775809}", OverflowPolicy::Saturate);
+    assert_eq!(
+        saturated.unwrap().0.root.get("a"),
+        Some(&Value::Integer(i64::MIN))
+    );
+}
+
+#[test]
+#[cfg(feature = "ini")]
+fn documents_import_from_ini() {
+    use crate::ini::*;
+
+    let content = "; global\nname = app\n\n[server.main]\nport = 8080\ndebug = yes\nratio: 0.5\nlabel = \"42\"\nflag\n";
+
+    let plain = Document::from_ini_str(content).unwrap();
+    assert_eq!(plain.root.get("name"), Some(&"app".into()));
+    assert_eq!(
+        plain.root.lookup(&"\"server.main\".port".parse().unwrap()),
+        Some(&"8080".into())
+    );
+    assert_eq!(
+        plain.root.get_entry("name").unwrap().comments.leading,
+        [" global"]
+    );
+
+    let typed =
+        Document::from_ini_str_with(content, &IniOptions::new().infer(true).nested(true)).unwrap();
+    let get = |path: &str| typed.root.lookup(&path.parse().unwrap()).cloned();
+    assert_eq!(get("server.main.port"), Some(Value::Integer(8080)));
+    assert_eq!(get("server.main.debug"), Some(Value::Bool(true)));
+    assert_eq!(get("server.main.ratio"), Some(Value::Float(0.5)));
+    assert_eq!(get("server.main.label"), Some("42".into()));
+    assert_eq!(get("server.main.flag"), Some("".into()));
+
+    let span = typed.root.get_entry("name").unwrap().span;
+    assert_eq!(
+        (span.begin.line, span.begin.col, span.begin.offset),
+        (2, 8, 16)
+    );
+
+    let error = |content| Document::from_ini_str(content).unwrap_err().kind;
+    assert_eq!(error("[open"), IniErrorKind::UnterminatedSection);
+    assert_eq!(error("= 1"), IniErrorKind::EmptyKey);
+    assert_eq!(error("a = 1\n[a]"), IniErrorKind::ConflictingKey);
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serde_serializes_to_documents() {
+    use crate::{emit::EmitOptions, ser::*};
+    use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    enum Mode {
+        Fast,
+        Limited(u32),
+    }
+
+    #[derive(Serialize)]
+    struct Database {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Serialize)]
+    struct Config {
+        name: &'static str,
+        ratio: f64,
+        missing: Option<i32>,
+        tags: Vec<&'static str>,
+        modes: Vec<Mode>,
+        db: Database,
+    }
+
+    let config = Config {
+        name: "app",
+        ratio: 0.5,
+        missing: None,
+        tags: vec!["a", "b"],
+        modes: vec![Mode::Fast, Mode::Limited(3)],
+        db: Database {
+            host: "localhost".into(),
+            port: 5432,
+        },
+    };
+
+    let text = crate::to_string(&config).unwrap();
+    assert_eq!(
+        text,
+        "name = \"app\"\nratio = 0.5\ntags = [\"a\", \"b\"]\nmodes = [\"Fast\", { Limited = 3 }]\n\n[db]\nhost = \"localhost\"\nport = 5432\n"
+    );
+    assert_eq!(document(&text).root, to_document(&config).unwrap().root);
+
+    let minified = crate::to_string_with(&config.db, &EmitOptions::minified()).unwrap();
+    assert_eq!(minified, "host=\"localhost\" port=5432");
+
+    let mut keyed = BTreeMap::new();
+    keyed.insert(vec![1], 1);
+    let error = to_value(&keyed).unwrap_err();
+    assert_eq!(error.kind, SerializeErrorKind::KeyMustBeAString);
+    assert_eq!(
+        to_document(&1).unwrap_err().kind,
+        SerializeErrorKind::NotATable
+    );
+
+    let error = to_value(&(1, [Some(2), None])).unwrap_err();
+    assert_eq!(error.kind, SerializeErrorKind::MissingValue);
+    assert_eq!(alloc::format!("{}", error.path), "[1][1]");
+
+    assert_eq!(to_value(&(i64::MAX as u64)), Ok(Value::Integer(i64::MAX)));
+    assert_eq!(
+        to_value(&u64::MAX).unwrap_err().kind,
+        SerializeErrorKind::IntegerOutOfRange
+    );
+    assert_eq!(
+        to_value(&i128::MIN).unwrap_err().kind,
+        SerializeErrorKind::IntegerOutOfRange
+    );
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serde_deserializes_spanned_values() {
+    use crate::de::*;
+    use alloc::{string::String, vec::Vec};
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum Mode {
+        Fast,
+        Limited(u32),
+    }
+
+    #[derive(Deserialize)]
+    struct Database {
+        host: String,
+        port: Spanned<u16>,
+    }
+
+    #[derive(Deserialize)]
+    struct Config {
+        name: Spanned<String>,
+        missing: Option<i32>,
+        tags: Vec<Spanned<String>>,
+        modes: Vec<Mode>,
+        db: Database,
+    }
+
+    let doc = document(
+        "name = \"app\"\ntags = [\"a\", \"b\"]\nmodes = [\"Fast\", { Limited = 3 }]\n\n[db]\nhost = \"localhost\"\nport = 5432",
+    );
+    let config: Config = from_document(&doc).unwrap();
+
+    assert_eq!(*config.name, "app");
+    assert_eq!(config.name.span, doc.root.get_entry("name").unwrap().span);
+    assert_eq!(config.missing, None);
+    assert!(
+        config
+            .tags
+            .iter()
+            .all(|tag| tag.span == doc.root.get_entry("tags").unwrap().span)
+    );
+    assert_eq!(config.modes, [Mode::Fast, Mode::Limited(3)]);
+    assert_eq!(config.db.host, "localhost");
+    assert_eq!(config.db.port.into_inner(), 5432);
+    assert_eq!(
+        (
+            config.db.port.span.begin.line,
+            config.db.port.span.begin.col
+        ),
+        (7, 8)
+    );
+
+    assert!(from_document::<Database>(&document("host = 1")).is_err());
+
+    #[derive(Deserialize)]
+    struct Cluster {
+        servers: Vec<Database>,
+    }
+
+    let src = "servers = [{ host = \"a\", port = 1 }, { host = \"b\", port = 22 }]";
+    let cluster: Cluster = from_document(&document(src)).unwrap();
+    let ports: Vec<_> = cluster
+        .servers
+        .iter()
+        .map(|server| &src[server.port.span.begin.offset..server.port.span.end.offset])
+        .collect();
+    assert_eq!(ports, ["1", "22"]);
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serde_errors_carry_paths_and_spans() {
+    use crate::de::*;
+    use alloc::vec::Vec;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Server {
+        host: alloc::string::String,
+        ports: Vec<u16>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Config {
+        servers: Vec<Server>,
+    }
+
+    let doc =
+        document("servers = [{ host = \"a\", ports = [80] }, { host = \"b\", ports = [1, -2] }]");
+    let error = from_document::<Config>(&doc).unwrap_err();
+    assert_eq!(alloc::format!("{}", error.path), "servers[1].ports[1]");
+    assert_eq!((error.span.begin.col, error.span.end.col), (64, 71));
+    assert!(matches!(
+        error.kind,
+        DeserializeErrorKind::InvalidValue { .. }
+    ));
+
+    let doc = document("[db]\nhost = \"x\"");
+    let error = from_document::<Config>(&doc).unwrap_err();
+    assert_eq!(error.kind, DeserializeErrorKind::MissingField("servers"));
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Database {
+        port: u16,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Outer {
+        db: Database,
+    }
+
+    let error = from_document::<Outer>(&doc).unwrap_err();
+    assert_eq!(
+        alloc::format!("{}", error),
+        "[db:1 2..4] encountered missing field `port` during deserialization"
+    );
+
+    let error = from_document::<Outer>(&document("[db]\nport = \"x\"")).unwrap_err();
+    assert_eq!(
+        alloc::format!("{}", error),
+        "[db.port:2 8..11] encountered string \"x\" during deserialization, expected u16"
+    );
+}
+
+#[test]
+#[cfg(feature = "derive")]
+fn derived_conversions_read_and_write_documents() {
+    use crate::{
+        FromPcf, ToPcf,
+        emit::ToPcf as _,
+        extract::{Cursor, ExtractErrorKind, FromValue},
+    };
+    use alloc::{string::String, vec::Vec};
+
+    #[derive(FromPcf, ToPcf, Debug, PartialEq)]
+    struct Name(String);
+
+    #[derive(FromPcf, ToPcf, Debug, PartialEq)]
+    struct Limits(u8, u8);
+
+    #[derive(FromPcf, ToPcf, Debug, PartialEq)]
+    struct Server {
+        #[pcf(rename = "hostname")]
+        host: Name,
+        #[pcf(default = 8080, range = 1..=9999)]
+        port: u16,
+        #[pcf(default)]
+        tags: Vec<String>,
+        weight: Option<f64>,
+        limits: Limits,
+    }
+
+    let server: Server = document("hostname = \"a\"\nlimits = [1, 2]")
+        .extract()
+        .unwrap();
+    assert_eq!(
+        server,
+        Server {
+            host: Name("a".into()),
+            port: 8080,
+            tags: Vec::new(),
+            weight: None,
+            limits: Limits(1, 2),
+        }
+    );
+
+    let emitted = server.to_pcf().unwrap();
+    let cursor = Cursor::new(&emitted, Default::default(), Default::default());
+    assert_eq!(Server::from_value(&cursor).unwrap(), server);
+    assert!(matches!(&emitted, crate::Value::Table(t) if t.contains_key("hostname")));
+
+    let error = document("hostname = \"a\"\nport = 10000\nlimits = [1, 2]")
+        .extract::<Server>()
+        .unwrap_err();
+    assert_eq!(error.kind, ExtractErrorKind::OutOfRange);
+    assert_eq!(alloc::format!("{}", error.path), "port");
+    assert_eq!((error.span.begin.line, error.span.begin.col), (2, 8));
+
+    let error = document("limits = [1, 2]").extract::<Server>().unwrap_err();
+    assert_eq!(error.kind, ExtractErrorKind::MissingKey);
+    assert_eq!(alloc::format!("{}", error.path), "hostname");
+
+    let error = document("hostname = \"a\"\nlimits = [1]")
+        .extract::<Server>()
+        .unwrap_err();
+    assert_eq!(error.kind, ExtractErrorKind::Invalid);
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serde_round_trips_enum_representations() {
+    use crate::{de::*, ser::*};
+    use alloc::{string::String, vec, vec::Vec};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum External {
+        Unit,
+        Newtype(i64),
+        Tuple(i64, String),
+        Struct { name: String },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(tag = "kind", content = "data")]
+    enum Adjacent {
+        Unit,
+        Newtype(i64),
+        Struct { name: String },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Config {
+        external: Vec<External>,
+        adjacent: Vec<Adjacent>,
+    }
+
+    let config = Config {
+        external: vec![
+            External::Unit,
+            External::Newtype(1),
+            External::Tuple(2, "b".into()),
+            External::Struct { name: "c".into() },
+        ],
+        adjacent: vec![
+            Adjacent::Unit,
+            Adjacent::Newtype(1),
+            Adjacent::Struct { name: "c".into() },
+        ],
+    };
+
+    let text = to_string(&config).unwrap();
+    assert_eq!(from_document::<Config>(&document(&text)).unwrap(), config);
+
+    let doc = document(
+        "external = [\"Unit\", { Newtype = 1 }, { Tuple = [2, \"b\"] }, { Struct = { name = \"c\" } }]\n\
+         adjacent = [{ kind = \"Unit\" }, { kind = \"Newtype\", data = 1 }, \
+         { kind = \"Struct\", data = { name = \"c\" } }]",
+    );
+    assert_eq!(from_document::<Config>(&doc).unwrap(), config);
+
+    let error = from_value::<External>(&Value::String("Missing".into())).unwrap_err();
+    assert_eq!(
+        error.kind,
+        DeserializeErrorKind::UnknownVariant {
+            variant: "Missing".into(),
+            suggestion: None,
+        }
+    );
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serde_round_trips_unit_fields() {
+    use crate::{de::*, ser::*};
+    use alloc::{vec, vec::Vec};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Marker;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Config {
+        unit: (),
+        marker: Marker,
+        some: Option<()>,
+        none: Option<()>,
+        units: Vec<()>,
+    }
+
+    let config = Config {
+        unit: (),
+        marker: Marker,
+        some: Some(()),
+        none: None,
+        units: vec![(), ()],
+    };
+
+    let text = to_string(&config).unwrap();
+    assert_eq!(
+        text,
+        "[unit]\n\n[marker]\n\n[some]\n\n[[units]]\n\n[[units]]\n"
+    );
+    assert_eq!(from_document::<Config>(&document(&text)).unwrap(), config);
+
+    let error =
+        from_document::<Config>(&document("unit = 1\nmarker = {}\nunits = []")).unwrap_err();
+    assert_eq!(alloc::format!("{}", error.path), "unit");
+}
+
+#[test]
+#[cfg(feature = "derive")]
+fn derived_enums_round_trip() {
+    use crate::{
+        FromPcf, ToPcf,
+        emit::ToPcf as _,
+        extract::{Cursor, ExtractErrorKind, FromValue},
+    };
+    use alloc::{string::String, vec, vec::Vec};
+
+    #[derive(FromPcf, ToPcf, Debug, PartialEq)]
+    enum External {
+        Unit,
+        #[pcf(rename = "new")]
+        Newtype(Option<i64>),
+        Tuple(i64, String),
+        Struct {
+            #[pcf(default)]
+            name: String,
+        },
+    }
+
+    #[derive(FromPcf, ToPcf, Debug, PartialEq)]
+    #[pcf(tag = "kind", content = "data")]
+    enum Adjacent {
+        Unit,
+        Newtype(i64),
+        Struct { name: String },
+    }
+
+    #[derive(FromPcf, ToPcf, Debug, PartialEq)]
+    struct Config {
+        external: Vec<External>,
+        adjacent: Vec<Adjacent>,
+    }
+
+    let config = Config {
+        external: vec![
+            External::Unit,
+            External::Newtype(Some(1)),
+            External::Newtype(None),
+            External::Tuple(2, "b".into()),
+            External::Struct { name: "c".into() },
+        ],
+        adjacent: vec![
+            Adjacent::Unit,
+            Adjacent::Newtype(1),
+            Adjacent::Struct { name: "c".into() },
+        ],
+    };
+
+    let doc = document(
+        "external = [\"Unit\", { new = 1 }, \"new\", { Tuple = [2, \"b\"] }, { Struct = { name = \"c\" } }]\n\
+         adjacent = [{ kind = \"Unit\" }, { kind = \"Newtype\", data = 1 }, \
+         { kind = \"Struct\", data = { name = \"c\" } }]",
+    );
+    assert_eq!(doc.extract::<Config>().unwrap(), config);
+
+    let emitted = config.to_pcf().unwrap();
+    let cursor = Cursor::new(&emitted, Default::default(), Default::default());
+    assert_eq!(Config::from_value(&cursor).unwrap(), config);
+
+    let error = document("external = [\"Other\"]\nadjacent = []")
+        .extract::<Config>()
+        .unwrap_err();
+    assert_eq!(error.kind, ExtractErrorKind::UnknownVariant);
+    assert_eq!(alloc::format!("{}", error.path), "external[0]");
+
+    let error = document("external = []\nadjacent = [{ data = 1 }]")
+        .extract::<Config>()
+        .unwrap_err();
+    assert_eq!(error.kind, ExtractErrorKind::MissingKey);
+    assert_eq!(alloc::format!("{}", error.path), "adjacent[0].kind");
+
+    let error = document("external = [{ Unit = 1 }]\nadjacent = []")
+        .extract::<Config>()
+        .unwrap_err();
+    assert_eq!(error.kind, ExtractErrorKind::Invalid);
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serde_reports_each_unknown_field_with_suggestions() {
+    use crate::{de::*, utils::*};
+    use alloc::{collections::BTreeMap, string::String, vec::Vec};
+    use serde::Deserialize;
+
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("prot", "port"), 1);
+    assert_eq!(suggest("prot", ["host", "port"]), Some("port"));
+    assert_eq!(suggest("name", ["host", "port"]), None);
+
+    #[derive(Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    #[allow(dead_code)]
+    struct Server {
+        host: String,
+        #[serde(default)]
+        port: u16,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Config {
+        server: Server,
+    }
+
+    let doc = document("[server]\nhots = \"a\"\nprot = 80\nname = \"x\"");
+
+    let error = from_document::<Config>(&doc).unwrap_err();
+    assert_eq!(
+        alloc::format!("{}", error),
+        "[server.hots:2 1..5] encountered unknown field `hots` during deserialization, \
+         did you mean `host`?"
+    );
+
+    // once the unknown keys are passed over, the walk carries on and finds `host` missing
+    let mut errors = from_document_recovering::<Config>(&doc).unwrap_err();
+    let missing = errors.pop().unwrap();
+    assert_eq!(missing.kind, DeserializeErrorKind::MissingField("host"));
+    assert_eq!(alloc::format!("{}", missing.path), "server");
+
+    let found: Vec<_> = errors
+        .iter()
+        .map(|error| {
+            let DeserializeErrorKind::UnknownField { suggestion, .. } = &error.kind else {
+                panic!("expected an unknown field, got {:?}", error.kind);
+            };
+            (
+                alloc::format!("{}", error.path),
+                error.span.begin.line,
+                *suggestion,
+            )
+        })
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("server.hots".into(), 2, Some("host")),
+            ("server.prot".into(), 3, Some("port")),
+            ("server.name".into(), 4, None),
+        ]
+    );
+
+    let doc = document("[server]\nhost = \"a\"");
+    assert!(from_document_recovering::<Config>(&doc).is_ok());
+
+    #[derive(Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    #[allow(dead_code)]
+    struct Cluster {
+        primary: Server,
+        replica: Server,
+        #[serde(default)]
+        name: String,
+    }
+
+    let doc = document(
+        "[cluster]\nnmae = \"c\"\n[cluster.primary]\nhost = \"a\"\nhots = \"a\"\n\
+         [cluster.replica]\nhost = \"b\"\nprot = 80\nextra = 1",
+    );
+    let errors = from_table_recovering::<BTreeMap<String, Cluster>>(&doc.root).unwrap_err();
+    let found: Vec<_> = errors
+        .iter()
+        .map(|error| alloc::format!("{}", error.path))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            "cluster.nmae",
+            "cluster.primary.hots",
+            "cluster.replica.prot",
+            "cluster.replica.extra",
+        ]
+    );
+
+    #[derive(Deserialize, Debug)]
+    enum Mode {
+        Primary,
+    }
+
+    let error = from_value::<Mode>(&crate::Value::String("Primray".into())).unwrap_err();
+    assert_eq!(
+        error.kind,
+        DeserializeErrorKind::UnknownVariant {
+            variant: "Primray".into(),
+            suggestion: Some("Primary"),
+        }
+    );
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serde_borrows_strings_from_documents() {
+    use crate::{de::*, extract::Cursor};
+    use alloc::collections::BTreeMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(tag = "kind")]
+    enum Backend<'a> {
+        File { path: &'a str },
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Config<'a> {
+        name: &'a str,
+        key: &'a [u8],
+        #[serde(borrow)]
+        labels: BTreeMap<&'a str, &'a str>,
+        #[serde(borrow)]
+        backend: Backend<'a>,
+    }
+
+    let doc = document(
+        "name = \"app\"\nkey = b\"hi\"\nlabels = { tier = \"web\" }\n\
+         backend = { kind = \"File\", path = \"/tmp\" }",
+    );
+    let config = from_document::<Config>(&doc).unwrap();
+    assert_eq!(config.name, "app");
+    assert_eq!(config.key, b"hi");
+    assert_eq!(config.labels.get("tier"), Some(&"web"));
+    assert_eq!(config.backend, Backend::File { path: "/tmp" });
+
+    let Some(Value::String(name)) = doc.root.get("name") else {
+        panic!("expected a string");
+    };
+    assert!(core::ptr::eq(config.name, name.as_str()));
+
+    let cursor = Cursor::root(&doc.root);
+    let name = cursor.child("name").unwrap().unwrap().str().unwrap();
+    assert!(core::ptr::eq(name, config.name));
+    assert_eq!(
+        cursor.child("key").unwrap().unwrap().bytes().unwrap(),
+        b"hi"
+    );
+    assert!(cursor.child("key").unwrap().unwrap().str().is_err());
+}
+
+#[test]
+#[cfg(feature = "derive")]
+fn derived_comments_are_emitted() {
+    use crate::{FromPcf, ToPcf, emit};
+    use alloc::string::String;
+
+    #[derive(FromPcf, ToPcf, Default)]
+    struct Database {
+        #[pcf(comment = "connection string for the primary")]
+        url: String,
+    }
+
+    #[derive(FromPcf, ToPcf)]
+    struct Config {
+        /// Address to listen on.
+        ///
+        /// Use `0.0.0.0` to accept remote connections.
+        #[pcf(comment)]
+        host: String,
+        port: u16,
+        database: Database,
+    }
+
+    let config = Config {
+        host: "127.0.0.1".into(),
+        port: 8080,
+        database: Database::default(),
+    };
+
+    let text = emit::to_string(&Document::from_pcf(&config).unwrap());
+    assert!(text.starts_with(
+        "# Address to listen on.\n#\n# Use `0.0.0.0` to accept remote connections.\nhost = \"127.0.0.1\"\nport = 8080\n"
+    ));
+    assert!(text.contains("# connection string for the primary\n"));
+
+    let parsed: Config = document(&text).extract().unwrap();
+    assert_eq!((parsed.host, parsed.port), (config.host, config.port));
+}
+
+#[test]
+fn documents_extract_subtrees() {
+    use crate::extract::{Cursor, ExtractError, ExtractErrorKind, FromValue};
+    use alloc::string::String;
+
+    #[derive(Debug, PartialEq)]
+    struct Http {
+        host: String,
+        port: u16,
+    }
+
+    impl FromValue for Http {
+        fn from_value(cursor: &Cursor<'_>) -> core::result::Result<Self, ExtractError> {
+            Ok(Self {
+                host: cursor.field("host")?,
+                port: cursor.field("port")?,
+            })
+        }
+    }
+
+    let doc = document(
+        "[server.http]\nhost = \"a\"\nport = 80\n[server.grpc]\nhost = \"b\"\nport = \"x\"",
+    );
+
+    assert_eq!(
+        doc.extract_at::<Http>("server.http").unwrap(),
+        Http {
+            host: "a".into(),
+            port: 80,
+        }
+    );
+    assert_eq!(doc.extract_at::<u16>("server.http.port").unwrap(), 80);
+    assert_eq!(
+        doc.extract_at::<Option<Http>>("server.admin").unwrap(),
+        None
+    );
+
+    let error = doc.extract_at::<Http>("server.grpc").unwrap_err();
+    assert_eq!(alloc::format!("{}", error.path), "server.grpc.port");
+    assert_eq!(error.span.begin.line, 6);
+
+    let error = doc.extract_at::<Http>("server.admin").unwrap_err();
+    assert_eq!(error.kind, ExtractErrorKind::MissingKey);
+    assert_eq!(alloc::format!("{}", error.path), "server.admin");
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serde_deserializes_subtrees() {
+    use crate::de::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Http<'a> {
+        host: &'a str,
+        port: u16,
+    }
+
+    let doc = document(
+        "[server.http]\nhost = \"a\"\nport = 80\n[server.grpc]\nhost = \"b\"\nport = \"x\"",
+    );
+
+    assert_eq!(
+        from_document_at::<Http>(&doc, "server.http").unwrap(),
+        Http {
+            host: "a",
+            port: 80,
+        }
+    );
+
+    let error = from_document_at::<Http>(&doc, "server.grpc").unwrap_err();
+    assert_eq!(alloc::format!("{}", error.path), "server.grpc.port");
+
+    let error = from_document_at::<Http>(&doc, "server.admin").unwrap_err();
+    assert_eq!(error.kind, DeserializeErrorKind::MissingKey);
+    assert_eq!(
+        alloc::format!("{}", error),
+        "[server.admin:1 2..8] encountered missing key during deserialization"
+    );
+}
+
+#[test]
+#[cfg(feature = "arbitrary")]
+fn arbitrary_sources_parse_back() {
+    use crate::{arbitrary::ValidSource, lex::Lexeme};
+    use ::arbitrary::{Arbitrary, Unstructured};
+    use alloc::vec::Vec;
+
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut bytes = |len: usize| -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    };
+
+    for len in (0..2048).step_by(8) {
+        let data = bytes(len);
+        let source = ValidSource::arbitrary(&mut Unstructured::new(&data)).unwrap();
+        let parsed = crate::parse_str("arbitrary", &source.text)
+            .unwrap_or_else(|e| panic!("{}\n{}", e, source.text));
+        assert_eq!(parsed.root, source.document.root, "{}", source.text);
+
+        let lexemes = Vec::<Lexeme>::arbitrary(&mut Unstructured::new(&data)).unwrap();
+        assert!(lexemes.iter().all(|l| l.span.begin <= l.span.end));
+        let outcome = crate::check::tokens(&source.text, lexemes.into());
+        assert!(!outcome.is_bug(), "{:?}", outcome);
+        let _ = Value::arbitrary(&mut Unstructured::new(&data)).unwrap();
+    }
+}
+
+#[test]
+fn owned_errors_outlive_their_source() {
+    use alloc::string::{String, ToString};
+
+    fn load(content: &str) -> core::result::Result<Document, alloc::boxed::Box<OwnedError>> {
+        let file = String::from("owned.pcf");
+        let src = Source::new(&file, content.into());
+        parse(&src).map_err(|e| e.into_owned().into())
+    }
+
+    fn is_static<T: Send + Sync + 'static>(_: &T) {}
+
+    let src = Source::new("owned.pcf", "a = 1\na = 2".into());
+    let borrowed = parse(&src).unwrap_err().to_string();
+    let owned = load("a = 1\na = 2").unwrap_err();
+
+    is_static(&owned);
+    assert_eq!(owned.to_string(), borrowed);
+    assert_eq!(owned.file, "owned.pcf");
+    assert_eq!(owned.snippet.text, "a = 1\na = 2");
+}
+
+#[cfg(feature = "proptest")]
+::proptest::proptest! {
+    #[test]
+    fn proptest_sources_parse_back((text, document) in crate::proptest::source()) {
+        let parsed = crate::parse_str("proptest", &text)
+            .unwrap_or_else(|e| panic!("{}\n{}", e, text));
+        ::proptest::prop_assert_eq!(&parsed.root, &document.root, "{}", text);
+
+        let src = Source::new("proptest", text.clone());
+        let arena = crate::parse::parse_arena(&src).unwrap_or_else(|e| panic!("{}\n{}", e, text));
+        ::proptest::prop_assert_eq!(arena.root().to_table(), document.root, "{}", text);
+    }
+
+    #[test]
+    fn proptest_near_valid_sources_never_panic((text, _) in crate::proptest::near_valid_source()) {
+        let outcome = crate::check::never_panics(&text);
+        ::proptest::prop_assert!(!outcome.is_bug(), "{:?}\n{}", outcome, text);
+    }
+}
+
+#[test]
+fn scanner_agrees_with_the_lexer_without_allocating() {
+    use crate::scan::*;
+    use alloc::{string::String, vec::Vec};
+
+    fn agree(content: &str, options: LexerOptions) {
+        let src = Source::new("scan", content.into());
+        let scanned: Vec<_> = scan_with(content, options).collect();
+
+        match lex_with(&src, options) {
+            Ok(lexemes) => {
+                assert_eq!(scanned.len(), lexemes.len(), "{}", content);
+
+                for (scanned, lexeme) in scanned.iter().zip(&lexemes) {
+                    let scanned = scanned.as_ref().unwrap();
+                    assert_eq!(scanned.span, lexeme.span, "{}", content);
+                    assert_eq!(scanned.kind.token(), lexeme.kind.token(), "{}", content);
+
+                    match (scanned.kind, &lexeme.kind) {
+                        (kind, LexemeKind::String(string)) => {
+                            assert_eq!(&kind.chars().unwrap().collect::<String>(), string)
+                        }
+                        (kind, LexemeKind::Bytes(bytes)) => {
+                            assert_eq!(&kind.bytes().unwrap().collect::<Vec<u8>>(), bytes)
+                        }
+                        (
+                            BorrowedKind::Ident(text)
+                            | BorrowedKind::Comment(text)
+                            | BorrowedKind::Directive(text),
+                            LexemeKind::Ident(owned)
+                            | LexemeKind::Comment(owned)
+                            | LexemeKind::Directive(owned),
+                        ) => assert_eq!(text, owned),
+                        (BorrowedKind::Integer(a), LexemeKind::Integer(b)) => assert_eq!(a, *b),
+                        (BorrowedKind::Float(a), LexemeKind::Float(b)) => assert_eq!(a, *b),
+                        (BorrowedKind::Bool(a), LexemeKind::Bool(b)) => assert_eq!(a, *b),
+                        _ => {}
+                    }
+                }
+            }
+            Err(error) => {
+                assert!(scanned[..scanned.len() - 1].iter().all(|s| s.is_ok()));
+                assert_eq!(
+                    scanned.last().unwrap().as_ref().unwrap_err(),
+                    &(error.span, error.kind),
+                    "{}",
+                    content
+                );
+            }
+        }
+    }
+
+    let comments = LexerOptions {
+        comments: true,
+        ..Default::default()
+    };
+
+    for content in [
+        "name = \"p\\u{63}f\\q\" # a comment\n",
+        "raw = r##\"a \"# b\"##, bytes = b\"\\x00\\n\u{e9}\"",
+        "data = base64\"aGk g\", padded = base64\"aGk=\"",
+        "---\n@include \"x\"\n[a.b] n = -1.5 + .5 - 3 * 4 / 2 & true",
+        "big = 99999999999999999999",
+        "a = 5-3, b = x+1.5, c = [1]-2 # -1\n-2, \"s\"+.5 -4",
+        "x = \"\\u{zz}\"",
+        "x = b\"\\x1\"",
+        "x = base64\"a\"",
+        "x = 1.2.3",
+        "x = r#\"never closed",
+        "x = @",
+        "x = $",
+    ] {
+        agree(content, comments);
+        agree(content, LexerOptions::default());
+    }
+
+    let limited = LexerOptions {
+        max_string_len: Some(2),
+        max_size: Some(64),
+        ..Default::default()
+    };
+    agree("a = \"\\u{e9}\", b = \"abc\"", limited);
+    agree(&"a = 1\n".repeat(20), limited);
+
+    let saturate = LexerOptions {
+        overflow: OverflowPolicy::Saturate,
+        ..Default::default()
+    };
+    agree("big = -99999999999999999999", saturate);
+
+    let tokens = LexerOptions {
+        max_tokens: Some(2),
+        ..Default::default()
+    };
+    assert_eq!(
+        scan_with("a = 1", tokens).last(),
+        Some(Err((
+            Span::new(Location::new(1, 5, 4), Location::new(1, 6, 5)),
+            ErrorKind::Limit(LimitError::TooManyTokens { limit: 2, found: 3 })
+        )))
+    );
+}
+
+#[test]
+fn arena_documents_match_parsed_ones() {
+    use crate::arena::ArenaValue;
+
+    fn agree(content: &str, options: ParserOptions) {
+        let src = Source::new("arena", content.into());
+
+        match (parse_with(&src, options), parse_arena_with(&src, options)) {
+            (Ok(document), Ok(arena)) => assert_eq!(arena.root().to_table(), document.root),
+            (Err(parsed), Err(arena)) => assert_eq!(
+                (arena.kind, arena.span),
+                (parsed.kind, parsed.span),
+                "{}",
+                content
+            ),
+            (parsed, arena) => panic!("{:?}\n{:?}\n{}", parsed, arena, content),
+        }
+    }
+
+    for content in [
+        "name = \"pcf\"\nbytes = b\"\\x01\"\nn = 1\nf = 2.5\nyes = true",
+        "a = [1, [true, false], { x = \"y\", z = [] }]\nb.c.d = 3\nb.e = 4",
+        "[server]\nhost = \"h\"\n[server.tls]\non = true\n[client]\nx = 1\n[server]\nport = 8",
+        "[[users]]\nname = \"a\"\n[users.role]\nadmin = true\n[[users]]\nname = \"b\"",
+        "a = 1\na = 2",
+        "a = { x = 1 }\na.x.y = 2",
+        "a = 1\n[a]\nb = 2",
+        "t = { x = 1, x = 2 }",
+        "a = 2 * 4 - 1\nb = \"x\" + \"y\"\nc = [1 + 1, 2.0 / 0]\nd = 1 / 0",
+        "a = [1 -2]",
+        "a = &x { y = 1 }\nb = *x\nc = [*x, &z 2]\nd = *z + 1\ne = *missing",
+        "a = 1\nb = a\n[t]\nc = a",
+        "[base]\nx = 1\ny = { z = 2 }\n[c]\nextends = base\ny.w = 3\n[[list]]\nextends = base",
+        "[a]\nextends = b\n[b]\nextends = a",
+        "[a]\nextends = missing",
+        "x = 0.0 / 0.0\ny = -1.0 / 0.0",
+    ] {
+        for duplicates in [
+            DuplicatePolicy::Error,
+            DuplicatePolicy::LastWins,
+            DuplicatePolicy::FirstWins,
+        ] {
+            agree(content, ParserOptions::default().duplicates(duplicates));
+        }
+    }
+
+    let merge = ParserOptions::default().duplicates(DuplicatePolicy::Merge);
+    agree("t = { a = 1 }\nt = { b = [2] }", merge);
+    agree("t = { a = 1 }\nt = { a = 2 }", merge);
+
+    let main = Source::new("main", "a = 1\n[s]\n@include \"shared\"\nz = 0".into());
+    let includes = [Source::new("shared", "b = { c = \"d\" }".into())];
+    let document = parse_with_includes(&main, &includes, ParserOptions::default()).unwrap();
+    let arena = Parser::new(&main, lex(&main).unwrap())
+        .with_includes(&includes)
+        .parse_arena()
+        .unwrap();
+    assert_eq!(arena.root().to_table(), document.root);
+
+    let root = arena.root();
+    assert_eq!(root.keys().collect::<alloc::vec::Vec<_>>(), ["a", "s"]);
+    let Some(ArenaValue::Table(s)) = root.get("s") else {
+        panic!("{:?}", root);
+    };
+    assert_eq!(s.len(), 2);
+    assert!(matches!(s.get("z"), Some(ArenaValue::Integer(0))));
+    assert!(root.get("missing").is_none());
+
+    // references reach into what an include brought in, as they do when parsing
+    let main = Source::new("main", "@include \"shared\"\nx = b.c + \"e\"".into());
+    let document = parse_with_includes(&main, &includes, ParserOptions::default()).unwrap();
+    let arena = Parser::new(&main, lex(&main).unwrap())
+        .with_includes(&includes)
+        .parse_arena()
+        .unwrap();
+    assert_eq!(arena.root().to_table(), document.root);
+    assert!(matches!(
+        arena.root().get("x"),
+        Some(ArenaValue::String("de"))
+    ));
+}
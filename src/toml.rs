@@ -0,0 +1,84 @@
+use crate::{emit::write_base64, utils::Span, value::*};
+use alloc::string::{String, ToString};
+
+// toml has no byte strings, so bytes become padded standard base64
+fn base64(bytes: &[u8]) -> String {
+    let mut s = String::new();
+    let _ = write_base64(&mut s, bytes);
+    s
+}
+
+impl From<&Value> for toml::Value {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::String(s) => Self::String(s.clone()),
+            Value::Bytes(b) => Self::String(base64(b)),
+            Value::Integer(i) => Self::Integer(*i),
+            Value::Float(f) => Self::Float(*f),
+            Value::Bool(b) => Self::Boolean(*b),
+            Value::Array(array) => Self::Array(array.iter().map(Self::from).collect()),
+            Value::Table(table) => Self::Table(table.into()),
+        }
+    }
+}
+
+impl From<&Table> for toml::Table {
+    fn from(table: &Table) -> Self {
+        table
+            .iter()
+            .map(|(key, entry)| (key.clone(), (&entry.value).into()))
+            .collect()
+    }
+}
+
+impl From<&Document> for toml::Table {
+    fn from(document: &Document) -> Self {
+        (&document.root).into()
+    }
+}
+
+impl From<&toml::Value> for Value {
+    fn from(value: &toml::Value) -> Self {
+        match value {
+            toml::Value::String(s) => Self::String(s.clone()),
+            toml::Value::Integer(i) => Self::Integer(*i),
+            toml::Value::Float(f) => Self::Float(*f),
+            toml::Value::Boolean(b) => Self::Bool(*b),
+            // pcf has no datetime type, so these keep their rfc 3339 text
+            toml::Value::Datetime(datetime) => Self::String(datetime.to_string()),
+            toml::Value::Array(array) => Self::Array(array.iter().map(Self::from).collect()),
+            toml::Value::Table(table) => Self::Table(table.into()),
+        }
+    }
+}
+
+impl From<&toml::Table> for Table {
+    fn from(table: &toml::Table) -> Self {
+        let mut result = Table::new();
+
+        for (key, value) in table {
+            result.insert(
+                key.clone(),
+                Entry::new(value.into(), Span::default(), Span::default()),
+            );
+        }
+
+        result
+    }
+}
+
+impl From<&toml::Table> for Document {
+    fn from(table: &toml::Table) -> Self {
+        Document::new(table.into())
+    }
+}
+
+impl Document {
+    pub fn from_toml_str(content: &str) -> core::result::Result<Self, toml::de::Error> {
+        content.parse::<toml::Table>().map(|table| (&table).into())
+    }
+
+    pub fn to_toml_string(&self) -> core::result::Result<String, toml::ser::Error> {
+        toml::to_string(&toml::Table::from(self))
+    }
+}
@@ -1,21 +1,43 @@
-use alloc::string::String;
+use crate::lex::TokenKind;
+#[cfg(feature = "alloc")]
+use crate::{
+    diagnostic::Severity,
+    messages::{English, Messages},
+};
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, string::String, vec::Vec};
 use core::{fmt, str};
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct Location {
     pub line: usize,
     pub col: usize,
+    pub offset: usize,
 }
 
 impl Default for Location {
     fn default() -> Self {
-        Self { line: 1, col: 1 }
+        Self {
+            line: 1,
+            col: 1,
+            offset: 0,
+        }
     }
 }
 
 impl Location {
-    pub fn new(line: usize, col: usize) -> Self {
-        Self { line, col }
+    pub const fn new(line: usize, col: usize, offset: usize) -> Self {
+        Self { line, col, offset }
+    }
+
+    pub fn advance(&mut self, chr: char) {
+        self.offset += chr.len_utf8();
+
+        if chr == '\n' {
+            self.new_line();
+        } else {
+            self.new_col();
+        }
     }
 
     pub fn new_line(&mut self) {
@@ -57,17 +79,19 @@ impl fmt::Display for Span {
 }
 
 impl Span {
-    pub fn new(begin: Location, end: Location) -> Self {
+    pub const fn new(begin: Location, end: Location) -> Self {
         Self { begin, end }
     }
 }
 
+#[cfg(feature = "alloc")]
 #[derive(Default, Debug, PartialEq, Eq, Clone)]
 pub struct Source<'a> {
     pub file: &'a str,
     pub content: String,
 }
 
+#[cfg(feature = "alloc")]
 impl<'a> Source<'a> {
     pub fn new(file: &'a str, content: String) -> Self {
         Self { file, content }
@@ -77,96 +101,446 @@ impl<'a> Source<'a> {
         self.content.chars().peekable()
     }
 
+    // walks the source with the lexer's own rule for moving past a character, so the offset found
+    // for a line and column is the one the lexer gave it
     pub fn extract_offset(&self, tar: Location) -> usize {
         let mut loc = Location::default();
-        let mut offset: usize = 0;
 
         for chr in self.chars() {
             if loc.line == tar.line && loc.col == tar.col {
                 break;
             }
 
-            offset += chr.len_utf8();
-
-            if chr == '\n' {
-                loc.new_line();
-            } else {
-                loc.new_col();
-            }
+            loc.advance(chr);
         }
 
-        offset
+        loc.offset
+    }
+
+    pub fn slice(&self, span: Span) -> &str {
+        self.content
+            .get(span.begin.offset..span.end.offset)
+            .unwrap_or_default()
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LexingError {
+    ConfusableCharacter { found: char, expected: char },
+    IntegerOverflow,
+    InvalidEscape,
+    MalformedBytes,
     MalformedNumber,
+    MixedIndentation,
     UnrecognizedToken,
     UnterminatedString,
+    UnusualWhitespace { found: char },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expected {
+    Token(TokenKind),
+    Key,
+    Value,
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Token(token) => write!(f, "{}", token),
+            Self::Key => write!(f, "key"),
+            Self::Value => write!(f, "value"),
+        }
+    }
+}
+
+const TOKENS: [TokenKind; 21] = [
+    TokenKind::String,
+    TokenKind::Bytes,
+    TokenKind::Ident,
+    TokenKind::Integer,
+    TokenKind::Float,
+    TokenKind::Bool,
+    TokenKind::LBrack,
+    TokenKind::RBrack,
+    TokenKind::LBrace,
+    TokenKind::RBrace,
+    TokenKind::Equal,
+    TokenKind::Comma,
+    TokenKind::Dot,
+    TokenKind::Plus,
+    TokenKind::Minus,
+    TokenKind::Star,
+    TokenKind::Slash,
+    TokenKind::Ampersand,
+    TokenKind::Separator,
+    TokenKind::Comment,
+    TokenKind::Directive,
+];
+
+// everything the parser would have accepted where it stopped, one bit per token kind followed by
+// keys and values, so it iterates in a fixed order and stays `Copy` like the errors holding it
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExpectedSet {
+    bits: u32,
+}
+
+impl ExpectedSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn bit(expected: Expected) -> u32 {
+        match expected {
+            Expected::Token(token) => 1 << token as u32,
+            Expected::Key => 1 << TOKENS.len(),
+            Expected::Value => 1 << (TOKENS.len() + 1),
+        }
+    }
+
+    pub fn insert(&mut self, expected: Expected) {
+        self.bits |= Self::bit(expected);
+    }
+
+    pub fn with(mut self, expected: Expected) -> Self {
+        self.insert(expected);
+        self
+    }
+
+    pub fn contains(&self, expected: Expected) -> bool {
+        self.bits & Self::bit(expected) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Expected> {
+        let set = *self;
+
+        TOKENS
+            .into_iter()
+            .map(Expected::Token)
+            .chain([Expected::Key, Expected::Value])
+            .filter(move |expected| set.contains(*expected))
+    }
+}
+
+impl fmt::Debug for ExpectedSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl From<Expected> for ExpectedSet {
+    fn from(expected: Expected) -> Self {
+        Self::new().with(expected)
+    }
+}
+
+impl FromIterator<Expected> for ExpectedSet {
+    fn from_iter<I: IntoIterator<Item = Expected>>(iter: I) -> Self {
+        let mut set = Self::new();
+
+        for expected in iter {
+            set.insert(expected);
+        }
+
+        set
+    }
+}
+
+impl fmt::Display for ExpectedSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.len();
+
+        for (i, expected) in self.iter().enumerate() {
+            match (i, len) {
+                (0, _) => {}
+                (1, 2) => f.write_str(" or ")?,
+                (i, len) if i + 1 == len => f.write_str(", or ")?,
+                _ => f.write_str(", ")?,
+            }
+
+            write!(f, "{}", expected)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParsingError {
+    ArithmeticOverflow,
+    ConflictingKey {
+        first: Span,
+    },
+    CyclicExtends,
+    DivisionByZero,
+    DuplicateAnchor {
+        first: Span,
+    },
+    DuplicateKey {
+        first: Span,
+    },
+    IncludeCycle,
+    IncludeNotFound,
+    InvalidExtends,
+    InvalidKey,
+    InvalidOperands {
+        left: Span,
+    },
+    MixedArray {
+        first: Span,
+    },
+    TrailingComma,
+    UnclosedDelimiter {
+        open: Span,
+    },
+    UnexpectedEof,
+    UnexpectedToken {
+        found: TokenKind,
+        expected: ExpectedSet,
+    },
+    UnknownAnchor,
+    UnknownDirective,
+    UnknownEscape,
+    UnknownReference,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ParsingError {}
+pub enum ResolvingError {
+    CyclicReference,
+    InvalidReference,
+    MissingReference,
+    UnknownProfile,
+    UnterminatedInterpolation,
+}
+
+// `found` is what the input held when the limit was hit, so the limit can be tuned from the
+// error alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitError {
+    DocumentTooLarge { limit: usize, found: usize },
+    NestingTooDeep { limit: usize, found: usize },
+    StringTooLong { limit: usize, found: usize },
+    TooManyTokens { limit: usize, found: usize },
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ErrorKind {
     Lexing(LexingError),
     Parsing(ParsingError),
+    Resolving(ResolvingError),
+    Limit(LimitError),
+}
+
+impl ErrorKind {
+    // every kind that's reported as a warning when it isn't fatal, which is where the lints come
+    // from; the fields don't matter, only the codes
+    #[cfg(feature = "alloc")]
+    pub(crate) const WARNINGS: [Self; 6] = {
+        let first = Span::new(Location::new(1, 1, 0), Location::new(1, 1, 0));
+
+        [
+            Self::Lexing(LexingError::MixedIndentation),
+            Self::Lexing(LexingError::UnusualWhitespace { found: ' ' }),
+            Self::Lexing(LexingError::ConfusableCharacter {
+                found: ' ',
+                expected: ' ',
+            }),
+            Self::Parsing(ParsingError::DuplicateKey { first }),
+            Self::Parsing(ParsingError::TrailingComma),
+            Self::Parsing(ParsingError::UnknownEscape),
+        ]
+    };
+
+    // codes are never renumbered or reused, new variants take the next free code of their phase
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::Lexing(LexingError::UnrecognizedToken) => "PCF0001",
+            Self::Lexing(LexingError::MalformedNumber) => "PCF0002",
+            Self::Lexing(LexingError::UnterminatedString) => "PCF0003",
+            Self::Lexing(LexingError::InvalidEscape) => "PCF0004",
+            Self::Lexing(LexingError::MalformedBytes) => "PCF0005",
+            Self::Lexing(LexingError::IntegerOverflow) => "PCF0006",
+            Self::Lexing(LexingError::MixedIndentation) => "PCF0007",
+            Self::Lexing(LexingError::UnusualWhitespace { .. }) => "PCF0008",
+            Self::Lexing(LexingError::ConfusableCharacter { .. }) => "PCF0009",
+            Self::Parsing(ParsingError::ArithmeticOverflow) => "PCF0101",
+            Self::Parsing(ParsingError::ConflictingKey { .. }) => "PCF0102",
+            Self::Parsing(ParsingError::CyclicExtends) => "PCF0103",
+            Self::Parsing(ParsingError::DivisionByZero) => "PCF0104",
+            Self::Parsing(ParsingError::DuplicateAnchor { .. }) => "PCF0105",
+            Self::Parsing(ParsingError::DuplicateKey { .. }) => "PCF0106",
+            Self::Parsing(ParsingError::IncludeCycle) => "PCF0107",
+            Self::Parsing(ParsingError::IncludeNotFound) => "PCF0108",
+            Self::Parsing(ParsingError::InvalidExtends) => "PCF0109",
+            Self::Parsing(ParsingError::InvalidKey) => "PCF0110",
+            Self::Parsing(ParsingError::InvalidOperands { .. }) => "PCF0111",
+            // PCF0112 was the depth limit, which is now `LimitError::NestingTooDeep`
+            Self::Parsing(ParsingError::MixedArray { .. }) => "PCF0113",
+            Self::Parsing(ParsingError::TrailingComma) => "PCF0114",
+            Self::Parsing(ParsingError::UnclosedDelimiter { .. }) => "PCF0115",
+            Self::Parsing(ParsingError::UnexpectedEof) => "PCF0116",
+            Self::Parsing(ParsingError::UnexpectedToken { .. }) => "PCF0117",
+            Self::Parsing(ParsingError::UnknownAnchor) => "PCF0118",
+            Self::Parsing(ParsingError::UnknownDirective) => "PCF0119",
+            Self::Parsing(ParsingError::UnknownEscape) => "PCF0120",
+            Self::Parsing(ParsingError::UnknownReference) => "PCF0121",
+            Self::Resolving(ResolvingError::CyclicReference) => "PCF0201",
+            Self::Resolving(ResolvingError::InvalidReference) => "PCF0202",
+            Self::Resolving(ResolvingError::MissingReference) => "PCF0203",
+            Self::Resolving(ResolvingError::UnknownProfile) => "PCF0204",
+            Self::Resolving(ResolvingError::UnterminatedInterpolation) => "PCF0205",
+            Self::Limit(LimitError::DocumentTooLarge { .. }) => "PCF0501",
+            Self::Limit(LimitError::NestingTooDeep { .. }) => "PCF0502",
+            Self::Limit(LimitError::StringTooLong { .. }) => "PCF0503",
+            Self::Limit(LimitError::TooManyTokens { .. }) => "PCF0504",
+        }
+    }
+
+    pub fn related(&self) -> Option<(Span, Label)> {
+        match self {
+            Self::Parsing(
+                ParsingError::ConflictingKey { first }
+                | ParsingError::DuplicateAnchor { first }
+                | ParsingError::DuplicateKey { first },
+            ) => Some((*first, Label::PreviousDefinition)),
+            Self::Parsing(ParsingError::UnclosedDelimiter { open }) => {
+                Some((*open, Label::OpenDelimiter))
+            }
+            Self::Parsing(ParsingError::InvalidOperands { left }) => {
+                Some((*left, Label::LeftOperand))
+            }
+            Self::Parsing(ParsingError::MixedArray { first }) => {
+                Some((*first, Label::FirstElement))
+            }
+            _ => None,
+        }
+    }
 }
 
+// what the spans around an error are labelled with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Label {
+    Here,
+    PreviousDefinition,
+    OpenDelimiter,
+    LeftOperand,
+    FirstElement,
+    IncludedHere,
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(English.label(*self))
+    }
+}
+
+// the notes and help pcf attaches itself are kept apart from free text so they can be translated
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hint {
+    Text(String),
+    ByteEscapes,
+    UnicodeEscape,
+    IntegerRange,
+    CloseString { close: String, line: usize },
+    CloseDelimiter { close: char, line: usize },
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        English.hint(f, self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<String> for Hint {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<&str> for Hint {
+    fn from(text: &str) -> Self {
+        Self::Text(text.into())
+    }
+}
+
+#[cfg(feature = "alloc")]
 impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Self::Lexing(l) => match l {
-                    LexingError::MalformedNumber => "encountered malformed number during lexing",
-                    LexingError::UnrecognizedToken =>
-                        "encountered unrecognized token during lexing",
-                    LexingError::UnterminatedString =>
-                        "encountered unterminated string during lexing",
-                },
-                Self::Parsing(p) => match p {
-                    _ => "",
-                },
-            }
-        )
+        English.error(f, self)
+    }
+}
+
+// an `@include` directive that pulled in the file an error is in, with the lines around it
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Included {
+    pub file: String,
+    pub span: Span,
+    pub snippet: Snippet,
+}
+
+#[cfg(feature = "alloc")]
+impl Included {
+    pub fn new(src: &Source<'_>, span: Span) -> Self {
+        Self {
+            file: src.file.into(),
+            span,
+            snippet: Snippet::new(&src.content, span),
+        }
     }
 }
 
+// extra context shown after the snippet, boxed so the many errors without any stay small;
+// `included` runs from the innermost directive out to the root file
+#[cfg(feature = "alloc")]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Hints {
+    pub note: Option<Hint>,
+    pub help: Option<Hint>,
+    pub included: Vec<Included>,
+}
+
+#[cfg(feature = "alloc")]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Error<'a> {
     pub span: Span,
     pub src: &'a Source<'a>,
     pub kind: ErrorKind,
+    pub hints: Option<Box<Hints>>,
 }
 
+#[cfg(feature = "alloc")]
 impl<'a> fmt::Display for Error<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "[{}{}] {}\n{}",
-            self.src.file,
-            self.span,
-            self.kind,
-            self.src
-                .content
-                .get(
-                    self.src.extract_offset(self.span.begin)
-                        ..self.src.extract_offset(self.span.end)
-                )
-                .unwrap_or("<failed to extract offsets of begin and end>")
-        )
+        self.render(f, &RenderOptions::default())
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<'a> core::error::Error for Error<'a> {}
 
+#[cfg(feature = "alloc")]
 impl<'a> Error<'a> {
     pub fn new(kind: ErrorKind, span: Span, src: &'a Source<'a>) -> Self {
-        Self { kind, span, src }
+        Self {
+            kind,
+            span,
+            src,
+            hints: None,
+        }
     }
 
     pub fn lexing(kind: LexingError, span: Span, src: &'a Source<'a>) -> Self {
@@ -176,6 +550,426 @@ impl<'a> Error<'a> {
     pub fn parsing(kind: ParsingError, span: Span, src: &'a Source<'a>) -> Self {
         Self::new(ErrorKind::Parsing(kind), span, src)
     }
+
+    pub fn limit(kind: LimitError, span: Span, src: &'a Source<'a>) -> Self {
+        Self::new(ErrorKind::Limit(kind), span, src)
+    }
+
+    pub fn resolving(kind: ResolvingError, span: Span, src: &'a Source<'a>) -> Self {
+        Self::new(ErrorKind::Resolving(kind), span, src)
+    }
+
+    pub fn with_note(mut self, note: impl Into<Hint>) -> Self {
+        self.hints.get_or_insert_default().note = Some(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<Hint>) -> Self {
+        self.hints.get_or_insert_default().help = Some(help.into());
+        self
+    }
+
+    pub fn included_from(mut self, src: &Source<'_>, span: Span) -> Self {
+        let included = Included::new(src, span);
+        self.hints.get_or_insert_default().included.push(included);
+        self
+    }
+
+    pub fn note(&self) -> Option<&Hint> {
+        self.hints.as_ref()?.note.as_ref()
+    }
+
+    pub fn help(&self) -> Option<&Hint> {
+        self.hints.as_ref()?.help.as_ref()
+    }
+
+    pub fn included(&self) -> &[Included] {
+        self.hints.as_ref().map_or(&[], |hints| &hints.included)
+    }
+
+    // copies the file name and snippets out so the error can outlive its source
+    pub fn into_owned(self) -> OwnedError {
+        self.into()
+    }
+
+    pub fn render(&self, f: &mut impl fmt::Write, options: &RenderOptions) -> fmt::Result {
+        let content = (self.src.content.as_str(), 1);
+        render(
+            f,
+            options,
+            self.src.file,
+            self.span,
+            self.kind,
+            content,
+            self.kind.related().map(|_| content),
+        )?;
+        write_hints(f, options, self.hints.as_deref())
+    }
 }
 
+#[cfg(feature = "alloc")]
 pub type Result<'a, T> = core::result::Result<T, Error<'a>>;
+
+#[cfg(feature = "alloc")]
+pub(crate) const ERROR: &str = "\x1b[1;31m";
+#[cfg(feature = "alloc")]
+pub(crate) const NOTE: &str = "\x1b[1;33m";
+#[cfg(feature = "alloc")]
+const RESET: &str = "\x1b[0m";
+
+// the catalog is `'static` so the options stay free of lifetimes, a catalog loaded at runtime can
+// be leaked once at startup
+#[cfg(feature = "alloc")]
+#[derive(Clone, Copy)]
+pub struct RenderOptions {
+    pub context: bool,
+    pub color: bool,
+    pub messages: &'static dyn Messages,
+}
+
+#[cfg(feature = "alloc")]
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            context: false,
+            color: false,
+            messages: &English,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Debug for RenderOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RenderOptions")
+            .field("context", &self.context)
+            .field("color", &self.color)
+            .finish_non_exhaustive()
+    }
+}
+
+// catalogs are compared by address, as there's nothing else to tell two of them apart by
+#[cfg(feature = "alloc")]
+impl PartialEq for RenderOptions {
+    fn eq(&self, other: &Self) -> bool {
+        self.context == other.context
+            && self.color == other.color
+            && core::ptr::addr_eq(self.messages, other.messages)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Eq for RenderOptions {}
+
+#[cfg(feature = "alloc")]
+impl RenderOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn messages(mut self, messages: &'static dyn Messages) -> Self {
+        self.messages = messages;
+        self
+    }
+
+    pub fn context(mut self, context: bool) -> Self {
+        self.context = context;
+        self
+    }
+
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    // the escape codes wrapping text of the given style, or nothing when color is off
+    pub(crate) fn paint(&self, style: &'static str) -> (&'static str, &'static str) {
+        match self.color {
+            true => (style, RESET),
+            false => ("", ""),
+        }
+    }
+}
+
+// the lines around a span, with one line of context either side, starting at line number `line`
+// and byte `offset` of the source
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub line: usize,
+    pub offset: usize,
+    pub text: String,
+}
+
+#[cfg(feature = "alloc")]
+impl Snippet {
+    pub fn new(content: &str, span: Span) -> Self {
+        let line = span.begin.line.saturating_sub(1).max(1);
+        let mut lines = content.split('\n');
+        let offset = lines.by_ref().take(line - 1).map(|l| l.len() + 1).sum();
+        // spans built from tokens out of order can end before they begin
+        let lines: Vec<&str> = lines
+            .take((span.end.line + 2).saturating_sub(line))
+            .collect();
+
+        Self {
+            line,
+            offset,
+            text: lines.join("\n"),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedError {
+    pub file: String,
+    pub span: Span,
+    pub kind: ErrorKind,
+    pub snippet: Snippet,
+    pub related: Option<Snippet>,
+    pub hints: Option<Box<Hints>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> From<Error<'a>> for OwnedError {
+    fn from(error: Error<'a>) -> Self {
+        Self {
+            file: error.src.file.into(),
+            span: error.span,
+            kind: error.kind,
+            snippet: Snippet::new(&error.src.content, error.span),
+            related: error
+                .kind
+                .related()
+                .map(|(span, _)| Snippet::new(&error.src.content, span)),
+            hints: error.hints,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl OwnedError {
+    pub fn note(&self) -> Option<&Hint> {
+        self.hints.as_ref()?.note.as_ref()
+    }
+
+    pub fn help(&self) -> Option<&Hint> {
+        self.hints.as_ref()?.help.as_ref()
+    }
+
+    pub fn included(&self) -> &[Included] {
+        self.hints.as_ref().map_or(&[], |hints| &hints.included)
+    }
+
+    pub fn render(&self, f: &mut impl fmt::Write, options: &RenderOptions) -> fmt::Result {
+        render(
+            f,
+            options,
+            &self.file,
+            self.span,
+            self.kind,
+            (&self.snippet.text, self.snippet.line),
+            self.related.as_ref().map(|s| (s.text.as_str(), s.line)),
+        )?;
+        write_hints(f, options, self.hints.as_deref())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Display for OwnedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render(f, &RenderOptions::default())
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn render(
+    f: &mut impl fmt::Write,
+    options: &RenderOptions,
+    file: &str,
+    span: Span,
+    kind: ErrorKind,
+    text: (&str, usize),
+    related: Option<(&str, usize)>,
+) -> fmt::Result {
+    let (on, off) = options.paint(ERROR);
+    write!(f, "{}[{}{}] {}: ", on, file, span, kind.code())?;
+    options.messages.error(f, &kind)?;
+    f.write_str(off)?;
+    write_snippet(f, options, ERROR, text, span, "")?;
+
+    if let (Some((span, label)), Some(text)) = (kind.related(), related) {
+        let (on, off) = options.paint(NOTE);
+        let label = options.messages.label(label);
+        write!(f, "\n{}[{}{}] {}{}", on, file, span, label, off)?;
+        write_snippet(f, options, NOTE, text, span, "")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "alloc")]
+fn write_hints(
+    f: &mut impl fmt::Write,
+    options: &RenderOptions,
+    hints: Option<&Hints>,
+) -> fmt::Result {
+    let Some(hints) = hints else {
+        return Ok(());
+    };
+
+    for included in &hints.included {
+        let (on, off) = options.paint(NOTE);
+        let label = options.messages.label(Label::IncludedHere);
+        write!(
+            f,
+            "\n{}[{}{}] {}{}",
+            on, included.file, included.span, label, off
+        )?;
+        write_snippet(
+            f,
+            options,
+            NOTE,
+            (&included.snippet.text, included.snippet.line),
+            included.span,
+            "",
+        )?;
+    }
+
+    if let Some(note) = &hints.note {
+        write!(f, "\n{}: ", options.messages.severity(Severity::Note))?;
+        options.messages.hint(f, note)?;
+    }
+
+    if let Some(help) = &hints.help {
+        write!(f, "\n{}: ", options.messages.help())?;
+        options.messages.hint(f, help)?;
+    }
+
+    Ok(())
+}
+
+// writes the lines a span covers with a gutter of line numbers and carets under the span itself,
+// followed by the label on the last line
+#[cfg(feature = "alloc")]
+pub(crate) fn write_snippet(
+    f: &mut impl fmt::Write,
+    options: &RenderOptions,
+    style: &'static str,
+    (text, first): (&str, usize),
+    span: Span,
+    label: &str,
+) -> fmt::Result {
+    // an exclusive end at the start of a line doesn't cover anything on it
+    let last = match span.end.col == 1 && span.end.line > span.begin.line {
+        true => span.end.line - 1,
+        false => span.end.line,
+    };
+    let context = usize::from(options.context);
+    let (from, to) = (span.begin.line.saturating_sub(context), last + context);
+
+    let lines: Vec<(usize, &str)> = text
+        .split('\n')
+        .enumerate()
+        .map(|(i, line)| (first + i, line.strip_suffix('\r').unwrap_or(line)))
+        .skip_while(|(n, _)| *n < from)
+        .take_while(|(n, _)| *n <= to)
+        .collect();
+    let width = lines.last().map_or(1, |(n, _)| n.ilog10() as usize + 1);
+
+    for (n, line) in lines {
+        write!(f, "\n{:>width$} |", n)?;
+
+        if !line.is_empty() {
+            write!(f, " {}", line)?;
+        }
+
+        if !(span.begin.line..=last).contains(&n) {
+            continue;
+        }
+
+        let begin = match n == span.begin.line {
+            true => span.begin.col,
+            false => 1,
+        };
+        let end = match n == span.end.line {
+            true => span.end.col,
+            false => line.chars().count() + 1,
+        };
+
+        write!(f, "\n{:width$} | ", "")?;
+
+        // tabs are repeated so the carets line up however wide the terminal draws them
+        let mut chars = line.chars();
+        for _ in 1..begin {
+            f.write_char(match chars.next() {
+                Some('\t') => '\t',
+                _ => ' ',
+            })?;
+        }
+
+        let (on, off) = options.paint(style);
+        f.write_str(on)?;
+
+        for _ in 0..end.saturating_sub(begin).max(1) {
+            f.write_char('^')?;
+        }
+
+        if n == last && !label.is_empty() {
+            write!(f, " {}", label)?;
+        }
+
+        f.write_str(off)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "alloc")]
+impl core::error::Error for OwnedError {}
+
+// levenshtein distance that also counts swapping two neighbours as one edit, the most common typo
+#[cfg(feature = "alloc")]
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut rows = [
+        alloc::vec![0; b.len() + 1],
+        (0..=b.len()).collect(),
+        alloc::vec![0; b.len() + 1],
+    ];
+
+    for i in 1..=a.len() {
+        rows[2][0] = i;
+
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[1][j] + 1)
+                .min(rows[2][j - 1] + 1)
+                .min(rows[1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[0][j - 2] + 1);
+            }
+
+            rows[2][j] = distance;
+        }
+
+        rows.rotate_left(1);
+    }
+
+    rows[1][b.len()]
+}
+
+// only close candidates are worth suggesting, so anything beyond a third of the key is dropped
+#[cfg(feature = "alloc")]
+pub fn suggest<'c>(key: &str, candidates: impl IntoIterator<Item = &'c str>) -> Option<&'c str> {
+    let limit = (key.chars().count() / 3).max(1);
+
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
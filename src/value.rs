@@ -0,0 +1,817 @@
+use crate::{extract::*, path::*, utils::*};
+use alloc::{
+    collections::btree_map::BTreeMap,
+    string::String,
+    vec::{self, Vec},
+};
+use core::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    String(String),
+    Bytes(Vec<u8>),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    String,
+    Bytes,
+    Integer,
+    Float,
+    Bool,
+    Array,
+    Table,
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::String => "string",
+                Self::Bytes => "bytes",
+                Self::Integer => "integer",
+                Self::Float => "float",
+                Self::Bool => "boolean",
+                Self::Array => "array",
+                Self::Table => "table",
+            }
+        )
+    }
+}
+
+impl Value {
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            Self::String(_) => ValueKind::String,
+            Self::Bytes(_) => ValueKind::Bytes,
+            Self::Integer(_) => ValueKind::Integer,
+            Self::Float(_) => ValueKind::Float,
+            Self::Bool(_) => ValueKind::Bool,
+            Self::Array(_) => ValueKind::Array,
+            Self::Table(_) => ValueKind::Table,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Self::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Self::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&Table> {
+        match self {
+            Self::Table(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn as_table_mut(&mut self) -> Option<&mut Table> {
+        match self {
+            Self::Table(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn is_str(&self) -> bool {
+        self.kind() == ValueKind::String
+    }
+
+    pub fn is_bytes(&self) -> bool {
+        self.kind() == ValueKind::Bytes
+    }
+
+    pub fn is_i64(&self) -> bool {
+        self.kind() == ValueKind::Integer
+    }
+
+    pub fn is_f64(&self) -> bool {
+        self.kind() == ValueKind::Float
+    }
+
+    pub fn is_bool(&self) -> bool {
+        self.kind() == ValueKind::Bool
+    }
+
+    pub fn is_array(&self) -> bool {
+        self.kind() == ValueKind::Array
+    }
+
+    pub fn is_table(&self) -> bool {
+        self.kind() == ValueKind::Table
+    }
+}
+
+impl Value {
+    fn rank(&self) -> u8 {
+        match self {
+            Self::String(_) => 0,
+            Self::Bytes(_) => 1,
+            Self::Integer(_) => 2,
+            Self::Float(_) => 3,
+            Self::Bool(_) => 4,
+            Self::Array(_) => 5,
+            Self::Table(_) => 6,
+        }
+    }
+}
+
+// floats compare with `f64::total_cmp` and hash by bit pattern, so `NaN == NaN` and `0.0 != -0.0`
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::String(a), Self::String(b)) => a.cmp(b),
+            (Self::Bytes(a), Self::Bytes(b)) => a.cmp(b),
+            (Self::Integer(a), Self::Integer(b)) => a.cmp(b),
+            (Self::Float(a), Self::Float(b)) => a.total_cmp(b),
+            (Self::Bool(a), Self::Bool(b)) => a.cmp(b),
+            (Self::Array(a), Self::Array(b)) => a.cmp(b),
+            (Self::Table(a), Self::Table(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+
+        match self {
+            Self::String(s) => s.hash(state),
+            Self::Bytes(b) => b.hash(state),
+            Self::Integer(i) => i.hash(state),
+            Self::Float(f) => f.to_bits().hash(state),
+            Self::Bool(b) => b.hash(state),
+            Self::Array(a) => a.hash(state),
+            Self::Table(t) => t.hash(state),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentPosition {
+    Leading,
+    Inline,
+    Trailing,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Comments {
+    pub leading: Vec<String>,
+    pub inline: Option<String>,
+    pub trailing: Vec<String>,
+}
+
+impl Comments {
+    pub fn is_empty(&self) -> bool {
+        self.leading.is_empty() && self.inline.is_none() && self.trailing.is_empty()
+    }
+
+    // parsed comments keep the text after `#` verbatim, so written ones get the usual leading space
+    pub fn set(&mut self, position: CommentPosition, text: &str) {
+        let mut lines = text.lines().map(|line| match line {
+            "" => String::new(),
+            _ => alloc::format!(" {}", line),
+        });
+
+        match position {
+            CommentPosition::Leading => self.leading = lines.collect(),
+            CommentPosition::Trailing => self.trailing = lines.collect(),
+            CommentPosition::Inline => {
+                self.inline = lines.next().map(|first| {
+                    lines.fold(first, |mut inline, line| {
+                        inline.push_str(&line);
+                        inline
+                    })
+                })
+            }
+        }
+    }
+}
+
+macro_rules! from_integer {
+    ($($ty:ty),+) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(value: $ty) -> Self {
+                    Self::Integer(value.into())
+                }
+            }
+        )+
+    };
+}
+
+from_integer!(i8, i16, i32, i64, u8, u16, u32);
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Self::Float(value.into())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Bytes(value)
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(value: &[u8]) -> Self {
+        Self::Bytes(value.into())
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(value: Vec<Value>) -> Self {
+        Self::Array(value)
+    }
+}
+
+impl From<Table> for Value {
+    fn from(value: Table) -> Self {
+        Self::Table(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub value: Value,
+    pub key_span: Span,
+    pub span: Span,
+    pub comments: Comments,
+}
+
+impl Entry {
+    pub fn new(value: Value, key_span: Span, span: Span) -> Self {
+        Self {
+            value,
+            key_span,
+            span,
+            comments: Comments::default(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct Table {
+    entries: Vec<(String, Entry)>,
+    index: BTreeMap<String, usize>,
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.index.contains_key(key)
+    }
+
+    pub fn position(&self, key: &str) -> Option<usize> {
+        self.index.get(key).copied()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.get_entry(key).map(|e| &e.value)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.get_entry_mut(key).map(|e| &mut e.value)
+    }
+
+    pub fn get_entry(&self, key: &str) -> Option<&Entry> {
+        self.position(key).map(|i| &self.entries[i].1)
+    }
+
+    pub fn get_entry_mut(&mut self, key: &str) -> Option<&mut Entry> {
+        self.position(key).map(|i| &mut self.entries[i].1)
+    }
+
+    pub fn insert(&mut self, key: String, entry: Entry) -> Option<Entry> {
+        match self.position(&key) {
+            Some(i) => Some(core::mem::replace(&mut self.entries[i].1, entry)),
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, entry));
+                None
+            }
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: Value) -> Option<Value> {
+        match self.get_mut(key) {
+            Some(existing) => Some(core::mem::replace(existing, value)),
+            None => {
+                self.insert(
+                    key.into(),
+                    Entry::new(value, Span::default(), Span::default()),
+                );
+                None
+            }
+        }
+    }
+
+    pub fn insert_at(&mut self, index: usize, key: String, entry: Entry) -> Option<Entry> {
+        let previous = self.remove(&key);
+        let index = index.min(self.entries.len());
+
+        self.entries.insert(index, (key, entry));
+        self.reindex();
+        previous
+    }
+
+    pub fn insert_after(&mut self, after: &str, key: String, entry: Entry) -> Option<Entry> {
+        let previous = self.remove(&key);
+        let index = self.position(after).map_or(self.entries.len(), |i| i + 1);
+
+        self.entries.insert(index, (key, entry));
+        self.reindex();
+        previous
+    }
+
+    fn reindex(&mut self) {
+        self.index = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, (k, _))| (k.clone(), i))
+            .collect();
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Entry> {
+        let i = self.index.remove(key)?;
+
+        for pos in self.index.values_mut() {
+            if *pos > i {
+                *pos -= 1;
+            }
+        }
+
+        Some(self.entries.remove(i).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Entry)> {
+        self.entries.iter().map(|(k, e)| (k, e))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&String, &mut Entry)> {
+        self.entries.iter_mut().map(|(k, e)| (&*k, e))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    pub fn lookup(&self, path: &KeyPath) -> Option<&Value> {
+        let (PathSegment::Key(key), rest) = path.segments().split_first()? else {
+            return None;
+        };
+
+        walk(self.get(key)?, rest)
+    }
+
+    pub fn lookup_mut(&mut self, path: &KeyPath) -> Option<&mut Value> {
+        let (PathSegment::Key(key), rest) = path.segments().split_first()? else {
+            return None;
+        };
+
+        walk_mut(self.get_mut(key)?, rest)
+    }
+}
+
+impl Table {
+    fn sorted(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.index
+            .iter()
+            .map(|(key, &i)| (key, &self.entries[i].1.value))
+    }
+}
+
+impl PartialEq for Table {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(k, e)| other.get(k).is_some_and(|v| *v == e.value))
+    }
+}
+
+impl Eq for Table {}
+
+impl Ord for Table {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sorted().cmp(other.sorted())
+    }
+}
+
+impl PartialOrd for Table {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for Table {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+
+        for (key, value) in self.sorted() {
+            key.hash(state);
+            value.hash(state);
+        }
+    }
+}
+
+impl IntoIterator for Table {
+    type Item = (String, Entry);
+    type IntoIter = vec::IntoIter<(String, Entry)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    pub root: Table,
+    pub comments: Vec<String>,
+}
+
+impl Document {
+    pub fn new(root: Table) -> Self {
+        Self {
+            root,
+            comments: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, path: impl ToKeyPath) -> Option<&Value> {
+        self.root.lookup(&path.to_key_path().ok()?)
+    }
+
+    pub fn get_mut(&mut self, path: impl ToKeyPath) -> Option<&mut Value> {
+        self.root.lookup_mut(&path.to_key_path().ok()?)
+    }
+
+    pub fn set(
+        &mut self,
+        path: impl ToKeyPath,
+        value: Value,
+    ) -> core::result::Result<Option<Value>, PathError> {
+        let path = path.to_key_path()?;
+        let Some((PathSegment::Key(key), rest)) = path.segments().split_first() else {
+            return Err(PathError::Malformed);
+        };
+
+        if rest.is_empty() {
+            return Ok(self.root.set(key, value));
+        }
+
+        check_set(self.root.get(key), rest)?;
+
+        if !self.root.contains_key(key) {
+            self.root.set(key, Value::Table(Table::new()));
+        }
+
+        match self.root.get_mut(key) {
+            Some(target) => set_in(target, rest, value),
+            None => Err(PathError::NotATable),
+        }
+    }
+
+    pub fn remove(&mut self, path: impl ToKeyPath) -> Option<Value> {
+        let mut path = path.to_key_path().ok()?;
+        let last = path.pop()?;
+
+        let parent = match path.is_empty() {
+            true => return self.root.remove(last.key()?).map(|e| e.value),
+            false => self.root.lookup_mut(&path)?,
+        };
+
+        match last {
+            PathSegment::Key(key) => parent.as_table_mut()?.remove(&key).map(|e| e.value),
+            PathSegment::Index(index) => {
+                let array = parent.as_array_mut()?;
+                (index < array.len()).then(|| array.remove(index))
+            }
+        }
+    }
+
+    pub fn insert_after(
+        &mut self,
+        path: impl ToKeyPath,
+        key: &str,
+        value: Value,
+    ) -> core::result::Result<(), PathError> {
+        let mut path = path.to_key_path()?;
+        let Some(PathSegment::Key(after)) = path.pop() else {
+            return Err(PathError::Malformed);
+        };
+        let parent = match path.is_empty() {
+            true => &mut self.root,
+            false => self
+                .root
+                .lookup_mut(&path)
+                .and_then(Value::as_table_mut)
+                .ok_or(PathError::NotATable)?,
+        };
+
+        parent.insert_after(
+            &after,
+            key.into(),
+            Entry::new(value, Span::default(), Span::default()),
+        );
+        Ok(())
+    }
+
+    pub fn set_comment(
+        &mut self,
+        path: impl ToKeyPath,
+        position: CommentPosition,
+        text: &str,
+    ) -> core::result::Result<(), PathError> {
+        let mut path = path.to_key_path()?;
+        let Some(PathSegment::Key(key)) = path.pop() else {
+            return Err(PathError::Malformed);
+        };
+        let parent = match path.is_empty() {
+            true => &mut self.root,
+            false => self
+                .root
+                .lookup_mut(&path)
+                .and_then(Value::as_table_mut)
+                .ok_or(PathError::NotATable)?,
+        };
+        let entry = parent.get_entry_mut(&key).ok_or(PathError::Missing)?;
+
+        entry.comments.set(position, text);
+        Ok(())
+    }
+
+    pub fn get_str(&self, path: impl ToKeyPath) -> core::result::Result<&str, ExtractError> {
+        self.typed(path, ValueKind::String, Value::as_str)
+    }
+
+    pub fn get_i64(&self, path: impl ToKeyPath) -> core::result::Result<i64, ExtractError> {
+        self.typed(path, ValueKind::Integer, Value::as_i64)
+    }
+
+    pub fn get_f64(&self, path: impl ToKeyPath) -> core::result::Result<f64, ExtractError> {
+        self.typed(path, ValueKind::Float, Value::as_f64)
+    }
+
+    pub fn get_bool(&self, path: impl ToKeyPath) -> core::result::Result<bool, ExtractError> {
+        self.typed(path, ValueKind::Bool, Value::as_bool)
+    }
+
+    pub fn get_array(
+        &self,
+        path: impl ToKeyPath,
+    ) -> core::result::Result<&Vec<Value>, ExtractError> {
+        self.typed(path, ValueKind::Array, Value::as_array)
+    }
+
+    pub fn get_table(&self, path: impl ToKeyPath) -> core::result::Result<&Table, ExtractError> {
+        self.typed(path, ValueKind::Table, Value::as_table)
+    }
+
+    fn typed<'v, T>(
+        &'v self,
+        path: impl ToKeyPath,
+        expected: ValueKind,
+        cast: impl FnOnce(&'v Value) -> Option<T>,
+    ) -> core::result::Result<T, ExtractError> {
+        let (value, path, span) = self.locate(path)?;
+
+        cast(value).ok_or_else(|| ExtractError {
+            path,
+            span,
+            kind: ExtractErrorKind::MismatchedType {
+                expected,
+                found: value.kind(),
+            },
+        })
+    }
+
+    pub(crate) fn locate(
+        &self,
+        path: impl ToKeyPath,
+    ) -> core::result::Result<(&Value, KeyPath, Span), ExtractError> {
+        let mut key_path = KeyPath::new();
+        let mut span = Span::default();
+        let mut value: Option<&Value> = None;
+
+        for segment in path.to_key_path().unwrap_or_default() {
+            value = match &segment {
+                PathSegment::Key(key) => match value {
+                    None => Some(&self.root),
+                    Some(value) => value.as_table(),
+                }
+                .and_then(|table| table.get_entry(key))
+                .map(|entry| {
+                    span = entry.span;
+                    &entry.value
+                }),
+                PathSegment::Index(index) => {
+                    value.and_then(Value::as_array).and_then(|a| a.get(*index))
+                }
+            };
+            key_path.push(segment);
+
+            if value.is_none() {
+                break;
+            }
+        }
+
+        match value {
+            Some(value) => Ok((value, key_path, span)),
+            None => Err(ExtractError {
+                path: key_path,
+                span,
+                kind: ExtractErrorKind::MissingKey,
+            }),
+        }
+    }
+}
+
+fn walk<'v>(mut value: &'v Value, segments: &[PathSegment]) -> Option<&'v Value> {
+    for segment in segments {
+        value = match segment {
+            PathSegment::Key(key) => value.as_table()?.get(key)?,
+            PathSegment::Index(index) => value.as_array()?.get(*index)?,
+        };
+    }
+
+    Some(value)
+}
+
+fn walk_mut<'v>(mut value: &'v mut Value, segments: &[PathSegment]) -> Option<&'v mut Value> {
+    for segment in segments {
+        value = match segment {
+            PathSegment::Key(key) => value.as_table_mut()?.get_mut(key)?,
+            PathSegment::Index(index) => value.as_array_mut()?.get_mut(*index)?,
+        };
+    }
+
+    Some(value)
+}
+
+// walks the path the way `set_in` does without changing anything, so that a set that's going to
+// fail does so before any of the tables along the way are made; a missing value stands for the
+// empty table that would be made there
+fn check_set(
+    target: Option<&Value>,
+    segments: &[PathSegment],
+) -> core::result::Result<(), PathError> {
+    let Some((segment, rest)) = segments.split_first() else {
+        return Ok(());
+    };
+
+    match (segment, target) {
+        (PathSegment::Key(_), None) => check_set(None, rest),
+        (PathSegment::Key(key), Some(Value::Table(table))) => check_set(table.get(key), rest),
+        (PathSegment::Key(_), Some(_)) => Err(PathError::NotATable),
+        (PathSegment::Index(index), Some(Value::Array(array))) => match array.get(*index) {
+            Some(value) => check_set(Some(value), rest),
+            None if rest.is_empty() && *index == array.len() => Ok(()),
+            None => Err(PathError::OutOfBounds),
+        },
+        (PathSegment::Index(_), _) => Err(PathError::NotAnArray),
+    }
+}
+
+fn set_in(
+    target: &mut Value,
+    segments: &[PathSegment],
+    value: Value,
+) -> core::result::Result<Option<Value>, PathError> {
+    let Some((segment, rest)) = segments.split_first() else {
+        return Ok(Some(core::mem::replace(target, value)));
+    };
+
+    let child = match segment {
+        PathSegment::Key(key) => {
+            let table = target.as_table_mut().ok_or(PathError::NotATable)?;
+
+            if rest.is_empty() {
+                return Ok(table.set(key, value));
+            }
+
+            if !table.contains_key(key) {
+                table.set(key, Value::Table(Table::new()));
+            }
+
+            table.get_mut(key).ok_or(PathError::NotATable)?
+        }
+        PathSegment::Index(index) => {
+            let array = target.as_array_mut().ok_or(PathError::NotAnArray)?;
+
+            if rest.is_empty() && *index == array.len() {
+                array.push(value);
+                return Ok(None);
+            }
+
+            array.get_mut(*index).ok_or(PathError::OutOfBounds)?
+        }
+    };
+
+    set_in(child, rest, value)
+}
@@ -0,0 +1,73 @@
+use crate::{path::*, utils::*, value::*};
+
+pub trait Visit {
+    fn visit_value(&mut self, _path: &KeyPath, _value: &Value, _span: Span) {}
+
+    fn enter_table(&mut self, _path: &KeyPath, _table: &Table, _span: Span) {}
+
+    fn exit_table(&mut self, _path: &KeyPath, _table: &Table, _span: Span) {}
+
+    fn enter_array(&mut self, _path: &KeyPath, _array: &[Value], _span: Span) {}
+
+    fn exit_array(&mut self, _path: &KeyPath, _array: &[Value], _span: Span) {}
+}
+
+pub fn walk_value<V: Visit + ?Sized>(
+    visitor: &mut V,
+    path: &mut KeyPath,
+    value: &Value,
+    span: Span,
+) {
+    visitor.visit_value(path, value, span);
+
+    match value {
+        Value::Table(table) => walk_table(visitor, path, table, span),
+        Value::Array(array) => {
+            visitor.enter_array(path, array, span);
+
+            for (i, value) in array.iter().enumerate() {
+                path.push(PathSegment::Index(i));
+                walk_value(visitor, path, value, span);
+                path.pop();
+            }
+
+            visitor.exit_array(path, array, span);
+        }
+        _ => {}
+    }
+}
+
+pub fn walk_table<V: Visit + ?Sized>(
+    visitor: &mut V,
+    path: &mut KeyPath,
+    table: &Table,
+    span: Span,
+) {
+    visitor.enter_table(path, table, span);
+
+    for (key, entry) in table.iter() {
+        path.push(PathSegment::Key(key.clone()));
+        walk_value(visitor, path, &entry.value, entry.span);
+        path.pop();
+    }
+
+    visitor.exit_table(path, table, span);
+}
+
+struct Walk<F>(F);
+
+impl<F: FnMut(&KeyPath, &Value, Span)> Visit for Walk<F> {
+    fn visit_value(&mut self, path: &KeyPath, value: &Value, span: Span) {
+        (self.0)(path, value, span)
+    }
+}
+
+impl Document {
+    pub fn visit<V: Visit + ?Sized>(&self, visitor: &mut V) {
+        walk_table(visitor, &mut KeyPath::new(), &self.root, Span::default());
+    }
+
+    pub fn walk(&self, f: impl FnMut(&KeyPath, &Value, Span)) {
+        self.visit(&mut Walk(f));
+    }
+}
@@ -0,0 +1,412 @@
+use crate::{
+    emit::{write_base64, write_float},
+    path::*,
+    utils::{Location, Span},
+    value::*,
+};
+use alloc::{string::String, vec::Vec};
+use core::{fmt, str::Chars};
+use yaml_rust2::{
+    Yaml, YamlEmitter,
+    parser::{Event, Parser},
+    scanner::{Marker, ScanError, TScalarStyle},
+};
+
+const MAX_DEPTH: usize = 128;
+const CORE: &str = "tag:yaml.org,2002:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YamlWarningKind {
+    Alias,
+    Anchor,
+    ComplexKey,
+    DuplicateKey,
+    MultipleDocuments,
+    NonStringKey,
+    Null,
+    Tag,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YamlWarning {
+    pub path: KeyPath,
+    pub span: Span,
+    pub kind: YamlWarningKind,
+}
+
+impl fmt::Display for YamlWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}{}] ", self.path, self.span)?;
+
+        match self.kind {
+            YamlWarningKind::Alias => write!(f, "encountered alias (copied) during conversion"),
+            YamlWarningKind::Anchor => write!(f, "encountered anchor (dropped) during conversion"),
+            YamlWarningKind::ComplexKey => {
+                write!(
+                    f,
+                    "encountered complex key (entry dropped) during conversion"
+                )
+            }
+            YamlWarningKind::DuplicateKey => {
+                write!(f, "encountered duplicate key (last kept) during conversion")
+            }
+            YamlWarningKind::MultipleDocuments => write!(
+                f,
+                "encountered multiple documents (first kept) during conversion"
+            ),
+            YamlWarningKind::NonStringKey => {
+                write!(
+                    f,
+                    "encountered non-string key (stringified) during conversion"
+                )
+            }
+            YamlWarningKind::Null => write!(f, "encountered null (dropped) during conversion"),
+            YamlWarningKind::Tag => write!(f, "encountered custom tag (ignored) during conversion"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum YamlErrorKind {
+    NestingTooDeep,
+    NotATable,
+    Syntax(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YamlError {
+    pub span: Span,
+    pub kind: YamlErrorKind,
+}
+
+impl fmt::Display for YamlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] ", self.span)?;
+
+        match &self.kind {
+            YamlErrorKind::NestingTooDeep => {
+                write!(f, "encountered excessive nesting during conversion")
+            }
+            YamlErrorKind::NotATable => {
+                write!(f, "encountered non-mapping document during conversion")
+            }
+            YamlErrorKind::Syntax(info) => write!(f, "encountered {} during conversion", info),
+        }
+    }
+}
+
+impl core::error::Error for YamlError {}
+
+fn to_yaml(value: &Value) -> Yaml {
+    match value {
+        Value::String(s) => Yaml::String(s.clone()),
+        // yaml's !!binary would need a tag the emitter cannot write, so bytes become plain base64
+        Value::Bytes(b) => {
+            let mut s = String::new();
+            let _ = write_base64(&mut s, b);
+            Yaml::String(s)
+        }
+        Value::Integer(i) => Yaml::Integer(*i),
+        Value::Float(float) if float.is_nan() => Yaml::Real(".nan".into()),
+        Value::Float(float) if float.is_infinite() => {
+            Yaml::Real(if *float > 0.0 { ".inf" } else { "-.inf" }.into())
+        }
+        Value::Float(float) => {
+            let mut s = String::new();
+            let _ = write_float(&mut s, *float);
+            Yaml::Real(s)
+        }
+        Value::Bool(b) => Yaml::Boolean(*b),
+        Value::Array(array) => Yaml::Array(array.iter().map(to_yaml).collect()),
+        Value::Table(table) => to_hash(table),
+    }
+}
+
+fn to_hash(table: &Table) -> Yaml {
+    Yaml::Hash(
+        table
+            .iter()
+            .map(|(key, entry)| (Yaml::String(key.clone()), to_yaml(&entry.value)))
+            .collect(),
+    )
+}
+
+// a scalar resolves to None when it is null, which pcf cannot represent
+fn scalar(text: String, style: TScalarStyle, forced: bool) -> Option<Value> {
+    if forced || style != TScalarStyle::Plain {
+        return Some(Value::String(text));
+    }
+
+    match Yaml::from_str(&text) {
+        Yaml::Null => None,
+        Yaml::Boolean(b) => Some(Value::Bool(b)),
+        Yaml::Integer(i) => Some(Value::Integer(i)),
+        real @ Yaml::Real(_) => real.as_f64().map(Value::Float),
+        _ => Some(Value::String(text)),
+    }
+}
+
+struct Loader<'s> {
+    parser: Parser<Chars<'s>>,
+    content: &'s str,
+    // markers count chars, so byte offsets are found by walking forward from the last one
+    cursor: (usize, usize),
+    anchors: Vec<(usize, Value)>,
+    warnings: Vec<YamlWarning>,
+    path: KeyPath,
+}
+
+impl<'s> Loader<'s> {
+    fn span(&mut self, mark: Marker) -> Span {
+        if mark.index() < self.cursor.0 {
+            self.cursor = (0, 0);
+        }
+
+        let (chars, bytes) = self.cursor;
+        let offset = bytes
+            + self.content[bytes..]
+                .chars()
+                .take(mark.index() - chars)
+                .map(char::len_utf8)
+                .sum::<usize>();
+
+        self.cursor = (mark.index(), offset);
+        let location = Location::new(mark.line(), mark.col() + 1, offset);
+
+        Span {
+            begin: location,
+            end: location,
+        }
+    }
+
+    fn error(&mut self, mark: Marker, kind: YamlErrorKind) -> YamlError {
+        YamlError {
+            span: self.span(mark),
+            kind,
+        }
+    }
+
+    fn warn(&mut self, mark: Marker, kind: YamlWarningKind) {
+        let span = self.span(mark);
+        self.warnings.push(YamlWarning {
+            path: self.path.clone(),
+            span,
+            kind,
+        });
+    }
+
+    fn next(&mut self) -> core::result::Result<(Event, Marker), YamlError> {
+        self.parser
+            .next_token()
+            .map_err(|error: ScanError| YamlError {
+                span: self.span(*error.marker()),
+                kind: YamlErrorKind::Syntax(error.info().into()),
+            })
+    }
+
+    fn node(
+        &mut self,
+        event: Event,
+        mark: Marker,
+        depth: usize,
+    ) -> core::result::Result<Option<Value>, YamlError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error(mark, YamlErrorKind::NestingTooDeep));
+        }
+
+        let (value, anchor) = match event {
+            Event::Alias(id) => {
+                self.warn(mark, YamlWarningKind::Alias);
+                let value = self.anchors.iter().rev().find(|(a, _)| *a == id);
+                (value.map(|(_, value)| value.clone()), 0)
+            }
+            Event::Scalar(text, style, anchor, tag) => {
+                let forced = match &tag {
+                    Some(tag) if tag.handle == CORE => tag.suffix == "str",
+                    Some(_) => {
+                        self.warn(mark, YamlWarningKind::Tag);
+                        false
+                    }
+                    None => false,
+                };
+
+                let value = scalar(text, style, forced);
+
+                if value.is_none() {
+                    self.warn(mark, YamlWarningKind::Null);
+                }
+
+                (value, anchor)
+            }
+            Event::SequenceStart(anchor, tag) => {
+                if tag.is_some_and(|tag| tag.handle != CORE) {
+                    self.warn(mark, YamlWarningKind::Tag);
+                }
+
+                let mut array = Vec::new();
+
+                loop {
+                    let (event, mark) = self.next()?;
+
+                    if event == Event::SequenceEnd {
+                        break;
+                    }
+
+                    self.path.push(PathSegment::Index(array.len()));
+                    let value = self.node(event, mark, depth + 1);
+                    self.path.pop();
+                    array.extend(value?);
+                }
+
+                (Some(Value::Array(array)), anchor)
+            }
+            Event::MappingStart(anchor, tag) => {
+                if tag.is_some_and(|tag| tag.handle != CORE) {
+                    self.warn(mark, YamlWarningKind::Tag);
+                }
+
+                (Some(Value::Table(self.mapping(depth)?)), anchor)
+            }
+            _ => return Err(self.error(mark, YamlErrorKind::Syntax("unexpected event".into()))),
+        };
+
+        if anchor > 0 {
+            self.warn(mark, YamlWarningKind::Anchor);
+
+            if let Some(value) = &value {
+                self.anchors.push((anchor, value.clone()));
+            }
+        }
+
+        Ok(value)
+    }
+
+    fn mapping(&mut self, depth: usize) -> core::result::Result<Table, YamlError> {
+        let mut table = Table::new();
+
+        loop {
+            let (event, mark) = self.next()?;
+
+            let key = match event {
+                Event::MappingEnd => break,
+                Event::Scalar(text, style, anchor, _) => {
+                    if anchor > 0 {
+                        self.warn(mark, YamlWarningKind::Anchor);
+                    }
+
+                    if !matches!(scalar(text.clone(), style, false), Some(Value::String(_))) {
+                        self.warn(mark, YamlWarningKind::NonStringKey);
+                    }
+
+                    Some(text)
+                }
+                event => {
+                    self.warn(mark, YamlWarningKind::ComplexKey);
+                    self.node(event, mark, depth + 1)?;
+                    None
+                }
+            };
+
+            let (event, value_mark) = self.next()?;
+
+            if let Some(key) = &key {
+                self.path.push(PathSegment::Key(key.clone()));
+            }
+
+            let value = self.node(event, value_mark, depth + 1);
+
+            if key.is_some() {
+                self.path.pop();
+            }
+
+            if let (Some(key), Some(value)) = (key, value?) {
+                let span = self.span(mark);
+                let value_span = self.span(value_mark);
+
+                if table
+                    .insert(key.clone(), Entry::new(value, span, value_span))
+                    .is_some()
+                {
+                    self.path.push(PathSegment::Key(key));
+                    self.warn(mark, YamlWarningKind::DuplicateKey);
+                    self.path.pop();
+                }
+            }
+        }
+
+        Ok(table)
+    }
+
+    fn document(&mut self) -> core::result::Result<Table, YamlError> {
+        let mut root = Table::new();
+
+        loop {
+            let (event, mark) = self.next()?;
+
+            match event {
+                Event::StreamStart | Event::DocumentEnd => {}
+                Event::StreamEnd => break,
+                Event::DocumentStart => {
+                    let (event, mark) = self.next()?;
+
+                    match event {
+                        Event::MappingStart(..) => {
+                            root = match self.node(event, mark, 0)? {
+                                Some(Value::Table(table)) => table,
+                                _ => Table::new(),
+                            }
+                        }
+                        Event::Scalar(ref text, TScalarStyle::Plain, ..)
+                            if matches!(Yaml::from_str(text), Yaml::Null) => {}
+                        _ => return Err(self.error(mark, YamlErrorKind::NotATable)),
+                    }
+
+                    let (event, mark) = self.next()?;
+
+                    if event == Event::DocumentEnd
+                        && self
+                            .next()
+                            .is_ok_and(|(event, _)| event == Event::DocumentStart)
+                    {
+                        self.warn(mark, YamlWarningKind::MultipleDocuments);
+                    }
+
+                    break;
+                }
+                _ => return Err(self.error(mark, YamlErrorKind::NotATable)),
+            }
+        }
+
+        Ok(root)
+    }
+}
+
+pub fn write_yaml(f: &mut impl fmt::Write, document: &Document) -> fmt::Result {
+    YamlEmitter::new(f)
+        .dump(&to_hash(&document.root))
+        .map_err(|_| fmt::Error)
+}
+
+impl Document {
+    pub fn to_yaml_string(&self) -> String {
+        let mut yaml = String::new();
+        let _ = write_yaml(&mut yaml, self);
+        yaml
+    }
+
+    pub fn from_yaml_str(
+        content: &str,
+    ) -> core::result::Result<(Self, Vec<YamlWarning>), YamlError> {
+        let mut loader = Loader {
+            parser: Parser::new_from_str(content),
+            content,
+            cursor: (0, 0),
+            anchors: Vec::new(),
+            warnings: Vec::new(),
+            path: KeyPath::new(),
+        };
+
+        let root = loader.document()?;
+        Ok((Document::new(root), loader.warnings))
+    }
+}